@@ -6,7 +6,7 @@ use std::{
 
 use ccitt_t4_t6::{
     bits::{BitIter, BitWriter, FillOrder},
-    g3::G3Decoder,
+    g3::{G3Decoder, G3Options},
     g42d::{fax_decode, FaxOptions, G4Decoder},
     pbm_to_io_writer, FaxImage,
 };
@@ -73,6 +73,10 @@ struct Options {
     /// print a bitmap after decoding
     #[argh(switch, short = 'v')]
     debug: bool,
+
+    /// continue after damaged rows and return a partial image
+    #[argh(switch, short = 'r')]
+    recover: bool,
 }
 
 fn value_into_rational(v: Value) -> Option<f64> {
@@ -179,20 +183,27 @@ fn main() -> eyre::Result<()> {
             dbg!(fax);
             let t4opt = T4Options::read(&mut tiff_decoder)?;
             dbg!(t4opt);
-            if t4opt.flag_2d {
-                todo!("Group3 2-D");
-            } else if t4opt.flag_uncompressed {
+            if t4opt.flag_uncompressed {
                 todo!("Group3 uncompressed");
             }
+            let mut g3_options = G3Options::default();
+            g3_options.recover = opt.recover;
+            // TIFF Group 3 lines always start with an EOL
+            g3_options.end_of_line = true;
+            g3_options.encoded_byte_align = t4opt.flag_fill_bits;
+            if t4opt.flag_2d {
+                // TIFF does not limit the number of consecutive 2D lines
+                g3_options.k = u32::MAX;
+            }
             for_each_strip(&mut tiff_decoder, |bytes| {
                 if let Some(out) = &opt.output {
                     std::fs::write(out, bytes)?;
                 }
 
-                let mut decoder = G3Decoder::new(fax.width as usize);
+                let mut decoder = G3Decoder::with_options(fax.width as usize, g3_options);
                 let mut bit_iter = BitIter::new(bytes);
                 bit_iter.set_fill_order(fax.fill_order);
-                let image = decoder.decode(&mut bit_iter).unwrap();
+                let image = decoder.decode(&mut bit_iter)?;
                 println!("DONE G3Decoder");
 
                 output(&opt, fax, image)?;
@@ -214,7 +225,8 @@ fn main() -> eyre::Result<()> {
                         fax_options.fill_order = fax.fill_order;
                         fax_options.width = fax.width as usize;
                         fax_options.debug = opt.debug;
-                        let image = fax_decode(bytes, fax_options).expect("fax_decode");
+                        fax_options.recover = opt.recover;
+                        let image = fax_decode(bytes, fax_options)?;
                         println!("DONE fax_decode");
 
                         output(&opt, fax, image)?;
@@ -223,7 +235,15 @@ fn main() -> eyre::Result<()> {
                     DecoderImpl::StateMachine => {
                         let mut decoder = G4Decoder::<BitWriter>::new(fax.width as usize);
                         decoder.set_fill_order(fax.fill_order);
-                        decoder.decode(&bytes)?;
+                        decoder.set_recover(opt.recover);
+                        if let Err(e) = decoder.decode(bytes) {
+                            return Err(eyre!("{} in row {}", e, decoder.rows()));
+                        }
+                        println!(
+                            "Decoded {} rows, damaged: {:?}",
+                            decoder.rows(),
+                            decoder.damaged_rows()
+                        );
                         let store = decoder.into_store();
 
                         let bitmap = store.done();
@@ -262,6 +282,12 @@ fn main() -> eyre::Result<()> {
 }
 
 fn output(opt: &Options, fax: &FaxTiffOptions, image: FaxImage) -> color_eyre::Result<()> {
+    println!(
+        "Decoded {} of {} rows, damaged: {:?}",
+        image.recovered_rows(),
+        image.height(),
+        image.damaged_rows()
+    );
     if let Some(out) = &opt.pbm {
         let file = std::fs::File::create(&out)?;
         let mut buf_writer = BufWriter::new(file);
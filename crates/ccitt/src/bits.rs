@@ -119,6 +119,14 @@ impl BitWriter {
         };
     }
 
+    /// Write 0 bits until `offset` bits of the current byte are used
+    pub fn fill_to(&mut self, offset: usize) {
+        debug_assert!(offset < 8);
+        while 7 - self.state.as_usize() != offset {
+            self.write(false);
+        }
+    }
+
     /// flush the output buffer
     pub fn flush(&mut self) {
         let offset = self.state.as_usize() + 1;
@@ -183,6 +191,12 @@ impl<'a> BitIter<'a> {
         self.fill_order = fill_order;
     }
 
+    /// Skip the remaining bits of the current byte, so that the next
+    /// bit is read from the start of the following byte.
+    pub fn align(&mut self) {
+        self.state = State::S0;
+    }
+
     /// Get the next two bits
     pub fn next_2(&mut self) -> Option<(bool, bool)> {
         let a = self.next()?;
@@ -262,7 +276,7 @@ impl Iterator for BitIter<'_> {
 
 #[cfg(test)]
 mod tests {
-    use super::{BitWriter, FillOrder, State};
+    use super::{BitIter, BitWriter, FillOrder, State};
 
     #[test]
     fn test_fill_order_msb_to_lsb() {
@@ -280,6 +294,18 @@ mod tests {
         assert_eq!(msbf.next(0b00000101), (0b00000010, true));
     }
 
+    #[test]
+    fn test_bit_iter_align() {
+        let mut bi = BitIter::new(&[0b1010_0000, 0b0110_0000]);
+        bi.align();
+        assert_eq!(bi.next_2(), Some((true, false)));
+        bi.align();
+        assert_eq!(bi.next_2(), Some((false, true)));
+        assert_eq!(bi.next(), Some(true));
+        bi.align();
+        assert_eq!(bi.next(), None);
+    }
+
     #[test]
     fn test_bit_writer_write_bits() {
         let mut bw = BitWriter::new();
@@ -3,13 +3,21 @@ use std::fmt;
 /// An error when parsing a CCITT encoded bi-level image
 #[non_exhaustive]
 #[derive(Debug)]
-pub enum FaxError {}
+pub enum FaxError {
+    /// A row contains an invalid code, a change outside of the row or the data ended early
+    Damaged {
+        /// The index of the row
+        row: usize,
+    },
+}
 
 impl std::error::Error for FaxError {}
 
 impl fmt::Display for FaxError {
-    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match *self {}
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Damaged { row } => write!(f, "Damaged data in row {}", row),
+        }
     }
 }
 
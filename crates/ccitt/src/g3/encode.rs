@@ -0,0 +1,87 @@
+//! Encoder implementation
+
+use crate::{bits::BitWriter, g42d::G4Encoder};
+
+use super::G3Options;
+
+/// The EOL code
+const EOL: usize = 0b000000000001;
+
+impl G3Options {
+    /// Write an EOL code
+    ///
+    /// With `encoded_byte_align`, fill bits are written before the EOL, so
+    /// that it ends on a byte boundary (see *Fill* in ITU-T T.4).
+    fn write_eol(&self, output: &mut BitWriter) {
+        if self.encoded_byte_align {
+            output.fill_to(4);
+        }
+        output.write_bits(EOL, 12);
+    }
+}
+
+/// The encoder
+pub struct Encoder<'a> {
+    inner: G4Encoder<'a>,
+    options: G3Options,
+    /// How many bits of the source image to skip at the start of each line
+    pub skip_lead: usize,
+    /// How many bits of the source image to skip at the end of each line
+    pub skip_tail: usize,
+}
+
+impl<'a> Encoder<'a> {
+    /// Create a new encoder for the given input, bit width and options
+    ///
+    /// The input is a sequence of lines with `width` bits each, where `1` is black.
+    pub fn new(width: usize, input: &'a [u8], options: G3Options) -> Self {
+        Self {
+            inner: G4Encoder::new(width, input),
+            options,
+            skip_lead: 0,
+            skip_tail: 0,
+        }
+    }
+
+    /// Encode the bitmap
+    ///
+    /// With `K > 0`, every K-th line (starting with the first) is coded in 1D
+    /// and the others in 2D. If EOLs are enabled, the image is terminated with
+    /// an RTC (return to control) sequence of six EOLs.
+    pub fn encode(mut self) -> Vec<u8> {
+        self.inner.skip_lead = self.skip_lead;
+        self.inner.skip_tail = self.skip_tail;
+
+        let k = self.options.k as usize;
+        let mut line = 0;
+        while self.inner.has_next_line() {
+            let one_d = k == 0 || line % k == 0;
+            let output = self.inner.output_mut();
+            if self.options.end_of_line {
+                self.options.write_eol(output);
+            } else if self.options.encoded_byte_align {
+                output.flush();
+            }
+            if k > 0 {
+                output.write(one_d);
+            }
+            if one_d {
+                self.inner.encode_1d_line();
+            } else {
+                self.inner.encode_2d_line();
+            }
+            line += 1;
+        }
+
+        let mut output = self.inner.into_output();
+        if self.options.end_of_line {
+            for _ in 0..6 {
+                self.options.write_eol(&mut output);
+                if k > 0 {
+                    output.write(true);
+                }
+            }
+        }
+        output.done()
+    }
+}
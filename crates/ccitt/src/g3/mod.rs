@@ -1,37 +1,367 @@
-//! # CCITT Group 3 1D-encoding
+//! # CCITT Group 3 1D- and 2D-encoding
 //!
-//! TODO: 2-D encoding
+//! Lines are either coded one-dimensionally with Modified Huffman (MH) codes
+//! or, if `K > 0`, mixed with Modified READ (MR) lines that are coded relative
+//! to the previous line (see [`crate::g42d`]).
 //!
 //! Spec: ITU-T Recommendation T.4 (07/03) <https://www.itu.int/rec/T-REC-T.4-200307-I/en>
-use crate::{bits::BitIter, FaxImage, FaxResult};
+use crate::{
+    bits::BitIter,
+    g42d::{FaxDecode, Step},
+    Color, FaxImage, FaxResult, Store,
+};
 
 mod decode_iter;
+mod encode;
+
+pub use encode::Encoder as G3Encoder;
+
+/// Options for Group 3 decoding and encoding
+///
+/// These correspond to the `K`, `EndOfLine` and `EncodedByteAlign` parameters
+/// of the PDF `CCITTFaxDecode` filter or the `T4Options` tag in TIFF files.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct G3Options {
+    /// `0` for pure 1D coding, `K > 0` for mixed 1D/2D coding.
+    ///
+    /// In mixed mode, a 1D line is followed by at most `K - 1` 2D lines and
+    /// every line (or the EOL before it) carries a tag bit with its coding.
+    pub k: u32,
+    /// Whether every coded line is preceded by an EOL
+    ///
+    /// The decoder accepts lines with and without EOL, but uses this to find
+    /// the fill bits for `encoded_byte_align`.
+    pub end_of_line: bool,
+    /// Whether the coded lines are aligned to byte boundaries
+    ///
+    /// With `end_of_line`, fill bits are inserted before every EOL so that it
+    /// ends on a byte boundary, otherwise every coded line starts on a byte
+    /// boundary.
+    pub encoded_byte_align: bool,
+    /// Return a partial image instead of failing on damaged data
+    ///
+    /// Damaged lines are filled in from the line above and decoding continues
+    /// after the next EOL. This is only used for decoding.
+    pub recover: bool,
+}
+
+/// What was found at the start of a line
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum LineStart {
+    /// An EOL code (`000000000001`), possibly after fill bits
+    Eol,
+    /// Line data without an EOL
+    Data,
+    /// The end of the input (possibly after fill bits)
+    End,
+}
+
+/// Skip fill bits and an EOL code, if present
+fn line_start(bit_iter: &mut BitIter<'_>) -> LineStart {
+    let mut peek = bit_iter.clone();
+    let mut zeros = 0;
+    loop {
+        match peek.next() {
+            None => return LineStart::End,
+            Some(false) => zeros += 1,
+            Some(true) if zeros >= 11 => {
+                *bit_iter = peek;
+                return LineStart::Eol;
+            }
+            Some(true) => return LineStart::Data,
+        }
+    }
+}
+
+/// Move to the next EOL code, skipping the rest of a damaged line
+///
+/// Returns `false` if there is no EOL before the end of the input.
+fn skip_to_eol(bit_iter: &mut BitIter<'_>) -> bool {
+    let mut peek = bit_iter.clone();
+    let mut run_start = peek.clone();
+    let mut zeros = 0;
+    loop {
+        let before = peek.clone();
+        match peek.next() {
+            None => return false,
+            Some(false) => {
+                if zeros == 0 {
+                    run_start = before;
+                }
+                zeros += 1;
+            }
+            Some(true) if zeros >= 11 => {
+                *bit_iter = run_start;
+                return true;
+            }
+            Some(true) => zeros = 0,
+        }
+    }
+}
 
 /// # Group 3 (T.4) Decoder
 pub struct G3Decoder {
     width: usize,
+    options: G3Options,
 }
 
 impl G3Decoder {
-    /// Create a new instance
+    /// Create a new instance for 1D coded images
     pub fn new(width: usize) -> Self {
-        Self { width }
+        Self::with_options(width, G3Options::default())
+    }
+
+    /// Create a new instance with the given options
+    pub fn with_options(width: usize, options: G3Options) -> Self {
+        Self { width, options }
     }
 
     /// Decode a Group 3 image
+    ///
+    /// Decoding stops at the end of the input or at the RTC (return to control)
+    /// sequence of consecutive EOLs.
+    ///
+    /// With [`G3Options::recover`], a damaged line is filled in from the line above
+    /// and decoding continues after the next EOL. The 2D lines following it are
+    /// marked as damaged too, until the next 1D line.
     pub fn decode(&mut self, bit_iter: &mut BitIter<'_>) -> FaxResult<FaxImage> {
-        let mut image = Vec::new();
+        let lines = self.decode_lines(bit_iter, FaxDecode::new(self.width))?;
+        Ok(lines.into_image())
+    }
+
+    /// Decode a Group 3 image into a [`Store`], e.g. a [`PackedBitmap`](crate::PackedBitmap)
+    ///
+    /// See [`G3Decoder::decode`]
+    pub fn decode_into<S: Store<Row = Vec<Color>>>(
+        &mut self,
+        bit_iter: &mut BitIter<'_>,
+        store: S,
+    ) -> FaxResult<S> {
+        let lines = self.decode_lines(bit_iter, FaxDecode::with_store(self.width, store))?;
+        Ok(lines.into_store())
+    }
+
+    fn decode_lines<S: Store<Row = Vec<Color>>>(
+        &mut self,
+        bit_iter: &mut BitIter<'_>,
+        mut lines: FaxDecode<S>,
+    ) -> FaxResult<FaxDecode<S>> {
+        lines.set_recover(self.options.recover);
+        let mut reference_damaged = false;
         loop {
-            let line = decode_iter::decode_1d_line(bit_iter, self.width);
-            if line.is_empty() {
+            // fill bits before an EOL are skipped as leading zeros
+            if self.options.encoded_byte_align && !self.options.end_of_line {
+                bit_iter.align();
+            }
+            if line_start(bit_iter) == LineStart::End {
+                break;
+            }
+            let two_d = match self.options.k {
+                0 => false,
+                _ => match bit_iter.next() {
+                    // tag bit: 1 for 1D, 0 for 2D
+                    Some(tag) => !tag,
+                    None => break,
+                },
+            };
+            if line_start(&mut bit_iter.clone()) != LineStart::Data {
+                // RTC or trailing EOL
                 break;
+            }
+            let data = bit_iter.clone();
+            let valid = if two_d {
+                lines.decode_2d_line(bit_iter) == Step::Continue
             } else {
-                image.extend_from_slice(&line);
+                match decode_iter::decode_1d_line(bit_iter, self.width) {
+                    Some(mut line) => {
+                        line.resize(self.width, Color::White);
+                        lines.push_line(&line);
+                        true
+                    }
+                    None => false,
+                }
+            };
+            if !valid {
+                lines.damaged_line()?;
+                reference_damaged = true;
+                *bit_iter = data;
+                if !skip_to_eol(bit_iter) {
+                    break;
+                }
+            } else if !two_d {
+                reference_damaged = false;
+            } else if reference_damaged {
+                lines.mark_last_damaged();
             }
         }
-        Ok(FaxImage {
-            width: self.width,
-            complete: image,
-        })
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{G3Decoder, G3Encoder, G3Options};
+    use crate::{bits::BitIter, Color, FaxError, PackedBitmap};
+
+    const WIDTH: usize = 24;
+    #[rustfmt::skip]
+    const IMAGE: [u8; 27] = [
+        0x00, 0x3C, 0x00,
+        0x00, 0x7E, 0x00,
+        0x01, 0xFF, 0x80,
+        0x0F, 0x00, 0xF0,
+        0xFF, 0xFF, 0xFF,
+        0x00, 0x00, 0x00,
+        0xAA, 0x55, 0xAA,
+        0x80, 0x00, 0x01,
+        0x3F, 0x81, 0xFC,
+    ];
+
+    fn round_trip(options: G3Options) {
+        let encoded = G3Encoder::new(WIDTH, &IMAGE, options).encode();
+        let mut decoder = G3Decoder::with_options(WIDTH, options);
+        let image = decoder.decode(&mut BitIter::new(&encoded)).unwrap();
+
+        let expected: Vec<Color> = BitIter::new(&IMAGE).map(Color::from).collect();
+        assert_eq!(image.complete, expected);
+
+        let bitmap = decoder
+            .decode_into(&mut BitIter::new(&encoded), PackedBitmap::default())
+            .unwrap();
+        assert_eq!(bitmap.as_bytes(), IMAGE);
+    }
+
+    #[test]
+    fn test_round_trip_1d() {
+        round_trip(G3Options::default());
+        round_trip(G3Options {
+            end_of_line: true,
+            ..G3Options::default()
+        });
+        round_trip(G3Options {
+            encoded_byte_align: true,
+            ..G3Options::default()
+        });
+    }
+
+    #[test]
+    fn test_round_trip_2d() {
+        for k in [1, 2, 4, u32::MAX] {
+            round_trip(G3Options {
+                k,
+                ..G3Options::default()
+            });
+            round_trip(G3Options {
+                k,
+                end_of_line: true,
+                ..G3Options::default()
+            });
+            round_trip(G3Options {
+                k,
+                end_of_line: true,
+                encoded_byte_align: true,
+                ..G3Options::default()
+            });
+        }
+    }
+
+    #[test]
+    fn test_decode_mixed() {
+        // EOL + 1 | W2 B3 W3 | EOL + 0 | V0 V0 V0 | EOL + 1 | EOL + 1
+        let data = [
+            0b0000_0000,
+            0b0001_1011,
+            0b1101_0000,
+            0b0000_0000,
+            0b0010_1110,
+            0b0000_0000,
+            0b0011_0000,
+            0b0000_0001,
+            0b1000_0000,
+        ];
+        let options = G3Options {
+            k: 2,
+            ..G3Options::default()
+        };
+        let mut decoder = G3Decoder::with_options(8, options);
+        let image = decoder.decode(&mut BitIter::new(&data)).unwrap();
+
+        use Color::{Black as B, White as W};
+        let line = [W, W, B, B, B, W, W, W];
+        assert_eq!(image.width, 8);
+        assert_eq!(image.complete, [line, line].concat());
+    }
+
+    #[test]
+    fn test_decode_byte_aligned() {
+        // W2 B3 W3 | W8 (each line on a byte boundary)
+        let data = [0b0111_1010, 0b0000_0000, 0b1001_1000];
+        let options = G3Options {
+            encoded_byte_align: true,
+            ..G3Options::default()
+        };
+        let mut decoder = G3Decoder::with_options(8, options);
+        let image = decoder.decode(&mut BitIter::new(&data)).unwrap();
+
+        use Color::{Black as B, White as W};
+        assert_eq!(image.complete, [[W, W, B, B, B, W, W, W], [W; 8]].concat());
+    }
+
+    #[test]
+    fn test_byte_aligned_eol() {
+        // fill | EOL | W2 B3 W3 | fill | EOL | W8 | RTC (fill | EOL, 6 times)
+        #[rustfmt::skip]
+        let data = [
+            0b0000_0000, 0b0000_0001,
+            0b0111_1010, 0b0000_0000, 0b0000_0001,
+            0b1001_1000, 0b0000_0000, 0b0000_0001,
+            0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01,
+        ];
+        let options = G3Options {
+            end_of_line: true,
+            encoded_byte_align: true,
+            ..G3Options::default()
+        };
+        let image = [0b0011_1000, 0b0000_0000];
+        assert_eq!(G3Encoder::new(8, &image, options).encode(), data);
+
+        let mut decoder = G3Decoder::with_options(8, options);
+        let decoded = decoder.decode(&mut BitIter::new(&data)).unwrap();
+
+        use Color::{Black as B, White as W};
+        assert_eq!(
+            decoded.complete,
+            [[W, W, B, B, B, W, W, W], [W; 8]].concat()
+        );
+    }
+
+    #[test]
+    fn test_decode_damaged() {
+        let options = G3Options {
+            end_of_line: true,
+            ..G3Options::default()
+        };
+        let mut encoded = G3Encoder::new(WIDTH, &IMAGE, options).encode();
+        // EOL | W10 B4 W10 (00111 | 011 | 00111), break the first line
+        assert_eq!(encoded[..3], [0b0000_0000, 0b0001_0011, 0b1011_0011]);
+        encoded[2] = 0b0000_0000;
+
+        let mut decoder = G3Decoder::with_options(WIDTH, options);
+        let result = decoder.decode(&mut BitIter::new(&encoded));
+        assert!(matches!(result, Err(FaxError::Damaged { row: 0 })));
+
+        let options = G3Options {
+            recover: true,
+            ..options
+        };
+        let mut decoder = G3Decoder::with_options(WIDTH, options);
+        let image = decoder.decode(&mut BitIter::new(&encoded)).unwrap();
+        assert_eq!(image.height(), 9);
+        assert_eq!(image.damaged_rows(), [0]);
+        assert_eq!(image.recovered_rows(), 8);
+
+        let expected: Vec<Color> = BitIter::new(&IMAGE).map(Color::from).collect();
+        assert_eq!(image.complete[WIDTH..], expected[WIDTH..]);
     }
 }
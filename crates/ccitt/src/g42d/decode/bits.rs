@@ -43,6 +43,11 @@ impl Bits {
         unsafe { std::mem::transmute((state & mask) as u8 | bit) }
     }
 
+    /// The number of bits in the pattern
+    pub fn len(self) -> usize {
+        7 - (self as u8).leading_zeros() as usize
+    }
+
     pub fn push(&self, state: &mut u16, off: &mut u8) {
         use Bits::*;
         match self {
@@ -4,8 +4,13 @@
 //! that consumes bytes in a 3-3-2 (bits) pattern and updates the
 //! internal state accordingly.
 
-use crate::{bits::FillOrder, Color, ColorLine, Store};
+use crate::{
+    bits::{BitIter, FillOrder},
+    Color, ColorLine, Store,
+};
 use bits::Bits;
+
+use super::FaxDecode;
 use thiserror::Error;
 
 mod bits;
@@ -66,7 +71,11 @@ pub struct Decoder<S: Store> {
     current: S::Row,
     color: Color,
     bit_loader: fn(u8) -> [Bits; 3],
+    fill_order: FillOrder,
     a0: usize,
+    rows: usize,
+    recover: bool,
+    damaged: Vec<usize>,
 }
 
 impl<S: Store> Decoder<S> {
@@ -81,8 +90,12 @@ impl<S: Store> Decoder<S> {
             reference: S::new_row(width),
             current: S::new_row(width),
             bit_loader: Bits::from_u8_msb_to_lsb,
+            fill_order: FillOrder::MsbToLsb,
             color: Color::White,
             a0: 0,
+            rows: 0,
+            recover: false,
+            damaged: vec![],
             #[cfg(feature = "debug")]
             debug: false,
         }
@@ -90,6 +103,7 @@ impl<S: Store> Decoder<S> {
 
     /// Set a different [`FillOrder`]
     pub fn set_fill_order(&mut self, fill_order: FillOrder) {
+        self.fill_order = fill_order;
         self.bit_loader = match fill_order {
             FillOrder::MsbToLsb => Bits::from_u8_msb_to_lsb,
             FillOrder::LsbToMsb => Bits::from_u8_lsb_to_msb,
@@ -187,6 +201,9 @@ impl<S: Store> Decoder<S> {
         let mut state = 0;
         bits.push(&mut state, &mut off);
         loop {
+            if off > 15 {
+                return Err(Err::InvalidCode);
+            }
             if let Some((val, rem)) = func(state, off) {
                 let bits = Bits::off(state, rem);
                 sum += val;
@@ -297,13 +314,101 @@ impl<S: Store> Decoder<S> {
         Ok(rest)
     }
 
+    /// Continue after damaged rows instead of returning an error
+    ///
+    /// Damaged rows are filled in from the row above. After the first one,
+    /// the rest of the data is decoded with the bit-based decoder (see
+    /// [`fax_decode`](super::fax_decode)), which can resynchronise.
+    pub fn set_recover(&mut self, recover: bool) {
+        self.recover = recover;
+    }
+
+    /// The number of rows that were decoded
+    ///
+    /// Without recovery, if [`Decoder::decode`] fails, this is the index of
+    /// the damaged row.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The indices of the rows that were damaged, see [`Decoder::set_recover`]
+    pub fn damaged_rows(&self) -> &[usize] {
+        &self.damaged
+    }
+
     /// Turn the decoder into it's result store
     pub fn into_store(self) -> S {
         self.store
     }
 
     /// Decode some input
-    pub fn decode(&mut self, mut input: &[u8]) -> Result<(), Err> {
+    pub fn decode(&mut self, data: &[u8]) -> Result<(), Err> {
+        let mut input = data;
+        match self.decode_rows(&mut input) {
+            Err(Err::EOS) if self.at_fill_bits() => Ok(()),
+            Err(_) if self.recover => {
+                let pos = (data.len() - input.len()) * 8 - self.pending_bits();
+                self.recover_from(data, pos);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// The number of bits that were loaded, but not yet consumed
+    fn pending_bits(&self) -> usize {
+        match self.next_bits {
+            NextBits::None => 0,
+            NextBits::A1(b1) => b1.len(),
+            NextBits::A2(b1, b2) => b1.len() + b2.len(),
+        }
+    }
+
+    /// Whether the current row only consists of `0` bits so far
+    fn at_fill_bits(&self) -> bool {
+        use {Cmd::*, ModePrefix::*};
+        let zeros = matches!(
+            self.stack.peek(),
+            MP(M | M0 | M00 | M000 | M0000 | M00000 | M000000) | EOFBP1(_)
+        );
+        self.a0 == 0 && zeros && matches!(self.stack.1, X)
+    }
+
+    /// Mark the current row as damaged and decode the data after bit `pos`
+    /// with a [`FaxDecode`], which can resynchronise
+    fn recover_from(&mut self, data: &[u8], pos: usize) {
+        let mut bit_iter = BitIter::new(data);
+        bit_iter.set_fill_order(self.fill_order);
+        bit_iter.by_ref().take(pos).for_each(drop);
+
+        let reference: Vec<Color> = (1..=self.width)
+            .map(|i| self.reference.color_at(i))
+            .collect();
+        let mut fax = FaxDecode::new(self.width);
+        fax.set_recover(true);
+        fax.set_reference(&reference);
+        let start = bit_iter.clone();
+        let first_row = self.rows;
+        let mut more = fax.skip_damaged(&start, &mut bit_iter).is_ok();
+        loop {
+            for row in std::mem::take(fax.store_mut()).chunks(self.width) {
+                let mut current = S::new_row(self.width);
+                for (i, color) in row.iter().enumerate() {
+                    current.set_color(i + 1, *color);
+                }
+                self.store.extend(&current);
+                self.rows += 1;
+            }
+            if !more {
+                break;
+            }
+            more = matches!(fax.decode_line(&mut bit_iter), Ok(true));
+        }
+        let damaged = fax.damaged_rows().iter().map(|row| first_row + row);
+        self.damaged.extend(damaged);
+    }
+
+    fn decode_rows(&mut self, input: &mut &[u8]) -> Result<(), Err> {
         loop {
             let cmd = self.stack.peek();
             match cmd {
@@ -311,11 +416,11 @@ impl<S: Store> Decoder<S> {
                 Cmd::MP(mp) => {
                     let (rest, bits) = self.next_bits(input)?;
                     self.stack = mp.next(bits)?;
-                    input = rest;
+                    *input = rest;
                 }
                 Cmd::EX(_) => break Err(Err::ExtNotSupported),
-                Cmd::EOFBP1(k) => input = self.eofbp1(input, k)?,
-                Cmd::EOFBP2(k) => input = self.eofbp2(input, k)?,
+                Cmd::EOFBP1(k) => *input = self.eofbp1(input, k)?,
+                Cmd::EOFBP2(k) => *input = self.eofbp2(input, k)?,
                 Cmd::EOFB => break Ok(()),
                 Cmd::P => {
                     self.pass_mode();
@@ -379,7 +484,7 @@ impl<S: Store> Decoder<S> {
                 Cmd::H(bits) => {
                     let (rest, bits) = self.horizontal(bits, input)?;
                     self.stack = ModePrefix::M.next(bits)?;
-                    input = rest;
+                    *input = rest;
                 }
             }
             if self.a0 > self.width {
@@ -389,6 +494,7 @@ impl<S: Store> Decoder<S> {
                     println!("#");
                 }
                 self.store.extend(&self.current);
+                self.rows += 1;
                 self.a0 = 0;
                 self.color = Color::White;
                 std::mem::swap(&mut self.reference, &mut self.current);
@@ -429,6 +535,10 @@ pub enum Err {
     /// Extensions are not supported
     #[error("Extensions are not supported")]
     ExtNotSupported,
+
+    /// Invalid run length code
+    #[error("Invalid run length code")]
+    InvalidCode,
 }
 
 #[derive(Debug, Copy, Clone, Default)]
@@ -665,7 +775,7 @@ fn black(state: u16, off: u8) -> Option<(u16, Rem)> {
         13 => black13(state),
         14 => black14(state),
         15 => black15(state),
-        _ => todo!(),
+        _ => None,
     }
 }
 
@@ -1089,7 +1199,7 @@ fn white(state: u16, off: u8) -> Option<(u16, Rem)> {
         11 => white11(state),
         12 => white12(state),
         13 => white13(state),
-        _ => todo!("white{off} {:016b}", state),
+        _ => None,
     }
 }
 
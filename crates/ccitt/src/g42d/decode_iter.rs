@@ -7,11 +7,28 @@
 use crate::{
     bits::BitIter,
     terminals::{black_terminal, fax_decode_h, white_terminal, TermFn},
-    Color, FaxImage, FaxResult,
+    Color, FaxError, FaxImage, FaxResult, Store,
 };
 
-pub struct FaxDecode {
-    complete: Vec<Color>,
+/// How many bits after the start of a damaged line are tried for resynchronisation
+const RESYNC_MAX_BITS: usize = 1 << 14;
+/// How many lines need to decode without errors to accept a resynchronisation
+const RESYNC_LINES: usize = 4;
+
+/// The outcome of decoding a code word or a line
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Step {
+    /// Continue with the next code word or line
+    Continue,
+    /// End of facsimile block (EOFB)
+    End,
+    /// An invalid code word, a change outside of the line or the data ended mid-line
+    Damaged,
+}
+
+pub struct FaxDecode<S: Store<Row = Vec<Color>> = Vec<Color>> {
+    store: S,
+    rows: usize,
     reference: Vec<Color>,
     current: Vec<Color>,
     width: usize,
@@ -19,13 +36,36 @@ pub struct FaxDecode {
     color: Color,
     first: bool,
     debug: bool,
+    recover: bool,
+    damaged: Vec<usize>,
 }
 
 impl FaxDecode {
     pub fn new(width: usize) -> Self {
+        Self::with_store(width, Vec::new())
+    }
+
+    pub fn decode(mut self, bit_iter: &mut BitIter) -> FaxResult<FaxImage> {
+        while self.decode_line(bit_iter)? {}
+        Ok(self.into_image())
+    }
+
+    pub(crate) fn into_image(self) -> FaxImage {
+        FaxImage {
+            width: self.width,
+            complete: self.store,
+            damaged: self.damaged,
+        }
+    }
+}
+
+impl<S: Store<Row = Vec<Color>>> FaxDecode<S> {
+    /// Create a decoder that adds every decoded line to `store`
+    pub fn with_store(width: usize, store: S) -> Self {
         let reference = vec![Color::White; width];
         FaxDecode {
-            complete: vec![],
+            store,
+            rows: 0,
             width,
             reference,
             current: vec![Color::White; width],
@@ -33,6 +73,8 @@ impl FaxDecode {
             color: Color::White,
             first: true,
             debug: false,
+            recover: false,
+            damaged: vec![],
         }
     }
 
@@ -40,56 +82,168 @@ impl FaxDecode {
         self.debug = debug;
     }
 
-    pub fn decode(mut self, bit_iter: &mut BitIter) -> FaxResult<FaxImage> {
-        loop {
-            let mut done = None;
-            while self.a0 <= self.width {
-                done = self.next(bit_iter);
-                if done == Some(true) {
-                    break;
-                }
-            }
-            if self.debug {
-                println!();
+    /// Continue after damaged lines instead of returning an error
+    pub fn set_recover(&mut self, recover: bool) {
+        self.recover = recover;
+    }
+
+    /// Decode the next line, returns `false` at the end of the image
+    pub(crate) fn decode_line(&mut self, bit_iter: &mut BitIter) -> FaxResult<bool> {
+        // EOFB or the end of the input
+        if bit_iter.clone().next().is_none() {
+            return Ok(false);
+        }
+        let start = bit_iter.clone();
+        match self.decode_2d_line(bit_iter) {
+            Step::Continue => Ok(true),
+            Step::End => Ok(false),
+            Step::Damaged => {
+                self.skip_damaged(&start, bit_iter)?;
+                Ok(true)
             }
-            if done == Some(true) {
-                break;
+        }
+    }
+
+    /// Handle a damaged line that started at `start` and continue after it
+    ///
+    /// See [`Self::damaged_line`] and [`Self::resync`].
+    pub(crate) fn skip_damaged<'a>(
+        &mut self,
+        start: &BitIter<'a>,
+        bit_iter: &mut BitIter<'a>,
+    ) -> FaxResult<()> {
+        self.damaged_line()?;
+        match self.resync(start) {
+            Some(resynced) => *bit_iter = resynced,
+            // no way to continue, skip the rest of the input
+            None => *bit_iter = BitIter::new(&[]),
+        }
+        Ok(())
+    }
+
+    /// Decode a single line that is coded relative to the previous line
+    pub(crate) fn decode_2d_line(&mut self, bit_iter: &mut BitIter) -> Step {
+        while self.a0 <= self.width {
+            match self.next(bit_iter) {
+                Some(Step::Continue) => {}
+                Some(step) => return step,
+                None => return Step::Damaged,
             }
+        }
+        if self.debug {
+            println!();
+        }
+        self.end_line();
+        Step::Continue
+    }
 
-            self.a0 = 0;
-            self.color = Color::White;
-            self.complete.extend_from_slice(&self.current);
-            self.first = true;
-            std::mem::swap(&mut self.current, &mut self.reference);
+    /// Add a line that was decoded by other means, e.g. 1D coding
+    pub(crate) fn push_line(&mut self, line: &[Color]) {
+        self.current.copy_from_slice(line);
+        self.end_line();
+    }
+
+    /// Handle a line that could not be decoded
+    ///
+    /// In recovery mode, the rest of the line is filled in from the reference
+    /// line and the line is marked as damaged, otherwise this returns an error.
+    pub(crate) fn damaged_line(&mut self) -> FaxResult<()> {
+        let row = self.rows;
+        if !self.recover {
+            return Err(FaxError::Damaged { row });
         }
-        Ok(FaxImage {
-            width: self.width,
-            complete: self.complete,
-        })
+        if self.debug {
+            println!(" DAMAGED");
+        }
+        let start = self.a0.saturating_sub(1).min(self.width);
+        self.current[start..].copy_from_slice(&self.reference[start..]);
+        self.damaged.push(row);
+        self.end_line();
+        Ok(())
+    }
+
+    /// Set the line that the next line is coded relative to
+    pub(crate) fn set_reference(&mut self, reference: &[Color]) {
+        self.reference.copy_from_slice(reference);
+    }
+
+    /// Mark the last line as damaged, e.g. because its reference line was damaged
+    pub(crate) fn mark_last_damaged(&mut self) {
+        if let Some(row) = self.rows.checked_sub(1) {
+            self.damaged.push(row);
+        }
+    }
+
+    /// Find the position after `start` from which the next lines decode without errors
+    ///
+    /// As there are no EOL codes in Group 4 data, this tries every bit offset
+    /// and decodes [`RESYNC_LINES`] lines relative to the last reference line.
+    fn resync<'a>(&self, start: &BitIter<'a>) -> Option<BitIter<'a>> {
+        let mut candidate = start.clone();
+        for _ in 0..RESYNC_MAX_BITS {
+            candidate.next()?;
+            let mut trial = FaxDecode::new(self.width);
+            trial.reference.copy_from_slice(&self.reference);
+            let mut bit_iter = candidate.clone();
+            if (0..RESYNC_LINES).all(|_| trial.decode_2d_line(&mut bit_iter) == Step::Continue) {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+
+    fn end_line(&mut self) {
+        self.a0 = 0;
+        self.color = Color::White;
+        self.store.extend(&self.current);
+        self.rows += 1;
+        self.first = true;
+        std::mem::swap(&mut self.current, &mut self.reference);
+    }
+
+    /// The rows that were damaged so far
+    pub(crate) fn damaged_rows(&self) -> &[usize] {
+        &self.damaged
+    }
+
+    pub(crate) fn store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    pub(crate) fn into_store(self) -> S {
+        self.store
     }
 
-    fn vertical(&mut self, new_a0: usize) {
+    fn vertical(&mut self, b1: usize, offset: isize) -> Step {
+        let new_a0 = match b1.checked_add_signed(offset) {
+            Some(new_a0) => new_a0,
+            None => return Step::Damaged,
+        };
         if self.debug {
             print!(" [{} v]", new_a0);
         }
-        if new_a0 > self.width + 1 {
+        if new_a0 > self.width + 1 || new_a0 <= self.a0 {
             if self.debug {
                 println!("ERROR!");
             }
-            self.a0 = self.width + 1;
-            return;
+            return Step::Damaged;
         }
         for i in (self.a0 + 1)..new_a0 {
             self.current[i - 1] = self.color;
         }
         self.color.invert();
         self.a0 = new_a0;
+        Step::Continue
     }
 
-    fn next(&mut self, bit_iter: &mut BitIter) -> Option<bool> {
+    fn next(&mut self, bit_iter: &mut BitIter) -> Option<Step> {
         if self.debug {
             print!("[{}]", self.a0);
         }
+        if self.a0 == 0 && bit_iter.clone().all(|bit| !bit) {
+            // fill bits at the end of the data
+            return Some(Step::End);
+        }
         let mut ref_ink = if self.a0 == 0 {
             Color::White
         } else {
@@ -124,40 +278,48 @@ impl FaxDecode {
             print!("({},{})", b1, b2);
         }
 
-        if bit_iter.next().unwrap() {
+        let mut step = Step::Continue;
+        if bit_iter.next()? {
             if self.debug {
                 print!(" 0 V");
             }
             // 1 --> V(0) --> a_1 just under b_1
-            self.vertical(b1);
-        } else if bit_iter.next().unwrap() {
+            step = self.vertical(b1, 0);
+        } else if bit_iter.next()? {
             // 01
-            if bit_iter.next().unwrap() {
+            if bit_iter.next()? {
                 // 011 --> V_R(1) --> a_1 is 1 right of b_1
                 if self.debug {
                     print!(" 1 VR");
                 }
-                self.vertical(b1 + 1);
+                step = self.vertical(b1, 1);
             } else {
                 // 010 --> V_L(1) --> a_1 is 1 left of b_1
                 if self.debug {
                     print!(" 1 VL");
                 }
-                self.vertical(b1 - 1);
+                step = self.vertical(b1, -1);
             }
-        } else if bit_iter.next().unwrap() {
+        } else if bit_iter.next()? {
             // 001 --> horizontal writing mode
             let (aterm, bterm): (TermFn, TermFn) = match self.color {
                 Color::Black => (black_terminal, white_terminal),
                 Color::White => (white_terminal, black_terminal),
             };
-            let a = fax_decode_h(bit_iter, aterm)?.to_sum().unwrap();
-            let b = fax_decode_h(bit_iter, bterm)?.to_sum().unwrap();
+            let a = fax_decode_h(bit_iter, aterm)?.to_sum();
+            let b = fax_decode_h(bit_iter, bterm)?.to_sum();
+            let (a, b) = match a.zip(b) {
+                Some(ab) => ab,
+                None => return Some(Step::Damaged),
+            };
 
             if self.debug {
                 print!(" {} {} H", a, b);
             }
             let start = if self.first { 0 } else { 1 };
+            if self.a0 + usize::from(a.saturating_sub(start)) + usize::from(b) > self.width {
+                return Some(Step::Damaged);
+            }
             for _ in start..a {
                 self.current[self.a0] = self.color;
                 self.a0 += 1;
@@ -167,7 +329,7 @@ impl FaxDecode {
                 self.a0 += 1;
             }
             self.a0 += 1;
-        } else if bit_iter.next().unwrap() {
+        } else if bit_iter.next()? {
             // 0001 -> passtrough
             if self.debug {
                 print!(" P");
@@ -178,39 +340,38 @@ impl FaxDecode {
                 self.current[i - 1] = self.color;
             }
             self.a0 = b2;
-        } else if bit_iter.next().unwrap() {
+        } else if bit_iter.next()? {
             // 00001
-            if bit_iter.next().unwrap() {
+            if bit_iter.next()? {
                 if self.debug {
                     print!(" 2 VR"); // 000011
                 }
-                self.vertical(b1 + 2);
+                step = self.vertical(b1, 2);
             } else {
                 if self.debug {
                     print!(" 2 VL"); // 000010
                 }
-                self.vertical(b1 - 2);
+                step = self.vertical(b1, -2);
             }
-        } else if bit_iter.next().unwrap() {
+        } else if bit_iter.next()? {
             // 000001
-            if bit_iter.next().unwrap() {
+            if bit_iter.next()? {
                 if self.debug {
                     print!(" 3 VR"); // 0000011
                 }
-                self.vertical(b1 + 3);
+                step = self.vertical(b1, 3);
             } else {
                 if self.debug {
                     print!(" 3 VL"); // 0000010
                 }
-                self.vertical(b1 - 3);
-            }
-        } else if bit_iter.next().unwrap() {
-            // 0000001
-            let a = bit_iter.next()?;
-            let b = bit_iter.next()?;
-            let c = bit_iter.next()?;
-            let bit = |v: bool| if v { 1 } else { 0 };
-            panic!("Extension {}{}{}", bit(a), bit(b), bit(c));
+                step = self.vertical(b1, -3);
+            }
+        } else if bit_iter.next()? {
+            // 0000001 --> extension (e.g. uncompressed mode), not supported
+            if self.debug {
+                print!(" X");
+            }
+            return Some(Step::Damaged);
         } else {
             // 0000000
             let bi2 = bit_iter.clone();
@@ -221,18 +382,24 @@ impl FaxDecode {
                     false, false, false, false, false, true,
                 ]
             {
-                return Some(true);
+                return Some(Step::End);
+            } else if !rest.contains(&true) && self.a0 == 0 {
+                // fill bits at the end of the data
+                return Some(Step::End);
             } else {
                 if self.debug {
                     println!("Unknown");
                 }
-                return Some(true);
+                return Some(Step::Damaged);
             }
         }
+        if step != Step::Continue {
+            return Some(step);
+        }
         if self.a0 <= self.width {
             self.current[self.a0 - 1] = self.color;
         }
         self.first = false;
-        Some(false)
+        Some(Step::Continue)
     }
 }
@@ -104,195 +104,248 @@ impl<'a> Encoder<'a> {
 
     /// Encode the bitmap
     pub fn encode(mut self) -> Vec<u8> {
-        let mut color = Color::White;
+        while self.has_next_line() {
+            self.encode_2d_line();
+        }
 
+        self.output.write_bits(0b000000000001000000000001, 24);
+        self.output.done()
+    }
+
+    /// Whether there is (the start of) another line in the input
+    pub(crate) fn has_next_line(&self) -> bool {
+        !self.done && self.iter.clone().nth(self.skip_lead).is_some()
+    }
+
+    pub(crate) fn output_mut(&mut self) -> &mut BitWriter {
+        &mut self.output
+    }
+
+    pub(crate) fn into_output(self) -> BitWriter {
+        self.output
+    }
+
+    #[cfg(feature = "debug")]
+    fn debug_line(&self) {
+        let mut cl = self.iter.clone();
+        print!("|");
+        for _ in 0..self.skip_lead {
+            match cl.next() {
+                Some(true) => print!("#"),
+                Some(false) => print!("_"),
+                None => print!(" "),
+            }
+        }
+        print!("|");
+        for _ in 0..self.width {
+            match cl.next() {
+                Some(true) => print!("#"),
+                Some(false) => print!("_"),
+                None => print!(" "),
+            }
+        }
+        print!("|");
+        for _ in 0..self.skip_tail {
+            match cl.next() {
+                Some(true) => print!("#"),
+                Some(false) => print!("_"),
+                None => print!(" "),
+            }
+        }
+        println!("|");
+    }
+
+    fn start_line(&mut self) {
+        #[cfg(feature = "debug")]
+        if self.debug {
+            self.debug_line();
+        }
+
+        for _ in 0..self.skip_lead {
+            self.done |= self.iter.next().is_none();
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.read_color = Color::White;
+        self.ref_color = Color::White;
+        self.read_pos = 0;
+        self.ref_pos = 0;
+        std::mem::swap(&mut self.reference, &mut self.current);
+
+        for _ in 0..self.skip_tail {
+            self.iter.next();
+        }
+    }
+
+    /// Encode the next line with 1D (modified huffman) run lengths
+    pub(crate) fn encode_1d_line(&mut self) {
+        self.start_line();
+
+        let mut color = Color::White;
+        let mut a0 = 1;
         loop {
+            let a1 = self.find_next_changing_element();
             #[cfg(feature = "debug")]
             if self.debug {
-                let mut cl = self.iter.clone();
-                print!("|");
-                for _ in 0..self.skip_lead {
-                    match cl.next() {
-                        Some(true) => print!("#"),
-                        Some(false) => print!("_"),
-                        None => print!(" "),
-                    }
-                }
-                print!("|");
-                for _ in 0..self.width {
-                    match cl.next() {
-                        Some(true) => print!("#"),
-                        Some(false) => print!("_"),
-                        None => print!(" "),
-                    }
-                }
-                print!("|");
-                for _ in 0..self.skip_tail {
-                    match cl.next() {
-                        Some(true) => print!("#"),
-                        Some(false) => print!("_"),
-                        None => print!(" "),
-                    }
-                }
-                println!("|");
+                print!("R({})", a1 - a0);
             }
-
-            for _ in 0..self.skip_lead {
-                self.done |= self.iter.next().is_none();
+            match color {
+                Color::White => write_white_len(&mut self.output, a1 - a0),
+                Color::Black => write_black_len(&mut self.output, a1 - a0),
             }
-
-            let mut a0 = 0;
-            let mut a1 = self.find_next_changing_element();
-
-            if self.done {
+            if a1 > self.width {
                 break;
             }
-            let mut a2 = self.find_next_changing_element();
+            a0 = a1;
+            color.invert();
+        }
+        #[cfg(feature = "debug")]
+        if self.debug {
+            println!("#");
+        }
+        self.end_line();
+    }
 
-            let mut b1 = self.find_next_opposite_ref(color);
-            let mut b2 = self.find_next_changing_ref();
+    /// Encode the next line relative to the previous line (2D modes)
+    pub(crate) fn encode_2d_line(&mut self) {
+        self.start_line();
 
-            loop {
-                if a0 == 0 {
-                    a0 = 1;
-                }
+        let mut color = Color::White;
+        let mut a0 = 0;
+        let mut a1 = self.find_next_changing_element();
 
-                if b2 < a1 {
-                    // pass mode
-                    #[cfg(feature = "debug")]
-                    if self.debug {
-                        print!("P({})", b2);
-                    }
-                    self.output.write_bits(0b0001, 4);
-                    a0 = b2;
+        if self.done {
+            return;
+        }
+        let mut a2 = self.find_next_changing_element();
 
-                    self.find_b1_b2_at(a0, color, &mut b1, &mut b2);
-                } else {
-                    let d = (a1 as isize) - (b1 as isize);
-                    let v = match d {
-                        -3 => {
-                            #[cfg(feature = "debug")]
-                            if self.debug {
-                                print!("VL3");
-                            }
-                            self.output.write_bits(0b0000010, 7);
-                            true
-                        }
-                        -2 => {
-                            #[cfg(feature = "debug")]
-                            if self.debug {
-                                print!("VL2");
-                            }
-                            self.output.write_bits(0b000010, 6);
-                            true
-                        }
-                        -1 => {
-                            #[cfg(feature = "debug")]
-                            if self.debug {
-                                print!("VL1");
-                            }
-                            self.output.write_bits(0b010, 3);
-                            true
-                        }
-                        0 => {
-                            #[cfg(feature = "debug")]
-                            if self.debug {
-                                print!("V0");
-                            }
-                            self.output.write_bits(0b1, 1);
-                            true
+        let mut b1 = self.find_next_opposite_ref(color);
+        let mut b2 = self.find_next_changing_ref();
+
+        loop {
+            if a0 == 0 {
+                a0 = 1;
+            }
+
+            if b2 < a1 {
+                // pass mode
+                #[cfg(feature = "debug")]
+                if self.debug {
+                    print!("P({})", b2);
+                }
+                self.output.write_bits(0b0001, 4);
+                a0 = b2;
+
+                self.find_b1_b2_at(a0, color, &mut b1, &mut b2);
+            } else {
+                let d = (a1 as isize) - (b1 as isize);
+                let v = match d {
+                    -3 => {
+                        #[cfg(feature = "debug")]
+                        if self.debug {
+                            print!("VL3");
                         }
-                        1 => {
-                            #[cfg(feature = "debug")]
-                            if self.debug {
-                                print!("VR1");
-                            }
-                            self.output.write_bits(0b011, 3);
-                            true
+                        self.output.write_bits(0b0000010, 7);
+                        true
+                    }
+                    -2 => {
+                        #[cfg(feature = "debug")]
+                        if self.debug {
+                            print!("VL2");
                         }
-                        2 => {
-                            #[cfg(feature = "debug")]
-                            if self.debug {
-                                print!("VR2");
-                            }
-                            self.output.write_bits(0b000011, 6);
-                            true
+                        self.output.write_bits(0b000010, 6);
+                        true
+                    }
+                    -1 => {
+                        #[cfg(feature = "debug")]
+                        if self.debug {
+                            print!("VL1");
                         }
-                        3 => {
-                            #[cfg(feature = "debug")]
-                            if self.debug {
-                                print!("VR3");
-                            }
-                            self.output.write_bits(0b0000011, 7);
-                            true
+                        self.output.write_bits(0b010, 3);
+                        true
+                    }
+                    0 => {
+                        #[cfg(feature = "debug")]
+                        if self.debug {
+                            print!("V0");
                         }
-                        _ => false,
-                    };
-
-                    if v {
+                        self.output.write_bits(0b1, 1);
+                        true
+                    }
+                    1 => {
                         #[cfg(feature = "debug")]
                         if self.debug {
-                            print!("({})", b1);
+                            print!("VR1");
                         }
-                        a0 = a1;
-                        a1 = a2;
-                        a2 = self.find_next_changing_element();
-                        color.invert();
-                        self.find_b1_b2_at(a0, color, &mut b1, &mut b2);
-                    } else {
-                        // horizontal mode
-                        let a0a1 = a1 - a0;
-                        let a1a2 = a2 - a1;
+                        self.output.write_bits(0b011, 3);
+                        true
+                    }
+                    2 => {
                         #[cfg(feature = "debug")]
                         if self.debug {
-                            print!("H({},{})", a0a1, a1a2);
+                            print!("VR2");
                         }
-                        self.output.write_bits(0b001, 3);
-                        match color {
-                            Color::White => {
-                                write_white_len(&mut self.output, a0a1);
-                                write_black_len(&mut self.output, a1a2);
-                            }
-                            Color::Black => {
-                                write_black_len(&mut self.output, a0a1);
-                                write_white_len(&mut self.output, a1a2);
-                            }
+                        self.output.write_bits(0b000011, 6);
+                        true
+                    }
+                    3 => {
+                        #[cfg(feature = "debug")]
+                        if self.debug {
+                            print!("VR3");
                         }
-
-                        a0 = a2;
-                        a1 = self.find_next_changing_element();
-                        a2 = self.find_next_changing_element();
-                        self.find_b1_b2_at(a0, color, &mut b1, &mut b2);
+                        self.output.write_bits(0b0000011, 7);
+                        true
                     }
-                }
+                    _ => false,
+                };
 
-                if a0 > self.width {
+                if v {
                     #[cfg(feature = "debug")]
                     if self.debug {
-                        println!("#");
+                        print!("({})", b1);
+                    }
+                    a0 = a1;
+                    a1 = a2;
+                    a2 = self.find_next_changing_element();
+                    color.invert();
+                    self.find_b1_b2_at(a0, color, &mut b1, &mut b2);
+                } else {
+                    // horizontal mode
+                    let a0a1 = a1 - a0;
+                    let a1a2 = a2 - a1;
+                    #[cfg(feature = "debug")]
+                    if self.debug {
+                        print!("H({},{})", a0a1, a1a2);
+                    }
+                    self.output.write_bits(0b001, 3);
+                    match color {
+                        Color::White => {
+                            write_white_len(&mut self.output, a0a1);
+                            write_black_len(&mut self.output, a1a2);
+                        }
+                        Color::Black => {
+                            write_black_len(&mut self.output, a0a1);
+                            write_white_len(&mut self.output, a1a2);
+                        }
                     }
-                    break;
-                }
-            }
-
-            color = Color::White;
-            self.read_color = Color::White;
-            self.ref_color = Color::White;
-            self.read_pos = 0;
-            self.ref_pos = 0;
-            std::mem::swap(&mut self.reference, &mut self.current);
 
-            for _ in 0..self.skip_tail {
-                self.iter.next();
+                    a0 = a2;
+                    a1 = self.find_next_changing_element();
+                    a2 = self.find_next_changing_element();
+                    self.find_b1_b2_at(a0, color, &mut b1, &mut b2);
+                }
             }
 
-            if self.done {
+            if a0 > self.width {
+                #[cfg(feature = "debug")]
+                if self.debug {
+                    println!("#");
+                }
                 break;
             }
         }
-
-        self.output.write_bits(0b000000000001000000000001, 24);
-        self.output.done()
+        self.end_line();
     }
 }
 
@@ -4,7 +4,8 @@
 
 use crate::{
     bits::{BitIter, FillOrder},
-    FaxResult,
+    store::pack_row,
+    Color, FaxResult, Store,
 };
 
 mod decode;
@@ -13,7 +14,7 @@ mod encode;
 
 pub use crate::FaxImage;
 pub use decode::Decoder as G4Decoder;
-use decode_iter::FaxDecode;
+pub(crate) use decode_iter::{FaxDecode, Step};
 pub use encode::Encoder as G4Encoder;
 
 /// Options for fax decoding
@@ -26,6 +27,12 @@ pub struct FaxOptions {
     pub fill_order: FillOrder,
     /// Print to console after decoding
     pub debug: bool,
+    /// Return a partial image instead of failing on damaged data
+    ///
+    /// Damaged rows are filled in from the row above and decoding continues
+    /// at the next position from which multiple rows decode without errors.
+    /// See [`FaxImage::damaged_rows`] and [`G4Decoder::set_recover`].
+    pub recover: bool,
 }
 
 /// Decode a bitmap and print it to the console
@@ -36,5 +43,223 @@ pub fn fax_decode(glyph_data: &[u8], options: FaxOptions) -> FaxResult<FaxImage>
     bit_iter.set_fill_order(options.fill_order);
     let mut fax_decode = FaxDecode::new(options.width);
     fax_decode.set_debug(options.debug);
+    fax_decode.set_recover(options.recover);
     fax_decode.decode(&mut bit_iter)
 }
+
+/// Decode a bitmap into a [`Store`], e.g. a [`PackedBitmap`](crate::PackedBitmap)
+///
+/// Unlike [`fax_decode`], this does not keep a [`Color`] for every pixel in memory.
+pub fn fax_decode_into<S: Store<Row = Vec<Color>>>(
+    glyph_data: &[u8],
+    options: FaxOptions,
+    store: S,
+) -> FaxResult<S> {
+    let mut bit_iter = BitIter::new(glyph_data);
+    bit_iter.set_fill_order(options.fill_order);
+    let mut fax_decode = FaxDecode::with_store(options.width, store);
+    fax_decode.set_debug(options.debug);
+    fax_decode.set_recover(options.recover);
+    while fax_decode.decode_line(&mut bit_iter)? {}
+    Ok(fax_decode.into_store())
+}
+
+/// Keeps only the last row, packed
+#[derive(Default)]
+struct LastRow(Vec<u8>);
+
+impl Store for LastRow {
+    type Row = Vec<Color>;
+
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn new_row(width: usize) -> Self::Row {
+        vec![Color::White; width]
+    }
+
+    fn extend(&mut self, row: &Self::Row) {
+        self.0.clear();
+        pack_row(row, &mut self.0);
+    }
+}
+
+/// Decode a bitmap row by row
+///
+/// Every item is a row with one bit per pixel (`1` is black), most significant
+/// bit first and padded to a full byte. Only the current and the previous row
+/// are kept in memory, so this can be used to write large images to a PBM
+/// (`P4`) encoder as they are decoded. For a 1-bit grayscale PNG encoder, where
+/// `0` is black, the bits need to be inverted.
+pub struct FaxRows<'a> {
+    bit_iter: BitIter<'a>,
+    inner: FaxDecode<LastRow>,
+    done: bool,
+}
+
+impl<'a> FaxRows<'a> {
+    /// Create a new row iterator
+    pub fn new(data: &'a [u8], options: FaxOptions) -> Self {
+        let mut bit_iter = BitIter::new(data);
+        bit_iter.set_fill_order(options.fill_order);
+        let mut inner = FaxDecode::with_store(options.width, LastRow::new());
+        inner.set_debug(options.debug);
+        inner.set_recover(options.recover);
+        Self {
+            bit_iter,
+            inner,
+            done: false,
+        }
+    }
+
+    /// The indices of the rows returned so far that were damaged
+    ///
+    /// See [`FaxOptions::recover`]
+    pub fn damaged_rows(&self) -> &[usize] {
+        self.inner.damaged_rows()
+    }
+}
+
+impl Iterator for FaxRows<'_> {
+    type Item = FaxResult<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.inner.decode_line(&mut self.bit_iter) {
+            Ok(true) => Some(Ok(std::mem::take(&mut self.inner.store_mut().0))),
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fax_decode, fax_decode_into, FaxOptions, FaxRows, G4Decoder, G4Encoder};
+    use crate::{bits::BitIter, Color, FaxError, PackedBitmap};
+
+    const WIDTH: usize = 16;
+
+    fn image() -> Vec<u8> {
+        (0..16u8)
+            .flat_map(|row| [0x0F << (row % 4), 0xF0 >> (row % 3)])
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_damaged() {
+        let image = image();
+        let mut encoded = G4Encoder::new(WIDTH, &image).encode();
+        let options = || FaxOptions {
+            width: WIDTH,
+            ..FaxOptions::default()
+        };
+        let decoded = fax_decode(&encoded, options()).unwrap();
+        let expected: Vec<Color> = BitIter::new(&image).map(Color::from).collect();
+        assert_eq!(decoded.complete, expected);
+        assert!(decoded.damaged_rows().is_empty());
+
+        // break the second row
+        encoded[3] = 0x00;
+        let result = fax_decode(&encoded, options());
+        assert!(matches!(result, Err(FaxError::Damaged { row: 1 })));
+
+        let decoded = fax_decode(
+            &encoded,
+            FaxOptions {
+                recover: true,
+                ..options()
+            },
+        )
+        .unwrap();
+        assert_eq!(decoded.damaged_rows(), [1]);
+        assert_eq!(decoded.complete[..WIDTH], expected[..WIDTH]);
+        // rows may be lost until decoding is back in sync
+        assert!(decoded.height() > 2 && decoded.height() <= 16);
+    }
+
+    #[test]
+    fn test_decode_rows() {
+        let image = image();
+        let encoded = G4Encoder::new(WIDTH, &image).encode();
+        let options = || FaxOptions {
+            width: WIDTH,
+            ..FaxOptions::default()
+        };
+        let rows = FaxRows::new(&encoded, options())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(rows.len(), 16);
+        assert_eq!(rows.concat(), image);
+
+        let bitmap = fax_decode_into(&encoded, options(), PackedBitmap::default()).unwrap();
+        assert_eq!(bitmap.as_bytes(), image);
+    }
+
+    #[test]
+    fn test_state_machine_rows() {
+        let image = image();
+        let mut encoded = G4Encoder::new(WIDTH, &image).encode();
+        let mut decoder = G4Decoder::<Vec<Color>>::new(WIDTH);
+        decoder.decode(&encoded).unwrap();
+        assert_eq!(decoder.rows(), 16);
+
+        // break the second row
+        encoded[3] = 0x00;
+        let mut decoder = G4Decoder::<Vec<Color>>::new(WIDTH);
+        assert!(decoder.decode(&encoded).is_err());
+        assert_eq!(decoder.rows(), 1);
+
+        let mut decoder = G4Decoder::<Vec<Color>>::new(WIDTH);
+        decoder.set_recover(true);
+        decoder.decode(&encoded).unwrap();
+        assert_eq!(decoder.damaged_rows(), [1]);
+        let rows = decoder.rows();
+        let decoded = decoder.into_store();
+        assert_eq!(decoded.len(), rows * WIDTH);
+        let expected: Vec<Color> = BitIter::new(&image).map(Color::from).collect();
+        assert_eq!(decoded[..WIDTH], expected[..WIDTH]);
+        // the damaged row is filled in from the row above
+        assert_eq!(decoded[WIDTH..2 * WIDTH], expected[..WIDTH]);
+        assert!(rows > 2 && rows <= 16);
+    }
+
+    #[test]
+    fn test_empty() {
+        let decoded = fax_decode(&[], FaxOptions::default()).unwrap();
+        assert_eq!(decoded.width(), 0);
+        assert_eq!(decoded.height(), 0);
+    }
+
+    #[test]
+    fn test_trailing_fill_bits() {
+        // V0 | V0 | fill bits, without EOFB
+        let data = [0b1100_0000];
+        let options = || FaxOptions {
+            width: 8,
+            ..FaxOptions::default()
+        };
+        let decoded = fax_decode(&data, options()).unwrap();
+        assert_eq!(decoded.height(), 2);
+        assert_eq!(decoded.complete, [Color::White; 16]);
+
+        let mut decoder = G4Decoder::<Vec<Color>>::new(8);
+        decoder.decode(&data).unwrap();
+        assert_eq!(decoder.rows(), 2);
+
+        // a code word that is cut off is still an error
+        let data = [0b1100_0001];
+        assert!(fax_decode(&data, options()).is_err());
+        let mut decoder = G4Decoder::<Vec<Color>>::new(8);
+        assert!(decoder.decode(&data).is_err());
+    }
+}
@@ -6,9 +6,32 @@ use crate::{ascii_art::BorderDrawing, Color, ASCII};
 pub struct FaxImage {
     pub(crate) width: usize,
     pub(crate) complete: Vec<Color>,
+    pub(crate) damaged: Vec<usize>,
 }
 
 impl FaxImage {
+    /// The width of the image
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The number of rows in the image
+    pub fn height(&self) -> usize {
+        self.complete.len().checked_div(self.width).unwrap_or(0)
+    }
+
+    /// The rows that could not be decoded and were filled in from the row above
+    ///
+    /// This is only ever non-empty when decoding in recovery mode.
+    pub fn damaged_rows(&self) -> &[usize] {
+        &self.damaged
+    }
+
+    /// The number of rows that were decoded from the data
+    pub fn recovered_rows(&self) -> usize {
+        self.height() - self.damaged.len()
+    }
+
     fn print_border(&self, b: &BorderDrawing) {
         print!("{}", b.left);
         for _ in 0..self.width {
@@ -21,7 +44,7 @@ impl FaxImage {
     pub fn print(&self, invert: bool) {
         let b = ASCII;
         self.print_border(&b.top);
-        for row in self.complete.chunks_exact(self.width) {
+        for row in self.complete.chunks_exact(self.width.max(1)) {
             print!("{}", b.left);
             for bit in row {
                 let ink = bool::from(*bit) ^ invert;
@@ -49,7 +72,7 @@ impl FaxImage {
             height <<= 1;
         }
         writeln!(writer, "P1 {} {}", self.width, height)?;
-        for row in RepeatIter::new(self.complete.chunks_exact(self.width), 2) {
+        for row in RepeatIter::new(self.complete.chunks_exact(self.width.max(1)), 2) {
             for bit in row {
                 // PBM: 1 is black, 0 is white
                 let ink = bool::from(*bit) ^ invert;
@@ -21,10 +21,11 @@ pub mod g42d;
 mod image;
 mod store;
 pub(crate) mod terminals;
+pub mod tiff;
 
 pub(crate) use ascii_art::ASCII;
 pub use ascii_art::{ascii_art, pbm_to_io_writer};
 pub use color::Color;
 pub use error::{FaxError, FaxResult};
 pub use image::FaxImage;
-pub use store::{ColorLine, Store};
+pub use store::{ColorLine, PackedBitmap, Store};
@@ -73,3 +73,52 @@ impl Store for Vec<Color> {
         self.extend_from_slice(row);
     }
 }
+
+/// Append a row to `out` with one bit per pixel (`1` is black), padded to a full byte
+pub(crate) fn pack_row(row: &[Color], out: &mut Vec<u8>) {
+    out.extend(row.chunks(8).map(|chunk| {
+        chunk
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c == Color::Black)
+            .fold(0u8, |byte, (i, _)| byte | (0x80 >> i))
+    }));
+}
+
+/// A bitmap with one bit per pixel (`1` is black), most significant bit first
+///
+/// Every row is padded to a full byte, which is the layout of the data in
+/// binary PBM (`P4`) files. For a 1-bit grayscale PNG, where `0` is black,
+/// the bits need to be inverted.
+#[derive(Debug, Default, Clone)]
+pub struct PackedBitmap {
+    data: Vec<u8>,
+}
+
+impl PackedBitmap {
+    /// The packed rows
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Return the packed rows
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl Store for PackedBitmap {
+    type Row = Vec<Color>;
+
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn new_row(width: usize) -> Self::Row {
+        vec![Color::White; width]
+    }
+
+    fn extend(&mut self, row: &Self::Row) {
+        pack_row(row, &mut self.data);
+    }
+}
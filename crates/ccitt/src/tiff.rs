@@ -0,0 +1,285 @@
+//! # TIFF writer
+//!
+//! Writes bi-level images as CCITT Group 4 compressed pages (TIFF Class F) into
+//! a single, possibly multi-page, little-endian TIFF file.
+//!
+//! Spec: TIFF Revision 6.0 <https://www.itu.int/itudoc/itu-t/com16/tiff-fx/docs/tiff6.pdf>
+
+use std::{
+    convert::TryInto,
+    io::{self, Seek, SeekFrom, Write},
+};
+
+use crate::g42d::G4Encoder;
+
+const NEW_SUBFILE_TYPE: u16 = 254;
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const BITS_PER_SAMPLE: u16 = 258;
+const COMPRESSION: u16 = 259;
+const PHOTOMETRIC_INTERPRETATION: u16 = 262;
+const FILL_ORDER: u16 = 266;
+const STRIP_OFFSETS: u16 = 273;
+const SAMPLES_PER_PIXEL: u16 = 277;
+const ROWS_PER_STRIP: u16 = 278;
+const STRIP_BYTE_COUNTS: u16 = 279;
+const X_RESOLUTION: u16 = 282;
+const Y_RESOLUTION: u16 = 283;
+const T6_OPTIONS: u16 = 293;
+const RESOLUTION_UNIT: u16 = 296;
+const PAGE_NUMBER: u16 = 297;
+
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+
+/// `NewSubfileType`: a single page of a multi-page image
+const SUBFILE_PAGE: u32 = 2;
+/// `Compression`: CCITT T.6 bi-level encoding
+const COMPRESSION_G4: u32 = 4;
+/// `PhotometricInterpretation`: 0 is white
+const WHITE_IS_ZERO: u32 = 0;
+/// `ResolutionUnit`: inch
+const INCH: u32 = 2;
+
+/// The number of entries in every IFD
+const IFD_ENTRIES: u16 = 16;
+
+/// A bi-level page image
+#[derive(Debug, Copy, Clone)]
+pub struct TiffPage<'a> {
+    /// The width in pixels
+    pub width: u32,
+    /// The height in pixels
+    pub height: u32,
+    /// The rows of the image, with `1` for black, each padded to `bytes_per_line`
+    pub bitmap: &'a [u8],
+    /// The number of bytes per row in `bitmap`
+    pub bytes_per_line: u32,
+    /// The horizontal and vertical resolution in dots per inch
+    pub dpi: (u32, u32),
+}
+
+/// Writes pages into a multi-page TIFF file
+pub struct TiffWriter<W: Write + Seek> {
+    inner: W,
+    /// The position of the header, all offsets are relative to it
+    start: u64,
+    /// The position of the offset to the next IFD
+    next_ifd: u64,
+    /// The number of pages written so far
+    pages: u16,
+}
+
+impl<W: Write + Seek> TiffWriter<W> {
+    /// Write the TIFF header and return a new writer
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        let start = inner.stream_position()?;
+        inner.write_all(b"II")?;
+        inner.write_all(&42u16.to_le_bytes())?;
+        inner.write_all(&0u32.to_le_bytes())?;
+        Ok(Self {
+            inner,
+            start,
+            next_ifd: start + 4,
+            pages: 0,
+        })
+    }
+
+    /// Encode a page with [`G4Encoder`] and add it to the file
+    ///
+    /// Fails if the `width` does not fit into `bytes_per_line`.
+    pub fn write_page(&mut self, page: &TiffPage) -> io::Result<()> {
+        let skip_tail = (page.bytes_per_line as usize * 8)
+            .checked_sub(page.width as usize)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the page is wider than `bytes_per_line`",
+                )
+            })?;
+        let len = page.bytes_per_line as usize * page.height as usize;
+        let bitmap = &page.bitmap[..len.min(page.bitmap.len())];
+        let mut encoder = G4Encoder::new(page.width as usize, bitmap);
+        encoder.skip_tail = skip_tail;
+        let data = encoder.encode();
+        self.write_encoded(page, &data)
+    }
+
+    /// Add a page that is already encoded with CCITT Group 4
+    ///
+    /// The `bitmap` of the page is ignored.
+    pub fn write_encoded(&mut self, page: &TiffPage, data: &[u8]) -> io::Result<()> {
+        let data_offset = self.align()?;
+        self.inner.write_all(data)?;
+        let ifd_offset = self.align()?;
+        // the rationals are placed directly after the IFD
+        let x_res_offset = ifd_offset + 2 + 12 * u32::from(IFD_ENTRIES) + 4;
+        let y_res_offset = x_res_offset + 8;
+
+        let entries: [(u16, u16, u32, u32); IFD_ENTRIES as usize] = [
+            (NEW_SUBFILE_TYPE, LONG, 1, SUBFILE_PAGE),
+            (IMAGE_WIDTH, LONG, 1, page.width),
+            (IMAGE_LENGTH, LONG, 1, page.height),
+            (BITS_PER_SAMPLE, SHORT, 1, 1),
+            (COMPRESSION, SHORT, 1, COMPRESSION_G4),
+            (PHOTOMETRIC_INTERPRETATION, SHORT, 1, WHITE_IS_ZERO),
+            (FILL_ORDER, SHORT, 1, 1),
+            (STRIP_OFFSETS, LONG, 1, data_offset),
+            (SAMPLES_PER_PIXEL, SHORT, 1, 1),
+            (ROWS_PER_STRIP, LONG, 1, page.height),
+            (STRIP_BYTE_COUNTS, LONG, 1, to_u32(data.len())?),
+            (X_RESOLUTION, RATIONAL, 1, x_res_offset),
+            (Y_RESOLUTION, RATIONAL, 1, y_res_offset),
+            (T6_OPTIONS, LONG, 1, 0),
+            (RESOLUTION_UNIT, SHORT, 1, INCH),
+            // the total number of pages is unknown (0)
+            (PAGE_NUMBER, SHORT, 2, u32::from(self.pages)),
+        ];
+
+        self.inner.write_all(&IFD_ENTRIES.to_le_bytes())?;
+        for (tag, kind, count, value) in entries {
+            self.inner.write_all(&tag.to_le_bytes())?;
+            self.inner.write_all(&kind.to_le_bytes())?;
+            self.inner.write_all(&count.to_le_bytes())?;
+            if kind == SHORT {
+                // left-justified, i.e. the first short in the lower bytes
+                self.inner.write_all(&(value as u16).to_le_bytes())?;
+                self.inner.write_all(&[0, 0])?;
+            } else {
+                self.inner.write_all(&value.to_le_bytes())?;
+            }
+        }
+        let next_ifd = self.inner.stream_position()?;
+        self.inner.write_all(&0u32.to_le_bytes())?;
+        for dpi in [page.dpi.0, page.dpi.1] {
+            self.inner.write_all(&dpi.to_le_bytes())?;
+            self.inner.write_all(&1u32.to_le_bytes())?;
+        }
+
+        self.link(ifd_offset)?;
+        self.next_ifd = next_ifd;
+        self.pages += 1;
+        Ok(())
+    }
+
+    /// The number of pages written so far
+    pub fn pages(&self) -> u16 {
+        self.pages
+    }
+
+    /// Flush and return the inner writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Pad the output to a word boundary and return the offset
+    fn align(&mut self) -> io::Result<u32> {
+        let offset = self.inner.stream_position()? - self.start;
+        if offset % 2 == 1 {
+            self.inner.write_all(&[0])?;
+        }
+        to_u32(offset + offset % 2)
+    }
+
+    /// Point the previous IFD (or the header) to the IFD at `offset`
+    fn link(&mut self, offset: u32) -> io::Result<()> {
+        let end = self.inner.stream_position()?;
+        self.inner.seek(SeekFrom::Start(self.next_ifd))?;
+        self.inner.write_all(&offset.to_le_bytes())?;
+        self.inner.seek(SeekFrom::Start(end))?;
+        Ok(())
+    }
+}
+
+fn to_u32<T: TryInto<u32>>(value: T) -> io::Result<u32> {
+    value
+        .try_into()
+        .map_err(|_| io::Error::other("TIFF files are limited to 4 GiB"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use ::tiff::{
+        decoder::{ifd::Value, Decoder},
+        tags::Tag,
+    };
+
+    use super::{TiffPage, TiffWriter};
+    use crate::{
+        bits::BitIter,
+        g42d::{fax_decode, FaxOptions},
+        Color,
+    };
+
+    #[test]
+    fn test_write_tiff() {
+        #[rustfmt::skip]
+        let bitmap = [
+            0x00, 0x3C,
+            0x00, 0x7E,
+            0x01, 0xFF,
+            0x0F, 0x00,
+            0xFF, 0xFF,
+        ];
+        let mut writer = TiffWriter::new(Cursor::new(Vec::new())).unwrap();
+        for height in [5, 3] {
+            let page = TiffPage {
+                width: 14,
+                height,
+                bitmap: &bitmap[..height as usize * 2],
+                bytes_per_line: 2,
+                dpi: (300, 150),
+            };
+            writer.write_page(&page).unwrap();
+        }
+        assert_eq!(writer.pages(), 2);
+        let file = writer.into_inner().unwrap().into_inner();
+
+        let mut decoder = Decoder::new(Cursor::new(file.clone())).unwrap();
+        for (page, height) in [(0, 5), (1, 3)] {
+            assert_eq!(decoder.dimensions().unwrap(), (14, height));
+            let page_number = decoder.get_tag(Tag::Unknown(297)).unwrap();
+            assert_eq!(page_number.into_u16_vec().unwrap(), [page, 0]);
+            let x_res = decoder.get_tag(Tag::XResolution).unwrap();
+            assert!(matches!(x_res, Value::Rational(300, 1)));
+
+            let offset = decoder.get_tag_u32(Tag::StripOffsets).unwrap() as usize;
+            let len = decoder.get_tag_u32(Tag::StripByteCounts).unwrap() as usize;
+            let options = FaxOptions {
+                width: 14,
+                ..FaxOptions::default()
+            };
+            let image = fax_decode(&file[offset..][..len], options).unwrap();
+            let expected: Vec<Color> = bitmap
+                .chunks(2)
+                .take(height as usize)
+                .flat_map(|row| BitIter::new(row).take(14).map(Color::from))
+                .collect();
+            assert_eq!(image.complete, expected);
+
+            if decoder.more_images() {
+                decoder.next_image().unwrap();
+            }
+        }
+        assert!(!decoder.more_images());
+    }
+
+    #[test]
+    fn test_write_too_wide() {
+        let mut writer = TiffWriter::new(Cursor::new(Vec::new())).unwrap();
+        let page = TiffPage {
+            width: 17,
+            height: 1,
+            bitmap: &[0x00, 0x00],
+            bytes_per_line: 2,
+            dpi: (300, 300),
+        };
+        let err = writer.write_page(&page).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(writer.pages(), 0);
+    }
+}
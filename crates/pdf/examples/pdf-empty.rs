@@ -25,6 +25,7 @@ pub fn main() -> eyre::Result<()> {
         },
         resources: Resources::default(),
         contents: Vec::new(),
+        links: Vec::new(),
     };
 
     // Add the page to the document
@@ -60,6 +60,7 @@ pub fn main() -> eyre::Result<()> {
         },
         resources,
         contents: contents.into_bytes(),
+        links: Vec::new(),
     };
     doc.pages.push(page);
 
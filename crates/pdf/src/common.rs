@@ -14,7 +14,7 @@ use crate::{
 };
 
 /// A PDF Byte string
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct PdfString(Vec<u8>);
 
 impl PdfString {
@@ -256,7 +256,12 @@ pub struct FontDescriptor {
 
 impl Serialize for FontDescriptor {
     fn write(&self, f: &mut Formatter) -> io::Result<()> {
-        let mut dict = f.pdf_dict();
+        f.pdf_dict().embed(self)?.finish()
+    }
+}
+
+impl ToDict for FontDescriptor {
+    fn write(&self, dict: &mut crate::write::PdfDict<'_, '_>) -> io::Result<()> {
         dict.field("Type", &PdfName("FontDescriptor"))?
             .field("FontName", &self.font_name.as_ref())?
             .field("FontFamily", &self.font_family)?
@@ -271,8 +276,7 @@ impl Serialize for FontDescriptor {
             .opt_field("CapHeight", &self.cap_height)?
             .opt_field("XHeight", &self.x_height)?
             .opt_field("StemV", &self.stem_v)?
-            .opt_field("StemH", &self.stem_h)?
-            .finish()?;
+            .opt_field("StemH", &self.stem_h)?;
         Ok(())
     }
 }
@@ -304,6 +308,64 @@ impl Serialize for PageLabelKind {
     }
 }
 
+/// A standard structure type of a tagged PDF
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StructType {
+    /// A complete document, usually the root element
+    Document,
+    /// A large division of a document
+    Part,
+    /// A section of a document
+    Sect,
+    /// A generic block-level element
+    Div,
+    /// A paragraph
+    P,
+    /// A heading
+    H,
+    /// A heading of level 1
+    H1,
+    /// A heading of level 2
+    H2,
+    /// A heading of level 3
+    H3,
+    /// A figure, i.e. an image or a drawing
+    Figure,
+    /// A caption of a figure or table
+    Caption,
+    /// A generic inline element
+    Span,
+    /// A link, i.e. a link annotation
+    Link,
+}
+
+impl StructType {
+    /// The name of the structure type, e.g. for the tag of marked content
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Document => "Document",
+            Self::Part => "Part",
+            Self::Sect => "Sect",
+            Self::Div => "Div",
+            Self::P => "P",
+            Self::H => "H",
+            Self::H1 => "H1",
+            Self::H2 => "H2",
+            Self::H3 => "H3",
+            Self::Figure => "Figure",
+            Self::Caption => "Caption",
+            Self::Span => "Span",
+            Self::Link => "Link",
+        }
+    }
+}
+
+impl Serialize for StructType {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        PdfName(self.name()).write(f)
+    }
+}
+
 /// Specification for the labels of a sequence of pages
 #[derive(Debug, Clone)]
 pub struct PageLabel {
@@ -379,6 +441,11 @@ impl<T> NumberTree<T> {
     pub fn insert(&mut self, key: usize, value: T) -> Option<T> {
         self.inner.insert(key, value)
     }
+
+    /// Checks whether the tree contains the key
+    pub fn contains_key(&self, key: usize) -> bool {
+        self.inner.contains_key(&key)
+    }
 }
 
 impl<T: Serialize> Serialize for NumberTree<T> {
@@ -387,6 +454,62 @@ impl<T: Serialize> Serialize for NumberTree<T> {
     }
 }
 
+/// A tree of names, e.g. for named destinations
+///
+/// The tree is written as a single root node, with the keys in lexical order.
+pub struct NameTree<T> {
+    inner: BTreeMap<PdfString, T>,
+}
+
+impl<T> Default for NameTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<BTreeMap<PdfString, T>> for NameTree<T> {
+    fn from(tree: BTreeMap<PdfString, T>) -> Self {
+        Self { inner: tree }
+    }
+}
+
+impl<T> NameTree<T> {
+    /// Creates a new tree
+    pub fn new() -> Self {
+        Self {
+            inner: BTreeMap::new(),
+        }
+    }
+
+    /// Checks whether the tree is empty
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Inserts a node into the tree
+    pub fn insert(&mut self, key: PdfString, value: T) -> Option<T> {
+        self.inner.insert(key, value)
+    }
+
+    /// Returns the value of a node
+    pub fn get(&self, key: &PdfString) -> Option<&T> {
+        self.inner.get(key)
+    }
+
+    /// Iterate over all nodes in lexical order
+    pub fn iter(&self) -> impl Iterator<Item = (&PdfString, &T)> {
+        self.inner.iter()
+    }
+}
+
+impl<T: Serialize> Serialize for NameTree<T> {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        f.pdf_dict()
+            .field("Names", &BTreeSer(&self.inner))?
+            .finish()
+    }
+}
+
 #[derive(Debug, Clone)]
 /// A vector of options
 pub struct SparseSet<T> {
@@ -876,6 +999,43 @@ impl ToDict for ImageMetadata {
     }
 }
 
+/// The `DecodeParms` of the `CCITTFaxDecode` filter
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CCITTFaxDecodeParams {
+    /// The coding scheme, `< 0` for Group 4, `0` for Group 3 1D and `> 0`
+    /// for mixed Group 3 1D and 2D coding
+    pub k: i32,
+    /// The width of the image in pixels
+    pub columns: usize,
+    /// The height of the image in pixels (`0` if unknown)
+    pub rows: usize,
+    /// Whether `1` bits are black, i.e. the decoded data does not need to be inverted
+    pub black_is_1: bool,
+}
+
+impl CCITTFaxDecodeParams {
+    /// Parameters for an image with Group 4 (T.6) coding
+    pub fn group_4(columns: usize, rows: usize) -> Self {
+        Self {
+            k: -1,
+            columns,
+            rows,
+            black_is_1: false,
+        }
+    }
+}
+
+impl Serialize for CCITTFaxDecodeParams {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        f.pdf_dict()
+            .field("K", &self.k)?
+            .field("Columns", &self.columns)?
+            .default_field("Rows", &self.rows)?
+            .default_field("BlackIs1", &self.black_is_1)?
+            .finish()
+    }
+}
+
 /// The metadata for a stream
 #[derive(Debug, Copy, Clone)]
 pub enum StreamMetadata {
@@ -887,6 +1047,8 @@ pub enum StreamMetadata {
     ColorProfile(ICCColorProfileMetadata),
     /// Metadata
     MetadataXML,
+    /// A CFF font program, embedded as `/FontFile3`
+    Type1C,
 }
 
 impl ToDict for StreamMetadata {
@@ -900,6 +1062,10 @@ impl ToDict for StreamMetadata {
                 dict.field("Subtype", &PdfName("XML"))?;
                 Ok(())
             }
+            Self::Type1C => {
+                dict.field("Subtype", &PdfName("Type1C"))?;
+                Ok(())
+            }
         }
     }
 }
@@ -938,7 +1104,7 @@ impl Serialize for Trapped {
 }
 
 #[allow(missing_docs, non_camel_case_types, clippy::upper_case_acronyms)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum OutputIntentSubtype {
     GTS_PDFX,
     GTS_PDFA1,
@@ -0,0 +1,230 @@
+//! Read the advance widths from a *Compact Font Format* (CFF) font program
+//!
+//! See Adobe Tech Note #5176 *The Compact Font Format Specification* and
+//! Adobe Tech Note #5177 *The Type 2 Charstring Format*.
+
+use std::collections::BTreeMap;
+
+/// The number of standard strings, i.e. the first custom SID
+const N_STD_STRINGS: usize = 391;
+
+// Top DICT operators
+const OP_CHARSET: u16 = 15;
+const OP_CHAR_STRINGS: u16 = 17;
+const OP_PRIVATE: u16 = 18;
+
+// Private DICT operators
+const OP_DEFAULT_WIDTH_X: u16 = 20;
+const OP_NOMINAL_WIDTH_X: u16 = 21;
+
+fn be16(data: &[u8], at: usize) -> Option<usize> {
+    let bytes = data.get(at..at + 2)?;
+    Some(usize::from(u16::from_be_bytes([bytes[0], bytes[1]])))
+}
+
+/// Read an INDEX structure, returning the items and the offset after it
+fn index(data: &[u8], at: usize) -> Option<(Vec<&[u8]>, usize)> {
+    let count = be16(data, at)?;
+    if count == 0 {
+        return Some((vec![], at + 2));
+    }
+    let off_size = usize::from(*data.get(at + 2)?);
+    let offset = |i: usize| -> Option<usize> {
+        let start = at + 3 + i * off_size;
+        let bytes = data.get(start..start + off_size)?;
+        Some(bytes.iter().fold(0, |acc, b| acc << 8 | usize::from(*b)))
+    };
+    let base = at + 2 + (count + 1) * off_size;
+    let mut items = Vec::with_capacity(count);
+    for i in 0..count {
+        items.push(data.get(base + offset(i)?..base + offset(i + 1)?)?);
+    }
+    Some((items, base + offset(count)?))
+}
+
+/// Read the operands and operators of a DICT
+fn dict(data: &[u8]) -> Option<BTreeMap<u16, Vec<f32>>> {
+    let mut out = BTreeMap::new();
+    let mut operands = Vec::new();
+    let mut i = 0;
+    while let Some(&b0) = data.get(i) {
+        i += 1;
+        match b0 {
+            0..=11 | 13..=21 => {
+                out.insert(u16::from(b0), std::mem::take(&mut operands));
+            }
+            12 => {
+                let b1 = *data.get(i)?;
+                i += 1;
+                out.insert(0x0C00 | u16::from(b1), std::mem::take(&mut operands));
+            }
+            28 => {
+                operands.push(f32::from(i16::from_be_bytes([
+                    *data.get(i)?,
+                    *data.get(i + 1)?,
+                ])));
+                i += 2;
+            }
+            29 => {
+                let bytes = data.get(i..i + 4)?;
+                let v = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                operands.push(v as f32);
+                i += 4;
+            }
+            30 => {
+                // real numbers are not needed here, skip to the end nibble
+                while data.get(i)? & 0x0F != 0x0F && data.get(i)? >> 4 != 0x0F {
+                    i += 1;
+                }
+                i += 1;
+                operands.push(0.0);
+            }
+            32..=246 => operands.push(f32::from(i16::from(b0) - 139)),
+            247..=254 => {
+                let b1 = i16::from(*data.get(i)?);
+                i += 1;
+                let v = match b0 {
+                    247..=250 => (i16::from(b0) - 247) * 256 + b1 + 108,
+                    _ => -(i16::from(b0) - 251) * 256 - b1 - 108,
+                };
+                operands.push(f32::from(v));
+            }
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+/// Get the advance width of a Type 2 charstring
+///
+/// The width is the optional first operand of the first stack-clearing
+/// operator. Returns `None` if that is in a subroutine.
+fn charstring_width(data: &[u8], default: f32, nominal: f32) -> Option<f32> {
+    let mut operands = Vec::new();
+    let mut i = 0;
+    while let Some(&b0) = data.get(i) {
+        i += 1;
+        let has_width = match b0 {
+            // hstem, vstem, hstemhm, hintmask, cntrmask, vstemhm
+            1 | 3 | 18 | 19 | 20 | 23 => operands.len() % 2 == 1,
+            // rmoveto
+            21 => operands.len() > 2,
+            // vmoveto, hmoveto
+            4 | 22 => operands.len() > 1,
+            // endchar
+            14 => operands.len() == 1 || operands.len() == 5,
+            // callsubr, callgsubr
+            10 | 29 => return None,
+            28 => {
+                operands.push(f32::from(i16::from_be_bytes([
+                    *data.get(i)?,
+                    *data.get(i + 1)?,
+                ])));
+                i += 2;
+                continue;
+            }
+            0..=31 => false,
+            32..=246 => {
+                operands.push(f32::from(i16::from(b0) - 139));
+                continue;
+            }
+            247..=254 => {
+                let b1 = i16::from(*data.get(i)?);
+                i += 1;
+                let v = match b0 {
+                    247..=250 => (i16::from(b0) - 247) * 256 + b1 + 108,
+                    _ => -(i16::from(b0) - 251) * 256 - b1 - 108,
+                };
+                operands.push(f32::from(v));
+                continue;
+            }
+            255 => {
+                let bytes = data.get(i..i + 4)?;
+                let v = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                operands.push(v as f32 / 65536.0);
+                i += 4;
+                continue;
+            }
+        };
+        return Some(if has_width {
+            nominal + operands[0]
+        } else {
+            default
+        });
+    }
+    None
+}
+
+/// Read the SIDs of the glyphs from a charset, starting with GID 1
+fn charset(data: &[u8], at: usize, n_glyphs: usize) -> Option<Vec<usize>> {
+    let n_sids = n_glyphs.saturating_sub(1);
+    let mut sids = Vec::with_capacity(n_sids);
+    let mut i = at + 1;
+    while sids.len() < n_sids {
+        match data.get(at)? {
+            0 => {
+                sids.push(be16(data, i)?);
+                i += 2;
+            }
+            format @ 1 | format @ 2 => {
+                let first = be16(data, i)?;
+                let n_left = match format {
+                    1 => usize::from(*data.get(i + 2)?),
+                    _ => be16(data, i + 2)?,
+                };
+                sids.extend(first..=first + n_left);
+                i += 2 + usize::from(*format);
+            }
+            _ => return None,
+        }
+    }
+    sids.truncate(n_sids);
+    Some(sids)
+}
+
+/// Get the advance width of every glyph in a CFF font program, by glyph name
+///
+/// Only glyphs that are named by a custom string are included, as the
+/// standard strings are not known here.
+pub(super) fn advance_widths(data: &[u8]) -> Option<BTreeMap<&str, f32>> {
+    let (_names, at) = index(data, usize::from(*data.get(2)?))?;
+    let (top_dicts, at) = index(data, at)?;
+    let (strings, _) = index(data, at)?;
+    let top_dict = dict(top_dicts.first()?)?;
+    let offset = |dict: &BTreeMap<u16, Vec<f32>>, op, i: usize| {
+        dict.get(&op).and_then(|v| v.get(i)).map(|v| *v as usize)
+    };
+
+    let (char_strings, _) = index(data, offset(&top_dict, OP_CHAR_STRINGS, 0)?)?;
+    // The predefined charsets (0, 1 and 2) only use standard strings
+    let sids = match offset(&top_dict, OP_CHARSET, 0).unwrap_or(0) {
+        0..=2 => vec![],
+        at => charset(data, at, char_strings.len())?,
+    };
+
+    let (default, nominal) = match top_dict.get(&OP_PRIVATE).map(Vec::as_slice) {
+        Some(&[size, at]) => {
+            let (size, at) = (size as usize, at as usize);
+            let private = dict(data.get(at..at + size)?)?;
+            let value = |op| private.get(&op).and_then(|v| v.first()).copied();
+            (
+                value(OP_DEFAULT_WIDTH_X).unwrap_or(0.0),
+                value(OP_NOMINAL_WIDTH_X).unwrap_or(0.0),
+            )
+        }
+        _ => (0.0, 0.0),
+    };
+
+    let mut widths = BTreeMap::new();
+    for (sid, char_string) in sids.into_iter().zip(char_strings.iter().skip(1)) {
+        let name = sid
+            .checked_sub(N_STD_STRINGS)
+            .and_then(|i| strings.get(i))
+            .and_then(|name| std::str::from_utf8(name).ok());
+        let width = charstring_width(char_string, default, nominal);
+        if let (Some(name), Some(width)) = (name, width) {
+            widths.insert(name, width);
+        }
+    }
+    Some(widths)
+}
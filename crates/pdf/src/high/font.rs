@@ -1,7 +1,9 @@
+use std::borrow::Cow;
+
 use crate::{
     common::{Dict, Encoding, FontDescriptor, Matrix, ObjRef, Point, Rectangle},
     low,
-    lowering::{DebugName, LowerBox, Lowerable},
+    lowering::{make_ref, DebugName, LowerBox, Lowerable},
     util::NextId,
     write::PdfNameBuf,
 };
@@ -56,11 +58,47 @@ impl Default for Type3Font<'_> {
     }
 }
 
+#[derive(Debug, Clone)]
+/// An embedded font program
+pub enum FontFile<'a> {
+    /// A CFF font program (`/FontFile3` with `/Subtype /Type1C`)
+    Type1C(Cow<'a, [u8]>),
+}
+
+impl DebugName for FontFile<'_> {
+    fn debug_name() -> &'static str {
+        "FontFile"
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A type 1 font, i.e. a font with PostScript outlines
+pub struct Type1Font<'a> {
+    /// The PostScript name of the font
+    pub base_font: PdfNameBuf,
+    /// The first used char key
+    pub first_char: u8,
+    /// The last used char key
+    pub last_char: u8,
+    /// Width of every char between first and last (in 1/1000 of text space units)
+    pub widths: Vec<u32>,
+    /// Font characteristics
+    pub font_descriptor: FontDescriptor,
+    /// The embedded font program
+    pub font_file: Option<FontFile<'a>>,
+    /// Dict of encoding value to char names
+    pub encoding: Encoding<'a>,
+    /// ToUnicode CMap stream
+    pub to_unicode: Option<Resource<ToUnicode>>,
+}
+
 #[derive(Debug, Clone)]
 /// A Font resource
 pub enum Font<'a> {
     /// A type 3 font i.e. arbitrary glyph drawings
     Type3(Type3Font<'a>),
+    /// A type 1 font i.e. glyph outlines
+    Type1(Type1Font<'a>),
 }
 
 impl DebugName for Font<'_> {
@@ -83,6 +121,8 @@ pub(crate) struct LowerFontCtx<'a> {
     text_streams: LowerBox<'a, Ascii85Stream<'a>>,
     encodings: LowerBox<'a, Encoding<'a>>,
     to_unicode: LowerBox<'a, ToUnicode>,
+    font_files: LowerBox<'a, FontFile<'a>>,
+    font_descriptors: Vec<(ObjRef, low::FontDescriptor<'a>)>,
 }
 
 impl<'a> LowerFontCtx<'a> {
@@ -95,9 +135,21 @@ impl<'a> LowerFontCtx<'a> {
             text_streams: LowerBox::new(char_procs),
             encodings: LowerBox::new(encodings),
             to_unicode: LowerBox::new(to_unicode),
+            font_files: LowerBox::new(&[]),
+            font_descriptors: Vec::new(),
         }
     }
 
+    pub(crate) fn font_file_values(&self) -> impl Iterator<Item = (ObjRef, &'a FontFile<'a>)> + '_ {
+        self.font_files.store_values()
+    }
+
+    pub(crate) fn font_descriptor_values(
+        &self,
+    ) -> impl Iterator<Item = &(ObjRef, low::FontDescriptor<'a>)> + '_ {
+        self.font_descriptors.iter()
+    }
+
     pub(crate) fn text_stream_values(
         &self,
     ) -> impl Iterator<Item = (ObjRef, &'a Ascii85Stream<'a>)> + '_ {
@@ -145,5 +197,34 @@ fn lower_font<'a>(
                 to_unicode,
             })
         }
+        Font::Type1(font) => {
+            let font_file = font
+                .font_file
+                .as_ref()
+                .map(|file| (file, ctx.font_files.put(file, id_gen)));
+            let descriptor_ref = make_ref(id_gen.next());
+            ctx.font_descriptors.push((
+                descriptor_ref,
+                low::FontDescriptor {
+                    inner: &font.font_descriptor,
+                    font_file: font_file.map(|(file, r)| match file {
+                        FontFile::Type1C(_) => low::FontFileRef::FontFile3(r),
+                    }),
+                },
+            ));
+            let to_unicode = font
+                .to_unicode
+                .as_ref()
+                .map(|res| ctx.to_unicode.map_ref(res, id_gen));
+            low::Font::Type1(low::Type1Font {
+                base_font: font.base_font.as_ref(),
+                first_char: font.first_char,
+                last_char: font.last_char,
+                widths: &font.widths,
+                font_descriptor: descriptor_ref,
+                encoding: low::Resource::Immediate(font.encoding.clone()),
+                to_unicode,
+            })
+        }
     }
 }
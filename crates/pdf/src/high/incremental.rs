@@ -0,0 +1,368 @@
+use std::{borrow::Cow, collections::HashSet, io};
+
+use crate::{
+    common::{ObjRef, PdfString, StreamMetadata},
+    low,
+    lowering::{lower_global_resources, lower_link, make_ref, GlobalRefs, LowerPagesCtx, Lowering},
+    write::Formatter,
+};
+
+use super::{stream::ToStream, write_resources, Handle, Head, LinkAction, Page};
+
+/// Writes a document page by page
+///
+/// Create one with [`Handle::write_incremental`]. Every page is written as soon
+/// as it is passed to [`IncrementalWriter::write_page`], together with the
+/// XObjects it uses for the first time, so they do not need to be kept in
+/// memory. The fonts, page tree, outline, structure and xref are written by
+/// [`IncrementalWriter::finish`].
+///
+/// The pages may only use global fonts and XObjects from [`Handle::res`], which
+/// may still be added to while the pages are written. As the structure is only
+/// known at the end, every page of a tagged document gets a `/StructParents`
+/// key and the link annotations are kept until [`IncrementalWriter::finish`].
+/// A document is tagged if it has a structure when the writer is created, or
+/// if [`IncrementalWriter::set_tagged`] is called before writing any page.
+///
+/// The PDF/A pre-flight check runs when the writer is created, i.e. before
+/// anything is written. Fonts that are added later are not checked.
+pub struct IncrementalWriter<'w> {
+    fmt: Formatter<'w>,
+    lowering: Lowering,
+    head: Head,
+    pages_ref: ObjRef,
+    /// The references of all pages that were written or linked to
+    kids: Vec<ObjRef>,
+    /// The number of pages that were written
+    count: usize,
+    /// The named destinations that written links point to
+    named_links: Vec<PdfString>,
+    /// The link annotations of every written page, which are written at the end
+    annots: Vec<Vec<(ObjRef, low::LinkAnnotation)>>,
+    refs: GlobalRefs,
+    /// The indices of the global XObjects that were already written
+    written_x_objects: HashSet<usize>,
+    /// Whether the pages get a `/StructParents` key
+    tagged: bool,
+}
+
+impl<'w> IncrementalWriter<'w> {
+    pub(super) fn new(hnd: &Handle, w: &'w mut dyn io::Write) -> io::Result<Self> {
+        hnd.check_write()?;
+        let mut fmt = Formatter::new(w);
+        let mut lowering = Lowering::new();
+        let head = hnd.write_head(&mut fmt, &mut lowering)?;
+        let pages_ref = make_ref(lowering.id_gen.next());
+        Ok(Self {
+            fmt,
+            lowering,
+            head,
+            pages_ref,
+            kids: vec![],
+            count: 0,
+            named_links: vec![],
+            annots: vec![],
+            refs: GlobalRefs::default(),
+            written_x_objects: HashSet::new(),
+            tagged: !hnd.structure.is_empty(),
+        })
+    }
+
+    /// Set whether the document is tagged, i.e. will have a structure
+    ///
+    /// This must be set before the first page is written.
+    pub fn set_tagged(&mut self, tagged: bool) {
+        debug_assert_eq!(self.count, 0, "pages were already written");
+        self.tagged = tagged;
+    }
+
+    /// The number of pages that were written so far
+    pub fn page_count(&self) -> usize {
+        self.count
+    }
+
+    /// Get the reference of a page, which may not have been written yet
+    fn page_ref(&mut self, index: usize) -> ObjRef {
+        while self.kids.len() <= index {
+            self.kids.push(make_ref(self.lowering.id_gen.next()));
+        }
+        self.kids[index]
+    }
+
+    /// Write the next page, returning its index
+    pub fn write_page(&mut self, hnd: &Handle, page: &Page) -> io::Result<usize> {
+        let index = self.count;
+        let page_ref = self.page_ref(index);
+
+        let contents_ref = make_ref(self.lowering.id_gen.next());
+        let contents = low::Stream {
+            data: Cow::Borrowed(&page.contents),
+            meta: StreamMetadata::None,
+        };
+        self.fmt.obj(contents_ref, &contents)?;
+
+        let mut annots = Vec::with_capacity(page.links.len());
+        for link in &page.links {
+            match &link.action {
+                LinkAction::GoTo(dest) => {
+                    self.page_ref(dest.page());
+                }
+                LinkAction::GoToNamed(name) => self.named_links.push(name.clone()),
+                LinkAction::Uri(_) => {}
+            }
+            let annot_ref = make_ref(self.lowering.id_gen.next());
+            annots.push((annot_ref, lower_link(&self.kids, link)));
+        }
+        let annot_refs = annots.iter().map(|(r, _)| *r).collect();
+        self.annots.push(annots);
+
+        let resources = lower_global_resources(
+            &page.resources,
+            &hnd.res,
+            &mut self.refs,
+            &mut self.lowering.id_gen,
+        )?;
+        let page_low = low::Page {
+            parent: self.pages_ref,
+            resources,
+            contents: contents_ref,
+            media_box: Some(page.media_box),
+            annots: annot_refs,
+            struct_parents: Some(index).filter(|_| self.tagged),
+        };
+        self.fmt.obj(page_ref, &page_low)?;
+
+        let written = &self.written_x_objects;
+        let mut x_objects: Vec<_> = self
+            .refs
+            .x_objects
+            .iter()
+            .filter(|(index, _)| !written.contains(index))
+            .map(|(&index, &x_ref)| (index, x_ref))
+            .collect();
+        x_objects.sort_unstable_by_key(|(index, _)| *index);
+        for (index, x_ref) in x_objects {
+            let stream = hnd.res.x_objects[index].to_stream().unwrap();
+            self.fmt.obj(x_ref, &stream)?;
+            self.written_x_objects.insert(index);
+        }
+        self.fmt
+            .flush_full_object_streams(&mut self.lowering.id_gen)?;
+        self.count += 1;
+        Ok(index)
+    }
+
+    /// Write the pages left in the handle, the link annotations, resources,
+    /// page tree, outline, structure and the xref
+    pub fn finish(mut self, hnd: &Handle) -> io::Result<()> {
+        for page in &hnd.pages {
+            self.write_page(hnd, page)?;
+        }
+        hnd.check_dests(self.count)?;
+        if self.kids.len() > self.count {
+            let msg = format!(
+                "Link to page {}, but only {} pages were written",
+                self.kids.len() - 1,
+                self.count
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+        for name in &self.named_links {
+            hnd.check_named_dest(name)?;
+        }
+        if !self.tagged && !hnd.structure.is_empty() {
+            let msg = "The document has a structure, but the pages were not tagged";
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+
+        let annot_refs: Vec<Vec<ObjRef>> = self
+            .annots
+            .iter()
+            .map(|annots| annots.iter().map(|(r, _)| *r).collect())
+            .collect();
+        let struct_tree = hnd.write_structure(
+            &mut self.fmt,
+            &mut self.lowering,
+            &self.kids,
+            &annot_refs,
+            true,
+        )?;
+        for (page, annots) in self.annots.into_iter().enumerate() {
+            for (index, (annot_ref, mut annot)) in annots.into_iter().enumerate() {
+                annot.struct_parent = struct_tree
+                    .as_ref()
+                    .and_then(|tree| tree.link_key(page, index));
+                self.fmt.obj(annot_ref, &annot)?;
+            }
+        }
+        let struct_tree_root = struct_tree.map(|tree| tree.root);
+
+        let written = &self.written_x_objects;
+        self.refs
+            .x_objects
+            .retain(|index, _| !written.contains(index));
+        let mut pages_ctx = LowerPagesCtx::with_refs(hnd, self.pages_ref, &self.refs);
+        write_resources(&mut self.fmt, &mut pages_ctx, &mut self.lowering)?;
+
+        let pages = low::Pages { kids: self.kids };
+        hnd.write_tail(
+            self.fmt,
+            self.lowering,
+            self.head,
+            self.pages_ref,
+            pages,
+            struct_tree_root,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::{ColorIs, ColorSpace, ImageMetadata, StructType},
+        high::{DictResource, Handle, Image, ImageEncoding, Resource, StructElem, StructKid},
+    };
+
+    use super::super::tests::{links_handle, object, page, value_of};
+
+    fn image_handle() -> Handle<'static> {
+        let mut hnd = links_handle("chapter");
+        let image = hnd.res.push_xobject(Image {
+            meta: ImageMetadata {
+                width: 8,
+                height: 8,
+                color_space: ColorSpace::DeviceGray,
+                bits_per_component: 1,
+                image_mask: true,
+                decode: ColorIs::One,
+            },
+            encoding: ImageEncoding::Raw,
+            data: vec![0; 8],
+        });
+        let mut x_objects = DictResource::new();
+        x_objects.insert("I0".to_owned(), Resource::from(image));
+        hnd.pages[0].resources.x_objects = Resource::Immediate(Box::new(x_objects));
+        hnd
+    }
+
+    /// Replace the object numbers in references (`7 0 R`) with `N`
+    fn without_refs(text: &str) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(at) = rest.find(" 0 R") {
+            let start = rest[..at].trim_end_matches(|c: char| c.is_ascii_digit());
+            out.push_str(start);
+            out.push_str("N 0 R");
+            rest = &rest[at + 4..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// Get the page objects without references and the number of xref entries
+    fn page_tree_and_xref(data: &[u8]) -> (String, Vec<String>, usize) {
+        let text = &String::from_utf8_lossy(data);
+        let count = value_of(text, "/Count ", "\n").to_owned();
+        let kids = value_of(text, "/Kids[", "]");
+        let pages = kids
+            .split(" R")
+            .filter(|r| !r.trim().is_empty())
+            .map(|r| {
+                let page = object(text, &format!("{} R", r.trim()));
+                without_refs(page)
+            })
+            .collect();
+
+        let xref = &text[text.rfind("\nxref\n").unwrap() + 6..];
+        let size: usize = value_of(xref, "0 ", "\n").parse().unwrap();
+        for (id, entry) in xref.lines().skip(1).take(size).enumerate().skip(1) {
+            let offset: usize = entry[..10].parse().unwrap();
+            let head = format!("{} 0 obj\n", id);
+            assert!(data[offset..].starts_with(head.as_bytes()), "object {}", id);
+        }
+        (count, pages, size)
+    }
+
+    #[test]
+    fn test_incremental() {
+        let mut hnd = image_handle();
+        let mut expected = Vec::new();
+        hnd.write(&mut expected).unwrap();
+
+        let pages = std::mem::take(&mut hnd.pages);
+        let mut out = Vec::new();
+        let mut writer = hnd.write_incremental(&mut out).unwrap();
+        for page in &pages {
+            writer.write_page(&hnd, page).unwrap();
+        }
+        writer.finish(&hnd).unwrap();
+        assert_eq!(page_tree_and_xref(&out), page_tree_and_xref(&expected));
+        let text = String::from_utf8_lossy(&out);
+
+        // The image is written together with the first page
+        let kids = value_of(&text, "/Kids[", "]");
+        let second_page = kids.split(" R").nth(1).unwrap().trim();
+        let second_page = text.find(&format!("\n{} obj\n", second_page)).unwrap();
+        assert!(text.find("/Subtype/Image").unwrap() < second_page);
+    }
+
+    #[test]
+    fn test_incremental_tagged() {
+        let mut hnd = image_handle();
+        let pages = std::mem::take(&mut hnd.pages);
+        let mut out = Vec::new();
+        let mut writer = hnd.write_incremental(&mut out).unwrap();
+        writer.set_tagged(true);
+        for page in &pages {
+            writer.write_page(&hnd, page).unwrap();
+        }
+        let mut elem = StructElem::new(StructType::Link);
+        elem.children.push(StructKid::Link { page: 0, index: 0 });
+        hnd.structure.push(elem);
+        writer.finish(&hnd).unwrap();
+        let text = String::from_utf8_lossy(&out);
+
+        let kids = value_of(&text, "/Kids[", "]");
+        for (index, r) in kids
+            .split(" R")
+            .filter(|r| !r.trim().is_empty())
+            .enumerate()
+        {
+            let page = object(&text, &format!("{} R", r.trim()));
+            assert_eq!(value_of(page, "/StructParents ", "\n"), index.to_string());
+            assert!(page.contains("/Tabs/S"));
+        }
+    }
+
+    #[test]
+    fn test_incremental_untagged_structure() {
+        let mut hnd = image_handle();
+        hnd.structure.push(StructElem::new(StructType::Document));
+        let pages = std::mem::take(&mut hnd.pages);
+        let mut out = Vec::new();
+        let mut writer = hnd.write_incremental(&mut out).unwrap();
+        writer.set_tagged(false);
+        for page in &pages {
+            writer.write_page(&hnd, page).unwrap();
+        }
+        let err = writer.finish(&hnd).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_incremental_object_streams() {
+        let mut hnd = Handle::new();
+        hnd.object_streams = true;
+        let mut out = Vec::new();
+        let mut writer = hnd.write_incremental(&mut out).unwrap();
+        for _ in 0..150 {
+            writer.write_page(&hnd, &page(vec![])).unwrap();
+        }
+        writer.finish(&hnd).unwrap();
+        let text = String::from_utf8_lossy(&out);
+
+        // The first object stream is written before the last page
+        let last_contents = text.rfind("/Length 0\n").unwrap();
+        assert!(text.find("/Type/ObjStm").unwrap() < last_contents);
+    }
+}
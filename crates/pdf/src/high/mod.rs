@@ -13,30 +13,40 @@ use page::lower_page;
 
 use crate::{
     common::{
-        self, Dict, Encoding, ICCColorProfileMetadata, NumberTree, ObjRef, PageLabel, PdfString,
-        StreamMetadata,
+        self, Dict, Encoding, ICCColorProfileMetadata, NameTree, NumberTree, ObjRef, PageLabel,
+        PdfString, StreamMetadata,
+    },
+    low::{self, PdfVersion, ID},
+    lowering::{
+        lower_dest, lower_dict, lower_link, lower_outline_items, lower_struct_elem, make_ref,
+        LowerPagesCtx, Lowerable, Lowering, ParentTree,
     },
-    low::{self, ID},
-    lowering::{lower_dict, lower_outline_items, LowerPagesCtx, Lowerable, Lowering},
     write::{Formatter, Serialize},
     xmp::{self, XmpWriter},
 };
 
+mod cff;
 pub mod cmap;
 mod font;
+mod incremental;
 mod metadata;
 mod outline;
 mod page;
+mod pdfa;
 mod stream;
+mod structure;
 mod xobject;
 
 pub use cmap::ToUnicodeCMap as ToUnicode;
-pub use font::{Font, Type3Font};
+pub use font::{Font, FontFile, Type1Font, Type3Font};
+pub use incremental::IncrementalWriter;
 pub use metadata::{Info, Metadata};
 pub use outline::{Destination, Outline, OutlineItem};
-pub use page::{Page, Resources};
+pub use page::{Link, LinkAction, Page, Resources};
+pub use pdfa::{PdfAConformance, PreflightError, Violation};
 pub use stream::Ascii85Stream;
-pub use xobject::{Image, XObject};
+pub use structure::{StructElem, StructKid};
+pub use xobject::{Image, ImageEncoding, XObject};
 
 pub(crate) use font::LowerFontCtx;
 pub(crate) use stream::ToStream;
@@ -176,10 +186,24 @@ pub struct Handle<'a> {
     pub page_labels: NumberTree<PageLabel>,
     /// The outline for a PDF viewer
     pub outline: Outline,
+    /// The named destinations, for use in links or from other documents
+    pub dests: NameTree<Destination>,
+    /// The top-level elements of the logical structure
+    ///
+    /// If this is not empty, the document is written as a tagged PDF.
+    pub structure: Vec<StructElem>,
     /// The global resource struct
     pub res: Res<'a>,
     /// The output intents
     pub output_intents: Vec<OutputIntent>,
+    /// The PDF/A conformance level to declare and check before writing
+    pub pdfa: Option<PdfAConformance>,
+    /// The version in the header of the file
+    pub version: PdfVersion,
+    /// Whether to compress objects into object streams
+    ///
+    /// This needs at least [`PdfVersion::V1_5`] and is not allowed in PDF/A-1.
+    pub object_streams: bool,
 }
 
 impl Default for Handle<'_> {
@@ -205,7 +229,7 @@ fn pdf_list_of(o: &[String]) -> io::Result<Option<PdfString>> {
 struct Xmp {
     pdf: xmp::Pdf,
     dc: xmp::DublinCore,
-    pdfa_id: xmp::PdfAId,
+    pdfa_id: Option<xmp::PdfAId>,
     basic: xmp::XmpBasic,
     mm: xmp::XmpMM,
 }
@@ -215,7 +239,9 @@ impl Xmp {
         let mut writer = XmpWriter::new(Vec::new())?;
         writer.add_description(&self.pdf)?;
         writer.add_description(&self.dc)?;
-        writer.add_description(&self.pdfa_id)?;
+        if let Some(pdfa_id) = &self.pdfa_id {
+            writer.add_description(pdfa_id)?;
+        }
         writer.add_description(&self.basic)?;
         writer.add_description(&self.mm)?;
         writer.finish()
@@ -235,6 +261,103 @@ impl<'a> ToStream<'a> for Xmp {
     }
 }
 
+/// Check that a destination points to one of the pages
+fn check_dest(dest: &Destination, page_count: usize) -> io::Result<()> {
+    match dest.page() {
+        index if index < page_count => Ok(()),
+        index => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Destination page {} out of range, the document has {} pages",
+                index, page_count
+            ),
+        )),
+    }
+}
+
+/// The objects written before the pages, which are referenced from the catalog and trailer
+struct Head {
+    catalog_id: u64,
+    info: Option<ObjRef>,
+    metadata: ObjRef,
+    output_intents: Vec<ObjRef>,
+}
+
+/// A structure tree that was written, with the keys of its parent tree
+struct StructTree {
+    root: ObjRef,
+    parent_tree: NumberTree<low::StructParents>,
+    /// The keys of the tagged links, by page and index of the link
+    links: BTreeMap<(usize, usize), usize>,
+}
+
+impl StructTree {
+    /// The `/StructParents` key of a page, if it has marked content
+    fn page_key(&self, page: usize) -> Option<usize> {
+        Some(page).filter(|page| self.parent_tree.contains_key(*page))
+    }
+
+    /// The `/StructParent` key of a link annotation, if it is tagged
+    fn link_key(&self, page: usize, index: usize) -> Option<usize> {
+        self.links.get(&(page, index)).copied()
+    }
+}
+
+/// Write all fonts and XObjects that the pages refer to
+fn write_resources(
+    fmt: &mut Formatter,
+    pages_ctx: &mut LowerPagesCtx,
+    lowering: &mut Lowering,
+) -> io::Result<()> {
+    for (font_dict_ref, font_dict) in pages_ctx.font_dicts.store_values() {
+        let dict = lower_dict(
+            font_dict,
+            &mut pages_ctx.fonts,
+            &mut pages_ctx.font_ctx,
+            &mut lowering.id_gen,
+        );
+        fmt.obj(font_dict_ref, &dict)?;
+    }
+
+    for (font_ref, font) in pages_ctx.fonts.store_values() {
+        let font_low = font.lower(&mut pages_ctx.font_ctx, &mut lowering.id_gen);
+        fmt.obj(font_ref, &font_low)?;
+    }
+
+    for (x_ref, x) in pages_ctx.x_objects.store_values() {
+        fmt.obj(x_ref, &x.to_stream().unwrap())?;
+    }
+
+    // FIXME: this only works AFTER all fonts are lowered
+    for (cproc_ref, char_proc) in pages_ctx.font_ctx.text_stream_values() {
+        fmt.obj(cproc_ref, &char_proc.to_stream().unwrap())?;
+    }
+
+    for (descriptor_ref, descriptor) in pages_ctx.font_ctx.font_descriptor_values() {
+        fmt.obj(*descriptor_ref, descriptor)?;
+    }
+
+    for (file_ref, file) in pages_ctx.font_ctx.font_file_values() {
+        let stream = match file {
+            FontFile::Type1C(data) => low::FlateStream {
+                data: Cow::Borrowed(data.as_ref()),
+                meta: StreamMetadata::Type1C,
+            },
+        };
+        fmt.obj(file_ref, &stream)?;
+    }
+
+    for (cmap_ref, cmap) in pages_ctx.font_ctx.to_unicode_values() {
+        let stream = cmap.to_stream().map_err(io::Error::other)?;
+        fmt.obj(cmap_ref, &stream)?;
+    }
+
+    for (encoding_ref, encoding) in pages_ctx.font_ctx.encoding_values() {
+        fmt.obj(encoding_ref, &encoding.lower(&mut (), &mut lowering.id_gen))?;
+    }
+    Ok(())
+}
+
 impl Handle<'_> {
     /// Creates a new handle
     pub fn new() -> Self {
@@ -243,8 +366,13 @@ impl Handle<'_> {
             res: Res::default(),
             page_labels: NumberTree::new(),
             outline: Outline::new(),
+            dests: NameTree::new(),
+            structure: vec![],
             pages: vec![],
             output_intents: vec![],
+            pdfa: None,
+            version: PdfVersion::V1_5,
+            object_streams: false,
         }
     }
 
@@ -266,10 +394,10 @@ impl Handle<'_> {
                 creator: self.meta.author.clone(),
                 publisher: self.meta.publisher.clone(),
             },
-            pdfa_id: xmp::PdfAId {
-                part: 2,
-                conformance: 'B',
-            },
+            pdfa_id: self.pdfa.map(|pdfa| xmp::PdfAId {
+                part: pdfa.part(),
+                conformance: pdfa.conformance(),
+            }),
             basic: xmp::XmpBasic {
                 creator_tool: self
                     .meta
@@ -287,16 +415,177 @@ impl Handle<'_> {
         }
     }
 
+    /// Generate the info dictionary for the document, if there is any metadata
+    fn info(&self) -> io::Result<Option<Info>> {
+        if self.meta.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Info {
+            title: pdf_string_of(&self.meta.title)?,
+            author: pdf_list_of(&self.meta.author)?,
+            subject: pdf_string_of(&self.meta.subject)?,
+            keywords: pdf_list_of(&self.meta.keywords)?,
+            creator: pdf_string_of(&self.meta.creator)?,
+            producer: Some(PdfString::from_str(&self.meta.producer)?),
+            creation_date: Some(self.meta.creation_date),
+            mod_date: Some(self.meta.modify_date),
+            trapped: None,
+        }))
+    }
+
+    /// Check that all links and named destinations point to existing pages
+    fn check_dests(&self, page_count: usize) -> io::Result<()> {
+        for (_, dest) in self.dests.iter() {
+            check_dest(dest, page_count)?;
+        }
+        for page in &self.pages {
+            for link in &page.links {
+                match &link.action {
+                    LinkAction::GoTo(dest) => check_dest(dest, page_count)?,
+                    LinkAction::GoToNamed(name) => self.check_named_dest(name)?,
+                    LinkAction::Uri(_) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a named destination exists
+    fn check_named_dest(&self, name: &PdfString) -> io::Result<()> {
+        match self.dests.get(name) {
+            Some(_) => Ok(()),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Link to unknown named destination {:?}",
+                    String::from_utf8_lossy(name.as_bytes())
+                ),
+            )),
+        }
+    }
+
+    /// Check the document against the selected PDF/A conformance level
+    ///
+    /// Returns an empty list if no level is selected.
+    pub fn preflight(&self) -> Vec<Violation> {
+        match self.pdfa {
+            Some(conformance) => pdfa::preflight(self, conformance),
+            None => vec![],
+        }
+    }
+
+    /// Check the settings and run the pre-flight check before writing
+    fn check_write(&self) -> io::Result<()> {
+        if self.object_streams && self.version < PdfVersion::V1_5 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Object streams need PDF 1.5, not {}", self.version.as_str()),
+            ));
+        }
+        if let Some(conformance) = self.pdfa {
+            let violations = self.preflight();
+            if !violations.is_empty() {
+                let err = PreflightError {
+                    conformance,
+                    violations,
+                };
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+            }
+        }
+        Ok(())
+    }
+
     /// Write the whole PDF to the given writer
+    ///
+    /// If a PDF/A conformance level is selected, this fails with a [`PreflightError`]
+    /// before writing anything if the document violates it.
     pub fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        self.check_dests(self.pages.len())?;
+        self.check_write()?;
         let mut fmt = Formatter::new(w);
-
-        let gen = 0;
-        let make_ref = move |id: u64| ObjRef { id, gen };
         let mut lowering = Lowering::new();
+        let head = self.write_head(&mut fmt, &mut lowering)?;
+
+        // **Pages**
+        let mut pages = low::Pages { kids: vec![] };
+        let pages_id = lowering.id_gen.next();
+        let pages_ref = make_ref(pages_id);
+
+        let mut pages_ctx = LowerPagesCtx::new(self, pages_ref);
+
+        // Links may point to any page, so the references need to be known upfront
+        pages.kids = (0..self.pages.len())
+            .map(|_| make_ref(lowering.id_gen.next()))
+            .collect();
+
+        let annots: Vec<Vec<ObjRef>> = self
+            .pages
+            .iter()
+            .map(|page| {
+                let links = page.links.iter();
+                links.map(|_| make_ref(lowering.id_gen.next())).collect()
+            })
+            .collect();
+
+        // **Structure**
+        let struct_tree =
+            self.write_structure(&mut fmt, &mut lowering, &pages.kids, &annots, false)?;
+
+        let page_refs = self.pages.iter().zip(&pages.kids).zip(&annots);
+        for (index, ((page, &page_ref), annot_refs)) in page_refs.enumerate() {
+            let contents_id = lowering.id_gen.next();
+            let contents_ref = make_ref(contents_id);
+
+            let contents = low::Stream {
+                data: Cow::Borrowed(&page.contents),
+                meta: StreamMetadata::None,
+            };
+            fmt.obj(contents_ref, &contents)?;
+
+            for (link_index, (link, &annot_ref)) in page.links.iter().zip(annot_refs).enumerate() {
+                let mut annot = lower_link(&pages.kids, link);
+                annot.struct_parent = struct_tree
+                    .as_ref()
+                    .and_then(|tree| tree.link_key(index, link_index));
+                fmt.obj(annot_ref, &annot)?;
+            }
+
+            let page_low = lower_page(
+                page,
+                &mut pages_ctx,
+                &mut lowering.id_gen,
+                contents_ref,
+                annot_refs.clone(),
+                struct_tree.as_ref().and_then(|tree| tree.page_key(index)),
+            );
+            fmt.obj(page_ref, &page_low)?;
+        }
+
+        write_resources(&mut fmt, &mut pages_ctx, &mut lowering)?;
+
+        let struct_tree_root = struct_tree.map(|tree| tree.root);
+        self.write_tail(fmt, lowering, head, pages_ref, pages, struct_tree_root)
+    }
+
+    /// Start writing the PDF page by page
+    ///
+    /// The metadata and output intents are written right away, so they need to
+    /// be set before calling this. See [`IncrementalWriter`] for details.
+    pub fn write_incremental<'w, W: io::Write>(
+        &self,
+        w: &'w mut W,
+    ) -> io::Result<IncrementalWriter<'w>> {
+        IncrementalWriter::new(self, w)
+    }
+
+    /// Write the header, output intents, info dictionary and metadata
+    fn write_head(&self, fmt: &mut Formatter, lowering: &mut Lowering) -> io::Result<Head> {
+        if self.object_streams {
+            fmt.use_object_streams();
+        }
 
         // Start
-        writeln!(fmt.inner, "%PDF-1.5")?;
+        writeln!(fmt.inner, "%PDF-{}", self.version.as_str())?;
         fmt.inner.write_all(&[b'%', 180, 200, 220, 240, b'\n'])?;
 
         // **OutputIntent**
@@ -333,92 +622,108 @@ impl Handle<'_> {
         let catalog_id = lowering.id_gen.next();
 
         // **Info**
-        let info_id = if self.meta.is_empty() {
-            None
-        } else {
-            let info_id = lowering.id_gen.next();
-            let r = make_ref(info_id);
-
-            let info = Info {
-                title: pdf_string_of(&self.meta.title)?,
-                author: pdf_list_of(&self.meta.author)?,
-                subject: pdf_string_of(&self.meta.subject)?,
-                keywords: pdf_list_of(&self.meta.keywords)?,
-                creator: pdf_string_of(&self.meta.creator)?,
-                producer: Some(PdfString::from_str(&self.meta.producer)?),
-                creation_date: Some(self.meta.creation_date),
-                mod_date: Some(self.meta.modify_date),
-                trapped: None,
-            };
-
-            fmt.obj(r, &info)?;
-            Some(r)
+        let info = match self.info()? {
+            None => None,
+            Some(info) => {
+                let info_id = lowering.id_gen.next();
+                let r = make_ref(info_id);
+                fmt.obj(r, &info)?;
+                Some(r)
+            }
         };
 
         // **Metadata**
         let meta_id = lowering.id_gen.next();
-        let meta_ref = make_ref(meta_id);
+        let metadata = make_ref(meta_id);
         let xmp = self.xmp();
-        fmt.obj(meta_ref, &xmp.to_stream()?)?;
+        fmt.obj(metadata, &xmp.to_stream()?)?;
 
-        // **Pages**
-        let mut pages = low::Pages { kids: vec![] };
-        let pages_id = lowering.id_gen.next();
-        let pages_ref = make_ref(pages_id);
-
-        let mut pages_ctx = LowerPagesCtx::new(self, pages_ref);
-
-        for page in &self.pages {
-            let page_id = lowering.id_gen.next();
-            let contents_id = lowering.id_gen.next();
-            let contents_ref = make_ref(contents_id);
-
-            let contents = low::Stream {
-                data: Cow::Borrowed(&page.contents),
-                meta: StreamMetadata::None,
-            };
-            fmt.obj(contents_ref, &contents)?;
+        Ok(Head {
+            catalog_id,
+            info,
+            metadata,
+            output_intents,
+        })
+    }
 
-            let page_ref = make_ref(page_id);
-            let page_low = lower_page(page, &mut pages_ctx, &mut lowering.id_gen, contents_ref);
-            fmt.obj(page_ref, &page_low)?;
-            pages.kids.push(page_ref);
+    /// Write the structure tree, if there is one
+    ///
+    /// The `annots` are the references of the link annotations of every page.
+    /// If `all_pages` is set, the parent tree has an entry for every page, even
+    /// if the page has no marked content.
+    fn write_structure(
+        &self,
+        fmt: &mut Formatter,
+        lowering: &mut Lowering,
+        page_refs: &[ObjRef],
+        annots: &[Vec<ObjRef>],
+        all_pages: bool,
+    ) -> io::Result<Option<StructTree>> {
+        if self.structure.is_empty() {
+            return Ok(None);
         }
-
-        for (font_dict_ref, font_dict) in pages_ctx.font_dicts.store_values() {
-            let dict = lower_dict(
-                font_dict,
-                &mut pages_ctx.fonts,
-                &mut pages_ctx.font_ctx,
+        let root_ref = make_ref(lowering.id_gen.next());
+        let mut acc = Vec::new();
+        let mut parent_tree = ParentTree::default();
+        let mut kids = Vec::with_capacity(self.structure.len());
+        for elem in &self.structure {
+            kids.push(lower_struct_elem(
+                &mut acc,
+                &mut parent_tree,
+                page_refs,
+                annots,
+                elem,
+                root_ref,
                 &mut lowering.id_gen,
-            );
-            fmt.obj(font_dict_ref, &dict)?;
+            )?);
         }
-
-        for (font_ref, font) in pages_ctx.fonts.store_values() {
-            let font_low = font.lower(&mut pages_ctx.font_ctx, &mut lowering.id_gen);
-            fmt.obj(font_ref, &font_low)?;
+        for (r, elem) in acc {
+            fmt.obj(r, &elem)?;
         }
-
-        for (x_ref, x) in pages_ctx.x_objects.store_values() {
-            fmt.obj(x_ref, &x.to_stream().unwrap())?;
-        }
-
-        // FIXME: this only works AFTER all fonts are lowered
-        for (cproc_ref, char_proc) in pages_ctx.font_ctx.text_stream_values() {
-            fmt.obj(cproc_ref, &char_proc.to_stream().unwrap())?;
-        }
-
-        for (cmap_ref, cmap) in pages_ctx.font_ctx.to_unicode_values() {
-            let stream = cmap.to_stream().map_err(io::Error::other)?;
-            fmt.obj(cmap_ref, &stream)?;
+        if all_pages {
+            for index in 0..page_refs.len() {
+                parent_tree.pages.entry(index).or_default();
+            }
         }
 
-        for (encoding_ref, encoding) in pages_ctx.font_ctx.encoding_values() {
-            fmt.obj(encoding_ref, &encoding.lower(&mut (), &mut lowering.id_gen))?;
+        // The pages use their index as the key, the links follow after them
+        let mut entries: BTreeMap<_, _> = parent_tree
+            .pages
+            .into_iter()
+            .map(|(page, elems)| (page, low::StructParents::Content(elems)))
+            .collect();
+        let mut links = BTreeMap::new();
+        for (key, (link, elem)) in (page_refs.len()..).zip(parent_tree.links) {
+            entries.insert(key, low::StructParents::Annot(elem));
+            links.insert(link, key);
         }
+        let parent_tree_ref = make_ref(lowering.id_gen.next());
+        let parent_tree = NumberTree::from(entries);
+        fmt.obj(parent_tree_ref, &parent_tree)?;
+
+        let root = low::StructTreeRoot {
+            kids,
+            parent_tree: parent_tree_ref,
+            parent_tree_next_key: page_refs.len() + links.len(),
+        };
+        fmt.obj(root_ref, &root)?;
+        Ok(Some(StructTree {
+            root: root_ref,
+            parent_tree,
+            links,
+        }))
+    }
 
-        let pages_ref = make_ref(pages_id);
+    /// Write the page tree, page labels, outline, names, catalog and xref
+    fn write_tail(
+        &self,
+        mut fmt: Formatter,
+        mut lowering: Lowering,
+        head: Head,
+        pages_ref: ObjRef,
+        pages: low::Pages,
+        struct_tree_root: Option<ObjRef>,
+    ) -> io::Result<()> {
         fmt.obj(pages_ref, &pages)?;
 
         let pl_ref = if !self.page_labels.is_empty() {
@@ -459,31 +764,61 @@ impl Handle<'_> {
             None
         };
 
+        // **Names**
+        let names = if !self.dests.is_empty() {
+            let dests: BTreeMap<_, _> = self
+                .dests
+                .iter()
+                .map(|(name, dest)| (name.clone(), lower_dest(&pages.kids, *dest)))
+                .collect();
+            let dests_ref = make_ref(lowering.id_gen.next());
+            fmt.obj(dests_ref, &NameTree::from(dests))?;
+            Some(low::Names {
+                dests: Some(dests_ref),
+            })
+        } else {
+            None
+        };
+
         // **Catalog**
         let catalog = low::Catalog {
             version: None,
             pages: pages_ref,
             page_labels: pl_ref,
             outline: ol_ref,
-            output_intents,
-            metadata: Some(meta_ref),
+            output_intents: head.output_intents,
+            metadata: Some(head.metadata),
+            names,
+            mark_info: struct_tree_root.map(|_| low::MarkInfo { marked: true }),
+            struct_tree_root,
         };
-        let catalog_ref = make_ref(catalog_id);
+        let catalog_ref = make_ref(head.catalog_id);
         fmt.obj(catalog_ref, &catalog)?;
 
         // **xref**
-        let startxref = fmt.xref()?;
-        let id = self.compute_id(&fmt);
+        let startxref = if self.object_streams {
+            let trailer = low::Trailer {
+                size: 0,
+                root: catalog_ref,
+                info: head.info,
+                id: self.compute_id(&fmt),
+            };
+            fmt.xref_stream(trailer)?
+        } else {
+            let startxref = fmt.xref()?;
+            let id = self.compute_id(&fmt);
 
-        writeln!(fmt.inner, "trailer")?;
+            writeln!(fmt.inner, "trailer")?;
 
-        let trailer = low::Trailer {
-            size: fmt.xref.len(),
-            root: make_ref(catalog_id),
-            info: info_id,
-            id,
+            let trailer = low::Trailer {
+                size: fmt.xref.len(),
+                root: catalog_ref,
+                info: head.info,
+                id,
+            };
+            trailer.write(&mut fmt)?;
+            startxref
         };
-        trailer.write(&mut fmt)?;
 
         writeln!(fmt.inner, "startxref")?;
         writeln!(fmt.inner, "{}", startxref)?;
@@ -539,3 +874,204 @@ impl Handle<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Read},
+        str::FromStr,
+    };
+
+    use flate2::read::ZlibDecoder;
+
+    use crate::common::{PdfString, Point, Rectangle, StructType};
+
+    use super::{Destination, Handle, Link, LinkAction, Page, StructElem, StructKid};
+
+    pub(super) fn page(links: Vec<Link>) -> Page<'static> {
+        Page {
+            media_box: Rectangle::a4_media_box(),
+            resources: Default::default(),
+            contents: vec![],
+            links,
+        }
+    }
+
+    fn link(action: LinkAction) -> Link {
+        let (ll, ur) = (Point { x: 10, y: 10 }, Point { x: 100, y: 20 });
+        Link {
+            rect: Rectangle { ll, ur },
+            action,
+        }
+    }
+
+    /// Get the text between `key` and the next `end`
+    pub(super) fn value_of<'a>(text: &'a str, key: &str, end: &str) -> &'a str {
+        let start = text.find(key).unwrap() + key.len();
+        let len = text[start..].find(end).unwrap();
+        &text[start..start + len]
+    }
+
+    /// Get the body of the object that `r` (e.g. `7 0 R`) refers to
+    pub(super) fn object<'a>(text: &'a str, r: &str) -> &'a str {
+        let head = format!("\n{} obj\n", r.strip_suffix(" R").unwrap());
+        value_of(text, &head, "endobj")
+    }
+
+    pub(super) fn links_handle(chapter: &str) -> Handle<'static> {
+        let mut hnd = Handle::new();
+        let name = PdfString::from_str("chapter").unwrap();
+        hnd.dests.insert(name, Destination::PageFit(1));
+        let chapter = PdfString::from_str(chapter).unwrap();
+        hnd.pages.push(page(vec![
+            link(LinkAction::GoTo(Destination::PageFit(1))),
+            link(LinkAction::GoToNamed(chapter)),
+        ]));
+        hnd.pages.push(page(vec![]));
+        hnd
+    }
+
+    #[test]
+    fn test_links() {
+        let mut out = Vec::new();
+        links_handle("chapter").write(&mut out).unwrap();
+        let text = String::from_utf8_lossy(&out);
+
+        let kids = value_of(&text, "/Kids[", "]");
+        let second_page = &kids[kids.find(" R").unwrap() + 3..];
+        let annots = value_of(&text, "/Annots[", "]");
+        let annots: Vec<_> = annots.split(" R").filter(|r| !r.is_empty()).collect();
+        assert_eq!(annots.len(), 2);
+
+        let goto = object(&text, &format!("{} R", annots[0].trim()));
+        assert!(goto.contains("/Subtype/Link"));
+        assert!(goto.contains(&format!("/Dest[{}/Fit]", second_page)));
+        let named = object(&text, &format!("{} R", annots[1].trim()));
+        assert!(named.contains("/Dest(chapter)"));
+
+        let names = value_of(&text, "/Names\n  <<", ">>");
+        let dests = value_of(names, "/Dests ", "\n");
+        let dests = object(&text, dests);
+        let expected = format!("/Names[(chapter)[{}/Fit]]", second_page);
+        assert!(dests.contains(&expected), "{}", dests);
+    }
+
+    #[test]
+    fn test_unknown_named_link() {
+        let mut out = Vec::new();
+        let err = links_handle("appendix").write(&mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_tagged_link() {
+        let mut hnd = links_handle("chapter");
+        let mut elem = StructElem::new(StructType::Link);
+        elem.children.push(StructKid::Link { page: 0, index: 1 });
+        let mut root = StructElem::new(StructType::Document);
+        root.children.push(StructKid::Elem(elem));
+        hnd.structure.push(root);
+
+        let mut out = Vec::new();
+        hnd.write(&mut out).unwrap();
+        let text = String::from_utf8_lossy(&out);
+
+        let annots = value_of(&text, "/Annots[", "]");
+        let annot = annots.split(" R").nth(1).unwrap().trim();
+        let named = object(&text, &format!("{} R", annot));
+        let key = value_of(named, "/StructParent ", "\n");
+
+        let parent_tree = value_of(&text, "/ParentTree ", "\n");
+        let nums = value_of(object(&text, parent_tree), "/Nums[", "]");
+        let elem = nums.strip_prefix(key).unwrap().trim();
+        let elem = object(&text, elem);
+        assert!(elem.contains("/S/Link"));
+        assert_eq!(value_of(elem, "/Obj ", "\n"), format!("{} R", annot));
+    }
+
+    /// Find the first occurrence of `pat` in `data`
+    pub(super) fn find(data: &[u8], pat: &[u8]) -> usize {
+        data.windows(pat.len()).position(|w| w == pat).unwrap()
+    }
+
+    /// Parse the numbers after `key`, up to the next `]` or line end
+    pub(super) fn numbers_after(data: &[u8], key: &[u8]) -> Vec<usize> {
+        let start = find(data, key) + key.len();
+        let len = data[start..].iter().position(|b| b"]\n".contains(b));
+        let text = std::str::from_utf8(&data[start..start + len.unwrap()]).unwrap();
+        text.split_ascii_whitespace()
+            .map(|n| n.parse().unwrap())
+            .collect()
+    }
+
+    /// Get the decoded data of the flate-encoded stream at `offset`
+    fn stream_data(data: &[u8], offset: usize) -> Vec<u8> {
+        let obj = &data[offset..];
+        let len = numbers_after(obj, b"/Length ")[0];
+        let start = find(obj, b"stream\n") + 7;
+        let mut out = Vec::new();
+        let mut decoder = ZlibDecoder::new(&obj[start..start + len]);
+        decoder.read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_object_streams() {
+        let mut hnd = links_handle("chapter");
+        hnd.object_streams = true;
+        let mut out = Vec::new();
+        hnd.write(&mut out).unwrap();
+
+        let startxref = numbers_after(&out, b"startxref\n")[0];
+        let xref = &out[startxref..];
+        let xref_id: usize = std::str::from_utf8(&xref[..find(xref, b" 0 obj")])
+            .unwrap()
+            .parse()
+            .unwrap();
+        let widths = numbers_after(xref, b"/W[");
+        assert_eq!((widths.len(), widths[0], widths[2]), (3, 1, 2));
+        let size = numbers_after(xref, b"/Size ")[0];
+        assert_eq!(numbers_after(xref, b"/Index["), [0, size]);
+
+        let entries = stream_data(&out, startxref);
+        let entry_len = widths.iter().sum::<usize>();
+        assert_eq!(entries.len(), size * entry_len);
+        let field = |bytes: &[u8]| bytes.iter().fold(0, |acc, b| acc << 8 | usize::from(*b));
+
+        let mut compressed = 0;
+        for (id, entry) in entries.chunks(entry_len).enumerate() {
+            let (f2, f3) = entry[1..].split_at(widths[1]);
+            let (f2, f3) = (field(f2), field(f3));
+            match entry[0] {
+                0 => assert_eq!((id, f3), (0, 65535)),
+                1 => {
+                    let head = format!("{} 0 obj\n", id);
+                    assert!(out[f2..].starts_with(head.as_bytes()), "object {}", id);
+                }
+                2 => {
+                    // The object stream is at the top level
+                    let stream = &entries[f2 * entry_len..][..entry_len];
+                    assert_eq!(stream[0], 1);
+                    let offset = field(&stream[1..=widths[1]]);
+                    let n = numbers_after(&out[offset..], b"/N ")[0];
+                    let first = numbers_after(&out[offset..], b"/First ")[0];
+                    let data = stream_data(&out, offset);
+                    let header = std::str::from_utf8(&data[..first]).unwrap();
+                    let header: Vec<usize> = header
+                        .split_ascii_whitespace()
+                        .map(|n| n.parse().unwrap())
+                        .collect();
+                    assert_eq!(header.len(), 2 * n);
+                    assert!(f3 < n);
+                    assert_eq!(header[2 * f3], id);
+                    assert!(first + header[2 * f3 + 1] < data.len());
+                    compressed += 1;
+                }
+                kind => panic!("Invalid entry type {} for object {}", kind, id),
+            }
+        }
+        assert!(compressed > 0);
+        assert_eq!(xref_id, size - 1);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::common::PdfString;
+use crate::common::{PdfString, Rectangle};
 
 #[derive(Debug, Clone)]
 /// Information for the Outline of the document
@@ -32,8 +32,29 @@ pub struct OutlineItem {
 }
 
 /// A destination of a GoTo Action
+///
+/// Coordinates are in the default user space of the page, `None` keeps
+/// the current value of the viewer.
 #[derive(Debug, Copy, Clone)]
 pub enum Destination {
     /// Scroll to page {0} at height {1} while fitting the page to the viewer
     PageFitH(usize, usize),
+    /// Show all of page {0}
+    PageFit(usize),
+    /// Scroll to page {0} with the upper left corner at ({1}, {2}) and zoom {3}
+    PageXYZ(usize, Option<i32>, Option<i32>, Option<f32>),
+    /// Show the rectangle {1} of page {0}
+    PageFitR(usize, Rectangle<i32>),
+}
+
+impl Destination {
+    /// The index of the page
+    pub fn page(&self) -> usize {
+        match *self {
+            Self::PageFitH(page, _)
+            | Self::PageFit(page)
+            | Self::PageXYZ(page, ..)
+            | Self::PageFitR(page, _) => page,
+        }
+    }
 }
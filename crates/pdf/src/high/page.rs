@@ -1,11 +1,11 @@
 use crate::{
-    common::{ObjRef, ProcSet, Rectangle},
+    common::{ObjRef, PdfString, ProcSet, Rectangle},
     low,
     lowering::{DebugName, LowerPagesCtx},
     util::NextId,
 };
 
-use super::{Font, ResDictRes, Resource, XObject};
+use super::{Destination, Font, ResDictRes, Resource, XObject};
 
 /// A single page
 pub struct Page<'a> {
@@ -15,6 +15,28 @@ pub struct Page<'a> {
     pub resources: Resources<'a>,
     /// The content stream of the page
     pub contents: Vec<u8>,
+    /// The link annotations on the page
+    pub links: Vec<Link>,
+}
+
+/// A link annotation
+#[derive(Debug, Clone)]
+pub struct Link {
+    /// The active area of the link in default user space
+    pub rect: Rectangle<i32>,
+    /// What happens when the link is activated
+    pub action: LinkAction,
+}
+
+/// The action of a link annotation
+#[derive(Debug, Clone)]
+pub enum LinkAction {
+    /// Go to a destination within the document
+    GoTo(Destination),
+    /// Go to a named destination, see [`Handle::dests`](super::Handle::dests)
+    GoToNamed(PdfString),
+    /// Open a (7-bit ASCII) URI
+    Uri(PdfString),
 }
 
 /// The resources of a page
@@ -48,6 +70,8 @@ pub(crate) fn lower_page<'a>(
     ctx: &mut LowerPagesCtx<'a>,
     id_gen: &mut NextId,
     contents_ref: ObjRef,
+    annots: Vec<ObjRef>,
+    struct_parents: Option<usize>,
 ) -> low::Page<'a> {
     low::Page {
         parent: ctx.pages_ref,
@@ -67,5 +91,7 @@ pub(crate) fn lower_page<'a>(
         },
         contents: contents_ref,
         media_box: Some(page.media_box),
+        annots,
+        struct_parents,
     }
 }
@@ -0,0 +1,532 @@
+//! PDF/A conformance levels and a pre-flight check
+
+use std::{error::Error, fmt};
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone};
+
+use crate::{
+    common::{BaseEncoding, Encoding, OutputIntentSubtype},
+    encoding::win_ansi_glyph_name,
+    write::{Formatter, Serialize},
+};
+
+use super::{cff, Font, FontFile, Handle, Resource, Type1Font, Type3Font};
+
+/// A PDF/A conformance level
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PdfAConformance {
+    /// PDF/A-1b (ISO 19005-1, level B)
+    A1b,
+    /// PDF/A-2b (ISO 19005-2, level B)
+    A2b,
+    /// PDF/A-3b (ISO 19005-3, level B)
+    A3b,
+}
+
+impl PdfAConformance {
+    /// The part of ISO 19005, for `pdfaid:part`
+    pub fn part(&self) -> u8 {
+        match self {
+            Self::A1b => 1,
+            Self::A2b => 2,
+            Self::A3b => 3,
+        }
+    }
+
+    /// The conformance level, for `pdfaid:conformance`
+    pub fn conformance(&self) -> char {
+        'B'
+    }
+
+    /// The highest major version of embedded ICC profiles
+    fn max_icc_version(&self) -> u8 {
+        match self {
+            // ICC.1:1998-09 or ICC.1A:1999-04
+            Self::A1b => 2,
+            // ICC.1:2004-10
+            Self::A2b | Self::A3b => 4,
+        }
+    }
+}
+
+impl fmt::Display for PdfAConformance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PDF/A-{}{}", self.part(), self.conformance())
+    }
+}
+
+/// A violation of a PDF/A requirement
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// There is no `GTS_PDFA1` output intent with an ICC profile
+    MissingOutputIntent,
+    /// The ICC profile of an output intent has an unsupported version
+    IccProfileVersion {
+        /// The major version of the profile
+        version: u8,
+        /// The highest supported major version
+        max: u8,
+    },
+    /// The font program is not embedded
+    FontNotEmbedded(String),
+    /// The `/Widths` array does not cover `FirstChar` to `LastChar`
+    WidthsLength {
+        /// The name of the font
+        font: String,
+        /// The number of widths
+        len: usize,
+        /// The number of chars between `FirstChar` and `LastChar`
+        expected: usize,
+    },
+    /// A glyph procedure has no `d0` or `d1` operator at the start
+    MissingGlyphWidth {
+        /// The name of the font
+        font: String,
+        /// The name of the glyph
+        glyph: String,
+    },
+    /// The width in `/Widths` differs from the one in the glyph procedure or
+    /// font program
+    WidthMismatch {
+        /// The name of the font
+        font: String,
+        /// The character code
+        code: u8,
+        /// The width in the `/Widths` array
+        widths: u32,
+        /// The width in the glyph procedure or font program
+        glyph: f32,
+    },
+    /// Object streams or cross-reference streams are not allowed
+    ObjectStreams,
+    /// The document or instance ID of the XMP metadata is nil
+    MissingDocumentId,
+    /// A date in the info dictionary differs from the one in the XMP metadata
+    DateMismatch {
+        /// The key in the info dictionary
+        key: &'static str,
+        /// The value in the info dictionary
+        info: String,
+        /// The value in the XMP metadata
+        xmp: String,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingOutputIntent => write!(f, "Missing GTS_PDFA1 output intent"),
+            Self::IccProfileVersion { version, max } => {
+                write!(f, "ICC profile version {} is newer than {}", version, max)
+            }
+            Self::FontNotEmbedded(font) => write!(f, "Font {:?} is not embedded", font),
+            Self::WidthsLength {
+                font,
+                len,
+                expected,
+            } => write!(
+                f,
+                "Font {:?} has {} widths, expected {}",
+                font, len, expected
+            ),
+            Self::MissingGlyphWidth { font, glyph } => write!(
+                f,
+                "Glyph {:?} of font {:?} does not start with d0 or d1",
+                glyph, font
+            ),
+            Self::WidthMismatch {
+                font,
+                code,
+                widths,
+                glyph,
+            } => write!(
+                f,
+                "Width of char {} in font {:?} is {} in /Widths but {} in the glyph",
+                code, font, widths, glyph
+            ),
+            Self::ObjectStreams => write!(f, "Object streams are not allowed"),
+            Self::MissingDocumentId => write!(f, "Missing XMP document or instance ID"),
+            Self::DateMismatch { key, info, xmp } => write!(
+                f,
+                "{} in the info dictionary ({}) does not match XMP ({})",
+                key, info, xmp
+            ),
+        }
+    }
+}
+
+/// The error returned when writing a document that fails the pre-flight check
+#[derive(Debug)]
+pub struct PreflightError {
+    /// The selected conformance level
+    pub conformance: PdfAConformance,
+    /// All violations that were found
+    pub violations: Vec<Violation>,
+}
+
+impl Error for PreflightError {}
+impl fmt::Display for PreflightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Document does not conform to {}:", self.conformance)?;
+        for violation in &self.violations {
+            write!(f, "\n- {}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the width from the `d0` or `d1` operator of a glyph procedure
+fn glyph_width(data: &[u8]) -> Option<f32> {
+    let text = std::str::from_utf8(data.get(..data.len().min(128))?).ok()?;
+    let mut operands = Vec::new();
+    for token in text.split_ascii_whitespace() {
+        match token {
+            "d0" | "d1" => return operands.first().copied(),
+            _ => operands.push(token.parse().ok()?),
+        }
+    }
+    None
+}
+
+/// Get the name of the glyph for `code`, from the differences or the base encoding
+///
+/// Only the `WinAnsiEncoding` is supported as a base encoding.
+fn glyph_name<'a>(encoding: &'a Encoding<'_>, code: u8) -> Option<&'a str> {
+    let difference = encoding
+        .differences
+        .as_ref()
+        .and_then(|d| d.get(usize::from(code)))
+        .and_then(Option::as_ref);
+    match (difference, encoding.base_encoding) {
+        (Some(name), _) => Some(name.0),
+        (None, Some(BaseEncoding::WinAnsiEncoding)) => win_ansi_glyph_name(code),
+        (None, _) => None,
+    }
+}
+
+/// Check that `/Widths` covers `FirstChar` to `LastChar`
+fn check_widths_len(name: &str, widths: &[u32], first: u8, last: u8, out: &mut Vec<Violation>) {
+    let expected = usize::from(last.saturating_sub(first)) + 1;
+    if widths.len() != expected {
+        out.push(Violation::WidthsLength {
+            font: name.to_owned(),
+            len: widths.len(),
+            expected,
+        });
+    }
+}
+
+/// Check the width of `code` in `/Widths` against the one of the glyph
+fn check_width(name: &str, code: u8, widths: u32, glyph: f32, out: &mut Vec<Violation>) {
+    if (glyph - widths as f32).abs() > 0.5 {
+        out.push(Violation::WidthMismatch {
+            font: name.to_owned(),
+            code,
+            widths,
+            glyph,
+        });
+    }
+}
+
+fn check_type3_font(name: &str, font: &Type3Font, out: &mut Vec<Violation>) {
+    check_widths_len(name, &font.widths, font.first_char, font.last_char, out);
+    for (code, widths) in (font.first_char..=font.last_char).zip(&font.widths) {
+        let glyph = match glyph_name(&font.encoding, code) {
+            Some(glyph) => glyph,
+            None => continue,
+        };
+        let proc = match font.char_procs.get(glyph) {
+            Some(proc) => proc,
+            None => continue,
+        };
+        match glyph_width(&proc.data) {
+            None => out.push(Violation::MissingGlyphWidth {
+                font: name.to_owned(),
+                glyph: glyph.to_owned(),
+            }),
+            Some(width) => check_width(name, code, *widths, width, out),
+        }
+    }
+}
+
+fn check_type1_font(name: &str, font: &Type1Font, out: &mut Vec<Violation>) {
+    check_widths_len(name, &font.widths, font.first_char, font.last_char, out);
+    let advances = match &font.font_file {
+        Some(FontFile::Type1C(data)) => cff::advance_widths(data).unwrap_or_default(),
+        None => {
+            out.push(Violation::FontNotEmbedded(name.to_owned()));
+            return;
+        }
+    };
+    for (code, widths) in (font.first_char..=font.last_char).zip(&font.widths) {
+        let advance = glyph_name(&font.encoding, code).and_then(|glyph| advances.get(glyph));
+        if let Some(advance) = advance {
+            check_width(name, code, *widths, *advance, out);
+        }
+    }
+}
+
+fn check_font(name: &str, font: &Font, out: &mut Vec<Violation>) {
+    match font {
+        Font::Type3(font) => check_type3_font(name, font, out),
+        Font::Type1(font) => check_type1_font(name, font, out),
+    }
+}
+
+/// Parse a date string (`D:YYYYMMDDHHmmSS+HH'mm`) as written to the info dictionary
+fn parse_pdf_date(text: &str) -> Option<DateTime<FixedOffset>> {
+    let text = text.strip_prefix("(D:")?.strip_suffix(')')?;
+    let (local, offset) = text.split_at(14.min(text.len()));
+    let local = NaiveDateTime::parse_from_str(local, "%Y%m%d%H%M%S").ok()?;
+    let sign = match offset.get(..1)? {
+        "+" | "Z" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let hours: i32 = offset.get(1..3).unwrap_or("0").parse().ok()?;
+    let minutes: i32 = offset.get(4..6).unwrap_or("0").parse().ok()?;
+    let offset = FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))?;
+    offset.from_local_datetime(&local).single()
+}
+
+/// Get the string value of `key` in a serialized info dictionary, with the parentheses
+fn info_value<'a>(info: &'a str, key: &str) -> Option<&'a str> {
+    let start = info.find(&format!("/{}(", key))? + key.len() + 1;
+    let len = info[start..].find(')')? + 1;
+    Some(&info[start..start + len])
+}
+
+/// Get the value of the `xmp:{tag}` element in a serialized XMP packet
+fn xmp_value<'a>(xmp: &'a str, tag: &str) -> Option<&'a str> {
+    let start_tag = format!("<xmp:{}>", tag);
+    let start = xmp.find(&start_tag)? + start_tag.len();
+    let len = xmp[start..].find(&format!("</xmp:{}>", tag))?;
+    Some(&xmp[start..start + len])
+}
+
+/// Check that the date `key` in the info dictionary matches `tag` in the XMP metadata
+fn check_date(key: &'static str, tag: &str, info: &str, xmp: &str, out: &mut Vec<Violation>) {
+    let info = info_value(info, key).unwrap_or_default();
+    let xmp = xmp_value(xmp, tag).unwrap_or_default();
+    let xmp_date = DateTime::parse_from_rfc3339(xmp).ok();
+    if xmp_date.is_none() || parse_pdf_date(info) != xmp_date {
+        out.push(Violation::DateMismatch {
+            key,
+            info: info.to_owned(),
+            xmp: xmp.to_owned(),
+        });
+    }
+}
+
+/// Check the dates of the info dictionary and XMP metadata as they are written
+fn check_dates(hnd: &Handle, out: &mut Vec<Violation>) {
+    // If the info dictionary can't be encoded, writing fails anyway
+    let info = match hnd.info() {
+        Ok(Some(info)) => info,
+        Ok(None) | Err(_) => return,
+    };
+    let mut buf = Vec::new();
+    if info.write(&mut Formatter::new(&mut buf)).is_err() {
+        return;
+    }
+    let info = String::from_utf8_lossy(&buf);
+    let xmp = match hnd.xmp().write() {
+        Ok(xmp) => xmp,
+        Err(_) => return,
+    };
+    let xmp = String::from_utf8_lossy(&xmp);
+    check_date("CreationDate", "CreateDate", &info, &xmp, out);
+    check_date("ModDate", "ModifyDate", &info, &xmp, out);
+}
+
+pub(super) fn preflight(hnd: &Handle, conformance: PdfAConformance) -> Vec<Violation> {
+    let mut out = Vec::new();
+
+    let pdfa_intents = hnd
+        .output_intents
+        .iter()
+        .filter(|oi| oi.subtype == OutputIntentSubtype::GTS_PDFA1)
+        .filter_map(|oi| oi.dest_output_profile.as_ref());
+    let mut has_output_intent = false;
+    for profile in pdfa_intents {
+        has_output_intent = true;
+        // The major version is stored in byte 8 of the profile header
+        let version = profile.stream.get(8).copied().unwrap_or(0);
+        let max = conformance.max_icc_version();
+        if version > max {
+            out.push(Violation::IccProfileVersion { version, max });
+        }
+    }
+    if !has_output_intent {
+        out.push(Violation::MissingOutputIntent);
+    }
+
+    for (index, font) in hnd.res.fonts.iter().enumerate() {
+        check_font(&format!("#{}", index), font, &mut out);
+    }
+    let font_dicts = hnd
+        .res
+        .font_dicts
+        .iter()
+        .chain(
+            hnd.pages
+                .iter()
+                .filter_map(|page| match &page.resources.fonts {
+                    Resource::Immediate(dict) => Some(dict.as_ref()),
+                    Resource::Global(_) => None,
+                }),
+        );
+    for dict in font_dicts {
+        for (key, font) in dict {
+            if let Resource::Immediate(font) = font {
+                check_font(key, font, &mut out);
+            }
+        }
+    }
+
+    if conformance == PdfAConformance::A1b && hnd.object_streams {
+        out.push(Violation::ObjectStreams);
+    }
+
+    if hnd.meta.document_id.is_nil() || hnd.meta.instance_id.is_nil() {
+        out.push(Violation::MissingDocumentId);
+    }
+    check_dates(hnd, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use chrono::{FixedOffset, TimeZone};
+
+    use crate::{
+        common::{
+            BaseEncoding, Encoding, ICCColorProfileMetadata, OutputIntentSubtype, PdfString,
+            SparseSet, StreamMetadata,
+        },
+        high::{Ascii85Stream, Font, Handle, ICCBasedColorProfile, OutputIntent, Type3Font},
+        write::PdfName,
+    };
+
+    use super::{PdfAConformance, Violation};
+
+    /// The header of an ICC profile with version 2
+    const PROFILE: [u8; 128] = {
+        let mut header = [0; 128];
+        header[8] = 2;
+        header
+    };
+
+    fn pdfa_handle(conformance: PdfAConformance) -> Handle<'static> {
+        let mut hnd = Handle::new();
+        hnd.pdfa = Some(conformance);
+        hnd.output_intents.push(OutputIntent {
+            subtype: OutputIntentSubtype::GTS_PDFA1,
+            output_condition: None,
+            output_condition_identifier: PdfString::new(b"sGray"),
+            registry_name: None,
+            info: None,
+            dest_output_profile: Some(ICCBasedColorProfile {
+                stream: &PROFILE,
+                meta: ICCColorProfileMetadata {
+                    alternate: None,
+                    num_components: 1,
+                },
+            }),
+        });
+        hnd
+    }
+
+    fn char_proc(data: &'static [u8]) -> Ascii85Stream<'static> {
+        Ascii85Stream {
+            data: Cow::Borrowed(data),
+            meta: StreamMetadata::None,
+        }
+    }
+
+    #[test]
+    fn test_preflight_ok() {
+        let hnd = pdfa_handle(PdfAConformance::A2b);
+        assert_eq!(hnd.preflight(), []);
+    }
+
+    #[test]
+    fn test_missing_output_intent() {
+        let mut hnd = pdfa_handle(PdfAConformance::A2b);
+        hnd.output_intents.clear();
+        assert_eq!(hnd.preflight(), [Violation::MissingOutputIntent]);
+    }
+
+    #[test]
+    fn test_object_streams() {
+        let mut hnd = pdfa_handle(PdfAConformance::A1b);
+        hnd.object_streams = true;
+        assert_eq!(hnd.preflight(), [Violation::ObjectStreams]);
+        hnd.pdfa = Some(PdfAConformance::A2b);
+        assert_eq!(hnd.preflight(), []);
+    }
+
+    #[test]
+    fn test_date_match() {
+        let mut hnd = pdfa_handle(PdfAConformance::A2b);
+        hnd.meta.title = Some("Test".to_owned());
+        let offset = FixedOffset::west_opt(5400).unwrap();
+        hnd.meta.creation_date = offset.with_ymd_and_hms(1992, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(hnd.preflight(), []);
+    }
+
+    #[test]
+    fn test_date_mismatch() {
+        let mut hnd = pdfa_handle(PdfAConformance::A2b);
+        hnd.meta.title = Some("Test".to_owned());
+        // Neither format can store a year with five digits
+        let offset = FixedOffset::east_opt(3600).unwrap();
+        let date = offset.with_ymd_and_hms(10000, 1, 2, 3, 4, 5).unwrap();
+        hnd.meta.modify_date = date;
+        let violations = hnd.preflight();
+        assert_eq!(violations.len(), 1);
+        assert!(matches!(
+            &violations[0],
+            Violation::DateMismatch { key: "ModDate", .. }
+        ));
+    }
+
+    #[test]
+    fn test_type3_widths() {
+        let mut hnd = pdfa_handle(PdfAConformance::A2b);
+        let mut differences = SparseSet::with_size(256);
+        differences[usize::from(b'B')] = Some(PdfName("C"));
+        let mut font = Type3Font {
+            first_char: b'A',
+            last_char: b'D',
+            widths: vec![500, 600, 600, 800],
+            encoding: Encoding {
+                base_encoding: Some(BaseEncoding::WinAnsiEncoding),
+                differences: Some(differences),
+            },
+            ..Type3Font::default()
+        };
+        // `B` is `C` in the differences, the other codes use the base encoding
+        let procs: [(&str, &[u8]); 4] = [
+            ("A", b"500 0 d0"),
+            ("B", b"500 0 d0"),
+            ("C", b"600 0 d0"),
+            ("D", b"750 0 d0"),
+        ];
+        for (name, data) in procs.iter() {
+            font.char_procs.insert(name.to_string(), char_proc(data));
+        }
+        hnd.res.fonts.push(Font::Type3(font));
+        assert_eq!(
+            hnd.preflight(),
+            [Violation::WidthMismatch {
+                font: "#0".to_owned(),
+                code: b'D',
+                widths: 800,
+                glyph: 750.0,
+            }]
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use crate::common::{PdfString, StructType};
+
+/// An element of the logical structure of a tagged PDF
+#[derive(Debug, Clone)]
+pub struct StructElem {
+    /// The structure type
+    pub kind: StructType,
+    /// An alternate description, which PDF/UA requires for figures
+    pub alt: Option<PdfString>,
+    /// The children, in reading order
+    pub children: Vec<StructKid>,
+}
+
+impl StructElem {
+    /// Creates a new element without children
+    pub fn new(kind: StructType) -> Self {
+        Self {
+            kind,
+            alt: None,
+            children: vec![],
+        }
+    }
+}
+
+/// A child of a structure element
+#[derive(Debug, Clone)]
+pub enum StructKid {
+    /// Another structure element
+    Elem(StructElem),
+    /// The marked-content sequence with the given MCID on a page
+    ///
+    /// The content stream of the page needs to contain a matching
+    /// `/Tag <</MCID mcid>> BDC ... EMC` sequence.
+    Content {
+        /// The index of the page
+        page: usize,
+        /// The marked-content identifier, unique within the page
+        mcid: u32,
+    },
+    /// The annotation of a link on a page, usually the kid of a [`StructType::Link`]
+    ///
+    /// The annotation gets a `/StructParent` key when it is written.
+    Link {
+        /// The index of the page
+        page: usize,
+        /// The index of the link in [`Page::links`](super::Page::links)
+        index: usize,
+    },
+}
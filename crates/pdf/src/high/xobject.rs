@@ -1,7 +1,7 @@
 use std::{borrow::Cow, convert::Infallible};
 
 use crate::{
-    common::{ImageMetadata, StreamMetadata},
+    common::{CCITTFaxDecodeParams, ImageMetadata, StreamMetadata},
     low,
     lowering::DebugName,
 };
@@ -15,11 +15,23 @@ pub enum XObject {
     Image(Image),
 }
 
+/// How the data of an [`Image`] is encoded
+#[derive(Debug, Copy, Clone, Default)]
+pub enum ImageEncoding {
+    /// Uncompressed samples
+    #[default]
+    Raw,
+    /// CCITT fax encoded (`/CCITTFaxDecode`)
+    CCITTFax(CCITTFaxDecodeParams),
+}
+
 #[derive(Debug)]
 /// An Image resource
 pub struct Image {
     /// The metadata for this image
     pub meta: ImageMetadata,
+    /// The encoding of `data`
+    pub encoding: ImageEncoding,
     /// The data for the image
     pub data: Vec<u8>,
 }
@@ -37,15 +49,23 @@ impl DebugName for XObject {
 }
 
 impl<'a> ToStream<'a> for XObject {
-    type Stream = low::Ascii85Stream<'a>;
+    type Stream = low::XObjectStream<'a>;
     type Error = Infallible;
 
     fn to_stream(&'a self) -> Result<Self::Stream, Self::Error> {
         match self {
-            Self::Image(i) => Ok(low::Ascii85Stream {
-                data: Cow::Borrowed(&i.data),
-                meta: StreamMetadata::Image(i.meta),
-            }),
+            Self::Image(i) => {
+                let data = Cow::Borrowed(i.data.as_slice());
+                let meta = StreamMetadata::Image(i.meta);
+                Ok(match i.encoding {
+                    ImageEncoding::Raw => {
+                        low::XObjectStream::Ascii85(low::Ascii85Stream { data, meta })
+                    }
+                    ImageEncoding::CCITTFax(params) => {
+                        low::XObjectStream::CCITTFax(low::CCITTFaxStream { data, meta, params })
+                    }
+                })
+            }
         }
     }
 }
@@ -28,6 +28,7 @@
 //!     },
 //!     resources: Resources::default(),
 //!     contents: Vec::new(),
+//!     links: Vec::new(),
 //! };
 //!
 //! // Add the page to the document
@@ -12,11 +12,11 @@ use flate2::{write::ZlibEncoder, Compression};
 
 use crate::{
     common::{
-        Dict, Encoding, FontDescriptor, Matrix, ObjRef, PdfString, ProcSet, Rectangle,
-        StreamMetadata,
+        self, CCITTFaxDecodeParams, Dict, Encoding, Matrix, ObjRef, PdfString, ProcSet, Rectangle,
+        StreamMetadata, StructType,
     },
     encoding::ascii_85_encode,
-    write::{Formatter, PdfName, Serialize, ToDict},
+    write::{Formatter, PdfDict, PdfName, PdfNull, Serialize, ToDict},
 };
 
 /// Destination of a GoTo action
@@ -24,13 +24,36 @@ use crate::{
 pub enum Destination {
     /// Page @0, fit the page into view and scroll to height {1}
     PageFitH(ObjRef, usize),
+    /// Page @0, fit the entire page into view
+    PageFit(ObjRef),
+    /// Page @0, upper left corner at ({1}, {2}) with zoom {3}, `None` is unchanged
+    PageXYZ(ObjRef, Option<i32>, Option<i32>, Option<f32>),
+    /// Page @0, fit the rectangle {1} into view
+    PageFitR(ObjRef, Rectangle<i32>),
+    /// A named destination, see [Names::dests]
+    Named(PdfString),
 }
 
 /// A PDF action
 #[derive(Debug, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Action {
     /// Go to some destination within the document
     GoTo(Destination),
+    /// Resolve a (7-bit ASCII) uniform resource identifier
+    URI(PdfString),
+}
+
+/// An optional value that is written as `null` if missing
+struct OrNull<'a, T>(&'a Option<T>);
+
+impl<T: Serialize> Serialize for OrNull<'_, T> {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        match self.0 {
+            Some(value) => value.write(f),
+            None => PdfNull.write(f),
+        }
+    }
 }
 
 impl Serialize for Destination {
@@ -42,6 +65,42 @@ impl Serialize for Destination {
                 .entry(&PdfName("FitH"))?
                 .entry(top)?
                 .finish(),
+            Self::PageFit(r) => f.pdf_arr().entry(r)?.entry(&PdfName("Fit"))?.finish(),
+            Self::PageXYZ(r, left, top, zoom) => f
+                .pdf_arr()
+                .entry(r)?
+                .entry(&PdfName("XYZ"))?
+                .entry(&OrNull(left))?
+                .entry(&OrNull(top))?
+                .entry(&OrNull(zoom))?
+                .finish(),
+            Self::PageFitR(r, rect) => f
+                .pdf_arr()
+                .entry(r)?
+                .entry(&PdfName("FitR"))?
+                .entry(&rect.ll.x)?
+                .entry(&rect.ll.y)?
+                .entry(&rect.ur.x)?
+                .entry(&rect.ur.y)?
+                .finish(),
+            Self::Named(name) => name.write(f),
+        }
+    }
+}
+
+impl Serialize for Action {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        match self {
+            Self::GoTo(dest) => f
+                .pdf_dict()
+                .field("S", &PdfName("GoTo"))?
+                .field("D", dest)?
+                .finish(),
+            Self::URI(uri) => f
+                .pdf_dict()
+                .field("S", &PdfName("URI"))?
+                .field("URI", uri)?
+                .finish(),
         }
     }
 }
@@ -101,6 +160,36 @@ impl Serialize for OutlineItem {
             .field("Count", &self.count)?;
         match &self.action {
             Action::GoTo(dest) => dict.field("Dest", dest),
+            action => dict.field("A", action),
+        }?;
+        dict.finish()
+    }
+}
+
+/// A link annotation
+#[derive(Debug, Clone)]
+pub struct LinkAnnotation {
+    /// The active area in default user space
+    pub rect: Rectangle<i32>,
+    /// The action to perform when the link is activated
+    pub action: Action,
+    /// The key of this annotation in the parent tree, if it is tagged
+    pub struct_parent: Option<usize>,
+}
+
+impl Serialize for LinkAnnotation {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        let mut dict = f.pdf_dict();
+        dict.field("Type", &PdfName("Annot"))?
+            .field("Subtype", &PdfName("Link"))?
+            .field("Rect", &self.rect)?
+            .field("Border", &[0, 0, 0])?
+            // Print
+            .field("F", &4)?
+            .opt_field("StructParent", &self.struct_parent)?;
+        match &self.action {
+            Action::GoTo(dest) => dict.field("Dest", dest),
+            action => dict.field("A", action),
         }?;
         dict.finish()
     }
@@ -117,20 +206,149 @@ pub struct Page<'a> {
     /// (required, inheritable) describes the bound of the physical page
     /// in default user units
     pub media_box: Option<Rectangle<i32>>,
+    /// The annotations on this page
+    pub annots: Vec<ObjRef>,
+    /// The key of this page in the parent tree, if it has marked content
+    pub struct_parents: Option<usize>,
 }
 
 impl Serialize for Page<'_> {
     fn write(&self, f: &mut Formatter) -> io::Result<()> {
-        f.pdf_dict()
-            .field("Type", &PdfName("Page"))?
+        let mut dict = f.pdf_dict();
+        dict.field("Type", &PdfName("Page"))?
             .field("Parent", &self.parent)?
             .opt_field("MediaBox", &self.media_box)?
             .field("Resources", &self.resources)?
             .field("Contents", &self.contents)?
+            .opt_arr_field("Annots", &self.annots)?
+            .opt_field("StructParents", &self.struct_parents)?;
+        if self.struct_parents.is_some() {
+            // Use the structure order for navigating annotations
+            dict.field("Tabs", &PdfName("S"))?;
+        }
+        dict.finish()
+    }
+}
+
+/// The root of the structure tree
+pub struct StructTreeRoot {
+    /// The top-level structure elements
+    pub kids: Vec<ObjRef>,
+    /// Reference to the parent tree
+    pub parent_tree: ObjRef,
+    /// The smallest key that is not used in the parent tree
+    pub parent_tree_next_key: usize,
+}
+
+impl Serialize for StructTreeRoot {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        f.pdf_dict()
+            .field("Type", &PdfName("StructTreeRoot"))?
+            .field("K", &self.kids)?
+            .field("ParentTree", &self.parent_tree)?
+            .field("ParentTreeNextKey", &self.parent_tree_next_key)?
+            .finish()
+    }
+}
+
+/// A child of a structure element
+#[derive(Debug, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum StructKid {
+    /// Another structure element
+    Elem(ObjRef),
+    /// A marked-content sequence on the page of the parent element
+    MCID(u32),
+    /// A marked-content sequence {1} on some other page @0
+    MCR(ObjRef, u32),
+    /// An annotation {1} on the page @0
+    OBJR(ObjRef, ObjRef),
+}
+
+impl Serialize for StructKid {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        match self {
+            Self::Elem(r) => r.write(f),
+            Self::MCID(mcid) => mcid.write(f),
+            Self::MCR(page, mcid) => f
+                .pdf_dict()
+                .field("Type", &PdfName("MCR"))?
+                .field("Pg", page)?
+                .field("MCID", mcid)?
+                .finish(),
+            Self::OBJR(page, obj) => f
+                .pdf_dict()
+                .field("Type", &PdfName("OBJR"))?
+                .field("Pg", page)?
+                .field("Obj", obj)?
+                .finish(),
+        }
+    }
+}
+
+/// A structure element
+#[derive(Debug, Clone)]
+pub struct StructElem {
+    /// The structure type
+    pub kind: StructType,
+    /// The parent element or the structure tree root
+    pub parent: ObjRef,
+    /// The page that contains the marked content
+    pub page: Option<ObjRef>,
+    /// The children
+    pub kids: Vec<StructKid>,
+    /// An alternate description, e.g. for figures
+    pub alt: Option<PdfString>,
+}
+
+impl Serialize for StructElem {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        f.pdf_dict()
+            .field("Type", &PdfName("StructElem"))?
+            .field("S", &self.kind)?
+            .field("P", &self.parent)?
+            .opt_field("Pg", &self.page)?
+            .field("K", &self.kids)?
+            .opt_field("Alt", &self.alt)?
             .finish()
     }
 }
 
+/// An entry in the parent tree
+pub enum StructParents {
+    /// The structure elements of the marked-content sequences of a page, by MCID
+    Content(Vec<Option<ObjRef>>),
+    /// The structure element of an annotation
+    Annot(ObjRef),
+}
+
+impl Serialize for StructParents {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        match self {
+            Self::Content(elems) => {
+                let mut arr = f.pdf_arr();
+                for elem in elems {
+                    arr.entry(&OrNull(elem))?;
+                }
+                arr.finish()
+            }
+            Self::Annot(elem) => elem.write(f),
+        }
+    }
+}
+
+/// The mark information dictionary
+pub struct MarkInfo {
+    /// Whether the document conforms to the tagged PDF conventions
+    pub marked: bool,
+}
+
+impl Serialize for MarkInfo {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        f.pdf_dict().field("Marked", &self.marked)?.finish()
+    }
+}
+
 /// A resource entry
 pub enum Resource<T> {
     /// Reference to another object
@@ -167,7 +385,7 @@ pub struct Type3Font<'a> {
     /// Width of every char between first and last
     pub widths: &'a [u32],
     /// Font characteristics
-    pub font_descriptor: Option<FontDescriptor>,
+    pub font_descriptor: Option<common::FontDescriptor>,
     /// Optional reference to a CMap stream
     pub to_unicode: Option<ObjRef>,
 }
@@ -188,10 +406,69 @@ impl ToDict for Type3Font<'_> {
     }
 }
 
+/// A reference to an embedded font program
+#[derive(Debug, Copy, Clone)]
+pub enum FontFileRef {
+    /// A font program of a subtype given in the stream dictionary
+    FontFile3(ObjRef),
+}
+
+/// A font descriptor with a reference to the embedded font program
+pub struct FontDescriptor<'a> {
+    /// The font characteristics
+    pub inner: &'a common::FontDescriptor,
+    /// The font program
+    pub font_file: Option<FontFileRef>,
+}
+
+impl Serialize for FontDescriptor<'_> {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        let mut dict = f.pdf_dict();
+        dict.embed(self.inner)?;
+        if let Some(FontFileRef::FontFile3(r)) = self.font_file {
+            dict.field("FontFile3", &r)?;
+        }
+        dict.finish()
+    }
+}
+
+/// A type 1 font resource
+pub struct Type1Font<'a> {
+    /// The PostScript name of the font
+    pub base_font: PdfName<'a>,
+    /// The first used char key
+    pub first_char: u8,
+    /// The last used char key
+    pub last_char: u8,
+    /// Width of every char between first and last
+    pub widths: &'a [u32],
+    /// Reference to the font descriptor
+    pub font_descriptor: ObjRef,
+    /// Dict of encoding value to char names
+    pub encoding: Resource<Encoding<'a>>,
+    /// Optional reference to a CMap stream
+    pub to_unicode: Option<ObjRef>,
+}
+
+impl ToDict for Type1Font<'_> {
+    fn write(&self, dict: &mut crate::write::PdfDict<'_, '_>) -> io::Result<()> {
+        dict.field("BaseFont", &self.base_font)?
+            .field("FirstChar", &self.first_char)?
+            .field("LastChar", &self.last_char)?
+            .arr_field("Widths", self.widths)?
+            .field("FontDescriptor", &self.font_descriptor)?
+            .field("Encoding", &self.encoding)?
+            .opt_field("ToUnicode", &self.to_unicode)?;
+        Ok(())
+    }
+}
+
 /// A font resource
 pub enum Font<'a> {
     /// A type 3 font resource
     Type3(Type3Font<'a>),
+    /// A type 1 font resource
+    Type1(Type1Font<'a>),
 }
 
 impl Serialize for Font<'_> {
@@ -203,6 +480,10 @@ impl Serialize for Font<'_> {
                 dict.field("Subtype", &PdfName("Type3"))?;
                 dict.embed(font)?;
             }
+            Self::Type1(font) => {
+                dict.field("Subtype", &PdfName("Type1"))?;
+                dict.embed(font)?;
+            }
         };
         dict.finish()?;
         Ok(())
@@ -283,9 +564,51 @@ impl Serialize for FlateAscii85Stream<'_> {
     }
 }
 
+/// A stream of CCITT fax encoded image data
+pub struct CCITTFaxStream<'a> {
+    /// The encoded data of this stream
+    pub data: Cow<'a, [u8]>,
+    /// The associated metadata
+    pub meta: StreamMetadata,
+    /// The parameters of the encoding
+    pub params: CCITTFaxDecodeParams,
+}
+
+impl Serialize for CCITTFaxStream<'_> {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        f.pdf_dict()
+            .embed(&self.meta)?
+            .field("Length", &self.data.len())?
+            .field("Filter", &Filter::CCITTFaxDecode)?
+            .field("DecodeParms", &self.params)?
+            .finish()?;
+        f.pdf_stream(&self.data)?;
+        Ok(())
+    }
+}
+
+/// The stream of an XObject
+pub enum XObjectStream<'a> {
+    /// An ASCII85 encoded stream
+    Ascii85(Ascii85Stream<'a>),
+    /// A CCITT fax encoded image
+    CCITTFax(CCITTFaxStream<'a>),
+}
+
+impl Serialize for XObjectStream<'_> {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        match self {
+            Self::Ascii85(stream) => stream.write(f),
+            Self::CCITTFax(stream) => stream.write(f),
+        }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
 enum Filter {
     ASCII85Decode,
     FlateDecode,
+    CCITTFaxDecode,
 }
 
 impl Serialize for Filter {
@@ -293,6 +616,7 @@ impl Serialize for Filter {
         match self {
             Filter::ASCII85Decode => PdfName("ASCII85Decode"),
             Filter::FlateDecode => PdfName("FlateDecode"),
+            Filter::CCITTFaxDecode => PdfName("CCITTFaxDecode"),
         }
         .write(f)
     }
@@ -365,6 +689,7 @@ impl Serialize for Stream<'_> {
 }
 
 /// Well-known PDF Versions
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum PdfVersion {
     /// PDF-1.0
     V1_0,
@@ -384,21 +709,28 @@ pub enum PdfVersion {
     V1_7,
 }
 
-impl Serialize for PdfVersion {
-    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+impl PdfVersion {
+    /// The version number, as used in the header and the catalog
+    pub fn as_str(&self) -> &'static str {
         match self {
-            Self::V1_0 => PdfName("1.0").write(f),
-            Self::V1_1 => PdfName("1.1").write(f),
-            Self::V1_2 => PdfName("1.2").write(f),
-            Self::V1_3 => PdfName("1.3").write(f),
-            Self::V1_4 => PdfName("1.4").write(f),
-            Self::V1_5 => PdfName("1.5").write(f),
-            Self::V1_6 => PdfName("1.6").write(f),
-            Self::V1_7 => PdfName("1.7").write(f),
+            Self::V1_0 => "1.0",
+            Self::V1_1 => "1.1",
+            Self::V1_2 => "1.2",
+            Self::V1_3 => "1.3",
+            Self::V1_4 => "1.4",
+            Self::V1_5 => "1.5",
+            Self::V1_6 => "1.6",
+            Self::V1_7 => "1.7",
         }
     }
 }
 
+impl Serialize for PdfVersion {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        PdfName(self.as_str()).write(f)
+    }
+}
+
 /// The catalog/root of the document
 pub struct Catalog {
     /// The PDF Version
@@ -414,6 +746,24 @@ pub struct Catalog {
     pub output_intents: Vec<ObjRef>,
     /// XMP metadata stream
     pub metadata: Option<ObjRef>,
+    /// The name dictionary
+    pub names: Option<Names>,
+    /// Information on whether this is a tagged PDF
+    pub mark_info: Option<MarkInfo>,
+    /// Reference to the root of the structure tree
+    pub struct_tree_root: Option<ObjRef>,
+}
+
+/// The name dictionary of the document
+pub struct Names {
+    /// Reference to the name tree of named destinations
+    pub dests: Option<ObjRef>,
+}
+
+impl Serialize for Names {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        f.pdf_dict().opt_field("Dests", &self.dests)?.finish()
+    }
 }
 
 impl Serialize for Catalog {
@@ -426,6 +776,9 @@ impl Serialize for Catalog {
             .opt_field("Outlines", &self.outline)?
             .opt_arr_field("OutputIntents", &self.output_intents)?
             .opt_field("Metadata", &self.metadata)?
+            .opt_field("Names", &self.names)?
+            .opt_field("MarkInfo", &self.mark_info)?
+            .opt_field("StructTreeRoot", &self.struct_tree_root)?
             .finish()
     }
 }
@@ -460,13 +813,78 @@ pub struct Trailer {
     pub id: ID,
 }
 
+impl ToDict for Trailer {
+    fn write(&self, dict: &mut PdfDict<'_, '_>) -> io::Result<()> {
+        dict.field("Size", &self.size)?
+            .opt_field("Info", &self.info)?
+            .field("Root", &self.root)?
+            .field("ID", &self.id)?;
+        Ok(())
+    }
+}
+
 impl Serialize for Trailer {
     fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        f.pdf_dict().embed(self)?.finish()
+    }
+}
+
+/// A stream of compressed objects (PDF 1.5)
+pub struct ObjectStream {
+    /// The number of objects
+    pub n: usize,
+    /// The offset of the first object in the data
+    pub first: usize,
+    /// The object numbers and offsets, followed by the objects
+    pub data: Vec<u8>,
+}
+
+impl Serialize for ObjectStream {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        let mut e = ZlibEncoder::new(Vec::new(), Compression::best());
+        e.write_all(&self.data)?;
+        let mut buf = e.finish()?;
+        let len = buf.len();
+        buf.push(10);
         f.pdf_dict()
-            .field("Size", &self.size)?
-            .opt_field("Info", &self.info)?
-            .field("Root", &self.root)?
-            .field("ID", &self.id)?
-            .finish()
+            .field("Type", &PdfName("ObjStm"))?
+            .field("N", &self.n)?
+            .field("First", &self.first)?
+            .field("Length", &len)?
+            .field("Filter", &Filter::FlateDecode)?
+            .finish()?;
+        f.pdf_stream(&buf)
+    }
+}
+
+/// A cross-reference stream, which replaces the xref section and trailer (PDF 1.5)
+pub struct XRefStream {
+    /// The entries of the trailer
+    pub trailer: Trailer,
+    /// The byte widths of the three fields of an entry
+    pub widths: [usize; 3],
+    /// The first object number and number of entries of each subsection
+    pub index: Vec<(usize, usize)>,
+    /// The binary entries
+    pub data: Vec<u8>,
+}
+
+impl Serialize for XRefStream {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        let mut e = ZlibEncoder::new(Vec::new(), Compression::best());
+        e.write_all(&self.data)?;
+        let mut buf = e.finish()?;
+        let len = buf.len();
+        buf.push(10);
+        let index: Vec<usize> = self.index.iter().flat_map(|&(a, b)| [a, b]).collect();
+        f.pdf_dict()
+            .field("Type", &PdfName("XRef"))?
+            .embed(&self.trailer)?
+            .field("W", &self.widths)?
+            .field("Index", &index)?
+            .field("Length", &len)?
+            .field("Filter", &Filter::FlateDecode)?
+            .finish()?;
+        f.pdf_stream(&buf)
     }
 }
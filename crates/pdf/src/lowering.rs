@@ -1,12 +1,16 @@
 //! Helpers to turn *high* types into *low* types
 
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+};
 
 use crate::{
     common::{Dict, Encoding, ObjRef},
     high::{
-        Destination, DictResource, Font, GlobalResource, Handle, LowerFontCtx, OutlineItem,
-        ResDictRes, Resource, ToStream, XObject,
+        Destination, DictResource, Font, GlobalResource, Handle, Link, LinkAction, LowerFontCtx,
+        OutlineItem, Res, ResDictRes, Resource, Resources, StructElem, StructKid, ToStream,
+        XObject,
     },
     low,
     util::NextId,
@@ -17,17 +21,104 @@ pub fn make_ref(id: u64) -> ObjRef {
     ObjRef { id, gen: 0 }
 }
 
-fn lower_dest(pages: &[ObjRef], dest: Destination) -> low::Action {
-    use low::Action::*;
+pub(super) fn lower_dest(pages: &[ObjRef], dest: Destination) -> low::Destination {
     use low::Destination::*;
     match dest {
-        Destination::PageFitH(a, top) => {
-            let page = pages[a];
-            GoTo(PageFitH(page, top))
-        }
+        Destination::PageFitH(a, top) => PageFitH(pages[a], top),
+        Destination::PageFit(a) => PageFit(pages[a]),
+        Destination::PageXYZ(a, left, top, zoom) => PageXYZ(pages[a], left, top, zoom),
+        Destination::PageFitR(a, rect) => PageFitR(pages[a], rect),
+    }
+}
+
+pub(super) fn lower_link(pages: &[ObjRef], link: &Link) -> low::LinkAnnotation {
+    use low::Action::*;
+    let action = match &link.action {
+        LinkAction::GoTo(dest) => GoTo(lower_dest(pages, *dest)),
+        LinkAction::GoToNamed(name) => GoTo(low::Destination::Named(name.clone())),
+        LinkAction::Uri(uri) => URI(uri.clone()),
+    };
+    low::LinkAnnotation {
+        rect: link.rect,
+        action,
+        struct_parent: None,
     }
 }
 
+/// The parents of marked content and annotations, see [`lower_struct_elem`]
+#[derive(Default)]
+pub(super) struct ParentTree {
+    /// The structure elements of the marked content of a page, by MCID
+    pub pages: BTreeMap<usize, Vec<Option<ObjRef>>>,
+    /// The structure element of a link, by page and index of the link
+    pub links: BTreeMap<(usize, usize), ObjRef>,
+}
+
+pub(super) fn lower_struct_elem(
+    acc: &mut Vec<(ObjRef, low::StructElem)>,
+    parent_tree: &mut ParentTree,
+    pages: &[ObjRef],
+    annots: &[Vec<ObjRef>],
+    elem: &StructElem,
+    parent: ObjRef,
+    id_gen: &mut NextId,
+) -> io::Result<ObjRef> {
+    let elem_ref = make_ref(id_gen.next());
+    let page_ref = |page: usize| {
+        pages.get(page).copied().ok_or_else(|| {
+            let msg = format!("Marked content on missing page {}", page);
+            io::Error::new(io::ErrorKind::InvalidInput, msg)
+        })
+    };
+
+    // The first page with marked content is the default for all kids
+    let first_page = elem.children.iter().find_map(|kid| match kid {
+        StructKid::Content { page, .. } => Some(*page),
+        StructKid::Elem(_) | StructKid::Link { .. } => None,
+    });
+    let mut kids = Vec::with_capacity(elem.children.len());
+    for kid in &elem.children {
+        kids.push(match kid {
+            StructKid::Elem(child) => {
+                let r =
+                    lower_struct_elem(acc, parent_tree, pages, annots, child, elem_ref, id_gen)?;
+                low::StructKid::Elem(r)
+            }
+            &StructKid::Link { page, index } => {
+                let annot = annots.get(page).and_then(|a| a.get(index)).ok_or_else(|| {
+                    let msg = format!("Missing link {} on page {}", index, page);
+                    io::Error::new(io::ErrorKind::InvalidInput, msg)
+                })?;
+                parent_tree.links.insert((page, index), elem_ref);
+                low::StructKid::OBJR(page_ref(page)?, *annot)
+            }
+            &StructKid::Content { page, mcid } => {
+                let parents = parent_tree.pages.entry(page).or_default();
+                let index = mcid as usize;
+                if parents.len() <= index {
+                    parents.resize(index + 1, None);
+                }
+                parents[index] = Some(elem_ref);
+                match Some(page) == first_page {
+                    true => low::StructKid::MCID(mcid),
+                    false => low::StructKid::MCR(page_ref(page)?, mcid),
+                }
+            }
+        });
+    }
+    acc.push((
+        elem_ref,
+        low::StructElem {
+            kind: elem.kind,
+            parent,
+            page: first_page.map(page_ref).transpose()?,
+            kids,
+            alt: elem.alt.clone(),
+        },
+    ));
+    Ok(elem_ref)
+}
+
 pub(super) fn lower_outline_items(
     acc: &mut Vec<(ObjRef, low::OutlineItem)>,
     pages: &[ObjRef],
@@ -46,7 +137,7 @@ pub(super) fn lower_outline_items(
                 Some((fc, lc)) => (Some(fc), Some(lc)),
                 None => (None, None),
             };
-            let action = lower_dest(pages, item.dest);
+            let action = low::Action::GoTo(lower_dest(pages, item.dest));
             let next = make_ref(id_gen.next());
             acc.push((
                 curr,
@@ -70,7 +161,7 @@ pub(super) fn lower_outline_items(
             Some((fc, lc)) => (Some(fc), Some(lc)),
             None => (None, None),
         };
-        let action = lower_dest(pages, last.dest);
+        let action = low::Action::GoTo(lower_dest(pages, last.dest));
         acc.push((
             curr,
             low::OutlineItem {
@@ -156,6 +247,15 @@ impl<'a, T> LowerBox<'a, T> {
     pub(crate) fn store_values(&self) -> impl Iterator<Item = (ObjRef, &'a T)> + '_ {
         self.store.values().copied()
     }
+
+    /// Use the given references for global resources
+    fn preset(&mut self, refs: &HashMap<usize, ObjRef>) {
+        for (&index, &r) in refs {
+            if let Some(val) = self.res.get(index) {
+                self.store.insert(index, (r, val));
+            }
+        }
+    }
 }
 
 impl<'a, T: DebugName> LowerBox<'a, T> {
@@ -289,6 +389,16 @@ pub(crate) struct LowerPagesCtx<'a> {
 }
 
 impl<'a> LowerPagesCtx<'a> {
+    /// Create a context where the given global resources already have references
+    pub(crate) fn with_refs(doc: &'a Handle, pages_ref: ObjRef, refs: &GlobalRefs) -> Self {
+        let mut ctx = Self::new(doc, pages_ref);
+        ctx.x_objects.preset(&refs.x_objects);
+        ctx.x_object_dicts.preset(&refs.x_object_dicts);
+        ctx.fonts.preset(&refs.fonts);
+        ctx.font_dicts.preset(&refs.font_dicts);
+        ctx
+    }
+
     pub(crate) fn new(doc: &'a Handle, pages_ref: ObjRef) -> Self {
         Self {
             x_objects: LowerBox::new(&doc.res.x_objects),
@@ -304,3 +414,100 @@ impl<'a> LowerPagesCtx<'a> {
         }
     }
 }
+
+/// The references of global resources, for pages that are written before
+/// the resources themselves
+#[derive(Default)]
+pub(crate) struct GlobalRefs {
+    pub x_objects: HashMap<usize, ObjRef>,
+    pub x_object_dicts: HashMap<usize, ObjRef>,
+    pub fonts: HashMap<usize, ObjRef>,
+    pub font_dicts: HashMap<usize, ObjRef>,
+}
+
+fn global_ref(
+    refs: &mut HashMap<usize, ObjRef>,
+    len: usize,
+    index: usize,
+    id_gen: &mut NextId,
+    name: &str,
+) -> io::Result<ObjRef> {
+    if index >= len {
+        let msg = format!("Couldn't find {} #{}", name, index);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+    }
+    Ok(*refs.entry(index).or_insert_with(|| make_ref(id_gen.next())))
+}
+
+fn global_refs_dict<T: DebugName>(
+    dict: &DictResource<T>,
+    refs: &mut HashMap<usize, ObjRef>,
+    res: &[T],
+    id_gen: &mut NextId,
+) -> io::Result<Dict<ObjRef>> {
+    let mut out = Dict::new();
+    for (key, value) in dict {
+        let r = match value {
+            Resource::Global(global) => {
+                global_ref(refs, res.len(), global.index, id_gen, T::debug_name())?
+            }
+            Resource::Immediate(_) => {
+                let msg = format!("Immediate {} {:?} in a streamed page", T::debug_name(), key);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+            }
+        };
+        out.insert(key.clone(), r);
+    }
+    Ok(out)
+}
+
+/// Lower the resources of a page that is written before the global resources
+///
+/// The fonts and XObjects need to be global resources, so that the page can be
+/// dropped before they are written.
+pub(crate) fn lower_global_resources<'p>(
+    resources: &'p Resources,
+    res: &Res,
+    refs: &mut GlobalRefs,
+    id_gen: &mut NextId,
+) -> io::Result<low::Resources<'p>> {
+    let font = match &resources.fonts {
+        Resource::Global(global) => {
+            let len = res.font_dicts.len();
+            let r = global_ref(&mut refs.font_dicts, len, global.index, id_gen, "Font Dict")?;
+            low::Resource::Ref(r)
+        }
+        Resource::Immediate(dict) => {
+            let dict = global_refs_dict(dict, &mut refs.fonts, &res.fonts, id_gen)?;
+            low::Resource::Immediate(
+                dict.into_iter()
+                    .map(|(k, r)| (k, low::Resource::Ref(r)))
+                    .collect(),
+            )
+        }
+    };
+    let x_object = match &resources.x_objects {
+        Resource::Global(global) => {
+            let len = res.x_object_dicts.len();
+            let r = global_ref(
+                &mut refs.x_object_dicts,
+                len,
+                global.index,
+                id_gen,
+                "XObject Dict",
+            )?;
+            low::Resource::Ref(r)
+        }
+        Resource::Immediate(dict) => low::Resource::Immediate(global_refs_dict(
+            dict,
+            &mut refs.x_objects,
+            &res.x_objects,
+            id_gen,
+        )?),
+    };
+    Ok(low::Resources {
+        font,
+        x_object,
+        proc_set: &resources.proc_sets,
+    })
+}
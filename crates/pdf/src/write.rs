@@ -10,7 +10,7 @@ use chrono::{DateTime, TimeZone};
 use crate::{
     common::{Dict, ObjRef, PdfString},
     low,
-    util::ByteCounter,
+    util::{ByteCounter, NextId},
 };
 
 /// API to serialize a dict
@@ -197,12 +197,30 @@ impl PdfArr<'_, '_> {
     }
 }
 
+/// The number of objects to put into a single object stream
+const OBJECTS_PER_STREAM: usize = 100;
+
+/// An entry in the cross-reference table
+#[derive(Debug, Copy, Clone)]
+pub(super) enum XrefEntry {
+    /// A free entry, with the generation to use when it is reused
+    Free(u16),
+    /// An object at a byte offset
+    InUse(usize, u16),
+    /// The object with the given index in an object stream
+    Compressed(u64, usize),
+}
+
 /// Formatter for a PDF document
 pub struct Formatter<'a> {
     pub(super) inner: ByteCounter<&'a mut dyn Write>,
     indent: usize,
     needs_space: bool,
-    pub(super) xref: Vec<Option<(usize, u16, bool)>>,
+    /// Whether a stream was written, i.e. the object can't be compressed
+    wrote_stream: bool,
+    pub(super) xref: Vec<Option<XrefEntry>>,
+    /// The objects waiting to be put into object streams, if enabled
+    compressed: Option<Vec<(u64, Vec<u8>)>>,
 }
 
 impl<'a> Formatter<'a> {
@@ -212,10 +230,20 @@ impl<'a> Formatter<'a> {
             inner: ByteCounter::new(w),
             indent: 0,
             needs_space: false,
-            xref: vec![Some((0, 65535, true))],
+            wrote_stream: false,
+            xref: vec![Some(XrefEntry::Free(65535))],
+            compressed: None,
         }
     }
 
+    /// Put all objects that are not streams into object streams (PDF 1.5)
+    ///
+    /// This requires finishing the document with [`Formatter::xref_stream`]
+    /// instead of a classic xref section and trailer.
+    pub fn use_object_streams(&mut self) {
+        self.compressed.get_or_insert_with(Vec::new);
+    }
+
     /// Start writing a PDF dict
     pub fn pdf_dict(&mut self) -> PdfDict<'a, '_> {
         PdfDict {
@@ -234,6 +262,7 @@ impl<'a> Formatter<'a> {
 
     /// Start writing a stream
     pub fn pdf_stream(&mut self, data: &[u8]) -> io::Result<()> {
+        self.wrote_stream = true;
         writeln!(self.inner, "stream")?;
         self.inner.write_all(data)?;
         if !data.ends_with(&[0x0a]) {
@@ -245,18 +274,159 @@ impl<'a> Formatter<'a> {
 
     /// Start writing an object
     pub fn obj(&mut self, r#ref: ObjRef, obj: &dyn Serialize) -> io::Result<()> {
+        if self.compressed.is_some() && r#ref.gen == 0 {
+            let mut buf = Vec::new();
+            let mut f = Formatter::new(&mut buf);
+            obj.write(&mut f)?;
+            // Streams need to stay at the top level
+            if !f.wrote_stream {
+                self.set_xref(r#ref.id, XrefEntry::Compressed(0, 0));
+                if let Some(pending) = &mut self.compressed {
+                    pending.push((r#ref.id, buf));
+                }
+                return Ok(());
+            }
+            let offset = self.inner.bytes_written();
+            writeln!(self.inner, "{} {} obj", r#ref.id, r#ref.gen)?;
+            self.inner.write_all(&buf)?;
+            writeln!(self.inner, "endobj")?;
+            self.set_xref(r#ref.id, XrefEntry::InUse(offset, r#ref.gen));
+            return Ok(());
+        }
+
         let offset = self.inner.bytes_written();
         writeln!(self.inner, "{} {} obj", r#ref.id, r#ref.gen)?;
         obj.write(self)?;
         writeln!(self.inner, "endobj")?;
+        self.set_xref(r#ref.id, XrefEntry::InUse(offset, r#ref.gen));
+        Ok(())
+    }
 
-        while self.xref.len() <= (r#ref.id as usize) {
+    fn set_xref(&mut self, id: u64, entry: XrefEntry) {
+        while self.xref.len() <= (id as usize) {
             self.xref.push(None);
         }
-        self.xref[r#ref.id as usize] = Some((offset, r#ref.gen, false));
+        self.xref[id as usize] = Some(entry);
+    }
+
+    /// Write the given objects into an object stream with the ID `stream_id`
+    fn write_object_stream(&mut self, stream_id: u64, chunk: &[(u64, Vec<u8>)]) -> io::Result<()> {
+        let mut offsets = Vec::new();
+        let mut data = Vec::new();
+        for (index, (id, buf)) in chunk.iter().enumerate() {
+            write!(offsets, "{} {} ", id, data.len())?;
+            data.extend_from_slice(buf);
+            self.xref[*id as usize] = Some(XrefEntry::Compressed(stream_id, index));
+        }
+        let first = offsets.len();
+        offsets.extend_from_slice(&data);
+        let stream = low::ObjectStream {
+            n: chunk.len(),
+            first,
+            data: offsets,
+        };
+        let r#ref = ObjRef {
+            id: stream_id,
+            gen: 0,
+        };
+        self.obj(r#ref, &stream)
+    }
+
+    /// Write all pending objects into object streams
+    ///
+    /// This is only used at the end of the document, where the IDs after the
+    /// last entry of the xref are free.
+    fn flush_object_streams(&mut self) -> io::Result<()> {
+        let pending = match &mut self.compressed {
+            Some(pending) => std::mem::take(pending),
+            None => return Ok(()),
+        };
+        for chunk in pending.chunks(OBJECTS_PER_STREAM) {
+            let stream_id = self.xref.len() as u64;
+            self.write_object_stream(stream_id, chunk)?;
+        }
         Ok(())
     }
 
+    /// Write the pending objects that fill a complete object stream
+    ///
+    /// This keeps the pending objects from growing with the document. The
+    /// object streams get their IDs from `id_gen`.
+    pub(crate) fn flush_full_object_streams(&mut self, id_gen: &mut NextId) -> io::Result<()> {
+        loop {
+            let chunk: Vec<_> = match &mut self.compressed {
+                Some(pending) if pending.len() >= OBJECTS_PER_STREAM => {
+                    pending.drain(..OBJECTS_PER_STREAM).collect()
+                }
+                _ => return Ok(()),
+            };
+            self.write_object_stream(id_gen.next(), &chunk)?;
+        }
+    }
+
+    /// Write the pending object streams and a cross-reference stream (PDF 1.5)
+    ///
+    /// The cross-reference stream contains the entries of the trailer, so this
+    /// replaces the `trailer` keyword. The `size` of the trailer is set here.
+    pub fn xref_stream(&mut self, mut trailer: low::Trailer) -> io::Result<usize> {
+        self.flush_object_streams()?;
+
+        let offset = self.inner.bytes_written();
+        let id = self.xref.len() as u64;
+        self.set_xref(id, XrefEntry::InUse(offset, 0));
+        trailer.size = self.xref.len();
+
+        let max = self
+            .xref
+            .iter()
+            .flatten()
+            .fold(0, |max, entry| match *entry {
+                XrefEntry::Free(_) => max,
+                XrefEntry::InUse(offset, _) => max.max(offset as u64),
+                XrefEntry::Compressed(stream, _) => max.max(stream),
+            });
+        let width = (1..8).find(|n| max >> (8 * n) == 0).unwrap_or(8);
+        let widths = [1, width, 2];
+
+        let mut index = Vec::new();
+        let mut data = Vec::new();
+        let mut start = None;
+        for (id, entry) in self.xref.iter().enumerate() {
+            let entry = match entry {
+                Some(entry) => entry,
+                None => {
+                    if let Some(start) = start.take() {
+                        index.push((start, id - start));
+                    }
+                    continue;
+                }
+            };
+            start.get_or_insert(id);
+            let (kind, field2, field3) = match *entry {
+                XrefEntry::Free(gen) => (0, 0, gen as usize),
+                XrefEntry::InUse(offset, gen) => (1, offset as u64, gen as usize),
+                XrefEntry::Compressed(stream, index) => (2, stream, index),
+            };
+            data.push(kind);
+            data.extend_from_slice(&field2.to_be_bytes()[(8 - width)..]);
+            data.extend_from_slice(&(field3 as u16).to_be_bytes());
+        }
+        if let Some(start) = start {
+            index.push((start, self.xref.len() - start));
+        }
+
+        let stream = low::XRefStream {
+            trailer,
+            widths,
+            index,
+            data,
+        };
+        writeln!(self.inner, "{} 0 obj", id)?;
+        stream.write(self)?;
+        writeln!(self.inner, "endobj")?;
+        Ok(offset)
+    }
+
     /// Write a classic xref section
     pub fn xref(&mut self) -> io::Result<usize> {
         let offset = self.inner.bytes_written();
@@ -272,8 +442,16 @@ impl<'a> Formatter<'a> {
 
             writeln!(self.inner, "{} {}", index, mid)?;
             for elem in a {
-                let (offset, gen, free) = elem.unwrap();
-                let mark = if free { 'f' } else { 'n' };
+                let (offset, gen, mark) = match elem.unwrap() {
+                    XrefEntry::Free(gen) => (0, gen, 'f'),
+                    XrefEntry::InUse(offset, gen) => (offset, gen, 'n'),
+                    XrefEntry::Compressed(..) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "Object streams require a cross-reference stream",
+                        ))
+                    }
+                };
                 // NOTE: the PDF spec requires the eol to be two bytes long (i.e. SP LF or CR LF)
                 writeln!(self.inner, "{:010} {:05} {} ", offset, gen, mark)?;
             }
@@ -342,6 +520,21 @@ serialize_display_impl!(NonZeroU32);
 serialize_display_impl!(f32);
 serialize_display_impl!(bool);
 
+/// The PDF `null` object
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PdfNull;
+
+impl Serialize for PdfNull {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        if f.needs_space {
+            write!(f.inner, " ")?;
+        }
+        write!(f.inner, "null")?;
+        f.needs_space = true;
+        Ok(())
+    }
+}
+
 impl<X: Serialize> Serialize for Vec<X> {
     fn write(&self, f: &mut Formatter) -> io::Result<()> {
         write!(f.inner, "[")?;
@@ -486,3 +679,22 @@ pub fn write_ref<W: Write>(plain_ref: ObjRef, w: &mut W) -> io::Result<bool> {
     write!(w, "{} {} R", plain_ref.id, plain_ref.gen)?;
     Ok(true)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::common::ObjRef;
+
+    use super::{Formatter, PdfName};
+
+    #[test]
+    fn test_xref_compressed() {
+        let mut out = Vec::new();
+        let mut fmt = Formatter::new(&mut out);
+        fmt.use_object_streams();
+        fmt.obj(ObjRef { id: 1, gen: 0 }, &PdfName("Test")).unwrap();
+        let err = fmt.xref().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
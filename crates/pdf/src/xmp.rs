@@ -114,6 +114,11 @@ impl XmpDescription for PdfAId {
     }
 }
 
+/// Format a date for XMP, with the same precision as in the info dictionary
+pub fn format_date(date: &chrono::DateTime<chrono::FixedOffset>) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S%:z").to_string()
+}
+
 /// Adobe XMP Basic namespace
 pub struct XmpBasic {
     /// Tool used to create the document
@@ -139,17 +144,17 @@ impl XmpDescription for XmpBasic {
         writeln!(
             w,
             "   <xmp:ModifyDate>{}</xmp:ModifyDate>",
-            self.modify_date.format("%+")
+            format_date(&self.modify_date)
         )?;
         writeln!(
             w,
             "   <xmp:CreateDate>{}</xmp:CreateDate>",
-            self.create_date.format("%+")
+            format_date(&self.create_date)
         )?;
         writeln!(
             w,
             "   <xmp:MetadataDate>{}</xmp:MetadataDate>",
-            self.metadata_date.format("%+")
+            format_date(&self.metadata_date)
         )?;
         Ok(())
     }
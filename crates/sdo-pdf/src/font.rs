@@ -3,6 +3,7 @@
 use std::{
     borrow::Cow,
     io::{self, Write},
+    num::NonZeroU32,
     str::FromStr,
 };
 
@@ -13,7 +14,8 @@ use pdf_create::{
         Rectangle, SparseSet, StreamMetadata,
     },
     high::{
-        Ascii85Stream, DictResource, Font, GlobalResource, Res, Resource, ToUnicode, Type3Font,
+        Ascii85Stream, DictResource, Font, FontFile, GlobalResource, Res, Resource, ToUnicode,
+        Type1Font, Type3Font,
     },
     write::{PdfName, PdfNameBuf},
 };
@@ -29,7 +31,10 @@ use signum::{
     util::Buf,
 };
 
-use crate::cmap;
+use crate::{
+    cmap,
+    outline::{OutlineFont, OutlineGlyph},
+};
 
 /// Names for all signum glyph positions, e.g. `Zfive`
 #[rustfmt::skip]
@@ -163,7 +168,10 @@ pub fn write_char_stream<W: Write>(
 /// Number of font-units (1/72000 of an inch) per horizontal signum unit (1/90 of an inch)
 pub(crate) const FONTUNITS_PER_SIGNUM_X: u32 = 800;
 
-const EMPTY_GLYPH_PROC: &[u8] = b"0 0 0 0 0 0 d1";
+/// A glyph procedure that draws nothing, but advances by `width` like in `/Widths`
+fn empty_glyph_proc(width: u32) -> Vec<u8> {
+    format!("{} 0 0 0 0 0 d1", width).into_bytes()
+}
 
 /// Calculate all the glyph widths
 pub fn glyph_widths(efont: &ESet<'_>) -> Vec<u32> {
@@ -227,7 +235,7 @@ pub fn type3_font<'a>(
                     name,
                     num_uses
                 );
-                procs.push((DEFAULT_NAMES[cvu], EMPTY_GLYPH_PROC.to_vec()));
+                procs.push((DEFAULT_NAMES[cvu], empty_glyph_proc(width)));
             }
         } else if num_uses > 0 {
             log::warn!(
@@ -236,7 +244,7 @@ pub fn type3_font<'a>(
                 name,
                 num_uses
             );
-            procs.push((DEFAULT_NAMES[cvu], EMPTY_GLYPH_PROC.to_vec()));
+            procs.push((DEFAULT_NAMES[cvu], empty_glyph_proc(width)));
         }
     }
 
@@ -309,6 +317,90 @@ pub fn type3_font<'a>(
     })
 }
 
+/// Create a type 1 font with outlines traced from the bitmaps
+///
+/// The glyphs are embedded as a CFF font program, see [`OutlineFont`].
+pub fn type1_font<'a>(
+    widths: &[u32], // in font-units, see `glyph_widths`
+    pfont: &PSet,
+    use_table: &UseTable,
+    to_unicode: Option<Resource<ToUnicode>>,
+    name: &str,
+    font_family: &str,
+) -> Option<Type1Font<'a>> {
+    let font_metrics = FontMetrics::from(pfont.pk);
+    let (first_char, last_char) = use_table.first_last()?;
+
+    let font = OutlineFont::subset(name, pfont, widths, |cval| {
+        use_table.chars[cval as usize] > 0
+    });
+    let mut cff = Vec::new();
+    font.write_cff(&mut cff).unwrap();
+
+    let fpy = font_metrics.fontunits_per_pixel_y / DEFAULT_FONT_SIZE as u32;
+    let (x0, y0, x1, y1) = font.bbox();
+    let font_bbox = Rectangle {
+        ll: Point { x: x0, y: y0 },
+        ur: Point { x: x1, y: y1 },
+    };
+    // The width of the stem of a lowercase `l`, if present
+    let stem_v = font
+        .glyphs
+        .iter()
+        .find(|g| g.cval == b'l')
+        .and_then(OutlineGlyph::bbox)
+        .map_or(80, |(x0, _, x1, _)| (x1 - x0) as u32);
+
+    let mut differences = SparseSet::with_size(256);
+    for cval in DIFFERENCES {
+        let i = *cval as usize;
+        if use_table.chars[i] > 0 {
+            differences[i] = Some(PdfName(DEFAULT_NAMES[i]));
+        }
+    }
+
+    let font_descriptor = FontDescriptor {
+        font_name: PdfNameBuf::new(&font.name),
+        font_family: PdfString::from_str(font_family).unwrap(),
+        font_stretch: None,
+        font_weight: None,
+        flags: FontFlags::SYMBOLIC,
+        font_bbox: Some(font_bbox),
+        italic_angle: 0,
+        ascent: Some(font.ascent),
+        descent: Some(font.descent),
+        leading: None,
+        cap_height: NonZeroU32::new(pfont.pk.reference_cap_height() * fpy),
+        x_height: None,
+        stem_v: NonZeroU32::new(stem_v),
+        stem_h: None,
+    };
+
+    Some(Type1Font {
+        base_font: PdfNameBuf::new(&font.name),
+        first_char,
+        last_char,
+        widths: widths[(first_char as usize)..=(last_char as usize)].to_vec(),
+        font_descriptor,
+        font_file: Some(FontFile::Type1C(Cow::Owned(cff))),
+        encoding: Encoding {
+            base_encoding: Some(BaseEncoding::WinAnsiEncoding),
+            differences: Some(differences),
+        },
+        to_unicode,
+    })
+}
+
+/// How the glyphs of the printer fonts are embedded into the PDF
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum FontMode {
+    /// Type3 fonts that draw the bitmaps, see [`type3_font`]
+    #[default]
+    Bitmap,
+    /// Type1 fonts with outlines traced from the bitmaps, see [`type1_font`]
+    Outline,
+}
+
 /// Information on one font
 pub struct FontInfo {
     /// The widths of each glyph (int fontunits, i.e. 1/72000 in)
@@ -330,6 +422,7 @@ impl FontInfo {
 /// Information on multiple fonts
 pub struct Fonts {
     info: Vec<Option<FontInfo>>,
+    mode: FontMode,
 }
 
 /// Error when creating fonts
@@ -350,9 +443,16 @@ impl Fonts {
     pub fn new(fonts_capacity: usize) -> Self {
         Fonts {
             info: Vec::with_capacity(fonts_capacity),
+            mode: FontMode::default(),
         }
     }
 
+    /// Set how the fonts are embedded by [`Fonts::make_fonts`]
+    pub fn with_mode(mut self, mode: FontMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
     /// For all fonts in a font cache, add them to the resources
     pub fn make_fonts<'a>(
         &mut self,
@@ -367,7 +467,7 @@ impl Fonts {
             let use_table = &use_table_vec.csets[index];
             let use_table_bold = &use_table_vec_bold.csets[index];
 
-            let info = make_font(res, pk, cs, use_table, use_table_bold);
+            let info = make_font(res, pk, cs, use_table, use_table_bold, self.mode);
             self.info.push(info);
         }
     }
@@ -379,6 +479,7 @@ fn make_font<'a>(
     cs: &'a CSet,
     use_table: &UseTable,
     use_table_bold: &UseTable,
+    mode: FontMode,
 ) -> Option<FontInfo> {
     let pfont = cs.printer(pk)?;
     let efont = cs.e24().expect("editor font required"); // FIXME: widths?
@@ -391,24 +492,23 @@ fn make_font<'a>(
         .map(Resource::from);
 
     let font_family = cs.name();
-    let font_regular = type3_font(
-        &widths,
-        pfont,
-        use_table,
-        to_unicode.clone(),
-        cs.name(),
-        font_family,
-    )
-    .map(|f| res.push_font(Font::Type3(f)));
-    let font_bold = type3_font(
-        &widths,
+    let make = |pfont: &PSet, use_table: &UseTable, to_unicode, name: &str| match mode {
+        FontMode::Bitmap => {
+            type3_font(&widths, pfont, use_table, to_unicode, name, font_family).map(Font::Type3)
+        }
+        FontMode::Outline => {
+            type1_font(&widths, pfont, use_table, to_unicode, name, font_family).map(Font::Type1)
+        }
+    };
+    let font_regular =
+        make(pfont, use_table, to_unicode.clone(), cs.name()).map(|f| res.push_font(f));
+    let font_bold = make(
         &pset_bold(pfont),
         use_table_bold,
         to_unicode,
         &format!("{}-Bold", cs.name()),
-        font_family,
     )
-    .map(|f| res.push_font(Font::Type3(f)));
+    .map(|f| res.push_font(f));
     if font_regular.is_none() && font_bold.is_none() {
         return None;
     }
@@ -466,6 +566,7 @@ pub fn prepare_pdf_fonts<'f, GC: GenerationContext>(
     gc: &GC,
     fc: &'f ChsetCache,
     pk: PrinterKind,
+    mode: FontMode,
 ) -> Fonts {
     let pages = gc.text_pages();
     let dfci = gc.fonts();
@@ -482,7 +583,154 @@ pub fn prepare_pdf_fonts<'f, GC: GenerationContext>(
         v
     };
 
-    let mut font_info = Fonts::new(8);
+    let mut font_info = Fonts::new(8).with_mode(mode);
     font_info.make_fonts(fc, res, use_table_vec, use_table_vec_bold, pk);
     font_info
 }
+
+#[cfg(test)]
+mod tests {
+    use pdf_create::{
+        common::Rectangle,
+        high::{
+            DictResource, Font, FontFile, Handle, Page, PdfAConformance, Resource, Resources,
+            Violation,
+        },
+    };
+    use signum::{
+        chsets::{
+            printer::{PSet, PSetChar, PrinterKind},
+            UseTable,
+        },
+        util::Buf,
+    };
+
+    use super::type1_font;
+    use crate::outline::bitmap_widths;
+
+    fn be16(data: &[u8], at: usize) -> usize {
+        usize::from(u16::from_be_bytes([data[at], data[at + 1]]))
+    }
+
+    /// Read a CFF INDEX, returning the items and the offset after it
+    fn cff_index(data: &[u8], at: usize) -> (Vec<&[u8]>, usize) {
+        let count = be16(data, at);
+        if count == 0 {
+            return (vec![], at + 2);
+        }
+        let off_size = usize::from(data[at + 2]);
+        let offset = |i: usize| {
+            let start = at + 3 + i * off_size;
+            let bytes = &data[start..start + off_size];
+            bytes.iter().fold(0, |v, b| (v << 8) | usize::from(*b))
+        };
+        let base = at + 2 + (count + 1) * off_size;
+        let items = (0..count)
+            .map(|i| &data[base + offset(i)..base + offset(i + 1)])
+            .collect();
+        (items, base + offset(count))
+    }
+
+    /// Get the glyph names of a CFF font program
+    fn cff_charset(cff: &[u8]) -> Vec<String> {
+        let (_, at) = cff_index(cff, usize::from(cff[2]));
+        let (top_dicts, at) = cff_index(cff, at);
+        let (strings, _) = cff_index(cff, at);
+        // The 5-byte operand of an operator in the top DICT
+        let operand = |op: u8| {
+            let top = top_dicts[0];
+            let at = top.windows(6).position(|w| w[0] == 29 && w[5] == op);
+            let b = &top[at.unwrap() + 1..];
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize
+        };
+        let (char_strings, _) = cff_index(cff, operand(17));
+        let charset = operand(15);
+        assert_eq!(cff[charset], 0);
+        (1..char_strings.len())
+            .map(|gid| {
+                let sid = be16(cff, charset + 1 + 2 * (gid - 1)) - 391;
+                String::from_utf8(strings[sid].to_vec()).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_type1_font() {
+        let bitmap = [0x3C, 0x7E, 0xFF, 0xFF, 0xFF, 0xFF, 0x7E, 0x3C];
+        let mut chars = vec![PSetChar::new(0, 0, 0, &[]); 128];
+        for cval in [b'.', b'@', b'o', b'{'] {
+            chars[cval as usize] = PSetChar::new(1, 8, 40, &bitmap);
+        }
+        let pset = PSet {
+            pk: PrinterKind::Needle24,
+            header: Buf(&[]),
+            chars,
+        };
+        let widths = bitmap_widths(&pset);
+        let use_table = UseTable::from(".@o{");
+        let font = type1_font(&widths, &pset, &use_table, None, "TEST", "TEST").unwrap();
+        assert_eq!((font.first_char, font.last_char), (b'.', b'{'));
+
+        // Every glyph name in the encoding is in the CFF charset
+        let charset = match &font.font_file {
+            Some(FontFile::Type1C(cff)) => cff_charset(cff),
+            None => panic!("font file missing"),
+        };
+        let differences = font.encoding.differences.as_ref().unwrap();
+        let mut names: Vec<_> = differences.iter().flatten().map(|n| n.0).collect();
+        assert_eq!(names, ["udieresis", "Odieresis"]);
+        // These codes are not in the differences, i.e. use the `WinAnsiEncoding`
+        names.extend(["period", "o"]);
+        for name in names {
+            assert!(charset.iter().any(|n| n == name), "{} not in charset", name);
+        }
+
+        let mut hnd = Handle::new();
+        let index = hnd.res.push_font(Font::Type1(font));
+        let mut fonts = DictResource::new();
+        fonts.insert("C0".to_owned(), Resource::from(index));
+        hnd.pages.push(Page {
+            media_box: Rectangle::a4_media_box(),
+            resources: Resources {
+                fonts: Resource::Immediate(Box::new(fonts)),
+                ..Default::default()
+            },
+            contents: vec![],
+            links: vec![],
+        });
+        let mut out = Vec::new();
+        hnd.write(&mut out).unwrap();
+        let text = String::from_utf8_lossy(&out);
+
+        let font = &text[text.find("/Subtype/Type1").unwrap()..];
+        let widths = &font[font.find("/Widths[").unwrap() + 8..];
+        let widths = &widths[..widths.find(']').unwrap()];
+        let expected = usize::from(b'{' - b'.') + 1;
+        assert_eq!(widths.split_ascii_whitespace().count(), expected);
+
+        let at = text.find("/FontFile3 ").unwrap() + 11;
+        let r = &text[at..at + text[at..].find(" R").unwrap()];
+        let obj = &text[text.find(&format!("\n{} obj", r)).unwrap()..];
+        let dict = &obj[..obj.find("stream").unwrap()];
+        assert!(dict.contains("/Subtype/Type1C"), "{}", dict);
+
+        // The widths match the advances in the font program, by glyph name
+        hnd.pdfa = Some(PdfAConformance::A2b);
+        let mismatches = |hnd: &Handle| -> Vec<u8> {
+            let violations = hnd.preflight().into_iter();
+            violations
+                .filter_map(|v| match v {
+                    Violation::WidthMismatch { code, .. } => Some(code),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(mismatches(&hnd), []);
+        if let Font::Type1(font) = &mut hnd.res.fonts[0] {
+            for cval in [b'.', b'@'] {
+                font.widths[usize::from(cval - b'.')] += 100;
+            }
+        }
+        assert_eq!(mismatches(&hnd), [b'.', b'@']);
+    }
+}
@@ -1,20 +1,117 @@
+use ccitt_t4_t6::g42d::G4Encoder;
 use pdf_create::{
-    common::{ColorIs, ColorSpace, ImageMetadata},
-    high::Image,
+    common::{CCITTFaxDecodeParams, ColorIs, ColorSpace, ImageMetadata},
+    high::{Image, ImageEncoding},
 };
 use signum::docs::{hcim::ImageSite, DocumentInfo};
 
 /// Return a PDF Image for a site
+///
+/// The image data is encoded with CCITT Group 4, `1` bits are black.
 pub(crate) fn image_for_site(di: &DocumentInfo, site: &ImageSite) -> Option<Image> {
-    di.image_at(site.img).map(|i| Image {
+    di.image_at(site.img).map(|i| {
+        let width = site.sel.w as usize;
+        let height = site.sel.h as usize;
+        // every row of the selection is padded to full bytes
+        let bitmap = i.select(site.sel);
+        ccitt_image(width, height, &bitmap)
+    })
+}
+
+/// Return a PDF Image for a bitmap, with every row padded to full bytes
+fn ccitt_image(width: usize, height: usize, bitmap: &[u8]) -> Image {
+    let mut encoder = G4Encoder::new(width, bitmap);
+    encoder.skip_tail = (8 - width % 8) % 8;
+    Image {
         meta: ImageMetadata {
-            width: site.sel.w as usize,
-            height: site.sel.h as usize,
+            width,
+            height,
             color_space: ColorSpace::DeviceGray,
             bits_per_component: 1,
             image_mask: true,
             decode: ColorIs::One,
         },
-        data: i.select(site.sel),
-    })
+        encoding: ImageEncoding::CCITTFax(CCITTFaxDecodeParams {
+            black_is_1: true,
+            ..CCITTFaxDecodeParams::group_4(width, height)
+        }),
+        data: encoder.encode(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ccitt_t4_t6::{
+        bits::BitIter,
+        g42d::{fax_decode_into, FaxOptions},
+        Color,
+    };
+    use pdf_create::{
+        common::Rectangle,
+        high::{DictResource, Handle, Page, Resource, Resources},
+    };
+
+    use super::ccitt_image;
+
+    #[test]
+    fn test_ccitt_image() {
+        #[rustfmt::skip]
+        let bitmap = [
+            0x00, 0x3C,
+            0x00, 0x7E,
+            0x01, 0xFF,
+            0x0F, 0x00,
+            0xFF, 0xFC,
+        ];
+        let (width, height) = (14, 5);
+
+        let mut hnd = Handle::new();
+        let index = hnd.res.push_xobject(ccitt_image(width, height, &bitmap));
+        let mut x_objects = DictResource::new();
+        x_objects.insert("I0".to_owned(), Resource::from(index));
+        hnd.pages.push(Page {
+            media_box: Rectangle::a4_media_box(),
+            resources: Resources {
+                x_objects: Resource::Immediate(Box::new(x_objects)),
+                ..Default::default()
+            },
+            contents: vec![],
+            links: vec![],
+        });
+        let mut out = Vec::new();
+        hnd.write(&mut out).unwrap();
+        let text = String::from_utf8_lossy(&out);
+
+        let at = text.find("/Filter/CCITTFaxDecode").unwrap();
+        let start = text[..at].rfind(" obj\n").unwrap();
+        let dict = &text[start..start + text[start..].find("stream\n").unwrap()];
+        let dict: String = dict.lines().map(str::trim).collect();
+        assert!(dict.contains("/Subtype/Image"), "{}", dict);
+        assert!(dict.contains("/ImageMask true"), "{}", dict);
+        assert!(
+            dict.contains("/DecodeParms<</K -1/Columns 14/Rows 5/BlackIs1 true>>"),
+            "{}",
+            dict
+        );
+
+        let len_at = dict.find("/Length ").unwrap() + 8;
+        let len = dict[len_at..]
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .unwrap();
+        let len: usize = len.parse().unwrap();
+        let data_start = out.windows(7).skip(start).position(|w| w == b"stream\n");
+        let data_start = start + data_start.unwrap() + 7;
+        let data = &out[data_start..data_start + len];
+
+        let mut options = FaxOptions::default();
+        options.width = width;
+        let decoded: Vec<Color> = fax_decode_into(data, options, Vec::new()).unwrap();
+        let expected: Vec<Color> = bitmap
+            .chunks(2)
+            .flat_map(|row| BitIter::new(row).take(width).map(Color::from))
+            .collect();
+        assert_eq!(decoded.len(), width * height);
+        assert_eq!(decoded, expected);
+    }
 }
@@ -5,7 +5,8 @@ use pdf_create::common::{
     ColorSpace, ICCColorProfileMetadata, OutputIntent, OutputIntentSubtype, PdfString,
 };
 use pdf_create::encoding::PDFDocEncodingError;
-use pdf_create::high::{Handle, ICCBasedColorProfile, Metadata};
+use pdf_create::high::{Handle, ICCBasedColorProfile, Metadata, PdfAConformance};
+use pdf_create::low::PdfVersion;
 use signum::docs::header::Header;
 
 /// Information to add into the PDF `/Info` dictionary
@@ -79,11 +80,23 @@ pub fn prepare_info(info: &mut Metadata, meta: &MetaInfo) -> Result<(), PDFDocEn
     Ok(())
 }
 
-/// Add a simple output intent for PDF/A
+/// Add a simple output intent for PDF/A and select the `conformance` level
 ///
-/// This is not yet properly implemented
-pub fn prepare_pdfa_output_intent(hnd: &mut Handle) -> crate::Result<()> {
-    hnd.output_intents.push(PdfAOutputIntent::default_grey());
+/// PDF/A-1 only allows ICC profiles up to version 2, so it gets a version 2
+/// profile and a PDF 1.4 header. All other levels use the version 4 profile.
+pub fn prepare_pdfa_output_intent(
+    hnd: &mut Handle,
+    conformance: PdfAConformance,
+) -> crate::Result<()> {
+    let output_intent = match conformance {
+        PdfAConformance::A1b => {
+            hnd.version = PdfVersion::V1_4;
+            PdfAOutputIntent::grey_v2()
+        }
+        PdfAConformance::A2b | PdfAConformance::A3b => PdfAOutputIntent::default_grey(),
+    };
+    hnd.output_intents.push(output_intent);
+    hnd.pdfa = Some(conformance);
     Ok(())
 }
 
@@ -103,6 +116,18 @@ impl PdfAOutputIntent {
             dest_output_profile: Some(ICC_SGREY_V4),
         }
     }
+
+    /// Return a grayscale sRGB output intent with an ICC version 2 profile
+    fn grey_v2() -> OutputIntent<ICCBasedColorProfile<'static>> {
+        OutputIntent {
+            subtype: OutputIntentSubtype::GTS_PDFA1,
+            output_condition_identifier: PdfString::new(b"sGry"),
+            output_condition: None,
+            registry_name: None,
+            info: Some(PdfString::new(b"ICC v2 Grayscale sRGB Curve")),
+            dest_output_profile: Some(ICC_SGREY_V2),
+        }
+    }
 }
 
 // https://github.com/saucecontrol/Compact-ICC-Profiles
@@ -113,3 +138,38 @@ const ICC_SGREY_V4: ICCBasedColorProfile<'static> = ICCBasedColorProfile {
         num_components: 1,
     },
 };
+
+// The white point of `sGrey-v4.icc` with the sRGB tone curve sampled at 1024 points
+const ICC_SGREY_V2: ICCBasedColorProfile<'static> = ICCBasedColorProfile {
+    stream: include_bytes!("../res/sGrey-v2.icc"),
+    meta: ICCColorProfileMetadata {
+        alternate: Some(ColorSpace::DeviceGray),
+        num_components: 1,
+    },
+};
+
+#[cfg(test)]
+mod tests {
+    use pdf_create::high::{Handle, PdfAConformance};
+
+    use super::{prepare_info, prepare_pdfa_output_intent, MetaInfo};
+
+    #[test]
+    fn test_pdfa_levels() {
+        for conformance in [PdfAConformance::A1b, PdfAConformance::A2b] {
+            let mut hnd = Handle::new();
+            let meta = MetaInfo {
+                title: Some("Test".to_owned()),
+                ..MetaInfo::default()
+            };
+            prepare_info(&mut hnd.meta, &meta).unwrap();
+            prepare_pdfa_output_intent(&mut hnd, conformance).unwrap();
+            assert_eq!(hnd.preflight(), [], "{}", conformance);
+
+            let mut out = Vec::new();
+            hnd.write(&mut out).unwrap();
+            let part = format!("<pdfaid:part>{}</pdfaid:part>", conformance.part());
+            assert!(String::from_utf8_lossy(&out).contains(&part));
+        }
+    }
+}
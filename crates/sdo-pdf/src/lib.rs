@@ -10,12 +10,14 @@ pub mod cmap;
 pub mod font;
 mod image;
 mod info;
+pub mod outline;
 pub mod sdoc;
 
 use std::{fmt, io};
 
-use font::prepare_pdf_fonts;
+use font::{prepare_pdf_fonts, FontMode};
 pub use info::{prepare_info, prepare_pdfa_output_intent, MetaInfo};
+pub use pdf_create::high::PdfAConformance;
 use pdf_create::{encoding::PDFDocEncodingError, high::Handle};
 use sdoc::generate_pdf_pages;
 use signum::{
@@ -79,17 +81,24 @@ impl<'a> Pdf<'a> {
 }
 
 /// Generate a PDF from a [GenerationContext]
+///
+/// If `tagged` is set, the logical structure of the document is included. The
+/// document is checked against the `pdfa` conformance level before writing.
+#[allow(clippy::too_many_arguments)]
 pub fn generate_pdf<'f, GC: GenerationContext>(
     fc: &'f ChsetCache,
     pk: PrinterKind,
     meta: &MetaInfo,
     overrides: &Overrides,
+    font_mode: FontMode,
+    tagged: bool,
+    pdfa: PdfAConformance,
     gc: &GC,
 ) -> crate::Result<Pdf<'f>> {
     let mut hnd = Handle::new();
     prepare_info(&mut hnd.meta, meta)?;
-    prepare_pdfa_output_intent(&mut hnd)?;
-    let font_info = prepare_pdf_fonts(&mut hnd.res, gc, fc, pk);
-    generate_pdf_pages(gc, &mut hnd, overrides, &font_info)?;
+    prepare_pdfa_output_intent(&mut hnd, pdfa)?;
+    let font_info = prepare_pdf_fonts(&mut hnd.res, gc, fc, pk, font_mode);
+    generate_pdf_pages(gc, &mut hnd, overrides, &font_info, tagged)?;
     Ok(Pdf { hnd })
 }
@@ -0,0 +1,286 @@
+//! # Compact Font Format (CFF)
+//!
+//! See [Adobe Tech Note #5176] *The Compact Font Format Specification* and
+//! [Adobe Tech Note #5177] *The Type 2 Charstring Format*.
+//!
+//! All glyph names are stored as custom strings, even if they are also one of
+//! the standard strings.
+//!
+//! [Adobe Tech Note #5176]: https://adobe-type-tools.github.io/font-tech-notes/pdfs/5176.CFF.pdf
+//! [Adobe Tech Note #5177]: https://adobe-type-tools.github.io/font-tech-notes/pdfs/5177.Type2.pdf
+
+use super::{OutlineFont, OutlineGlyph, PathOp, Point};
+
+/// The number of standard strings, i.e. the first custom SID
+const N_STD_STRINGS: u16 = 391;
+
+// Top DICT operators
+const OP_FULL_NAME: u8 = 2;
+const OP_FAMILY_NAME: u8 = 3;
+const OP_FONT_BBOX: u8 = 5;
+const OP_CHARSET: u8 = 15;
+const OP_ENCODING: u8 = 16;
+const OP_CHAR_STRINGS: u8 = 17;
+const OP_PRIVATE: u8 = 18;
+
+// Private DICT operators
+const OP_DEFAULT_WIDTH_X: u8 = 20;
+const OP_NOMINAL_WIDTH_X: u8 = 21;
+
+// Type 2 charstring operators
+const CS_RLINETO: u8 = 5;
+const CS_RRCURVETO: u8 = 8;
+const CS_ENDCHAR: u8 = 14;
+const CS_RMOVETO: u8 = 21;
+
+/// Write an INDEX structure
+fn write_index(out: &mut Vec<u8>, items: &[Vec<u8>]) {
+    out.extend_from_slice(&(items.len() as u16).to_be_bytes());
+    if items.is_empty() {
+        return;
+    }
+    let last_offset = 1 + items.iter().map(Vec::len).sum::<usize>();
+    let off_size = match last_offset {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x10000..=0xFF_FFFF => 3,
+        _ => 4,
+    };
+    out.push(off_size as u8);
+    let mut offset = 1;
+    let write_offset = |out: &mut Vec<u8>, offset: usize| {
+        out.extend_from_slice(&(offset as u32).to_be_bytes()[(4 - off_size)..]);
+    };
+    write_offset(out, offset);
+    for item in items {
+        offset += item.len();
+        write_offset(out, offset);
+    }
+    for item in items {
+        out.extend_from_slice(item);
+    }
+}
+
+/// Write an integer operand of a DICT
+fn dict_int(out: &mut Vec<u8>, v: i32) {
+    match v {
+        -107..=107 => out.push((v + 139) as u8),
+        108..=1131 => {
+            let v = v - 108;
+            out.extend_from_slice(&[(v / 256 + 247) as u8, (v % 256) as u8]);
+        }
+        -1131..=-108 => {
+            let v = -v - 108;
+            out.extend_from_slice(&[(v / 256 + 251) as u8, (v % 256) as u8]);
+        }
+        -32768..=32767 => {
+            out.push(28);
+            out.extend_from_slice(&(v as i16).to_be_bytes());
+        }
+        _ => dict_int32(out, v),
+    }
+}
+
+/// Write a fixed-size (5 byte) integer operand of a DICT, used for offsets
+fn dict_int32(out: &mut Vec<u8>, v: i32) {
+    out.push(29);
+    out.extend_from_slice(&v.to_be_bytes());
+}
+
+/// Write an integer operand of a Type 2 charstring
+fn cs_int(out: &mut Vec<u8>, v: i32) {
+    match v {
+        -1131..=1131 => dict_int(out, v),
+        _ => {
+            out.push(28);
+            out.extend_from_slice(&(v.clamp(-32768, 32767) as i16).to_be_bytes());
+        }
+    }
+}
+
+fn cs_op(out: &mut Vec<u8>, args: &[i32], op: u8) {
+    for arg in args {
+        cs_int(out, *arg);
+    }
+    out.push(op);
+}
+
+/// Encode the outlines of a glyph as a Type 2 charstring
+fn charstring(glyph: &OutlineGlyph) -> Vec<u8> {
+    let mut out = Vec::new();
+    // with a nominalWidthX of 0, this is the advance width
+    cs_int(&mut out, glyph.advance as i32);
+    let mut cur: Point = (0, 0);
+    let delta = |cur: &mut Point, (x, y): Point| {
+        let d = [x - cur.0, y - cur.1];
+        *cur = (x, y);
+        d
+    };
+    for contour in &glyph.contours {
+        let d = delta(&mut cur, contour.start);
+        cs_op(&mut out, &d, CS_RMOVETO);
+        for op in &contour.ops {
+            match *op {
+                PathOp::Line(p) => cs_op(&mut out, &delta(&mut cur, p), CS_RLINETO),
+                PathOp::Curve(c1, c2, p) => {
+                    let [a, b] = delta(&mut cur, c1);
+                    let [c, d] = delta(&mut cur, c2);
+                    let [e, f] = delta(&mut cur, p);
+                    cs_op(&mut out, &[a, b, c, d, e, f], CS_RRCURVETO);
+                }
+            }
+        }
+    }
+    out.push(CS_ENDCHAR);
+    out
+}
+
+fn top_dict(font_bbox: [i32; 4], offsets: [i32; 5]) -> Vec<u8> {
+    let [charset, encoding, char_strings, private_size, private] = offsets;
+    let mut out = Vec::new();
+    dict_int(&mut out, i32::from(N_STD_STRINGS));
+    out.push(OP_FULL_NAME);
+    dict_int(&mut out, i32::from(N_STD_STRINGS) + 1);
+    out.push(OP_FAMILY_NAME);
+    for v in font_bbox.iter() {
+        dict_int(&mut out, *v);
+    }
+    out.push(OP_FONT_BBOX);
+    dict_int32(&mut out, charset);
+    out.push(OP_CHARSET);
+    dict_int32(&mut out, encoding);
+    out.push(OP_ENCODING);
+    dict_int32(&mut out, char_strings);
+    out.push(OP_CHAR_STRINGS);
+    dict_int32(&mut out, private_size);
+    dict_int32(&mut out, private);
+    out.push(OP_PRIVATE);
+    out
+}
+
+/// Write a complete CFF font program
+pub(super) fn write_cff(font: &OutlineFont) -> Vec<u8> {
+    let (x0, y0, x1, y1) = font.bbox();
+    let font_bbox = [x0, y0, x1, y1];
+
+    let mut strings = vec![
+        font.name.as_bytes().to_vec(),
+        font.family.as_bytes().to_vec(),
+    ];
+    strings.extend(font.glyphs.iter().map(|g| g.name.as_bytes().to_vec()));
+
+    let mut charset = vec![0]; // format 0
+    for sid in 0..font.glyphs.len() {
+        let sid = N_STD_STRINGS + 2 + sid as u16;
+        charset.extend_from_slice(&sid.to_be_bytes());
+    }
+
+    let mut encoding = vec![0, font.glyphs.len() as u8]; // format 0
+    encoding.extend(font.glyphs.iter().map(|g| g.cval));
+
+    let notdef = OutlineGlyph {
+        cval: 0,
+        name: ".notdef",
+        advance: 0,
+        contours: Vec::new(),
+    };
+    let char_strings: Vec<_> = std::iter::once(&notdef)
+        .chain(&font.glyphs)
+        .map(charstring)
+        .collect();
+    let mut char_strings_index = Vec::new();
+    write_index(&mut char_strings_index, &char_strings);
+
+    let mut private = Vec::new();
+    dict_int(&mut private, 0);
+    private.push(OP_DEFAULT_WIDTH_X);
+    dict_int(&mut private, 0);
+    private.push(OP_NOMINAL_WIDTH_X);
+
+    let mut out = vec![1, 0, 4, 4]; // header: version 1.0, hdrSize, offSize
+    write_index(&mut out, &[font.name.as_bytes().to_vec()]);
+
+    // All offsets are written with 5 bytes, so the size does not depend on them
+    let top_dict_len = top_dict(font_bbox, [0; 5]).len();
+    let mut string_index = Vec::new();
+    write_index(&mut string_index, &strings);
+    let global_subrs_index = [0, 0];
+
+    let mut top_dict_index_len = Vec::new();
+    write_index(&mut top_dict_index_len, &[vec![0; top_dict_len]]);
+
+    let charset_offset = out.len() + top_dict_index_len.len() + string_index.len() + 2;
+    let encoding_offset = charset_offset + charset.len();
+    let char_strings_offset = encoding_offset + encoding.len();
+    let private_offset = char_strings_offset + char_strings_index.len();
+    let offsets = [
+        charset_offset as i32,
+        encoding_offset as i32,
+        char_strings_offset as i32,
+        private.len() as i32,
+        private_offset as i32,
+    ];
+    write_index(&mut out, &[top_dict(font_bbox, offsets)]);
+    out.extend_from_slice(&string_index);
+    out.extend_from_slice(&global_subrs_index);
+    out.extend_from_slice(&charset);
+    out.extend_from_slice(&encoding);
+    out.extend_from_slice(&char_strings_index);
+    out.extend_from_slice(&private);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{charstring, dict_int, write_index};
+    use crate::outline::{Contour, OutlineGlyph, PathOp};
+
+    #[test]
+    fn test_dict_int() {
+        let enc = |v| {
+            let mut out = Vec::new();
+            dict_int(&mut out, v);
+            out
+        };
+        assert_eq!(enc(0), [0x8b]);
+        assert_eq!(enc(100), [0xef]);
+        assert_eq!(enc(-100), [0x27]);
+        assert_eq!(enc(1000), [0xfa, 0x7c]);
+        assert_eq!(enc(-1000), [0xfe, 0x7c]);
+        assert_eq!(enc(10000), [0x1c, 0x27, 0x10]);
+        assert_eq!(enc(-10000), [0x1c, 0xd8, 0xf0]);
+        assert_eq!(enc(100000), [0x1d, 0x00, 0x01, 0x86, 0xa0]);
+    }
+
+    #[test]
+    fn test_index() {
+        let mut out = Vec::new();
+        write_index(&mut out, &[b"ab".to_vec(), b"c".to_vec()]);
+        assert_eq!(out, [0, 2, 1, 1, 3, 4, b'a', b'b', b'c']);
+    }
+
+    #[test]
+    fn test_charstring() {
+        let glyph = OutlineGlyph {
+            cval: 1,
+            name: "Zparenleft",
+            advance: 100,
+            contours: vec![Contour {
+                start: (10, 20),
+                ops: vec![
+                    PathOp::Line((10, 0)),
+                    PathOp::Curve((20, 0), (20, 20), (10, 20)),
+                ],
+            }],
+        };
+        let cs = charstring(&glyph);
+        #[rustfmt::skip]
+        assert_eq!(cs, [
+            239, // width
+            149, 159, 21, // rmoveto
+            139, 119, 5, // rlineto
+            149, 139, 139, 159, 129, 139, 8, // rrcurveto
+            14, // endchar
+        ]);
+    }
+}
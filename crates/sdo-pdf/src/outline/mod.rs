@@ -0,0 +1,338 @@
+//! # Outline fonts
+//!
+//! The printer fonts of Signum! are bitmaps. This module traces these bitmaps
+//! into smooth outlines (see [`signum::raster::trace`]) so that they can be
+//! written as scalable fonts:
+//!
+//! - [`OutlineFont::write_cff`] writes a bare *Compact Font Format* font (as
+//!   used for `/FontFile3` in PDF)
+//! - [`OutlineFont::write_otf`] writes an OpenType font with CFF outlines
+//!
+//! All coordinates are in font units, with 1000 units per em at the default
+//! font size, i.e. the same units as for the Type3 fonts in [`crate::font`].
+
+use std::io;
+
+use signum::{
+    chsets::{
+        encoding::Mapping,
+        printer::{PSet, PSetChar, PrinterKind},
+        Device,
+    },
+    raster::{self, Curve, Page, Segment},
+};
+
+use crate::font::{DEFAULT_FONT_SIZE, DEFAULT_NAMES};
+
+mod cff;
+mod otf;
+
+/// A point in font units
+pub type Point = (i32, i32);
+
+/// A single segment of a contour
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PathOp {
+    /// Straight line to a point
+    Line(Point),
+    /// Cubic Bézier curve with two control points to a point
+    Curve(Point, Point, Point),
+}
+
+/// A closed contour
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contour {
+    /// The start point
+    pub start: Point,
+    /// The segments, the last one ends at `start`
+    pub ops: Vec<PathOp>,
+}
+
+/// A glyph of an [`OutlineFont`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineGlyph {
+    /// The character value in the Signum! font
+    pub cval: u8,
+    /// The glyph name, see [`DEFAULT_NAMES`]
+    pub name: &'static str,
+    /// The advance width
+    pub advance: u32,
+    /// The outlines
+    pub contours: Vec<Contour>,
+}
+
+/// Bounding box `(x_min, y_min, x_max, y_max)`
+pub type BBox = (i32, i32, i32, i32);
+
+impl OutlineGlyph {
+    /// The bounding box of all points (including control points)
+    pub fn bbox(&self) -> Option<BBox> {
+        let mut points = self.contours.iter().flat_map(|c| {
+            std::iter::once(c.start).chain(c.ops.iter().flat_map(|op| match *op {
+                PathOp::Line(p) => vec![p],
+                PathOp::Curve(c1, c2, p) => vec![c1, c2, p],
+            }))
+        });
+        let (x, y) = points.next()?;
+        Some(points.fold((x, y, x, y), |(x0, y0, x1, y1), (x, y)| {
+            (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+        }))
+    }
+}
+
+/// A font with glyph outlines traced from a printer font
+#[derive(Debug, Clone)]
+pub struct OutlineFont {
+    /// The (PostScript) name of the font
+    pub name: String,
+    /// The family name of the font
+    pub family: String,
+    /// The glyphs, ordered by character value
+    pub glyphs: Vec<OutlineGlyph>,
+    /// The distance from the baseline to the top of the line
+    pub ascent: i32,
+    /// The distance from the baseline to the bottom of the line (negative)
+    pub descent: i32,
+}
+
+/// Scale from pixels of a printer to font units
+struct Scale {
+    baseline: f64,
+    x: f64,
+    y: f64,
+}
+
+impl Scale {
+    fn new(pk: PrinterKind) -> Self {
+        let res = pk.resolution();
+        let units_per_inch = f64::from(72 * 1000 / DEFAULT_FONT_SIZE);
+        Self {
+            baseline: f64::from(pk.baseline()),
+            x: units_per_inch / f64::from(res.x),
+            y: units_per_inch / f64::from(res.y),
+        }
+    }
+
+    fn point(&self, top: u8, (x, y): raster::Point) -> Point {
+        let fx = x * self.x;
+        let fy = (self.baseline - f64::from(top) - y) * self.y;
+        (fx.round() as i32, fy.round() as i32)
+    }
+}
+
+fn contour(scale: &Scale, top: u8, curve: &Curve) -> Contour {
+    let p = |point| scale.point(top, point);
+    let mut ops = Vec::with_capacity(curve.segments.len() * 2);
+    for segment in &curve.segments {
+        match *segment {
+            Segment::Corner { vertex, end } => {
+                ops.push(PathOp::Line(p(vertex)));
+                ops.push(PathOp::Line(p(end)));
+            }
+            Segment::Bezier { c1, c2, end } => ops.push(PathOp::Curve(p(c1), p(c2), p(end))),
+        }
+    }
+    Contour {
+        start: p(curve.start()),
+        ops,
+    }
+}
+
+/// Trace a single printer character into contours
+pub fn trace_char(pchar: &PSetChar, pk: PrinterKind) -> Vec<Contour> {
+    if pchar.width == 0 || pchar.height == 0 {
+        return Vec::new();
+    }
+    let scale = Scale::new(pk);
+    let page = Page::from(pchar);
+    raster::trace(&page)
+        .iter()
+        .map(|curve| contour(&scale, pchar.top, curve))
+        .collect()
+}
+
+/// Compute advance widths from the bitmaps, for when there is no editor font
+///
+/// This is the right edge of the ink plus one pixel.
+pub fn bitmap_widths(pfont: &PSet) -> Vec<u32> {
+    let scale = Scale::new(pfont.pk);
+    pfont
+        .chars
+        .iter()
+        .map(|pchar| match pchar.hbounds() {
+            Some(hb) if hb.max_lead < hb.width * 8 => {
+                ((hb.right_x() + 1) as f64 * scale.x).round() as u32
+            }
+            _ => 0,
+        })
+        .collect()
+}
+
+impl OutlineFont {
+    /// Trace all characters of a printer font
+    ///
+    /// The `widths` are the advance widths in font units, see [`crate::font::glyph_widths`]
+    /// or [`bitmap_widths`]. Characters that have neither ink nor a width are skipped.
+    pub fn new(name: &str, pfont: &PSet, widths: &[u32]) -> Self {
+        Self::subset(name, pfont, widths, |_| true)
+    }
+
+    /// Trace the characters of a printer font for which `used` returns true
+    pub fn subset<F: Fn(u8) -> bool>(name: &str, pfont: &PSet, widths: &[u32], used: F) -> Self {
+        let scale = Scale::new(pfont.pk);
+        let glyphs = pfont
+            .chars
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .skip(1)
+            .filter(|(cval, _)| used(*cval as u8))
+            .filter(|(_, (pchar, width))| pchar.width > 0 || **width > 0)
+            .map(|(cval, (pchar, width))| OutlineGlyph {
+                cval: cval as u8,
+                name: DEFAULT_NAMES[cval],
+                advance: *width,
+                contours: trace_char(pchar, pfont.pk),
+            })
+            .collect();
+        Self {
+            name: postscript_name(name),
+            family: name.to_owned(),
+            glyphs,
+            ascent: (f64::from(pfont.pk.max_ascent()) * scale.y).round() as i32,
+            descent: -(f64::from(pfont.pk.max_descent()) * scale.y).round() as i32,
+        }
+    }
+
+    /// The bounding box of all glyphs
+    pub fn bbox(&self) -> BBox {
+        self.glyphs
+            .iter()
+            .filter_map(OutlineGlyph::bbox)
+            .reduce(|(a0, b0, c0, d0), (a1, b1, c1, d1)| {
+                (a0.min(a1), b0.min(b1), c0.max(c1), d0.max(d1))
+            })
+            .unwrap_or_default()
+    }
+
+    /// Write the font as a CFF (Type 2 charstrings) font program
+    pub fn write_cff<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&cff::write_cff(self))
+    }
+
+    /// Write the font as an OpenType font with CFF outlines
+    ///
+    /// If a mapping is given, the `cmap` maps unicode to the glyphs, otherwise
+    /// every character value `c` is mapped from `U+F000 + c` as a symbol font.
+    pub fn write_otf<W: io::Write>(&self, w: &mut W, mapping: Option<&Mapping>) -> io::Result<()> {
+        w.write_all(&otf::write_otf(self, mapping))
+    }
+}
+
+/// Remove characters that are not allowed in a PostScript name
+fn postscript_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_graphic() && !"[](){}<>/%".contains(*c))
+        .take(63)
+        .collect();
+    if name.is_empty() {
+        String::from("Signum")
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use signum::{
+        chsets::printer::{PSet, PSetChar, PrinterKind},
+        util::Buf,
+    };
+
+    use super::{bitmap_widths, trace_char, OutlineFont, PathOp};
+
+    fn be16(data: &[u8], at: usize) -> usize {
+        usize::from(u16::from_be_bytes([data[at], data[at + 1]]))
+    }
+
+    fn be32(data: &[u8], at: usize) -> usize {
+        u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]) as usize
+    }
+
+    #[test]
+    fn test_write_otf() {
+        let bitmap = [0x3C, 0x7E, 0xFF, 0xFF, 0xFF, 0xFF, 0x7E, 0x3C];
+        let mut chars = vec![PSetChar::new(0, 0, 0, &[]); 128];
+        chars[b'o' as usize] = PSetChar::new(1, 8, 40, &bitmap);
+        chars[b'.' as usize] = PSetChar::new(1, 4, 54, &bitmap[2..6]);
+        let pset = PSet {
+            pk: PrinterKind::Needle24,
+            header: Buf(&[]),
+            chars,
+        };
+        let widths = bitmap_widths(&pset);
+        assert_eq!(widths[b'o' as usize], 180);
+        let font = OutlineFont::new("TEST FONT", &pset, &widths);
+        assert_eq!(font.name, "TESTFONT");
+        assert_eq!(font.glyphs.len(), 2);
+
+        let mut otf = Vec::new();
+        font.write_otf(&mut otf, None).unwrap();
+        assert_eq!(&otf[..4], b"OTTO");
+        assert_eq!(otf.len() % 4, 0);
+        let total = otf
+            .chunks(4)
+            .fold(0u32, |s, w| s.wrapping_add(be32(w, 0) as u32));
+        assert_eq!(total, 0xB1B0_AFBA);
+
+        let num_tables = be16(&otf, 4);
+        let tables: Vec<_> = (0..num_tables)
+            .map(|i| {
+                let rec = 12 + 16 * i;
+                let tag = &otf[rec..rec + 4];
+                (tag, be32(&otf, rec + 8), be32(&otf, rec + 12))
+            })
+            .collect();
+        let (_, offset, len) = tables[0];
+        assert_eq!(tables[0].0, b"CFF ");
+        let cff = &otf[offset..offset + len];
+        assert_eq!(cff[..4], [1, 0, 4, 4]);
+        // Name INDEX
+        assert_eq!(be16(cff, 4), 1);
+        assert_eq!(&cff[9..17], b"TESTFONT");
+
+        let mut expected = Vec::new();
+        font.write_cff(&mut expected).unwrap();
+        assert_eq!(cff, &expected[..]);
+
+        // The 5-byte operand of the CharStrings operator (17)
+        let at = cff
+            .windows(6)
+            .position(|w| w[0] == 29 && w[5] == 17)
+            .unwrap();
+        let char_strings = be32(cff, at + 1);
+        assert_eq!(be16(cff, char_strings), 3);
+    }
+
+    #[test]
+    fn test_trace_char() {
+        // a 16x16 pixel block, 42 pixels above the baseline
+        let bitmap = [0xFF; 2 * 16];
+        let pchar = PSetChar::new(2, 16, 16, &bitmap);
+        let contours = trace_char(&pchar, PrinterKind::Needle24);
+        assert_eq!(contours.len(), 1);
+        let contour = &contours[0];
+        let corners: Vec<_> = contour
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                PathOp::Line(p) => Some(*p),
+                PathOp::Curve(..) => None,
+            })
+            .step_by(2)
+            .collect();
+        // counter-clockwise, 20 units per pixel
+        assert_eq!(corners, [(0, 520), (320, 520), (320, 840), (0, 840)]);
+    }
+}
@@ -0,0 +1,329 @@
+//! # OpenType font with CFF outlines
+//!
+//! See the [OpenType specification]. This writes the minimal set of tables
+//! for a font with CFF outlines: `CFF `, `OS/2`, `cmap`, `head`, `hhea`,
+//! `hmtx`, `maxp`, `name` and `post`.
+//!
+//! [OpenType specification]: https://learn.microsoft.com/en-us/typography/opentype/spec/
+
+use std::convert::TryFrom;
+
+use signum::chsets::encoding::Mapping;
+
+use super::{cff, OutlineFont};
+
+const UNITS_PER_EM: u16 = 1000;
+/// Private use area for symbol fonts
+const SYMBOL_BASE: u32 = 0xF000;
+
+fn u16s(out: &mut Vec<u8>, values: &[u16]) {
+    for v in values {
+        out.extend_from_slice(&v.to_be_bytes());
+    }
+}
+
+fn i16s(out: &mut Vec<u8>, values: &[i16]) {
+    for v in values {
+        out.extend_from_slice(&v.to_be_bytes());
+    }
+}
+
+fn u32s(out: &mut Vec<u8>, values: &[u32]) {
+    for v in values {
+        out.extend_from_slice(&v.to_be_bytes());
+    }
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// `(searchRange, entrySelector, rangeShift)` for binary search tables
+fn search_params(count: u16, size: u16) -> [u16; 3] {
+    let mut entry_selector = 0;
+    while (2 << entry_selector) <= count {
+        entry_selector += 1;
+    }
+    let search_range = (1 << entry_selector) * size;
+    [search_range, entry_selector, count * size - search_range]
+}
+
+struct Metrics {
+    num_glyphs: u16,
+    bbox: [i16; 4],
+    advance_max: u16,
+    min_lsb: i16,
+    min_rsb: i16,
+    max_extent: i16,
+    avg_width: i16,
+}
+
+impl Metrics {
+    fn new(font: &OutlineFont) -> Self {
+        let (x0, y0, x1, y1) = font.bbox();
+        let mut advance_max = 0;
+        let mut min_lsb = i16::MAX;
+        let mut min_rsb = i16::MAX;
+        let mut max_extent = i16::MIN;
+        let mut sum = 0;
+        let mut count = 0;
+        for glyph in &font.glyphs {
+            advance_max = advance_max.max(glyph.advance as u16);
+            if glyph.advance > 0 {
+                sum += glyph.advance;
+                count += 1;
+            }
+            if let Some((gx0, _, gx1, _)) = glyph.bbox() {
+                min_lsb = min_lsb.min(gx0 as i16);
+                min_rsb = min_rsb.min(glyph.advance as i16 - gx1 as i16);
+                max_extent = max_extent.max(gx1 as i16);
+            }
+        }
+        if max_extent == i16::MIN {
+            (min_lsb, min_rsb, max_extent) = (0, 0, 0);
+        }
+        Self {
+            num_glyphs: font.glyphs.len() as u16 + 1,
+            bbox: [x0 as i16, y0 as i16, x1 as i16, y1 as i16],
+            advance_max,
+            min_lsb,
+            min_rsb,
+            max_extent,
+            avg_width: sum.checked_div(count).unwrap_or(0) as i16,
+        }
+    }
+}
+
+fn head(m: &Metrics) -> Vec<u8> {
+    let mut out = Vec::with_capacity(54);
+    u32s(&mut out, &[0x0001_0000, 0x0001_0000, 0, 0x5F0F_3CF5]);
+    u16s(&mut out, &[0x0003, UNITS_PER_EM]);
+    u32s(&mut out, &[0, 0, 0, 0]); // created, modified
+    i16s(&mut out, &m.bbox);
+    u16s(&mut out, &[0, 8]); // macStyle, lowestRecPPEM
+    i16s(&mut out, &[2, 0, 0]); // fontDirectionHint, indexToLocFormat, glyphDataFormat
+    out
+}
+
+fn hhea(font: &OutlineFont, m: &Metrics) -> Vec<u8> {
+    let mut out = Vec::with_capacity(36);
+    u32s(&mut out, &[0x0001_0000]);
+    i16s(&mut out, &[font.ascent as i16, font.descent as i16, 0]);
+    u16s(&mut out, &[m.advance_max]);
+    i16s(&mut out, &[m.min_lsb, m.min_rsb, m.max_extent]);
+    i16s(&mut out, &[1, 0, 0, 0, 0, 0, 0, 0]); // caret, reserved, metricDataFormat
+    u16s(&mut out, &[m.num_glyphs]);
+    out
+}
+
+fn hmtx(font: &OutlineFont) -> Vec<u8> {
+    let mut out = vec![0; 4]; // .notdef
+    for glyph in &font.glyphs {
+        let lsb = glyph.bbox().map_or(0, |(x0, ..)| x0);
+        u16s(&mut out, &[glyph.advance as u16]);
+        i16s(&mut out, &[lsb as i16]);
+    }
+    out
+}
+
+fn maxp(m: &Metrics) -> Vec<u8> {
+    let mut out = Vec::with_capacity(6);
+    u32s(&mut out, &[0x0000_5000]);
+    u16s(&mut out, &[m.num_glyphs]);
+    out
+}
+
+fn os2(font: &OutlineFont, m: &Metrics, first: u16, last: u16, symbol: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(96);
+    let (ascent, descent) = (font.ascent as i16, font.descent as i16);
+    u16s(&mut out, &[4]); // version
+    i16s(&mut out, &[m.avg_width]);
+    u16s(&mut out, &[400, 5, 0]); // usWeightClass, usWidthClass, fsType
+    #[rustfmt::skip]
+    i16s(&mut out, &[
+        650, 600, 0, 75, // subscript: x size, y size, x offset, y offset
+        650, 600, 0, 350, // superscript
+        50, 250, // strikeout: size, position
+        0, // sFamilyClass
+    ]);
+    out.extend_from_slice(&[0; 10]); // panose
+    u32s(&mut out, &[0; 4]); // ulUnicodeRange
+    out.extend_from_slice(b"SDO ");
+    u16s(&mut out, &[0x0040, first, last]); // fsSelection: REGULAR
+    i16s(&mut out, &[ascent, descent, 0]);
+    u16s(&mut out, &[ascent as u16, (-descent) as u16]);
+    // ulCodePageRange: Latin 1 or Symbol
+    u32s(&mut out, &[if symbol { 1 << 31 } else { 1 }, 0]);
+    i16s(&mut out, &[0, 0]); // sxHeight, sCapHeight
+    u16s(&mut out, &[0, 0x20, 1]); // usDefaultChar, usBreakChar, usMaxContext
+    out
+}
+
+/// Find the glyph for each code point
+fn code_points(font: &OutlineFont, mapping: Option<&Mapping>) -> Vec<(u16, u16)> {
+    let chars: Option<Vec<&[char]>> = mapping.map(|m| m.chars().collect());
+    let mut map: Vec<(u16, u16)> = font
+        .glyphs
+        .iter()
+        .enumerate()
+        .filter_map(|(index, glyph)| {
+            let gid = index as u16 + 1;
+            let code = match &chars {
+                Some(chars) => match chars.get(usize::from(glyph.cval))? {
+                    [c] if *c != char::REPLACEMENT_CHARACTER => u32::from(*c),
+                    _ => return None,
+                },
+                None => SYMBOL_BASE + u32::from(glyph.cval),
+            };
+            u16::try_from(code).ok().map(|code| (code, gid))
+        })
+        .collect();
+    map.sort_unstable();
+    map.dedup_by_key(|(code, _)| *code);
+    map
+}
+
+/// A `cmap` with a single format 4 subtable
+fn cmap(map: &[(u16, u16)], symbol: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    u16s(&mut out, &[0, 1, 3, if symbol { 0 } else { 1 }]);
+    u32s(&mut out, &[12]);
+
+    // one segment per character, and the final 0xFFFF segment
+    let seg_count = map.len() as u16 + 1;
+    let mut sub = Vec::new();
+    u16s(&mut sub, &[4, 0, 0, seg_count * 2]);
+    u16s(&mut sub, &search_params(seg_count, 2));
+    for (code, _) in map {
+        u16s(&mut sub, &[*code]);
+    }
+    u16s(&mut sub, &[0xFFFF, 0]); // endCode, reservedPad
+    for (code, _) in map {
+        u16s(&mut sub, &[*code]);
+    }
+    u16s(&mut sub, &[0xFFFF]);
+    for (code, gid) in map {
+        u16s(&mut sub, &[gid.wrapping_sub(*code)]);
+    }
+    u16s(&mut sub, &[1]);
+    u16s(&mut sub, &vec![0; usize::from(seg_count)]); // idRangeOffset
+    let len = sub.len() as u16;
+    sub[2..4].copy_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(&sub);
+    out
+}
+
+fn name(font: &OutlineFont) -> Vec<u8> {
+    let records: [(u16, &str); 5] = [
+        (1, &font.family),
+        (2, "Regular"),
+        (3, &font.name),
+        (4, &font.family),
+        (6, &font.name),
+    ];
+    let mut strings = Vec::new();
+    let mut out = Vec::new();
+    u16s(
+        &mut out,
+        &[0, records.len() as u16, 6 + 12 * records.len() as u16],
+    );
+    for (name_id, text) in records.iter() {
+        let utf16: Vec<u16> = text.encode_utf16().collect();
+        let offset = strings.len() as u16;
+        u16s(&mut strings, &utf16);
+        let len = utf16.len() as u16 * 2;
+        u16s(&mut out, &[3, 1, 0x0409, *name_id, len, offset]);
+    }
+    out.extend_from_slice(&strings);
+    out
+}
+
+fn post() -> Vec<u8> {
+    let mut out = Vec::with_capacity(32);
+    u32s(&mut out, &[0x0003_0000, 0]); // version, italicAngle
+    i16s(&mut out, &[-100, 50]); // underlinePosition, underlineThickness
+    u32s(&mut out, &[0; 5]); // isFixedPitch, memory usage
+    out
+}
+
+/// Write a complete OpenType font
+pub(super) fn write_otf(font: &OutlineFont, mapping: Option<&Mapping>) -> Vec<u8> {
+    let m = Metrics::new(font);
+    let symbol = mapping.is_none();
+    let map = code_points(font, mapping);
+    let first = map.first().map_or(0, |(code, _)| *code);
+    let last = map.last().map_or(0, |(code, _)| *code);
+
+    // sorted by tag
+    let tables: [(&[u8; 4], Vec<u8>); 9] = [
+        (b"CFF ", cff::write_cff(font)),
+        (b"OS/2", os2(font, &m, first, last, symbol)),
+        (b"cmap", cmap(&map, symbol)),
+        (b"head", head(&m)),
+        (b"hhea", hhea(font, &m)),
+        (b"hmtx", hmtx(font)),
+        (b"maxp", maxp(&m)),
+        (b"name", name(font)),
+        (b"post", post()),
+    ];
+
+    let num_tables = tables.len() as u16;
+    let mut out = Vec::new();
+    out.extend_from_slice(b"OTTO");
+    u16s(&mut out, &[num_tables]);
+    u16s(&mut out, &search_params(num_tables, 16));
+
+    let mut offset = 12 + 16 * tables.len();
+    let mut head_offset = 0;
+    for (tag, data) in tables.iter() {
+        if *tag == b"head" {
+            head_offset = offset;
+        }
+        out.extend_from_slice(*tag);
+        u32s(
+            &mut out,
+            &[checksum(data), offset as u32, data.len() as u32],
+        );
+        offset += (data.len() + 3) & !3;
+    }
+    for (_, data) in tables.iter() {
+        out.extend_from_slice(data);
+        out.resize((out.len() + 3) & !3, 0);
+    }
+
+    let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&out));
+    out[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checksum, cmap, search_params};
+
+    #[test]
+    fn test_search_params() {
+        assert_eq!(search_params(9, 16), [128, 3, 16]);
+        assert_eq!(search_params(1, 2), [2, 0, 0]);
+        assert_eq!(search_params(5, 2), [8, 2, 2]);
+    }
+
+    #[test]
+    fn test_cmap() {
+        let out = cmap(&[(0x41, 1), (0x42, 2)], false);
+        assert_eq!(out[..8], [0, 0, 0, 1, 0, 3, 0, 1]);
+        let sub = &out[12..];
+        assert_eq!(sub[..4], [0, 4, 0, 40]);
+        assert_eq!(sub.len(), 40);
+        // segCountX2, searchRange, entrySelector, rangeShift
+        assert_eq!(sub[6..14], [0, 6, 0, 4, 0, 1, 0, 2]);
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum(&[0, 0, 0, 1, 0, 0, 1]), 0x101);
+    }
+}
@@ -3,7 +3,7 @@ use std::io::{self, Write};
 use pdf_create::common::MediaBox;
 use signum::docs::{hcim::ImageSite, pbuf, Overrides};
 
-use super::TextContents;
+use super::{structure::Mark, TextContents};
 
 /// The `Contents` stream of a PDF
 #[derive(Default)]
@@ -67,6 +67,16 @@ impl Contents {
         Ok(())
     }
 
+    /// Begin a marked-content sequence (`BDC` or `BMC` operator)
+    pub fn begin_marked_content(&mut self, mark: Mark) -> io::Result<()> {
+        writeln!(self.inner, "{}", mark)
+    }
+
+    /// End a marked-content sequence (`EMC` operator)
+    pub fn end_marked_content(&mut self) -> io::Result<()> {
+        writeln!(self.inner, "EMC")
+    }
+
     pub fn draw_line(&mut self, points: &[(f32, u32)]) -> io::Result<()> {
         if let Some((&(x0, y0), rest)) = points.split_first() {
             write!(
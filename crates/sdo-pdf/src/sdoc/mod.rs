@@ -4,21 +4,27 @@ use std::io;
 
 use log::warn;
 use pdf_create::{
-    common::{MediaBox, ProcSet, Rectangle},
-    high::{DictResource, Font, GlobalResource, Handle, Page, Res, Resource, Resources, XObject},
+    common::{MediaBox, ProcSet, Rectangle, StructType},
+    high::{
+        DictResource, Font, GlobalResource, Handle, Page, Res, Resource, Resources, StructElem,
+        StructKid, XObject,
+    },
 };
 use signum::{
     chsets::cache::{DocumentFontCacheInfo, FontCacheInfo},
     docs::{
         pbuf,
-        tebu::{self, PageText},
+        tebu::{self, Flags, PageText},
         GenerationContext, Overrides,
     },
 };
 
 mod contents;
+mod structure;
 mod text;
 use contents::Contents;
+use structure::Mark;
+pub use structure::StructureBuilder;
 use text::{TextContents, TEXT_MATRIX_SCALE_X, TEXT_MATRIX_SCALE_Y};
 
 use crate::{
@@ -27,16 +33,65 @@ use crate::{
     Error,
 };
 
+/// Select the marked-content sequence for a line at `y`
+///
+/// Lines above the body (`header`) or below it (`length`) are running
+/// headers or footers, all other lines are part of a paragraph.
+fn line_mark(
+    structure: &mut StructureBuilder,
+    current: Option<Mark>,
+    page_info: &pbuf::Page,
+    y: u32,
+    line: &tebu::Line,
+) -> Mark {
+    let format = &page_info.format;
+    let header = u32::from(format.header);
+    if y <= header {
+        Mark::Header
+    } else if y > header + u32::from(format.length) {
+        Mark::Footer
+    } else {
+        match current {
+            Some(mark @ Mark::Content(StructType::P, _)) if !line.flags.contains(Flags::PARA) => {
+                mark
+            }
+            Some(Mark::Content(StructType::P, _)) => structure.paragraph(true),
+            // continues on a new page or after a header
+            _ => structure.paragraph(line.flags.contains(Flags::PARA)),
+        }
+    }
+}
+
 /// Write the text for a PDF page
+///
+/// If there is a `structure`, the text is tagged.
 fn write_pdf_page_text<O: io::Write>(
     contents: &mut TextContents<O>,
+    mut structure: Option<&mut StructureBuilder>,
     print: &DocumentFontCacheInfo,
     infos: &[Option<&FontInfo>; 8],
     page: &PageText,
+    page_info: &pbuf::Page,
 ) -> Result<(), Error> {
     contents.goto_origin().map_err(Error::Contents)?;
+    let mut y = 0;
+    let mut current = None;
     for (skip, line) in &page.content {
         contents.next_line(0, *skip as u32 + 1);
+        y += *skip as u32 + 1;
+
+        if let Some(structure) = structure.as_deref_mut().filter(|_| !line.data.is_empty()) {
+            let mark = line_mark(structure, current, page_info, y, line);
+            if current != Some(mark) {
+                if current.is_some() {
+                    contents.end_marked_content().map_err(Error::Contents)?;
+                }
+                contents
+                    .begin_marked_content(mark)
+                    .map_err(Error::Contents)?;
+                current = Some(mark);
+            }
+        }
 
         // How far we've drawn
         let mut pdf_page_cursor: u32 = 0;
@@ -102,6 +157,9 @@ fn write_pdf_page_text<O: io::Write>(
 
         contents.flush().map_err(Error::Contents)?;
     }
+    if current.is_some() {
+        contents.end_marked_content().map_err(Error::Contents)?;
+    }
     Ok(())
 }
 
@@ -180,13 +238,17 @@ fn write_pdf_page_underlines(
 }
 
 /// Write the images of a PDF page
+///
+/// If there is a `structure`, every image is tagged as a figure. The figures
+/// have no alternate description, as the documents do not contain one.
 fn write_pdf_page_images<GC: GenerationContext>(
     contents: &mut Contents,
+    mut structure: Option<&mut StructureBuilder>,
     gc: &GC,
     page_info: &pbuf::Page,
     res: &mut Res<'_>,
     x_objects: &mut DictResource<XObject>,
-) -> bool {
+) -> Result<bool, Error> {
     let mut has_images = false;
     for (index, site) in gc
         .image_sites()
@@ -203,14 +265,22 @@ fn write_pdf_page_images<GC: GenerationContext>(
         );
 
         if let Some(image) = image_for_site(gc.document_info(), site) {
-            contents.image(site, &key).unwrap();
+            if let Some(structure) = structure.as_deref_mut() {
+                contents
+                    .begin_marked_content(structure.figure())
+                    .map_err(Error::Contents)?;
+            }
+            contents.image(site, &key).map_err(Error::Contents)?;
+            if structure.is_some() {
+                contents.end_marked_content().map_err(Error::Contents)?;
+            }
             x_objects.insert(key.clone(), res.push_xobject(image).into());
             has_images |= true;
         } else {
-            warn!("Missing image {} on page {}", site.img, site.page);
+            warn!("Missing image {} on page {}", site.img, page_info.log_pnr);
         }
     }
-    has_images
+    Ok(has_images)
 }
 
 /// Select a suitable media box
@@ -228,8 +298,14 @@ fn select_media_box(page_info: &pbuf::Page) -> MediaBox {
 }
 
 /// Generate a single PDF page
+///
+/// If there is a `structure`, the page is tagged and its marked content is
+/// added to it. The builder needs to be started on the index of the page in
+/// the PDF.
+#[allow(clippy::too_many_arguments)]
 pub fn generate_pdf_page<GC: GenerationContext>(
     gc: &GC,
+    mut structure: Option<&mut StructureBuilder>,
     overrides: &Overrides,
     infos: &[Option<&FontInfo>; 8],
     fonts: GlobalResource<DictResource<Font<'static>>>,
@@ -243,11 +319,27 @@ pub fn generate_pdf_page<GC: GenerationContext>(
     let has_images: bool;
     let contents = {
         let mut contents = Contents::for_page(page_info, &media_box, overrides);
-        has_images = write_pdf_page_images(&mut contents, gc, page_info, res, &mut x_objects);
+        has_images = write_pdf_page_images(
+            &mut contents,
+            structure.as_deref_mut(),
+            gc,
+            page_info,
+            res,
+            &mut x_objects,
+        )?;
         let print = &gc.document_info().fonts;
+        let tagged = structure.is_some();
+        if tagged {
+            contents
+                .begin_marked_content(Mark::Artifact)
+                .map_err(Error::Contents)?;
+        }
         write_pdf_page_underlines(print, infos, &page.content, &mut contents)?;
+        if tagged {
+            contents.end_marked_content().map_err(Error::Contents)?;
+        }
         let mut contents = contents.start_text(TEXT_MATRIX_SCALE_X, TEXT_MATRIX_SCALE_Y);
-        write_pdf_page_text(&mut contents, print, infos, page)?;
+        write_pdf_page_text(&mut contents, structure, print, infos, page, page_info)?;
         contents.finish().map_err(Error::Contents)
     }?;
     let resources = Resources {
@@ -265,26 +357,95 @@ pub fn generate_pdf_page<GC: GenerationContext>(
         media_box: Rectangle::from(media_box),
         resources,
         contents,
+        links: Vec::new(),
     })
 }
 
+/// Add a structure element to the `Document` root element
+pub fn push_structure(hnd: &mut Handle, elem: StructElem) {
+    match hnd.structure.last_mut() {
+        Some(root) if root.kind == StructType::Document => {
+            root.children.push(StructKid::Elem(elem));
+        }
+        _ => {
+            let mut root = StructElem::new(StructType::Document);
+            root.children.push(StructKid::Elem(elem));
+            hnd.structure.push(root);
+        }
+    }
+}
+
 /// Generate a sequence of PDF pages
+///
+/// If `tagged` is set, the paragraphs, figures and running headers and
+/// footers are added to the logical structure as a `Part` of the `Document`.
 pub fn generate_pdf_pages<GC: GenerationContext>(
     gc: &GC,
     hnd: &mut Handle,
     overrides: &Overrides,
     font_info: &Fonts,
+    tagged: bool,
 ) -> Result<(), Error> {
-    let res = &mut hnd.res;
-    let pages = &mut hnd.pages;
+    let mut pages = Vec::new();
+    let first_page = hnd.pages.len();
+    generate_pdf_pages_with(
+        gc,
+        hnd,
+        overrides,
+        font_info,
+        tagged,
+        first_page,
+        |_, page| {
+            pages.push(page);
+            Ok::<_, Error>(())
+        },
+    )?;
+    hnd.pages.extend(pages);
+    Ok(())
+}
 
+/// Generate a sequence of PDF pages, passing each one to `emit` instead of
+/// adding it to the handle
+///
+/// Use this with an [`IncrementalWriter`](pdf_create::high::IncrementalWriter),
+/// where `first_page` is the number of pages that were already written.
+pub fn generate_pdf_pages_with<GC, F, E>(
+    gc: &GC,
+    hnd: &mut Handle,
+    overrides: &Overrides,
+    font_info: &Fonts,
+    tagged: bool,
+    first_page: usize,
+    mut emit: F,
+) -> Result<(), E>
+where
+    GC: GenerationContext,
+    F: FnMut(&Handle, Page<'static>) -> Result<(), E>,
+    E: From<Error>,
+{
     let (fonts, infos) = font_info.font_dict(gc.fonts());
-    let font_dict = res.push_font_dict(fonts);
-    for page in gc.text_pages() {
+    let font_dict = hnd.res.push_font_dict(fonts);
+    let mut structure = tagged.then(|| StructureBuilder::new(StructType::Part));
+    for (index, page) in gc.text_pages().iter().enumerate() {
         let page_info = gc.page_at(page.index as usize).unwrap();
 
-        let page = generate_pdf_page(gc, overrides, &infos, font_dict, page, page_info, res)?;
-        pages.push(page);
+        if let Some(structure) = &mut structure {
+            structure.start_page(first_page + index);
+        }
+        let page = generate_pdf_page(
+            gc,
+            structure.as_mut(),
+            overrides,
+            &infos,
+            font_dict,
+            page,
+            page_info,
+            &mut hnd.res,
+        )?;
+        emit(hnd, page)?;
+    }
+    if let Some(structure) = structure {
+        push_structure(hnd, structure.finish());
     }
     Ok(())
 }
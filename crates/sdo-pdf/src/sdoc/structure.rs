@@ -0,0 +1,144 @@
+use std::fmt;
+
+use pdf_create::{
+    common::StructType,
+    high::{StructElem, StructKid},
+};
+
+/// The kind of a marked-content sequence
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mark {
+    /// Content of a structure element, with its MCID
+    Content(StructType, u32),
+    /// A running header
+    Header,
+    /// A running footer
+    Footer,
+    /// Decoration that is not part of the content, e.g. underlines
+    Artifact,
+}
+
+impl fmt::Display for Mark {
+    /// Write the operator that starts the marked-content sequence
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Content(kind, mcid) => write!(f, "/{} <</MCID {}>> BDC", kind.name(), mcid),
+            Self::Header => write!(f, "/Artifact <</Type /Pagination /Subtype /Header>> BDC"),
+            Self::Footer => write!(f, "/Artifact <</Type /Pagination /Subtype /Footer>> BDC"),
+            Self::Artifact => write!(f, "/Artifact BMC"),
+        }
+    }
+}
+
+/// Collects the logical structure of a document while its pages are generated
+pub struct StructureBuilder {
+    elem: StructElem,
+    /// The index of the current page in the PDF
+    page: usize,
+    next_mcid: u32,
+    /// The index of the open paragraph in `elem.children`
+    para: Option<usize>,
+}
+
+impl StructureBuilder {
+    /// Create a new builder for a (part of a) document
+    pub fn new(kind: StructType) -> Self {
+        Self {
+            elem: StructElem::new(kind),
+            page: 0,
+            next_mcid: 0,
+            para: None,
+        }
+    }
+
+    /// Continue with the PDF page at `page`
+    pub fn start_page(&mut self, page: usize) {
+        self.page = page;
+        self.next_mcid = 0;
+    }
+
+    fn content(&mut self, kind: StructType) -> (StructKid, Mark) {
+        let mcid = self.next_mcid;
+        self.next_mcid += 1;
+        let kid = StructKid::Content {
+            page: self.page,
+            mcid,
+        };
+        (kid, Mark::Content(kind, mcid))
+    }
+
+    /// Mark the following text as part of a paragraph
+    ///
+    /// If `new` is not set, this continues the previous paragraph, possibly
+    /// from an earlier page.
+    pub fn paragraph(&mut self, new: bool) -> Mark {
+        let (kid, mark) = self.content(StructType::P);
+        let children = &mut self.elem.children;
+        match self.para.filter(|_| !new).and_then(|i| children.get_mut(i)) {
+            Some(StructKid::Elem(para)) => para.children.push(kid),
+            _ => {
+                let mut para = StructElem::new(StructType::P);
+                para.children.push(kid);
+                self.para = Some(children.len());
+                children.push(StructKid::Elem(para));
+            }
+        }
+        mark
+    }
+
+    /// Mark the following content as a figure
+    ///
+    /// The figure has no alternate description (`/Alt`).
+    pub fn figure(&mut self) -> Mark {
+        let (kid, mark) = self.content(StructType::Figure);
+        let mut figure = StructElem::new(StructType::Figure);
+        figure.children.push(kid);
+        self.elem.children.push(StructKid::Elem(figure));
+        mark
+    }
+
+    /// Return the structure element
+    pub fn finish(self) -> StructElem {
+        self.elem
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf_create::{common::StructType, high::StructKid};
+
+    use super::{Mark, StructureBuilder};
+
+    #[test]
+    fn test_paragraph_across_pages() {
+        let mut builder = StructureBuilder::new(StructType::Part);
+        builder.start_page(0);
+        assert_eq!(builder.paragraph(true), Mark::Content(StructType::P, 0));
+        assert_eq!(builder.paragraph(true), Mark::Content(StructType::P, 1));
+        builder.start_page(1);
+        assert_eq!(builder.figure(), Mark::Content(StructType::Figure, 0));
+        assert_eq!(builder.paragraph(false), Mark::Content(StructType::P, 1));
+
+        let part = builder.finish();
+        let kinds: Vec<_> = part
+            .children
+            .iter()
+            .map(|kid| match kid {
+                StructKid::Elem(elem) => (elem.kind, elem.children.len()),
+                _ => panic!("unexpected kid"),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (StructType::P, 1),
+                (StructType::P, 2),
+                (StructType::Figure, 1)
+            ]
+        );
+        assert_eq!(
+            Mark::Content(StructType::P, 1).to_string(),
+            "/P <</MCID 1>> BDC"
+        );
+    }
+}
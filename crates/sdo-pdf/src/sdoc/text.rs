@@ -2,6 +2,8 @@ use std::io;
 
 use pdf_create::write::write_string;
 
+use super::structure::Mark;
+
 /// Constant to get from 1/216th inches (y space) to 1/72th space (PDF space)
 const Y_SCALE_INVERSE: f32 = 3.0;
 
@@ -195,6 +197,18 @@ impl<O: io::Write> TextContents<O> {
         Ok(())
     }
 
+    /// Begin a marked-content sequence (`BDC` or `BMC` operator)
+    pub fn begin_marked_content(&mut self, mark: Mark) -> io::Result<()> {
+        self.flush()?;
+        writeln!(self.inner, "{}", mark)
+    }
+
+    /// End a marked-content sequence (`EMC` operator)
+    pub fn end_marked_content(&mut self) -> io::Result<()> {
+        self.flush()?;
+        writeln!(self.inner, "EMC")
+    }
+
     pub fn finish(mut self) -> io::Result<O> {
         writeln!(self.inner, "ET")?;
         writeln!(self.inner, "Q")?;
@@ -250,14 +250,15 @@ impl super::Handle {
             }
 
             ctx.set_stroke_style_str("blue");
-            if let Some(mut iter) = page.first_outline() {
+            if let Some(path) = raster::decompose(&page).first() {
                 super::log_val("Test", &JsValue::TRUE);
-                let (x0, y0) = iter.next().unwrap();
+                let (x0, y0) = path.points[0];
                 ctx.begin_path();
                 ctx.move_to((10 + x0 * 10) as f64, (10 + y0 * 10) as f64);
-                for (x, y) in iter {
+                for &(x, y) in &path.points[1..] {
                     ctx.line_to((10 + x * 10) as f64, (10 + y * 10) as f64);
                 }
+                ctx.close_path();
                 ctx.stroke();
             }
         });
@@ -13,7 +13,7 @@ use glue::{
 };
 use js_sys::{Array, Uint8Array};
 use log::{info, warn, Level};
-use sdo_pdf::{generate_pdf, MetaInfo};
+use sdo_pdf::{font::FontMode, generate_pdf, MetaInfo, PdfAConformance};
 use signum::{
     chsets::{cache::ChsetCache, encoding::decode_atari_str, v2::TAG_CHSET, FontKind},
     docs::{
@@ -223,7 +223,16 @@ impl Handle {
             FontKind::Editor => Err(JsError::new("editor font not supported")),
             FontKind::Printer(printer_kind) => Ok(printer_kind),
         }?;
-        let pdf = generate_pdf(&active_doc.fc, pk, &meta, &overrides, active_doc)?;
+        let pdf = generate_pdf(
+            &active_doc.fc,
+            pk,
+            &meta,
+            &overrides,
+            FontMode::Bitmap,
+            false,
+            PdfAConformance::A2b,
+            active_doc,
+        )?;
         let vec = Vec::new();
         let mut writer = BufWriter::new(vec);
         pdf.write(&mut writer)?;
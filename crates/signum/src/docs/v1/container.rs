@@ -1,10 +1,12 @@
 //! # The outer container / structure
 
+use std::{convert::TryFrom, io};
+
 use nom::{
-    bytes::complete::tag,
+    bytes::complete::{tag, take},
     combinator::eof,
     error::ParseError,
-    multi::{length_data, many_till},
+    multi::many_till,
     number::complete::be_u32,
     sequence::preceded,
     IResult,
@@ -30,6 +32,20 @@ impl<'a> Chunk<'a> {
             buf: Buf(data),
         }
     }
+
+    /// Write the chunk (i.e. tag + length + body) to a writer
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        write_chunk(buf, self.tag, self.buf.0)
+    }
+}
+
+/// Write a single document chunk (i.e. tag + length + body)
+pub fn write_chunk<W: io::Write>(buf: &mut W, tag: FourCC, data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "chunk too large"))?;
+    buf.write_all(tag.as_slice())?;
+    buf.write_all(&len.to_be_bytes())?;
+    buf.write_all(data)
 }
 
 /// A Signum! document container
@@ -44,7 +60,9 @@ pub fn parse_chunk<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], Chunk<'a>, E> {
     let (rest, tag) = four_cc(input)?;
-    let (rest, data) = length_data(be_u32)(rest)?;
+    // `length_data` is streaming, so a truncated chunk would be `Incomplete`
+    let (rest, len) = be_u32(rest)?;
+    let (rest, data) = take(len)(rest)?;
     let chunk = Chunk::new(tag, data);
     Ok((rest, chunk))
 }
@@ -56,3 +74,14 @@ pub fn parse_sdoc0001_container<'a, E: ParseError<&'a [u8]>>(
     let (input, (chunks, _)) = preceded(tag(b"sdoc"), many_till(parse_chunk, eof))(input)?;
     Ok((input, SDocContainer { chunks }))
 }
+
+impl SDocContainer<'_> {
+    /// Write the container (i.e. the `sdoc` magic and all chunks) to a writer
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(FourCC::SDOC.as_slice())?;
+        for chunk in &self.chunks {
+            chunk.write_to(buf)?;
+        }
+        Ok(())
+    }
+}
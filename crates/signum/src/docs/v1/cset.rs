@@ -1,6 +1,6 @@
 //! # (`cset`) The character set chunk
 
-use std::borrow::Cow;
+use std::{borrow::Cow, io};
 
 use bstr::BStr;
 use nom::{bytes::complete::take, combinator::map, error::ParseError, multi::many0, IResult};
@@ -50,6 +50,19 @@ impl<'a> CSet<'a> {
             .collect();
         CSet { names }
     }
+
+    /// Write the body of the `cset` chunk
+    ///
+    /// Every name is padded with nul bytes to 10 bytes.
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        for name in &self.names {
+            let mut slot = [0u8; 10];
+            let len = name.len().min(10);
+            slot[..len].copy_from_slice(&name[..len]);
+            buf.write_all(&slot)?;
+        }
+        Ok(())
+    }
 }
 
 impl<'a> Chunk<'a> for CSet<'a> {
@@ -0,0 +1,326 @@
+//! # Editable text buffer
+//!
+//! The [`TeBu`] chunk stores the horizontal position of every character as an
+//! offset from the previous one, so changing a single character shifts all
+//! characters after it. This module converts the text buffer into an owned
+//! model where every character stores the *gap* to the end of the previous
+//! character instead. Characters can then be inserted, removed, restyled or
+//! moved to another character set, and the offsets are recomputed from the
+//! character widths (e.g. [`DocumentFontCacheInfo::width`]) when the model is
+//! lowered back into a [`TeBu`].
+//!
+//! ```ignore
+//! let width = |k: &Char| dfci.width(&fc, k);
+//! let mut doc = EditDocument::from_tebu(&sdoc.tebu, width);
+//! doc.pages[0].lines[3].remove(5);
+//! sdoc.tebu = doc.to_tebu(width);
+//! ```
+//!
+//! [`DocumentFontCacheInfo::width`]: crate::chsets::cache::DocumentFontCacheInfo::width
+
+use std::ops::RangeBounds;
+
+use super::tebu::{Char, Flags, Line, PageText, Style, TeBu, TextBufferHeader};
+
+/// The largest offset that can be stored for a character
+const MAX_OFFSET: i32 = 0x07FF;
+
+/// A character in an [`EditLine`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EditChar {
+    /// The number of the character
+    pub cval: u8,
+    /// The number of the character set
+    pub cset: u8,
+    /// The style of the character
+    pub style: Style,
+    /// The horizontal space between the end of the previous character and this one
+    ///
+    /// For the first character in a line, this is the distance from the start of the line.
+    pub gap: i16,
+    /// Whether the character is written in the long form, see [`Char::long`]
+    pub long: bool,
+}
+
+impl EditChar {
+    /// Create a new character that directly follows the previous one
+    pub fn new(cval: u8, cset: u8, style: Style) -> Self {
+        Self {
+            cval,
+            cset,
+            style,
+            gap: 0,
+            long: false,
+        }
+    }
+
+    /// Return the character with the given offset
+    pub fn to_char(&self, offset: u16) -> Char {
+        Char {
+            cval: self.cval,
+            cset: self.cset,
+            offset,
+            style: self.style,
+            long: self.long,
+        }
+    }
+}
+
+/// A single line of an [`EditPage`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditLine {
+    /// The vertical distance to the previous line
+    pub skip: u16,
+    /// The flags for the line
+    pub flags: Flags,
+    /// The extra value (usually page number)
+    pub extra: u16,
+    /// The characters in the line
+    pub chars: Vec<EditChar>,
+}
+
+impl EditLine {
+    /// Create an editable line from a parsed [`Line`]
+    pub fn from_line<W: Fn(&Char) -> u8>(skip: u16, line: &Line, width: W) -> Self {
+        let mut chars = Vec::with_capacity(line.data.len());
+        let mut prev_width = 0;
+        for k in &line.data {
+            let gap = i32::from(k.offset) - i32::from(prev_width);
+            chars.push(EditChar {
+                cval: k.cval,
+                cset: k.cset,
+                style: k.style,
+                gap: gap as i16,
+                long: k.long,
+            });
+            prev_width = width(k);
+        }
+        Self {
+            skip,
+            flags: line.flags,
+            extra: line.extra,
+            chars,
+        }
+    }
+
+    /// Lower this line into a [`Line`], computing the offsets from the character widths
+    ///
+    /// Offsets that would be negative or too large are clamped.
+    pub fn to_line<W: Fn(&Char) -> u8>(&self, width: W) -> Line {
+        let mut data = Vec::with_capacity(self.chars.len());
+        let mut prev_width = 0;
+        for chr in &self.chars {
+            let offset = (i32::from(prev_width) + i32::from(chr.gap)).clamp(0, MAX_OFFSET);
+            let k = chr.to_char(offset as u16);
+            prev_width = width(&k);
+            data.push(k);
+        }
+        Line::new(self.flags, self.extra, data)
+    }
+
+    /// Insert a character at `index`, shifting all characters after it to the right
+    pub fn insert(&mut self, index: usize, chr: EditChar) {
+        self.chars.insert(index, chr);
+    }
+
+    /// Insert a string of characters at `index`
+    pub fn insert_all<I: IntoIterator<Item = EditChar>>(&mut self, index: usize, chars: I) {
+        let tail = self.chars.split_off(index);
+        self.chars.extend(chars);
+        self.chars.extend(tail);
+    }
+
+    /// Remove the character at `index`, shifting all characters after it to the left
+    ///
+    /// The character after the removed one keeps its gap.
+    pub fn remove(&mut self, index: usize) -> EditChar {
+        self.chars.remove(index)
+    }
+
+    /// Remove a range of characters
+    pub fn remove_range<R: RangeBounds<usize>>(&mut self, range: R) -> Vec<EditChar> {
+        self.chars.drain(range).collect()
+    }
+
+    /// Replace the style of a range of characters
+    pub fn set_style<R: RangeBounds<usize>>(&mut self, range: R, style: Style) {
+        let bounds = (range.start_bound().cloned(), range.end_bound().cloned());
+        for chr in &mut self.chars[bounds] {
+            chr.style = style;
+        }
+    }
+
+    /// Move all characters in character set `from` to character set `to`
+    pub fn retarget_cset(&mut self, from: u8, to: u8) {
+        for chr in &mut self.chars {
+            if chr.cset == from {
+                chr.cset = to;
+            }
+        }
+    }
+}
+
+/// The lines of a single page of an [`EditDocument`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditPage {
+    /// The index of the page
+    pub index: u16,
+    /// The vertical skip of the start page marker
+    pub skip: u16,
+    /// The flags of the start page marker
+    pub flags: Flags,
+    /// The vertical skip of the end page marker
+    pub rskip: u16,
+    /// The flags of the end page marker
+    pub rflags: Flags,
+    /// The lines on the page
+    pub lines: Vec<EditLine>,
+}
+
+impl EditPage {
+    /// Create an editable page from a parsed [`PageText`]
+    pub fn from_page_text<W: Fn(&Char) -> u8>(page: &PageText, width: W) -> Self {
+        let lines = page
+            .content
+            .iter()
+            .map(|(skip, line)| EditLine::from_line(*skip, line, &width))
+            .collect();
+        Self {
+            index: page.index,
+            skip: page.skip,
+            flags: page.flags,
+            rskip: page.rskip,
+            rflags: page.rflags,
+            lines,
+        }
+    }
+
+    /// Lower this page into a [`PageText`]
+    pub fn to_page_text<W: Fn(&Char) -> u8>(&self, width: W) -> PageText {
+        let content = self
+            .lines
+            .iter()
+            .map(|line| (line.skip, line.to_line(&width)))
+            .collect();
+        PageText {
+            index: self.index,
+            skip: self.skip,
+            flags: self.flags,
+            rskip: self.rskip,
+            rflags: self.rflags,
+            content,
+        }
+    }
+
+    /// Move the line at `from` so that it ends up at index `to`
+    ///
+    /// Every line keeps its vertical skip, i.e. the distance to the line above it.
+    pub fn move_line(&mut self, from: usize, to: usize) {
+        let line = self.lines.remove(from);
+        self.lines.insert(to, line);
+    }
+}
+
+/// An owned, editable version of a [`TeBu`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditDocument {
+    /// The `lines_total` value of the text buffer header
+    pub lines_total: u32,
+    /// The pages
+    pub pages: Vec<EditPage>,
+    /// The trailer of the text buffer
+    pub trailer: u32,
+}
+
+impl EditDocument {
+    /// Create an editable document from a text buffer
+    ///
+    /// The `width` function is used to compute the gaps between characters, usually
+    /// this is `|k| dfci.width(fc, k)` with a [`DocumentFontCacheInfo`].
+    ///
+    /// [`DocumentFontCacheInfo`]: crate::chsets::cache::DocumentFontCacheInfo
+    pub fn from_tebu<W: Fn(&Char) -> u8>(tebu: &TeBu, width: W) -> Self {
+        let pages = tebu
+            .pages
+            .iter()
+            .map(|page| EditPage::from_page_text(page, &width))
+            .collect();
+        Self {
+            lines_total: tebu.header.lines_total,
+            pages,
+            trailer: tebu.trailer,
+        }
+    }
+
+    /// Lower the document into a text buffer, recomputing all character offsets
+    ///
+    /// Using the same `width` function as in [`EditDocument::from_tebu`] on an unchanged
+    /// document returns the original offsets.
+    pub fn to_tebu<W: Fn(&Char) -> u8>(&self, width: W) -> TeBu {
+        let pages = self
+            .pages
+            .iter()
+            .map(|page| page.to_page_text(&width))
+            .collect();
+        TeBu {
+            header: TextBufferHeader {
+                lines_total: self.lines_total,
+            },
+            pages,
+            trailer: self.trailer,
+        }
+    }
+
+    /// Move all characters in character set `from` to character set `to`
+    pub fn retarget_cset(&mut self, from: u8, to: u8) {
+        for page in &mut self.pages {
+            for line in &mut page.lines {
+                line.retarget_cset(from, to);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EditChar, EditLine};
+    use crate::docs::tebu::{Char, Flags, Line, Style};
+
+    fn width(k: &Char) -> u8 {
+        if k.style.is_wide() {
+            20
+        } else {
+            10
+        }
+    }
+
+    fn chr(cval: u8, offset: u16) -> Char {
+        Char {
+            cval,
+            cset: 0,
+            offset,
+            style: Style::default(),
+            long: false,
+        }
+    }
+
+    fn offsets(line: &Line) -> Vec<u16> {
+        line.data.iter().map(|k| k.offset).collect()
+    }
+
+    #[test]
+    fn test_edit_line() {
+        let line = Line::new(Flags::LINE, 0, vec![chr(1, 30), chr(2, 10), chr(3, 14)]);
+        let mut edit = EditLine::from_line(5, &line, width);
+        assert_eq!(offsets(&edit.to_line(width)), [30, 10, 14]);
+
+        edit.remove(1);
+        assert_eq!(offsets(&edit.to_line(width)), [30, 14]);
+
+        edit.insert(0, EditChar::new(4, 0, Style::default()));
+        assert_eq!(offsets(&edit.to_line(width)), [0, 40, 14]);
+
+        edit.set_style(1..2, Style::WIDE);
+        assert_eq!(offsets(&edit.to_line(width)), [0, 40, 24]);
+    }
+}
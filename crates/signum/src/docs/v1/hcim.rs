@@ -1,6 +1,6 @@
 //! # (`hcim`) The hardcopy images
 
-use std::borrow::Cow;
+use std::{borrow::Cow, convert::TryFrom, io};
 
 use log::{debug, error, info};
 use nom::{
@@ -39,13 +39,25 @@ pub struct HcimHeader {
     pub d: Bytes32,
 }
 
+impl HcimHeader {
+    fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(&self.header_length.to_be_bytes())?;
+        buf.write_all(&self.img_count.to_be_bytes())?;
+        buf.write_all(&self.site_count.to_be_bytes())?;
+        buf.write_all(&self.c.0.to_be_bytes())?;
+        buf.write_all(&self.d.0.to_be_bytes())
+    }
+}
+
 #[derive(Debug, Serialize, Clone)]
 #[allow(non_snake_case)]
 /// Information on an image site
 ///
 /// This struct defines what part of an image is used at which position in the document
 pub struct ImageSite {
-    /// The index of the page that has this image
+    /// The physical page number of the page that has this image
+    ///
+    /// This is the `phys_pnr` of the page in `pbuf`, not its index in `tebu`.
     pub page: u16,
     /// The site of the image
     pub site: ImageArea,
@@ -67,6 +79,22 @@ pub struct ImageSite {
     pub _F: Bytes16,
 }
 
+impl ImageSite {
+    /// Write an entry of the site table
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(&self.page.to_be_bytes())?;
+        self.site.write_to(buf)?;
+        buf.write_all(&self._5.to_be_bytes())?;
+        self.sel.write_to(buf)?;
+        buf.write_all(&self._A.to_be_bytes())?;
+        buf.write_all(&self._B.to_be_bytes())?;
+        buf.write_all(&self._C.to_be_bytes())?;
+        buf.write_all(&self.img.to_be_bytes())?;
+        buf.write_all(&self._E.to_be_bytes())?;
+        buf.write_all(&self._F.to_bytes())
+    }
+}
+
 #[derive(Debug, Copy, Clone, Serialize)]
 /// The area of an image
 pub struct ImageArea {
@@ -80,6 +108,15 @@ pub struct ImageArea {
     pub h: u16,
 }
 
+impl ImageArea {
+    fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(&self.x.to_be_bytes())?;
+        buf.write_all(&self.y.to_be_bytes())?;
+        buf.write_all(&self.w.to_be_bytes())?;
+        buf.write_all(&self.h.to_be_bytes())
+    }
+}
+
 #[derive(Debug, Serialize, Clone)]
 /// A partially parsed HCIM
 pub struct Hcim<'a> {
@@ -107,6 +144,33 @@ impl Hcim<'_> {
         }
     }
 
+    /// Write the body of the `hcim` chunk
+    ///
+    /// The site table is padded with zeros to `header.header_length`.
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        self.header.write_to(buf)?;
+        let table_len = self.header.header_length as usize;
+        let mut table = Vec::with_capacity(table_len);
+        for site in &self.sites {
+            site.write_to(&mut table)?;
+        }
+        if table.len() > table_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "hcim site table exceeds header length",
+            ));
+        }
+        table.resize(table_len, 0);
+        buf.write_all(&table)?;
+        for img in &self.images {
+            let len = u32::try_from(img.len() + 4)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "image too large"))?;
+            buf.write_all(&len.to_be_bytes())?;
+            buf.write_all(img)?;
+        }
+        Ok(())
+    }
+
     /// Decode all images
     pub fn decode_images(&self) -> Vec<ImageEntry> {
         let mut images = Vec::with_capacity(self.header.img_count as usize);
@@ -153,6 +217,55 @@ pub struct ImageEntry {
     pub image: Page,
 }
 
+/// An image site together with the image it shows
+///
+/// The positions and dimensions of `site` and `sel` are in the same units as
+/// in the [`ImageSite`]. The image itself is not serialized.
+#[derive(Clone, Serialize)]
+pub struct PlacedImage<'a> {
+    /// The physical page number of the page that has this image
+    ///
+    /// See [`ImageSite::page`], this is matched against `pbuf::Page::phys_pnr`.
+    pub phys_pnr: u16,
+    /// The index of the page that has this image, if there is one
+    ///
+    /// This is the position of the page in `pbuf` and the `index` of its
+    /// [`PageText`](super::tebu::PageText).
+    pub page: Option<usize>,
+    /// The index of the image that is used
+    pub img: u16,
+    /// The name of the image
+    pub key: &'a str,
+    /// The site of the image
+    pub site: ImageArea,
+    /// The selection of the original image that is displayed
+    pub sel: ImageArea,
+    /// The width of the original image in pixels
+    pub width: u32,
+    /// The height of the original image in pixels
+    pub height: u32,
+    /// The original image
+    #[serde(skip)]
+    pub image: &'a Page,
+}
+
+impl<'a> PlacedImage<'a> {
+    /// Combine a site with the image entry it refers to and the index of its page
+    pub fn new(site: &ImageSite, entry: &'a ImageEntry, page: Option<usize>) -> Self {
+        Self {
+            phys_pnr: site.page,
+            page,
+            img: site.img,
+            key: &entry.key,
+            site: site.site,
+            sel: site.sel,
+            width: entry.image.bit_width(),
+            height: entry.image.bit_height(),
+            image: &entry.image,
+        }
+    }
+}
+
 #[derive(Debug)]
 /// A parsed image
 pub struct Image<'a> {
@@ -1,6 +1,6 @@
 //! # (`0001`) The header of a document.
 
-use std::{borrow::Cow, convert::TryInto, fmt};
+use std::{borrow::Cow, convert::TryInto, fmt, io};
 
 use nom::{
     bytes::streaming::take,
@@ -93,6 +93,15 @@ pub struct DateTime {
     pub time: Time,
 }
 
+impl DateTime {
+    /// Return the big-endian bytes of the date and time
+    pub fn to_bytes(&self) -> [u8; 4] {
+        let [d0, d1] = self.date.0.to_be_bytes();
+        let [t0, t1] = self.time.0.to_be_bytes();
+        [d0, d1, t0, t1]
+    }
+}
+
 impl fmt::Display for DateTime {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{} {}", self.date, self.time)
@@ -137,6 +146,14 @@ impl Header<'_> {
             trail: Cow::Owned(self.trail.into_owned()),
         }
     }
+
+    /// Write the body of the `0001` chunk
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(&self.lead)?;
+        buf.write_all(&self.ctime.to_bytes())?;
+        buf.write_all(&self.mtime.to_bytes())?;
+        buf.write_all(&self.trail)
+    }
 }
 
 /// Parse the time as a 16 bit integer
@@ -2,7 +2,7 @@
 //!
 //! This module contains the datastructures and parsers for reading SDO files.
 
-use hcim::{ImageEntry, ImageSite};
+use hcim::{ImageEntry, ImageSite, PlacedImage};
 use log::info;
 use nom::{
     combinator::map,
@@ -18,18 +18,26 @@ use crate::{
     util::{Bytes16, Bytes32, FourCC},
 };
 use fmt::Debug;
-use std::{borrow::Cow, collections::BTreeMap, fmt};
+use std::{borrow::Cow, collections::BTreeMap, fmt, io};
 
 use self::{
-    container::SDocContainer, cset::CSet, hcim::Hcim, header::Header, pbuf::PBuf, sysp::SysP,
+    container::{write_chunk, SDocContainer},
+    cset::CSet,
+    hcim::Hcim,
+    header::Header,
+    pbuf::PBuf,
+    sysp::SysP,
     tebu::TeBu,
 };
 
 pub mod container;
 pub mod cset;
+pub mod edit;
 pub mod hcim;
 pub mod header;
 pub mod pbuf;
+pub mod salvage;
+pub mod sclb;
 pub mod sysp;
 pub mod tebu;
 
@@ -74,6 +82,8 @@ pub struct SDoc<'a> {
     pub hcim: Option<Hcim<'a>>,
     /// Other unparsed chunks
     pub other: BTreeMap<FourCC, Cow<'a, [u8]>>,
+    /// The order of the chunks in the container
+    pub order: Vec<FourCC>,
 }
 
 type NomErr<'a> = nom::error::Error<&'a [u8]>;
@@ -91,6 +101,7 @@ impl<'a> SDoc<'a> {
             tebu,
             hcim,
             other,
+            order,
         } = self;
         let header = header.into_owned();
         let cset = cset.into_owned();
@@ -108,6 +119,7 @@ impl<'a> SDoc<'a> {
             tebu,
             hcim,
             other,
+            order,
         }
     }
 
@@ -128,8 +140,10 @@ impl<'a> SDoc<'a> {
         let mut tebu = None;
         let mut hcim = None;
         let mut other = BTreeMap::new();
+        let mut order = Vec::with_capacity(container.chunks.len());
         for chunk in container.chunks {
             info!("Parsing {}", chunk.tag);
+            order.push(chunk.tag);
             match chunk.tag {
                 FourCC::_0001 => {
                     header = Some(Header::unpack(chunk)?);
@@ -163,8 +177,57 @@ impl<'a> SDoc<'a> {
             tebu: tebu.ok_or(Error::MissingTag(TeBu::TAG))?,
             hcim,
             other,
+            order,
         })
     }
+
+    /// The tags of all chunks in the order they are written
+    ///
+    /// This is [`SDoc::order`], followed by all present chunks that are missing from it.
+    pub fn chunk_order(&self) -> Vec<FourCC> {
+        let mut order = self.order.clone();
+        let known = [Header::TAG, CSet::TAG, SysP::TAG, PBuf::TAG, TeBu::TAG];
+        let hcim = self.hcim.as_ref().map(|_| Hcim::TAG);
+        let other = self.other.keys().copied();
+        for tag in known.iter().copied().chain(hcim).chain(other) {
+            if !order.contains(&tag) {
+                order.push(tag);
+            }
+        }
+        order
+    }
+
+    /// Write the document as an `sdoc0001` container
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(FourCC::SDOC.as_slice())?;
+        let mut data = Vec::new();
+        for tag in self.chunk_order() {
+            data.clear();
+            match tag {
+                Header::TAG => self.header.write_to(&mut data)?,
+                CSet::TAG => self.cset.write_to(&mut data)?,
+                SysP::TAG => self.sysp.write_to(&mut data)?,
+                PBuf::TAG => self.pbuf.write_to(&mut data)?,
+                TeBu::TAG => self.tebu.write_to(&mut data)?,
+                Hcim::TAG if self.hcim.is_some() => {
+                    self.hcim.as_ref().unwrap().write_to(&mut data)?
+                }
+                _ => match self.other.get(&tag) {
+                    Some(bytes) => data.extend_from_slice(bytes),
+                    None => continue,
+                },
+            }
+            write_chunk(buf, tag, &data)?;
+        }
+        Ok(())
+    }
+
+    /// Write the document to a new buffer
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.write_to(&mut buf)?;
+        Ok(buf)
+    }
 }
 
 /// Take the next 16 bytes
@@ -230,6 +293,24 @@ impl DocumentInfo {
     pub fn images(&self) -> impl Iterator<Item = &ImageEntry> {
         self.images.iter()
     }
+
+    /// Iterator over all image sites with the image they show
+    ///
+    /// The `pages` are those of the page buffer, they are used to find the index
+    /// of the page for every site. Sites that refer to a missing image are skipped.
+    pub fn placed_images<'a>(
+        &'a self,
+        sites: &'a [ImageSite],
+        pages: &'a [Option<pbuf::Page>],
+    ) -> impl Iterator<Item = PlacedImage<'a>> + 'a {
+        sites.iter().filter_map(move |site| {
+            let entry = self.images.get(usize::from(site.img))?;
+            let page = pages
+                .iter()
+                .position(|p| p.as_ref().is_some_and(|p| p.phys_pnr == site.page));
+            Some(PlacedImage::new(site, entry, page))
+        })
+    }
 }
 
 /// Common adjustments to the graphics state for rendering
@@ -258,3 +339,152 @@ pub trait GenerationContext {
         &self.document_info().fonts
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use nom::Finish;
+
+    use super::{
+        container::parse_sdoc0001_container,
+        hcim::{ImageArea, ImageEntry, ImageSite},
+        pbuf,
+        sysp::SysP,
+        DocumentInfo, SDoc,
+    };
+    use crate::{raster::Page, util::Bytes16};
+
+    fn chunk(out: &mut Vec<u8>, tag: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(data);
+    }
+
+    fn sample() -> Vec<u8> {
+        let mut out = b"sdoc".to_vec();
+
+        let mut header = vec![0; 128];
+        header[0x48..0x50].copy_from_slice(&[0x1A, 0x21, 0x5C, 0x00, 0x1A, 0x22, 0x60, 0x21]);
+        chunk(&mut out, b"0001", &header);
+
+        let mut cset = vec![0; 80];
+        cset[..7].copy_from_slice(b"ANTIKRO");
+        cset[10..17].copy_from_slice(b"GRIECH\0");
+        chunk(&mut out, b"cset", &cset);
+
+        let mut sysp = vec![0; 0x50];
+        for word in [5u16, 0, 10, 6, 0, 585, 5, 5, 561, 0x5800, 0x0013, 0x0302, 0] {
+            sysp.extend_from_slice(&word.to_be_bytes());
+        }
+        sysp.extend_from_slice(&1u32.to_be_bytes());
+        chunk(&mut out, b"sysp", &sysp);
+
+        let mut pbuf = Vec::new();
+        for word in [1u32, 0x22, 1] {
+            pbuf.extend_from_slice(&word.to_be_bytes());
+        }
+        pbuf.extend_from_slice(b"undeundeundeundeunde");
+        for word in [0u16, 1, 1, 561, 0, 585, 5, 5, 0, 0] {
+            pbuf.extend_from_slice(&word.to_be_bytes());
+        }
+        pbuf.extend_from_slice(&[0, 1]);
+        pbuf.extend_from_slice(&[0; 12]);
+        chunk(&mut out, b"pbuf", &pbuf);
+
+        let tebu: &[u8] = &[
+            0x00, 0x00, 0x00, 0x02, // lines_total
+            0x00, 0x00, 0x00, 0x04, 0xC1, 0x80, 0x00, 0x01, // page start (with F8)
+            0x00, 0x0A, 0x00, 0x0C, 0x04, 0x00, // line
+            0x8A, 0x41, // short char
+            0x60, 0x42, 0x40, 0x0C, // long char (cset 4, bold, underlined)
+            0x00, 0x43, 0x00, 0x03, // long char that would fit the short form
+            0x00, 0x05, 0x00, 0x04, 0xA0, 0x80, 0x00, 0x01, // page end
+            0x00, 0x00, 0x00, 0x00, // trailer
+        ];
+        chunk(&mut out, b"tebu", tebu);
+
+        let mut hcim = Vec::new();
+        hcim.extend_from_slice(&32u32.to_be_bytes());
+        hcim.extend_from_slice(&[0, 1, 0, 1]);
+        hcim.extend_from_slice(&[0; 8]);
+        for word in [0u16, 10, 20, 100, 50, 0, 0, 0, 100, 50, 0, 0, 0, 0, 0, 0] {
+            hcim.extend_from_slice(&word.to_be_bytes());
+        }
+        hcim.extend_from_slice(&8u32.to_be_bytes());
+        hcim.extend_from_slice(b"IMG\0");
+        chunk(&mut out, b"hcim", &hcim);
+
+        chunk(&mut out, b"pl01", &[1, 2, 3, 4]);
+        out
+    }
+
+    fn roundtrip(input: &[u8]) -> Vec<u8> {
+        let (_, container) = parse_sdoc0001_container::<nom::error::Error<&[u8]>>(input)
+            .finish()
+            .unwrap();
+        let sdoc = SDoc::unpack(container).unwrap();
+        sdoc.to_bytes().unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip_sample() {
+        let input = sample();
+        assert_eq!(roundtrip(&input), input);
+    }
+
+    #[test]
+    fn test_placed_images() {
+        let area = |x, y, w, h| ImageArea { x, y, w, h };
+        let site = |page, img| ImageSite {
+            page,
+            site: area(10, 20, 100, 50),
+            _5: 0,
+            sel: area(0, 0, 64, 32),
+            _A: 0,
+            _B: 0,
+            _C: 0,
+            img,
+            _E: 0,
+            _F: Bytes16(0),
+        };
+        let images = vec![ImageEntry {
+            key: String::from("IMG"),
+            image: Page::new(64, 32),
+        }];
+        let info = DocumentInfo::new(Default::default(), images);
+        let sites = [site(2, 0), site(3, 1), site(5, 0)];
+        let sysp = SysP::default();
+        let pages = [
+            Some(pbuf::Page::from_sysp(0, &sysp)),
+            None,
+            Some(pbuf::Page::from_sysp(1, &sysp)),
+        ];
+
+        let placed: Vec<_> = info.placed_images(&sites, &pages).collect();
+        assert_eq!(placed.len(), 2);
+        assert_eq!((placed[0].phys_pnr, placed[0].key), (2, "IMG"));
+        assert_eq!((placed[0].page, placed[1].page), (Some(2), None));
+        assert_eq!((placed[0].width, placed[0].height), (64, 32));
+        assert_eq!(placed[0].site.w, 100);
+    }
+
+    #[test]
+    #[ignore = "needs Signum! documents (*.SDO) in docs/_examples"]
+    fn test_roundtrip_examples() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../docs/_examples");
+        let mut count = 0;
+        for entry in std::fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            let is_sdo = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("sdo"));
+            if is_sdo {
+                let input = std::fs::read(&path).unwrap();
+                assert!(roundtrip(&input) == input, "{} differs", path.display());
+                count += 1;
+            }
+        }
+        assert!(count > 0, "No SDO files found in docs/_examples");
+    }
+}
@@ -1,6 +1,6 @@
 //! # (`pbuf`) The page buffer
 
-use std::borrow::Cow;
+use std::{borrow::Cow, convert::TryFrom, io};
 
 use nom::{
     bytes::{complete::tag, streaming::take},
@@ -12,7 +12,7 @@ use serde::Serialize;
 
 use crate::util::{Bytes16, FourCC};
 
-use super::bytes16;
+use super::{bytes16, sysp::SysP};
 
 #[derive(Debug, Serialize)]
 /// The page buffer
@@ -52,6 +52,28 @@ impl PBuf<'_> {
     pub fn page_at(&self, index: usize) -> Option<&Page> {
         self.pages[index].as_ref().map(|(p, _)| p)
     }
+
+    /// Write the body of the `pbuf` chunk
+    ///
+    /// Pages are written in the order of their index.
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(&self.page_count.to_be_bytes())?;
+        buf.write_all(&self.elem_len.to_be_bytes())?;
+        buf.write_all(&self.first_page_nr.to_be_bytes())?;
+        for _ in 0..5 {
+            buf.write_all(b"unde")?;
+        }
+        for (index, entry) in self.pages.iter().enumerate() {
+            if let Some((page, rest)) = entry {
+                let index = u16::try_from(index)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "page index"))?;
+                buf.write_all(&index.to_be_bytes())?;
+                page.write_to(buf)?;
+                buf.write_all(rest)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -74,6 +96,14 @@ impl PageFormat {
     pub fn width(&self) -> u16 {
         self.right - self.left
     }
+
+    fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(&self.length.to_be_bytes())?;
+        buf.write_all(&self.left.to_be_bytes())?;
+        buf.write_all(&self.right.to_be_bytes())?;
+        buf.write_all(&self.header.to_be_bytes())?;
+        buf.write_all(&self.footer.to_be_bytes())
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -96,6 +126,37 @@ pub struct Page {
     pub vis_pnr: u8,
 }
 
+impl Page {
+    /// Create a page with the given index and the page format of a [`SysP`]
+    pub fn from_sysp(index: u16, sysp: &SysP) -> Self {
+        let pnr = index + 1;
+        Self {
+            phys_pnr: pnr,
+            log_pnr: pnr,
+            format: PageFormat {
+                length: sysp.page_length,
+                left: sysp.margin_left,
+                right: sysp.margin_right,
+                header: sysp.header,
+                footer: sysp.footer,
+            },
+            numbpos: Bytes16(0),
+            kapitel: Bytes16(0),
+            intern: 0,
+            vis_pnr: pnr as u8,
+        }
+    }
+
+    fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(&self.phys_pnr.to_be_bytes())?;
+        buf.write_all(&self.log_pnr.to_be_bytes())?;
+        self.format.write_to(buf)?;
+        buf.write_all(&self.numbpos.to_bytes())?;
+        buf.write_all(&self.kapitel.to_bytes())?;
+        buf.write_all(&[self.intern, self.vis_pnr])
+    }
+}
+
 /// Page header with data
 pub type PageData<'a> = (Page, Cow<'a, [u8]>);
 
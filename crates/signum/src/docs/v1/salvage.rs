@@ -0,0 +1,447 @@
+//! # Salvaging damaged documents
+//!
+//! Most of the `sdoc0001` format consists of length-prefixed sections, so a single
+//! damaged length makes everything after it unreadable for [`parse_sdoc0001_container`].
+//! This module contains a more forgiving parser, that skips over damaged bytes,
+//! looks for the next plausible chunk tag or `tebu` line header and continues from
+//! there. It returns a complete [`SDoc`] (using defaults for anything that could not
+//! be recovered) together with a [`SalvageReport`].
+//!
+//! [`parse_sdoc0001_container`]: super::container::parse_sdoc0001_container
+
+use std::{borrow::Cow, collections::BTreeMap, convert::TryInto, ops::Range};
+
+use log::warn;
+use nom::{combinator::verify, number::complete::be_u16, sequence::tuple};
+
+use crate::util::{Bytes16, FourCC};
+
+use super::{
+    container::Chunk as RawChunk,
+    cset::CSet,
+    hcim::Hcim,
+    header::{Date, DateTime, Header, Time},
+    pbuf::{PBuf, Page, PageFormat},
+    sysp::SysP,
+    tebu::{parse_line, Flags, Line, PageText, TeBu, TextBufferHeader},
+    Chunk, SDoc,
+};
+
+/// The chunk tags that are used to resynchronize after damaged bytes
+const KNOWN_TAGS: [FourCC; 8] = [
+    FourCC::_0001,
+    FourCC::_CSET,
+    FourCC::_SYSP,
+    FourCC::_PBUF,
+    FourCC::_TEBU,
+    FourCC::_HCIM,
+    FourCC::_PL01,
+    FourCC::_SYP2,
+];
+
+/// A range of bytes that was skipped while salvaging a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    /// The chunk that contained the bytes, if any
+    pub chunk: Option<FourCC>,
+    /// The byte offsets within the file
+    pub range: Range<usize>,
+}
+
+/// Information on the damage found while salvaging a document
+#[derive(Debug, Default, Clone)]
+pub struct SalvageReport {
+    /// The byte ranges that were skipped
+    pub skipped: Vec<Skipped>,
+    /// Chunks with a length that did not match the file, cut off at the next chunk tag
+    pub truncated: Vec<FourCC>,
+    /// Chunks that could not be recovered and were replaced with defaults
+    pub missing: Vec<FourCC>,
+    /// Pages of the text buffer that had no page buffer entry and were given a default one
+    pub synthesized_pages: Vec<u16>,
+}
+
+impl SalvageReport {
+    /// Returns `true` if no damage was found
+    pub fn is_clean(&self) -> bool {
+        self.skipped.is_empty()
+            && self.truncated.is_empty()
+            && self.missing.is_empty()
+            && self.synthesized_pages.is_empty()
+    }
+
+    fn skip(&mut self, chunk: Option<FourCC>, range: Range<usize>) {
+        if !range.is_empty() {
+            warn!(
+                "Skipping bytes {}..{} ({:?})",
+                range.start, range.end, chunk
+            );
+            self.skipped.push(Skipped { chunk, range });
+        }
+    }
+}
+
+/// A salvaged document
+#[derive(Debug)]
+pub struct Salvaged<'a> {
+    /// The (partial) document
+    pub sdoc: SDoc<'a>,
+    /// What had to be skipped or replaced
+    pub report: SalvageReport,
+}
+
+fn be_u32_at(input: &[u8], pos: usize) -> Option<u32> {
+    let bytes = input.get(pos..pos + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn is_plausible_tag(bytes: &[u8]) -> bool {
+    bytes.len() == 4
+        && bytes
+            .iter()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+}
+
+fn is_known_tag_at(input: &[u8], pos: usize) -> bool {
+    let tag = input.get(pos..pos + 4);
+    KNOWN_TAGS.iter().any(|t| Some(t.as_slice()) == tag)
+}
+
+/// Check whether there is a known chunk header at `pos` with a length that fits the input
+fn is_known_chunk_at(input: &[u8], pos: usize) -> bool {
+    let fits = be_u32_at(input, pos + 4).is_some_and(|len| (len as usize) <= input.len() - pos - 8);
+    fits && is_known_tag_at(input, pos)
+}
+
+fn find_known_chunk(input: &[u8], from: usize) -> Option<usize> {
+    (from..input.len()).find(|&pos| is_known_chunk_at(input, pos))
+}
+
+/// Split the container into chunks, together with the file offset of their data
+fn salvage_chunks<'a>(input: &'a [u8], report: &mut SalvageReport) -> Vec<(usize, RawChunk<'a>)> {
+    let mut chunks = Vec::new();
+    let mut pos = if input.starts_with(FourCC::SDOC.as_slice()) {
+        4
+    } else {
+        let start = find_known_chunk(input, 0).unwrap_or(input.len());
+        report.skip(None, 0..start);
+        start
+    };
+    while pos < input.len() {
+        let header = input.get(pos..pos + 4).zip(be_u32_at(input, pos + 4));
+        let (tag, len) = match header {
+            Some((tag, len)) if is_plausible_tag(tag) => (tag, len as usize),
+            _ => {
+                let next = find_known_chunk(input, pos + 1).unwrap_or(input.len());
+                report.skip(None, pos..next);
+                pos = next;
+                continue;
+            }
+        };
+        let tag = FourCC::new(tag.try_into().unwrap());
+        let start = pos + 8;
+        let end = start.saturating_add(len);
+        let end = if end == input.len() || (end < input.len() && is_known_tag_at(input, end)) {
+            end
+        } else {
+            warn!("Chunk '{}' at {} has a damaged length ({})", tag, pos, len);
+            report.truncated.push(tag);
+            find_known_chunk(input, start).unwrap_or(input.len())
+        };
+        chunks.push((start, RawChunk::new(tag, &input[start..end])));
+        pos = end;
+    }
+    chunks
+}
+
+/// Parse the `tebu` line at `pos`, if its content fills its length prefix
+///
+/// Returns the position of the next line, the vertical skip and the line.
+fn line_at(buf: &[u8], pos: usize, end: usize) -> Option<(usize, u16, Line)> {
+    type E<'a> = nom::error::Error<&'a [u8]>;
+    let input = buf.get(pos..end)?;
+    let mut header = tuple((be_u16::<_, E>, verify(be_u16, |l| *l < 0x8000)));
+    let (rest, (skip, len)) = header(input).ok()?;
+    let data = rest.get(..usize::from(len))?;
+    let (tail, line) = parse_line::<E>(data).ok()?;
+    tail.is_empty().then(|| (pos + 4 + data.len(), skip, line))
+}
+
+/// Check whether `pos` is a good place to continue after damaged bytes
+///
+/// This is the case if there's a valid line at `pos` that is followed by
+/// another valid line or the end of the buffer.
+fn is_resync_point(buf: &[u8], pos: usize, end: usize) -> bool {
+    match line_at(buf, pos, end) {
+        Some((next, _, _)) => next == end || line_at(buf, next, end).is_some(),
+        None => false,
+    }
+}
+
+/// Salvage the text buffer line by line
+fn salvage_tebu(buf: &[u8], base: usize, report: &mut SalvageReport) -> TeBu {
+    if let Ok(tebu) = TeBu::unpack(RawChunk::new(TeBu::TAG, buf)) {
+        return tebu;
+    }
+
+    let lines_total = be_u32_at(buf, 0).unwrap_or(0);
+    let end = buf.len().saturating_sub(4).max(4.min(buf.len()));
+    let trailer = be_u32_at(buf, end).unwrap_or(0);
+
+    let mut pages: Vec<PageText> = Vec::new();
+    let mut current: Option<PageText> = None;
+    let mut pos = 4.min(end);
+    while pos < end {
+        let (skip, line) = match line_at(buf, pos, end) {
+            Some((next, skip, line)) => {
+                pos = next;
+                (skip, line)
+            }
+            None => {
+                let next = (pos + 2..end)
+                    .step_by(2)
+                    .find(|&p| is_resync_point(buf, p, end))
+                    .unwrap_or(end);
+                report.skip(Some(TeBu::TAG), base + pos..base + next);
+                pos = next;
+                continue;
+            }
+        };
+        if line.flags.contains(Flags::PAGE | Flags::PNEW) {
+            pages.extend(current.take());
+            current = Some(PageText {
+                index: line.extra,
+                skip,
+                flags: line.flags,
+                rskip: 0,
+                rflags: PageText::END_FLAGS,
+                content: Vec::new(),
+            });
+        } else if line.flags.contains(Flags::PAGE | Flags::PEND) {
+            if let Some(mut page) = current.take() {
+                page.rskip = skip;
+                page.rflags = line.flags;
+                pages.push(page);
+            }
+        } else if !line.flags.contains(Flags::PAGE) {
+            let page = current.get_or_insert_with(|| PageText {
+                index: pages.last().map_or(1, |p| p.index + 1),
+                skip: 0,
+                flags: PageText::START_FLAGS,
+                rskip: 0,
+                rflags: PageText::END_FLAGS,
+                content: Vec::new(),
+            });
+            page.content.push((skip, line));
+        }
+    }
+    pages.extend(current);
+
+    TeBu {
+        header: TextBufferHeader { lines_total },
+        pages,
+        trailer,
+    }
+}
+
+fn empty_header() -> Header<'static> {
+    let datetime = DateTime {
+        date: Date(0x0021),
+        time: Time(0),
+    };
+    Header {
+        lead: Cow::Owned(vec![0; 0x48]),
+        ctime: datetime,
+        mtime: datetime,
+        trail: Cow::Owned(vec![0; 0x30]),
+    }
+}
+
+fn empty_pbuf() -> PBuf<'static> {
+    PBuf {
+        page_count: 0,
+        elem_len: 0x22,
+        first_page_nr: 1,
+        pages: Vec::new(),
+    }
+}
+
+/// Add page buffer entries for all text pages that don't have one
+fn fill_missing_pages(pbuf: &mut PBuf, tebu: &TeBu, sysp: &SysP, report: &mut SalvageReport) {
+    for text in &tebu.pages {
+        let index = usize::from(text.index);
+        if pbuf.pages.get(index).is_some_and(Option::is_some) {
+            continue;
+        }
+        if pbuf.pages.len() <= index {
+            pbuf.pages.resize_with(index + 1, || None);
+        }
+        let page = Page {
+            phys_pnr: text.index,
+            log_pnr: text.index,
+            format: PageFormat {
+                length: sysp.page_length,
+                left: sysp.margin_left,
+                right: sysp.margin_right,
+                header: sysp.header,
+                footer: sysp.footer,
+            },
+            numbpos: Bytes16(0),
+            kapitel: Bytes16(0),
+            intern: 0,
+            vis_pnr: text.index as u8,
+        };
+        pbuf.pages[index] = Some((page, Cow::Owned(vec![0; 12])));
+        pbuf.page_count += 1;
+        report.synthesized_pages.push(text.index);
+    }
+}
+
+/// Parse as much of a (possibly damaged) Signum! document as possible
+pub fn salvage_sdoc0001(input: &[u8]) -> Salvaged<'_> {
+    let mut report = SalvageReport::default();
+    let chunks = salvage_chunks(input, &mut report);
+
+    let mut header = None;
+    let mut cset = None;
+    let mut sysp = None;
+    let mut pbuf = None;
+    let mut tebu = None;
+    let mut hcim = None;
+    let mut other = BTreeMap::new();
+    let mut order = Vec::with_capacity(chunks.len());
+
+    for (start, chunk) in chunks {
+        let tag = chunk.tag;
+        let range = start..start + chunk.buf.0.len();
+        order.push(tag);
+        let ok = match tag {
+            Header::TAG => Header::unpack(chunk).map(|c| header = Some(c)),
+            CSet::TAG => CSet::unpack(chunk).map(|c| cset = Some(c)),
+            SysP::TAG => SysP::unpack(chunk).map(|c| sysp = Some(c)),
+            PBuf::TAG => PBuf::unpack(chunk).map(|c| pbuf = Some(c)),
+            Hcim::TAG => Hcim::unpack(chunk).map(|c| hcim = Some(c)),
+            TeBu::TAG => {
+                tebu = Some(salvage_tebu(chunk.buf.0, start, &mut report));
+                Ok(())
+            }
+            _ => {
+                other.insert(tag, Cow::Borrowed(chunk.buf.0));
+                Ok(())
+            }
+        };
+        if let Err(e) = ok {
+            warn!("Failed to parse '{}' chunk: {:?}", tag, e);
+            report.skip(Some(tag), range);
+        }
+    }
+
+    let mut missing = |tag: FourCC| {
+        if !report.missing.contains(&tag) {
+            report.missing.push(tag);
+        }
+    };
+    let header = header.unwrap_or_else(|| {
+        missing(Header::TAG);
+        empty_header()
+    });
+    let cset = cset.unwrap_or_else(|| {
+        missing(CSet::TAG);
+        CSet::new(Vec::new())
+    });
+    let sysp = sysp.unwrap_or_else(|| {
+        missing(SysP::TAG);
+        SysP::default()
+    });
+    let mut pbuf = pbuf.unwrap_or_else(|| {
+        missing(PBuf::TAG);
+        empty_pbuf()
+    });
+    let tebu = tebu.unwrap_or_else(|| {
+        missing(TeBu::TAG);
+        TeBu::default()
+    });
+    fill_missing_pages(&mut pbuf, &tebu, &sysp, &mut report);
+
+    Salvaged {
+        sdoc: SDoc {
+            header,
+            cset,
+            sysp,
+            pbuf,
+            tebu,
+            hcim,
+            other,
+            order,
+        },
+        report,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{line_at, salvage_sdoc0001};
+    use crate::util::FourCC;
+
+    fn chunk(out: &mut Vec<u8>, tag: &[u8; 4], len: u32, data: &[u8]) {
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&len.to_be_bytes());
+        out.extend_from_slice(data);
+    }
+
+    const TEBU: &[u8] = &[
+        0x00, 0x00, 0x00, 0x04, // lines_total
+        0x00, 0x00, 0x00, 0x04, 0xC0, 0x80, 0x00, 0x01, // page start
+        0x00, 0x0A, 0x00, 0x06, 0x04, 0x00, 0x8A, 0x41, 0x82, 0x42, // line
+        0x00, 0x0A, 0x7F, 0xF0, 0x04, 0x00, 0x8A, 0x43, 0x82, 0x44, // damaged line
+        0x00, 0x0A, 0x00, 0x06, 0x04, 0x00, 0x8A, 0x45, 0x82, 0x46, // line
+        0x00, 0x05, 0x00, 0x04, 0xA0, 0x80, 0x00, 0x01, // page end
+        0x00, 0x00, 0x00, 0x00, // trailer
+    ];
+
+    #[test]
+    fn test_salvage_tebu() {
+        let mut input = b"sdoc".to_vec();
+        chunk(&mut input, b"cset", 10, b"ANTIKRO\0\0\0");
+        chunk(&mut input, b"tebu", TEBU.len() as u32, TEBU);
+        chunk(&mut input, b"pl01", 0x1000, &[1, 2, 3, 4]);
+
+        let salvaged = salvage_sdoc0001(&input);
+        let report = &salvaged.report;
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].chunk, Some(FourCC::_TEBU));
+        assert_eq!(report.skipped[0].range, 52..62);
+        assert_eq!(report.truncated, [FourCC::_PL01]);
+        assert_eq!(report.synthesized_pages, [1]);
+
+        let sdoc = &salvaged.sdoc;
+        assert_eq!(sdoc.cset.names[0].as_ref(), "ANTIKRO");
+        assert_eq!(sdoc.tebu.pages.len(), 1);
+        let page = &sdoc.tebu.pages[0];
+        assert_eq!((page.index, page.rskip), (1, 5));
+        let text: Vec<u8> = page
+            .content
+            .iter()
+            .flat_map(|(_, line)| line.data.iter().map(|k| k.cval))
+            .collect();
+        assert_eq!(text, b"ABEF");
+        assert!(sdoc.pbuf.page_at(1).is_some());
+        assert_eq!(sdoc.other[&FourCC::_PL01].as_ref(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_line_at() {
+        let buf = [
+            0x00, 0x0A, 0x00, 0x06, 0x04, 0x00, 0x00, 0x41, 0x00, 0x05, // long form char
+            0x00, 0x0A, 0x00, 0x06, 0x04, 0x00, // truncated line
+        ];
+        let (next, skip, line) = line_at(&buf, 0, buf.len()).unwrap();
+        assert_eq!((next, skip), (10, 10));
+        assert_eq!((line.data[0].cval, line.data[0].offset), (0x41, 5));
+        assert!(line_at(&buf, next, buf.len()).is_none());
+
+        // a trailing byte that is not part of any character
+        let odd = [0x00, 0x0A, 0x00, 0x05, 0x04, 0x00, 0x8A, 0x41, 0x00];
+        assert!(line_at(&odd, 0, odd.len()).is_none());
+    }
+}
@@ -0,0 +1,197 @@
+//! # (`sclb`) The clipboard file
+//!
+//! Signum! stores text that was cut or copied to disk in `*.CLB` files. These use
+//! the same container as documents, with an `sclb` magic instead of `sdoc`:
+//!
+//! - `cset`: the character sets referenced by the lines
+//! - `tebu`: a text buffer header (the number of lines), followed by the lines
+//!   of the fragment, in the same encoding as within a page of a document,
+//!   and an optional 32 bit trailer
+//!
+//! Page start and end lines, if present, are skipped.
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take},
+    combinator::eof,
+    error::{context, ContextError, ParseError},
+    multi::many_till,
+    sequence::{preceded, terminated},
+    Finish, IResult,
+};
+
+use super::{
+    container::{parse_chunk, Chunk},
+    cset::CSet,
+    tebu::{parse_buffered_line, parse_tebu_header, Flags, Line, PageText, TeBu, TextBufferHeader},
+    Chunk as _, Error,
+};
+use crate::util::FourCC;
+
+/// A Signum! clipboard
+#[derive(Debug)]
+pub struct Clipboard<'a> {
+    /// The character sets
+    pub cset: CSet<'a>,
+    /// The lines and the vertical distance to the previous line
+    pub lines: Vec<(u16, Line)>,
+    /// Other unparsed chunks
+    pub other: BTreeMap<FourCC, Cow<'a, [u8]>>,
+}
+
+/// Parse the lines of the `tebu` chunk of a clipboard
+pub fn parse_sclb_lines<'a, E>(input: &'a [u8]) -> IResult<&'a [u8], Vec<(u16, Line)>, E>
+where
+    E: ParseError<&'a [u8]> + ContextError<&'a [u8]>,
+{
+    let (input, _header) = context("tebu_header", parse_tebu_header)(input)?;
+    let (input, (lines, _)) = many_till(
+        context("line", parse_buffered_line),
+        alt((eof, terminated(take(4usize), eof))),
+    )(input)?;
+    let lines = lines
+        .into_iter()
+        .filter(|(_, line)| !line.flags.contains(Flags::PAGE))
+        .collect();
+    Ok((input, lines))
+}
+
+/// Parse a Signum! clipboard file
+pub fn parse_sclb<'a, E>(input: &'a [u8]) -> IResult<&'a [u8], Vec<Chunk<'a>>, E>
+where
+    E: ParseError<&'a [u8]>,
+{
+    let (input, (chunks, _)) = preceded(tag(b"sclb"), many_till(parse_chunk, eof))(input)?;
+    Ok((input, chunks))
+}
+
+impl<'a> Clipboard<'a> {
+    /// Unpack a clipboard from its chunks
+    pub fn unpack(chunks: Vec<Chunk<'a>>) -> Result<Self, Error> {
+        let mut cset = None;
+        let mut lines = None;
+        let mut other = BTreeMap::new();
+        for chunk in chunks {
+            match chunk.tag {
+                CSet::TAG => cset = Some(CSet::unpack(chunk)?),
+                TeBu::TAG => {
+                    let chunk_len = chunk.buf.0.len();
+                    let (_, l) = parse_sclb_lines::<nom::error::Error<&'a [u8]>>(chunk.buf.0)
+                        .finish()
+                        .map_err(|e| Error::Nom {
+                            chunk_tag: TeBu::TAG,
+                            code: e.code,
+                            offset: chunk_len - e.input.len(),
+                        })?;
+                    lines = Some(l);
+                }
+                _ => {
+                    other.insert(chunk.tag, Cow::Borrowed(chunk.buf.0));
+                }
+            }
+        }
+        Ok(Self {
+            cset: cset.ok_or(Error::MissingTag(CSet::TAG))?,
+            lines: lines.ok_or(Error::MissingTag(TeBu::TAG))?,
+            other,
+        })
+    }
+
+    /// Parse a clipboard file
+    pub fn parse(input: &'a [u8]) -> Result<Self, Error> {
+        let (_, chunks) = parse_sclb::<nom::error::Error<&'a [u8]>>(input)
+            .finish()
+            .map_err(|e| Error::Nom {
+                chunk_tag: FourCC::SCLB,
+                code: e.code,
+                offset: input.len() - e.input.len(),
+            })?;
+        Self::unpack(chunks)
+    }
+
+    /// Return the lines as a text buffer with a single page
+    pub fn to_tebu(&self) -> TeBu {
+        let content: Vec<_> = self.lines.to_vec();
+        TeBu {
+            header: TextBufferHeader {
+                lines_total: content.len() as u32 + 2,
+            },
+            pages: vec![PageText {
+                index: 0,
+                skip: 0,
+                flags: PageText::START_FLAGS,
+                rskip: 0,
+                rflags: PageText::END_FLAGS,
+                content,
+            }],
+            trailer: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::ErrorKind;
+
+    use super::{Clipboard, Error};
+    use crate::util::FourCC;
+
+    fn chunk(out: &mut Vec<u8>, tag: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(data);
+    }
+
+    #[test]
+    fn test_parse_sclb() {
+        let mut input = b"sclb".to_vec();
+        let mut cset = b"ANTIKRO".to_vec();
+        cset.resize(40, 0);
+        chunk(&mut input, b"cset", &cset);
+        #[rustfmt::skip]
+        let tebu = [
+            0, 0, 0, 1, // lines_total
+            0x00, 0x0A, 0x00, 0x06, // skip, len
+            0x04, 0x00, // flags: LINE
+            0x84, 0x21, 0x98, 0x22, // two short chars
+        ];
+        chunk(&mut input, b"tebu", &tebu);
+
+        let clb = Clipboard::parse(&input).unwrap();
+        assert_eq!(clb.cset.names[0].as_ref(), "ANTIKRO");
+        assert_eq!(clb.lines.len(), 1);
+        let (skip, line) = &clb.lines[0];
+        assert_eq!(*skip, 10);
+        let chars: Vec<_> = line.data.iter().map(|k| (k.cval, k.offset)).collect();
+        assert_eq!(chars, [(0x21, 2), (0x22, 12)]);
+    }
+
+    #[test]
+    fn test_parse_sclb_error() {
+        let err = Clipboard::parse(b"sdoc").err();
+        assert!(matches!(
+            err,
+            Some(Error::Nom {
+                chunk_tag: FourCC::SCLB,
+                code: ErrorKind::Tag,
+                offset: 0,
+            })
+        ));
+
+        let mut input = b"sclb".to_vec();
+        input.extend_from_slice(b"cset");
+        input.extend_from_slice(&40u32.to_be_bytes());
+        input.extend_from_slice(b"ANTIKRO");
+        let err = Clipboard::parse(&input).err();
+        assert!(matches!(
+            err,
+            Some(Error::Nom {
+                chunk_tag: FourCC::SCLB,
+                code: ErrorKind::Eof,
+                offset: 12,
+            })
+        ));
+    }
+}
@@ -1,6 +1,10 @@
 //! # (`sysp`) The system parameters
 
-use nom::{bytes::streaming::take, error::ParseError, number::complete::be_u16, IResult};
+use std::io;
+
+use nom::{
+    bytes::streaming::take, combinator::rest, error::ParseError, number::complete::be_u16, IResult,
+};
 use serde::Serialize;
 
 use crate::util::{Bytes16, Bytes32, FourCC};
@@ -10,6 +14,9 @@ use super::{bytes16, bytes32};
 #[derive(Debug, Serialize)]
 /// The system parameters chunk
 pub struct SysP {
+    /// Leading bytes, unknown
+    #[serde(skip)]
+    pub lead: Vec<u8>,
     /// Width of a space
     pub space_width: u16,
     /// ???
@@ -36,6 +43,56 @@ pub struct SysP {
     _opts_2: Bytes16,
     _opts_3: Bytes16,
     _opts_4: Bytes32,
+
+    /// Trailing bytes, usually empty
+    #[serde(skip)]
+    pub trail: Vec<u8>,
+}
+
+impl Default for SysP {
+    /// The standard page format of a new document
+    fn default() -> Self {
+        Self {
+            lead: vec![0; 0x50],
+            space_width: 0,
+            letter_spacing: 0,
+            line_distance: 0,
+            index_distance: 0,
+            margin_left: 0,
+            margin_right: 585,
+            header: 5,
+            footer: 5,
+            page_length: 561,
+            page_numbering: PageNumbering::from_bits(0x5800),
+            format_options: Bytes16(0),
+            _opts_2: Bytes16(0x302),
+            _opts_3: Bytes16(0),
+            _opts_4: Bytes32(1),
+            trail: Vec::new(),
+        }
+    }
+}
+
+impl SysP {
+    /// Write the body of the `sysp` chunk
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(&self.lead)?;
+        buf.write_all(&self.space_width.to_be_bytes())?;
+        buf.write_all(&self.letter_spacing.to_be_bytes())?;
+        buf.write_all(&self.line_distance.to_be_bytes())?;
+        buf.write_all(&self.index_distance.to_be_bytes())?;
+        buf.write_all(&self.margin_left.to_be_bytes())?;
+        buf.write_all(&self.margin_right.to_be_bytes())?;
+        buf.write_all(&self.header.to_be_bytes())?;
+        buf.write_all(&self.footer.to_be_bytes())?;
+        buf.write_all(&self.page_length.to_be_bytes())?;
+        buf.write_all(&self.page_numbering.to_bits().to_be_bytes())?;
+        buf.write_all(&self.format_options.to_bytes())?;
+        buf.write_all(&self._opts_2.to_bytes())?;
+        buf.write_all(&self._opts_3.to_bytes())?;
+        buf.write_all(&self._opts_4.0.to_be_bytes())?;
+        buf.write_all(&self.trail)
+    }
 }
 
 /// Position of the page number
@@ -91,11 +148,38 @@ pub struct PageNumbering {
     pos_y: PageNumberVertical,
     pos_x: PageNumberPosition,
     chset: u8,
+    #[serde(skip)]
+    unknown: u16,
+}
+
+impl PageNumbering {
+    const UNKNOWN_MASK: u16 = 0x07F0;
+
+    fn from_bits(value: u16) -> Self {
+        Self {
+            enabled: value & 0x8000 > 0,
+            pos_y: PageNumberVertical::from_bits(value),
+            pos_x: PageNumberPosition::from_bits(value),
+            chset: (value & 0b1111) as u8,
+            unknown: value & Self::UNKNOWN_MASK,
+        }
+    }
+
+    /// Return the packed representation of the page numbering options
+    pub fn to_bits(&self) -> u16 {
+        let enabled = if self.enabled { 0x8000 } else { 0 };
+        let pos_y = match self.pos_y {
+            PageNumberVertical::Top => 0x4000,
+            PageNumberVertical::Bottom => 0,
+        };
+        let pos_x = (self.pos_x as u16) << 11;
+        enabled | pos_y | pos_x | self.unknown | u16::from(self.chset & 0b1111)
+    }
 }
 
 /// Parse the `sysp` chunk
 pub fn parse_sysp<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], SysP, E> {
-    let (input, _) = take(0x50usize)(input)?;
+    let (input, lead) = take(0x50usize)(input)?;
 
     // Standartseitenformat
     let (input, space_width) = be_u16(input)?; // Leerzeichenbreite
@@ -112,10 +196,12 @@ pub fn parse_sysp<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [
     let (input, opts_2) = bytes16(input)?; // H302 == trennen
     let (input, opts_3) = bytes16(input)?; // 0 == randausgleiche und Sperren
     let (input, opts_4) = bytes32(input)?; // 1 == nicht einrücken, absatzabstand mitkorrigieren
+    let (input, trail) = rest(input)?;
 
     Ok((
         input,
         SysP {
+            lead: lead.to_vec(),
             space_width,
             letter_spacing,
             line_distance,
@@ -125,16 +211,12 @@ pub fn parse_sysp<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [
             header,
             footer,
             page_length,
-            page_numbering: PageNumbering {
-                enabled: page_numbering.0 & 0x8000 > 0,
-                pos_y: PageNumberVertical::from_bits(page_numbering.0),
-                pos_x: PageNumberPosition::from_bits(page_numbering.0),
-                chset: (page_numbering.0 & 0b1111) as u8,
-            },
+            page_numbering: PageNumbering::from_bits(page_numbering.0),
             format_options,
             _opts_2: opts_2,
             _opts_3: opts_3,
             _opts_4: opts_4,
+            trail: trail.to_vec(),
         },
     ))
 }
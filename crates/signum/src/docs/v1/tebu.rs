@@ -1,4 +1,6 @@
 //! # (`tebu`) The text buffer
+use std::{convert::TryFrom, io};
+
 use bitflags::bitflags;
 use bstr::ByteSlice;
 use log::info;
@@ -99,6 +101,45 @@ pub struct Char {
     pub offset: u16,
     /// The style of the character
     pub style: Style,
+    /// Whether the character was stored in the long form, even if the short form fits
+    #[serde(skip)]
+    pub long: bool,
+}
+
+impl Char {
+    fn is_short(&self) -> bool {
+        !self.long && self.style.bits() & 0xF83E == 0 && self.cset < 4 && self.offset < 0x40
+    }
+
+    /// The number of bytes of the encoded character
+    pub fn encoded_len(&self) -> usize {
+        if self.is_short() {
+            2
+        } else {
+            4
+        }
+    }
+
+    /// Write the encoded character
+    ///
+    /// Characters without style, in one of the first four character sets and with
+    /// an offset below 64 use the short (one word) form, unless [`Char::long`] is set.
+    /// All others use the long (two word) form.
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        let cval = u16::from(self.cval & 0x7F);
+        let cset = u16::from(self.cset & 0x07);
+        let style = self.style.bits() & 0xF83E;
+        if self.is_short() {
+            let cmd = 0x8000 | (self.offset << 9) | (cset << 7) | cval;
+            buf.write_all(&cmd.to_be_bytes())
+        } else {
+            let val = (style & 0x003E) | (cset >> 2);
+            let cmd = (val << 9) | ((cset & 0b11) << 7) | cval;
+            let extra = (style & 0xF800) | (self.offset & 0x07FF);
+            buf.write_all(&cmd.to_be_bytes())?;
+            buf.write_all(&extra.to_be_bytes())
+        }
+    }
 }
 
 /// The text buffer header
@@ -140,6 +181,15 @@ impl TeBu {
     pub fn use_matrix(&self) -> UseMatrix {
         UseMatrix::from(&self.pages[..])
     }
+
+    /// Write the body of the `tebu` chunk
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(&self.header.lines_total.to_be_bytes())?;
+        for page in &self.pages {
+            page.write_to(buf)?;
+        }
+        buf.write_all(&self.trailer.to_be_bytes())
+    }
 }
 
 #[derive(Clone)]
@@ -189,6 +239,7 @@ fn te<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], Char, E
                 cset,
                 offset: val,
                 style: Style::default(),
+                long: false,
             },
         ))
     } else {
@@ -202,6 +253,7 @@ fn te<'a, E: ParseError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], Char, E
             cset,
             offset,
             style,
+            long: true,
         };
         Ok((input, char))
     }
@@ -218,7 +270,7 @@ pub struct LineBuf<'a> {
 
 bitflags! {
     /// The flags that of a line
-    #[derive(Serialize, Debug)]
+    #[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
     #[serde(transparent)]
     pub struct Flags: u16 {
         /// ???
@@ -256,7 +308,7 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 /// Structure that holds a parsed line
 pub struct Line {
     len: usize,
@@ -269,6 +321,37 @@ pub struct Line {
 }
 
 impl Line {
+    /// Create a new line
+    pub fn new(flags: Flags, extra: u16, data: Vec<Char>) -> Self {
+        let mut line = Self {
+            len: 0,
+            flags,
+            extra,
+            data,
+        };
+        line.len = line.encoded_len();
+        line
+    }
+
+    /// The number of bytes of the encoded line content
+    pub fn encoded_len(&self) -> usize {
+        let extra_len = if has_extra(self.flags) { 2 } else { 0 };
+        2 + extra_len + self.data.iter().map(Char::encoded_len).sum::<usize>()
+    }
+
+    /// Write the line buffer, i.e. the vertical skip, the length and the content
+    pub fn write_to<W: io::Write>(&self, skip: u16, buf: &mut W) -> io::Result<()> {
+        let mut data = Vec::with_capacity(self.len);
+        data.extend_from_slice(&self.flags.bits().to_be_bytes());
+        if has_extra(self.flags) {
+            data.extend_from_slice(&self.extra.to_be_bytes());
+        }
+        for chr in &self.data {
+            chr.write_to(&mut data)?;
+        }
+        write_line_buf(buf, skip, &data)
+    }
+
     /// Iterator over all charactes (and positions) in a line
     pub fn characters(&self) -> LineCharIter<'_> {
         LineCharIter {
@@ -316,6 +399,15 @@ impl Line {
     }
 }
 
+/// Whether a line with these flags stores an extra value after the flags
+fn has_extra(flags: Flags) -> bool {
+    if flags.contains(Flags::PAGE) {
+        flags.contains(Flags::PNUM)
+    } else {
+        flags.contains(Flags::FLAG)
+    }
+}
+
 /// Iterator over the characters in a line, keeping track of horizontal position
 pub struct LineCharIter<'a> {
     x: u16,
@@ -379,6 +471,16 @@ impl<'a> LineBuf<'a> {
     }
 }
 
+fn write_line_buf<W: io::Write>(buf: &mut W, skip: u16, data: &[u8]) -> io::Result<()> {
+    let len = u16::try_from(data.len())
+        .ok()
+        .filter(|l| *l < 0x8000)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "line too long"))?;
+    buf.write_all(&skip.to_be_bytes())?;
+    buf.write_all(&len.to_be_bytes())?;
+    buf.write_all(data)
+}
+
 fn parse_line_buf<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], LineBuf<'a>, E> {
@@ -387,7 +489,7 @@ fn parse_line_buf<'a, E: ParseError<&'a [u8]>>(
     Ok((input, LineBuf { skip, data }))
 }
 
-fn parse_buffered_line<'a, E: ParseError<&'a [u8]>>(
+pub(super) fn parse_buffered_line<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
 ) -> IResult<&'a [u8], (u16, Line), E> {
     tuple((
@@ -398,11 +500,11 @@ fn parse_buffered_line<'a, E: ParseError<&'a [u8]>>(
 
 fn parse_page_start_line<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
-) -> IResult<&'a [u8], (u16, u16), E> {
+) -> IResult<&'a [u8], (u16, Flags, u16), E> {
     map_res::<_, _, _, nom::error::Error<&'a [u8]>, _, _, _>(parse_buffered_line, |(a, l)| {
         log::trace!("START [skip={}] {:?} [extra={}]", a, l.flags, l.extra);
         if l.flags.contains(Flags::PAGE & Flags::PNEW) {
-            Ok((a, l.extra))
+            Ok((a, l.flags, l.extra))
         } else {
             Err("Expected the start of a page!")
         }
@@ -417,12 +519,32 @@ pub struct PageText {
     pub index: u16,
     /// The horizontal offset of the start page marker
     pub skip: u16,
+    /// The flags of the start page marker
+    pub flags: Flags,
     /// The horizontal offset of the end page marker
     pub rskip: u16,
+    /// The flags of the end page marker
+    pub rflags: Flags,
     /// The content
     pub content: Vec<(u16, Line)>,
 }
 
+impl PageText {
+    /// The usual flags of the start page marker
+    pub const START_FLAGS: Flags = Flags::PAGE.union(Flags::PNEW).union(Flags::PNUM);
+    /// The usual flags of the end page marker
+    pub const END_FLAGS: Flags = Flags::PAGE.union(Flags::PEND).union(Flags::PNUM);
+
+    /// Write the lines of the page, enclosed in page start and end markers
+    pub fn write_to<W: io::Write>(&self, buf: &mut W) -> io::Result<()> {
+        Line::new(self.flags, self.index, vec![]).write_to(self.skip, buf)?;
+        for (skip, line) in &self.content {
+            line.write_to(*skip, buf)?;
+        }
+        Line::new(self.rflags, self.index, vec![]).write_to(self.rskip, buf)
+    }
+}
+
 /// Parse the text of an entire page
 pub fn parse_page_text<'a, E: ParseError<&'a [u8]>>(
     input: &'a [u8],
@@ -435,7 +557,7 @@ pub fn parse_page_text<'a, E: ParseError<&'a [u8]>>(
             ErrorKind::Verify,
         )));
     }
-    let (input, (lskip, index)) = parse_page_start_line(input)?;
+    let (input, (lskip, flags, index)) = parse_page_start_line(input)?;
     let mut iter = iterator(input, parse_buffered_line);
 
     let mut content = vec![];
@@ -462,18 +584,24 @@ pub fn parse_page_text<'a, E: ParseError<&'a [u8]>>(
 
         if line.extra != index {
             let (rest, ()) = iter.finish()?;
-            panic!(
+            log::error!(
                 "Broken text buffer: {} != {} [bytes remaining={}]",
                 line.extra,
                 index,
                 rest.len()
             );
+            return Err(nom::Err::Failure(E::from_error_kind(
+                rest,
+                ErrorKind::Verify,
+            )));
         }
         return iter.finish().map(|(rest, ())| {
             let text = PageText {
                 index,
                 skip: lskip,
+                flags,
                 rskip: skip,
+                rflags: line.flags,
                 content,
             };
             (rest, text)
@@ -489,7 +617,7 @@ pub fn parse_page_text<'a, E: ParseError<&'a [u8]>>(
                 rest.len(),
                 diff
             );
-            recover_page(lskip, index, content, input)
+            recover_page(lskip, flags, index, content, input)
         }
         Err(e) => Err(e),
     }
@@ -497,6 +625,7 @@ pub fn parse_page_text<'a, E: ParseError<&'a [u8]>>(
 
 fn recover_page<'a, E: ParseError<&'a [u8]>>(
     lskip: u16,
+    flags: Flags,
     index: u16,
     content: Vec<(u16, Line)>,
     rest: &'a [u8],
@@ -507,16 +636,20 @@ fn recover_page<'a, E: ParseError<&'a [u8]>>(
     let offset = ahead.find([0xA0, 0x80, pnum[0], pnum[1]]);
     if let Some(offset) = offset {
         log::warn!("Invalid page, but found end marker at +{}", offset);
-        let rest = &rest[(offset - 4)..];
-        let (rest, (rskip, end_line)) = parse_buffered_line::<nom::error::Error<_>>(rest)
-            .expect("expected page end line at marker");
+        let rest = offset
+            .checked_sub(4)
+            .map(|start| &rest[start..])
+            .ok_or_else(|| nom::Err::Failure(E::from_error_kind(rest, ErrorKind::Tag)))?;
+        let (rest, (rskip, end_line)) = parse_buffered_line::<E>(rest)?;
         log::debug!("{:?}", end_line);
         Ok((
             rest,
             PageText {
                 index,
                 skip: lskip,
+                flags,
                 rskip,
+                rflags: end_line.flags,
                 content,
             },
         ))
@@ -549,7 +682,10 @@ where
             Ok((input, tebu))
         }
         Err(e) => match e {
-            nom::Err::Incomplete(needed) => panic!("Incomplete: {:?}", needed),
+            nom::Err::Incomplete(needed) => {
+                log::error!("Incomplete text buffer: {:?}", needed);
+                Err(nom::Err::Failure(E::from_error_kind(input, ErrorKind::Eof)))
+            }
             nom::Err::Error(e) => Err(nom::Err::Error(e)),
             nom::Err::Failure(e) => Err(nom::Err::Failure(e)),
         },
@@ -17,7 +17,7 @@ pub const CHUNK_FOUSED01: &[u8; 12] = b"\0\0foused01\0\0";
 pub type FontUsed<'a> = (u8, Cow<'a, str>);
 
 /// `foused01` chunk
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct FontsUsed<'a> {
     fonts: Vec<FontUsed<'a>>,
 }
@@ -0,0 +1,235 @@
+//! # Page layout of a Signum! 3/4 document
+//!
+//! Signum! 3/4 documents store their text as continuous streams per chapter.
+//! This module breaks these streams into pages, so that they can be passed to
+//! the same output paths as Signum! 1/2 documents.
+//!
+//! - Every chapter starts on a new page
+//! - The lines of the header/footer stream in the upper half of the page are
+//!   repeated at the top of every page of the chapter, the others at the
+//!   bottom, so that the last one ends at the page length
+//! - The main stream is followed by the 3rd and 4th stream, if present
+//!
+//! The result is only a preview of the document:
+//!
+//! - The page format of Signum! 3/4 documents is not decoded yet, so it needs
+//!   to be passed to [`Layout::new`]
+//! - Only the first [`FontMap::MAX_CSETS`] fonts get a character set of their
+//!   own, see [`FontMap::cset`]
+
+use std::convert::TryFrom;
+
+use crate::docs::{
+    cset::CSet,
+    pbuf::Page,
+    sysp::SysP,
+    tebu::{Line, PageText, TeBu, TextBufferHeader},
+};
+
+use super::{Chapter, Decoder, FontMap, SDocV3, Stream, TextLine};
+
+/// A Signum! 3/4 document, broken into pages
+pub struct Layout {
+    /// The fonts, as character sets
+    pub cset: CSet<'static>,
+    /// The page format used for layout
+    pub sysp: SysP,
+    /// The text of all pages
+    pub tebu: TeBu,
+    /// The page table
+    pub pages: Vec<Option<Page>>,
+}
+
+struct Paginator<'a> {
+    fonts: &'a FontMap<'a>,
+    page_length: u16,
+    pages: Vec<PageText>,
+    header: Vec<(u16, Line)>,
+    footer: Vec<(u16, Line)>,
+    content: Vec<(u16, Line)>,
+    page_y: u32,
+}
+
+impl<'a> Paginator<'a> {
+    fn new(fonts: &'a FontMap<'a>, page_length: u16) -> Self {
+        Self {
+            fonts,
+            page_length,
+            pages: Vec::new(),
+            header: Vec::new(),
+            footer: Vec::new(),
+            content: Vec::new(),
+            page_y: 0,
+        }
+    }
+
+    fn finish_page(&mut self) {
+        let mut content = std::mem::take(&mut self.content);
+        let index = self.pages.len() as u16;
+        let mut with_header = self.header.clone();
+        with_header.append(&mut content);
+        if let Some(((_, first), rest)) = self.footer.split_first() {
+            // Move the footer down, so that its last line ends at the page length
+            let skip = self.footer_top().saturating_sub(self.page_y);
+            with_header.push((u16::try_from(skip).unwrap_or(u16::MAX), first.clone()));
+            with_header.extend(rest.iter().cloned());
+        }
+        self.pages.push(PageText {
+            index,
+            skip: 0,
+            flags: PageText::START_FLAGS,
+            rskip: 0,
+            rflags: PageText::END_FLAGS,
+            content: with_header,
+        });
+        self.page_y = skip_sum(&self.header);
+    }
+
+    /// The position of the first footer line
+    fn footer_top(&self) -> u32 {
+        let rest = self.footer.get(1..).unwrap_or_default();
+        u32::from(self.page_length).saturating_sub(skip_sum(rest))
+    }
+
+    /// Whether a line with this skip fits on the current page
+    ///
+    /// If there is a footer, the main text needs to end one line above it.
+    fn fits(&self, skip: u32) -> bool {
+        let y = self.page_y + skip;
+        if self.footer.is_empty() {
+            y <= u32::from(self.page_length)
+        } else {
+            y + skip <= self.footer_top()
+        }
+    }
+
+    fn start_chapter(&mut self, header_footer: &[TextLine]) {
+        if !self.content.is_empty() {
+            self.finish_page();
+        }
+        let half = u32::from(self.page_length) / 2;
+        let (header, footer): (Vec<_>, Vec<_>) =
+            header_footer.iter().partition(|line| line.y <= half);
+        let fonts = self.fonts;
+        let lower = |lines: Vec<&TextLine>| {
+            lines
+                .into_iter()
+                .map(|line| (line.vskip, line.to_line(fonts)))
+                .collect()
+        };
+        self.header = lower(header);
+        self.footer = lower(footer);
+        self.page_y = skip_sum(&self.header);
+    }
+
+    fn push(&mut self, line: &TextLine) {
+        let skip = u32::from(line.vskip);
+        if !self.fits(skip) && !self.content.is_empty() {
+            self.finish_page();
+        }
+        self.page_y += skip;
+        self.content.push((line.vskip, line.to_line(self.fonts)));
+    }
+
+    fn finish(mut self) -> Vec<PageText> {
+        if !self.content.is_empty() || self.pages.is_empty() {
+            self.finish_page();
+        }
+        self.pages
+    }
+}
+
+fn skip_sum(lines: &[(u16, Line)]) -> u32 {
+    lines.iter().map(|(skip, _)| u32::from(*skip)).sum()
+}
+
+fn chapter_streams<'a>(chapter: &'a Chapter<'a>) -> impl Iterator<Item = &'a Stream<'a>> {
+    std::iter::once(chapter.main())
+        .chain(chapter.stream3())
+        .chain(chapter.stream4())
+}
+
+impl Layout {
+    /// Lay out all chapters of a document on pages of the given format
+    pub fn new(sdoc: &SDocV3, sysp: SysP) -> Self {
+        let fonts = FontMap::new(sdoc.foused01());
+        let mut paginator = Paginator::new(&fonts, sysp.page_length);
+        for chapter in sdoc.chapters() {
+            let header = Decoder::new().decode_stream(chapter.header_footer());
+            paginator.start_chapter(&header);
+            let mut decoder = Decoder::new();
+            for stream in chapter_streams(chapter) {
+                for line in decoder.decode_stream(stream) {
+                    paginator.push(&line);
+                }
+            }
+        }
+        let text_pages = paginator.finish();
+        let lines_total = text_pages.iter().map(|p| p.content.len() as u32 + 2).sum();
+        let pages = text_pages
+            .iter()
+            .map(|text| Some(Page::from_sysp(text.index, &sysp)))
+            .collect();
+        Self {
+            cset: fonts.to_cset(),
+            sysp,
+            tebu: TeBu {
+                header: TextBufferHeader { lines_total },
+                pages: text_pages,
+                trailer: 0,
+            },
+            pages,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Paginator;
+    use crate::docs::v3::{FontMap, FontsUsed, TextLine};
+
+    fn line(vskip: u16, y: u32) -> TextLine {
+        TextLine {
+            vskip,
+            y,
+            chars: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_paginate() {
+        let fonts = FontsUsed::default();
+        let fonts = FontMap::new(&fonts);
+        let mut paginator = Paginator::new(&fonts, 100);
+        paginator.start_chapter(&[line(10, 10)]);
+        for _ in 0..5 {
+            paginator.push(&line(30, 0));
+        }
+        paginator.start_chapter(&[]);
+        paginator.push(&line(30, 0));
+        let pages = paginator.finish();
+
+        let lens: Vec<_> = pages.iter().map(|p| p.content.len()).collect();
+        assert_eq!(lens, [4, 3, 1]);
+        assert_eq!(pages[2].index, 2);
+    }
+
+    #[test]
+    fn test_paginate_footer() {
+        let fonts = FontsUsed::default();
+        let fonts = FontMap::new(&fonts);
+        let mut paginator = Paginator::new(&fonts, 100);
+        paginator.start_chapter(&[line(10, 10), line(80, 90), line(5, 95)]);
+        for _ in 0..4 {
+            paginator.push(&line(20, 0));
+        }
+        let pages = paginator.finish();
+
+        // the header, 3 lines and the footer, which ends at the page length
+        let skips: Vec<_> = pages[0].content.iter().map(|(skip, _)| *skip).collect();
+        assert_eq!(skips, [10, 20, 20, 20, 25, 5]);
+        assert_eq!(skips.iter().sum::<u16>(), 100);
+        let skips: Vec<_> = pages[1].content.iter().map(|(skip, _)| *skip).collect();
+        assert_eq!(skips, [10, 20, 65, 5]);
+    }
+}
@@ -13,7 +13,9 @@ pub use flptrs::FilePointers;
 pub use foused::{FontUsed, FontsUsed};
 pub use header::Header;
 pub use kapit::ChapterHeader;
-pub use stream::Stream;
+pub use layout::Layout;
+pub use stream::{Line, Stream, TChar};
+pub use text::{Attributes, Decoder, FontMap, TextChar, TextLine};
 
 use crate::util::V3Chunk;
 
@@ -21,7 +23,9 @@ mod flptrs;
 mod foused;
 mod header;
 mod kapit;
+mod layout;
 mod stream;
+mod text;
 
 /// Tag for a v3 document
 pub const TAG_SDOC3: &[u8; 12] = b"\0\0sdoc  03\0\0";
@@ -45,9 +45,30 @@ impl Stream<'_> {
 
 /// A single character
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct TChar(u8, u16, u8);
 
+impl TChar {
+    /// The first byte, either an offset (`< 0x80`) or an escape code
+    pub fn code(&self) -> u8 {
+        self.0
+    }
+
+    /// The parameter of an escape code (0 for plain characters)
+    pub fn extra(&self) -> u16 {
+        self.1
+    }
+
+    /// The character value
+    pub fn cval(&self) -> u8 {
+        self.2
+    }
+
+    /// Whether this character has an escape code
+    pub fn is_escape(&self) -> bool {
+        self.0 & 0x80 > 0
+    }
+}
+
 #[derive(Default)]
 struct Chars(Vec<TChar>);
 
@@ -82,12 +103,23 @@ impl fmt::Debug for Chars {
 
 /// A single line in the text
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct Line {
     vskip: u16,
     chars: Chars,
 }
 
+impl Line {
+    /// The vertical distance to the previous line
+    pub fn vskip(&self) -> u16 {
+        self.vskip
+    }
+
+    /// The raw characters of the line
+    pub fn chars(&self) -> &[TChar] {
+        &self.chars.0
+    }
+}
+
 fn parse_tchar<'a, E>(input: &'a [u8]) -> IResult<&'a [u8], TChar, E>
 where
     E: ParseError<&'a [u8]>,
@@ -108,7 +140,7 @@ impl fmt::Display for TChar {
     }
 }
 
-fn parse_line<'a, E>(input: &'a [u8]) -> IResult<&'a [u8], Line, E>
+pub(super) fn parse_line<'a, E>(input: &'a [u8]) -> IResult<&'a [u8], Line, E>
 where
     E: ParseError<&'a [u8]>,
 {
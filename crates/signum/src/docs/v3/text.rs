@@ -0,0 +1,327 @@
+//! # Decoded text of a `stream01`
+//!
+//! Each line of a [`Stream`] is a sequence of [`TChar`]s, which are either
+//!
+//! - `dx cval` (2 bytes, `dx < 0x80`): character `cval`, placed `dx` units to
+//!   the right of the previous character
+//! - `code extra cval` (4 bytes, `code >= 0x80`): an escape code with a 16 bit
+//!   parameter, followed by a character. The codes below are hypotheses that
+//!   have not yet been verified against real documents (the test for that is
+//!   ignored, as no Signum! 3/4 documents are available to CI):
+//!   - `0x80`: switch font and attributes. The high byte of `extra` is the font
+//!     number in the `foused01` chunk, the low byte the [`Attributes`]. A non-zero
+//!     `cval` is placed at the position of the previous character.
+//!   - `0x90`, `0x98`: pair kerning, `cval` is placed `extra` (signed) units to
+//!     the right of the previous character.
+//!
+//! Character value `0` is a space, i.e. it only moves the position. Horizontal
+//! units are assumed to be the same as for Signum! 1/2 (1/90th of an inch),
+//! vertical units for `vskip` the same as for `tebu` lines.
+//!
+//! The [`TextLine`]s can be lowered into Signum! 1/2 [`Line`]s, see
+//! [`Layout`](super::Layout) to reuse the Signum! 1/2 output paths.
+
+use std::borrow::Cow;
+
+use bitflags::bitflags;
+use bstr::BString;
+
+use crate::docs::{
+    cset::CSet,
+    tebu::{Char, Flags, Line, Style},
+};
+
+use super::{FontsUsed, Stream, TChar};
+
+/// Escape code to switch font and attributes (unverified)
+const ESC_FONT: u8 = 0x80;
+/// Escape codes for pair kerning (unverified)
+const ESC_KERNING: [u8; 2] = [0x90, 0x98];
+
+bitflags! {
+    /// The attributes set with a font switch escape
+    ///
+    /// The meaning of the bits is a hypothesis, see the [module docs](self).
+    #[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+    pub struct Attributes: u8 {
+        /// Bold
+        const BOLD = 0x01;
+        /// Italic
+        const ITALIC = 0x02;
+        /// Underlined
+        const UNDERLINED = 0x04;
+        /// Double width
+        const WIDE = 0x08;
+        /// Double height
+        const TALL = 0x10;
+        /// Small
+        const SMALL = 0x20;
+        /// Footnote
+        const FOOTNOTE = 0x40;
+    }
+}
+
+impl Attributes {
+    /// Return the equivalent Signum! 1/2 [`Style`]
+    pub fn to_style(self) -> Style {
+        let mut style = Style::default();
+        let pairs = [
+            (Self::BOLD, Style::BOLD),
+            (Self::ITALIC, Style::ITALIC),
+            (Self::UNDERLINED, Style::UNDERLINED),
+            (Self::WIDE, Style::WIDE),
+            (Self::TALL, Style::TALL),
+            (Self::SMALL, Style::SMALL),
+            (Self::FOOTNOTE, Style::FOOTNOTE),
+        ];
+        for (attr, flag) in pairs.iter() {
+            style.set(*flag, self.contains(*attr));
+        }
+        style
+    }
+}
+
+/// A decoded character
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TextChar {
+    /// The character value
+    pub cval: u8,
+    /// The font number, as listed in [`FontsUsed`]
+    pub font: u8,
+    /// Horizontal position from the start of the line
+    pub x: u16,
+    /// The attributes of the character
+    pub attrs: Attributes,
+    /// An unknown escape code and parameter that preceded the character
+    pub escape: Option<(u8, u16)>,
+}
+
+/// A decoded line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextLine {
+    /// The vertical distance to the previous line
+    pub vskip: u16,
+    /// Vertical position from the start of the stream
+    pub y: u32,
+    /// The (non-space) characters of the line
+    pub chars: Vec<TextChar>,
+}
+
+/// State of the decoder, which carries over from one line to the next
+#[derive(Debug, Default)]
+pub struct Decoder {
+    font: u8,
+    attrs: Attributes,
+    y: u32,
+}
+
+impl Decoder {
+    /// Create a new decoder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn decode_char(&mut self, x: &mut u16, k: &TChar) -> Option<TextChar> {
+        let mut escape = None;
+        match k.code() {
+            ESC_FONT => {
+                let [font, attrs] = k.extra().to_be_bytes();
+                self.font = font;
+                self.attrs = Attributes::from_bits_retain(attrs);
+            }
+            code if code & 0x80 == 0 => *x = x.wrapping_add(u16::from(code)),
+            code if ESC_KERNING.contains(&code) => *x = x.wrapping_add(k.extra()),
+            code => escape = Some((code, k.extra())),
+        }
+        (k.cval() != 0).then(|| TextChar {
+            cval: k.cval(),
+            font: self.font,
+            x: *x,
+            attrs: self.attrs,
+            escape,
+        })
+    }
+
+    /// Decode the characters of a line
+    pub fn decode_line(&mut self, line: &super::stream::Line) -> TextLine {
+        self.y += u32::from(line.vskip());
+        let mut x = 0;
+        let chars = line
+            .chars()
+            .iter()
+            .filter_map(|k| self.decode_char(&mut x, k))
+            .collect();
+        TextLine {
+            vskip: line.vskip(),
+            y: self.y,
+            chars,
+        }
+    }
+
+    /// Decode all lines of a stream
+    pub fn decode_stream(&mut self, stream: &Stream) -> Vec<TextLine> {
+        stream
+            .text()
+            .iter()
+            .map(|line| self.decode_line(line))
+            .collect()
+    }
+}
+
+/// Maps the font numbers of a [`FontsUsed`] to character sets
+pub struct FontMap<'a> {
+    fonts: &'a FontsUsed<'a>,
+}
+
+impl<'a> FontMap<'a> {
+    /// The number of character sets in a Signum! 1/2 document
+    pub const MAX_CSETS: usize = 8;
+
+    /// Create a new font map
+    pub fn new(fonts: &'a FontsUsed<'a>) -> Self {
+        Self { fonts }
+    }
+
+    /// Return the character set for a font number
+    ///
+    /// The first 8 fonts map to character sets 0-7, all others to 0.
+    pub fn cset(&self, font: u8) -> u8 {
+        self.fonts
+            .fonts()
+            .iter()
+            .position(|(index, _)| *index == font)
+            .filter(|pos| *pos < Self::MAX_CSETS)
+            .map_or(0, |pos| pos as u8)
+    }
+
+    /// Return the `cset` chunk with the names of the fonts
+    pub fn to_cset(&self) -> CSet<'static> {
+        let names = self
+            .fonts
+            .fonts()
+            .iter()
+            .take(Self::MAX_CSETS)
+            .map(|(_, name)| Cow::Owned(BString::from(name.as_ref())))
+            .collect();
+        CSet::new(names)
+    }
+}
+
+impl TextLine {
+    /// Lower this line into a Signum! 1/2 [`Line`]
+    pub fn to_line(&self, fonts: &FontMap) -> Line {
+        let mut prev_x = 0;
+        let data = self
+            .chars
+            .iter()
+            .map(|k| {
+                // Characters moved left (kerning) are placed at the previous
+                // one, so the emitted position can be ahead of `k.x`
+                let offset = k.x.saturating_sub(prev_x);
+                prev_x += offset;
+                Char {
+                    cval: k.cval,
+                    cset: fonts.cset(k.font),
+                    offset,
+                    style: k.attrs.to_style(),
+                    long: false,
+                }
+            })
+            .collect();
+        Line::new(Flags::LINE, 0, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use nom::error::Error;
+
+    use super::{Attributes, Decoder, FontMap, TextChar, TextLine};
+    use crate::docs::v3::{parse_sdoc_v3, stream::parse_line, FontsUsed};
+
+    #[test]
+    fn test_decode_line() {
+        let input = [
+            0x00, 0x1A, 0x00, 0x0C, // len, vskip
+            0x80, 0x02, 0x01, 0x00, // font 2, bold
+            0x05, b'A', // A
+            0x0A, 0x00, // space
+            0x0C, b'b', // b
+            0x90, 0xFF, 0xFE, b'c', // kerned c
+            0xB0, 0x00, 0x40, b'd', // unknown escape
+            0x98, 0x00, 0x03, b'e', // kerned e
+        ];
+        let (rest, line) = parse_line::<Error<&[u8]>>(&input).unwrap();
+        assert!(rest.is_empty());
+
+        let mut decoder = Decoder::new();
+        let text = decoder.decode_line(&line);
+        assert_eq!(text.y, 12);
+        let chars: Vec<_> = text.chars.iter().map(|k| (k.cval, k.font, k.x)).collect();
+        let expected = [
+            (b'A', 2, 5),
+            (b'b', 2, 27),
+            (b'c', 2, 25),
+            (b'd', 2, 25),
+            (b'e', 2, 28),
+        ];
+        assert_eq!(chars, expected);
+        assert_eq!(text.chars[0].attrs, Attributes::BOLD);
+        assert_eq!(text.chars[2].escape, None);
+        assert_eq!(text.chars[3].escape, Some((0xB0, 0x40)));
+    }
+
+    #[test]
+    fn test_to_line() {
+        let char_at = |cval, x| TextChar {
+            cval,
+            font: 0,
+            x,
+            attrs: Attributes::empty(),
+            escape: None,
+        };
+        let text = TextLine {
+            vskip: 12,
+            y: 12,
+            chars: vec![
+                char_at(b'A', 5),
+                char_at(b'b', 27),
+                char_at(b'c', 25),
+                char_at(b'd', 25),
+                char_at(b'e', 28),
+            ],
+        };
+        let fonts = FontsUsed::default();
+        let line = text.to_line(&FontMap::new(&fonts));
+        let offsets: Vec<_> = line.data.iter().map(|k| k.offset).collect();
+        assert_eq!(offsets, [5, 22, 0, 0, 1]);
+        let end: u16 = offsets.iter().sum();
+        assert_eq!(end, 28);
+    }
+
+    #[test]
+    #[ignore = "needs Signum! 3/4 documents (*.SDK) in docs/_examples"]
+    fn test_decode_examples() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../docs/_examples");
+        let mut count = 0;
+        for entry in std::fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            let is_sdk = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("sdk"));
+            if is_sdk {
+                let input = std::fs::read(&path).unwrap();
+                let (_, sdoc) = parse_sdoc_v3::<Error<&[u8]>>(&input).unwrap();
+                for chapter in sdoc.chapters() {
+                    let lines = Decoder::new().decode_stream(chapter.main());
+                    assert_eq!(lines.len(), chapter.main().text().len());
+                    assert!(lines.iter().any(|line| !line.chars.is_empty()));
+                }
+                count += 1;
+            }
+        }
+        assert!(count > 0, "No SDK files found in docs/_examples");
+    }
+}
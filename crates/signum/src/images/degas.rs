@@ -0,0 +1,235 @@
+//! # The Degas (`*.PI3`, `*.PC3`) file formats
+//!
+//! These are the high resolution (monochrome) formats of the Degas and Degas Elite
+//! paint programs for the ATARI ST. The file starts with a resolution word and a
+//! palette of 16 colors, followed by a screen dump of 640x400 pixels.
+//!
+//! In compressed `*.PC3` files, every scanline is compressed with PackBits
+//! and the image is followed by the color animation tables of Degas Elite.
+
+use nom::{
+    bytes::complete::take,
+    error::{Error, ErrorKind},
+    number::complete::be_u16,
+    Err, IResult,
+};
+
+use super::imc::MonochromeScreen;
+
+/// The resolution word for high resolution (640x400, monochrome)
+const HIGH_RES: u16 = 0x0002;
+/// The flag in the resolution word for compressed images
+const COMPRESSED: u16 = 0x8000;
+/// The number of bytes in a scanline
+const BYTES_PER_LINE: usize = 80;
+/// The size of the color animation tables of Degas Elite
+const ANIMATION_LEN: usize = 32;
+/// The default palette of the ATARI ST desktop, i.e. white background and black ink
+const DEFAULT_PALETTE: [u16; 16] = [
+    0x0777, 0x0700, 0x0070, 0x0770, 0x0007, 0x0707, 0x0077, 0x0555, //
+    0x0333, 0x0733, 0x0373, 0x0773, 0x0337, 0x0737, 0x0377, 0x0000,
+];
+
+/// Degas metadata
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DegasHeader {
+    /// Whether the image is compressed (`*.PC3`)
+    pub compressed: bool,
+    /// The color palette
+    ///
+    /// On a monochrome monitor, only the lowest bit of the first entry is
+    /// used: if it is clear, the screen is inverted.
+    pub palette: [u16; 16],
+}
+
+impl DegasHeader {
+    /// Check whether the input looks like a monochrome Degas image
+    pub fn detect(input: &[u8]) -> bool {
+        match input {
+            [0x00, 0x02, ..] => input.len() >= 34 + 32000,
+            [0x80, 0x02, ..] => input.len() >= 34,
+            _ => false,
+        }
+    }
+
+    fn inverted(&self) -> bool {
+        self.palette[0] & 1 == 0
+    }
+}
+
+fn parse_degas_header(input: &[u8]) -> IResult<&[u8], DegasHeader> {
+    let (rest, resolution) = be_u16(input)?;
+    if resolution & !COMPRESSED != HIGH_RES {
+        return Err(Err::Error(Error {
+            input,
+            code: ErrorKind::Verify,
+        }));
+    }
+    let mut input = rest;
+    let mut palette = [0; 16];
+    for color in &mut palette {
+        let (rest, value) = be_u16(input)?;
+        *color = value;
+        input = rest;
+    }
+    let header = DegasHeader {
+        compressed: resolution & COMPRESSED != 0,
+        palette,
+    };
+    Ok((input, header))
+}
+
+/// Decode a PackBits compressed block of `len` bytes
+fn unpack_bits(mut input: &[u8], len: usize) -> IResult<&[u8], Vec<u8>> {
+    let mut out = Vec::with_capacity(len);
+    while out.len() < len {
+        let (rest, code) = take(1usize)(input)?;
+        input = match code[0] as i8 {
+            -128 => rest,
+            n @ 0..=127 => {
+                let (rest, bytes) = take(n as usize + 1)(rest)?;
+                out.extend_from_slice(bytes);
+                rest
+            }
+            n => {
+                let (rest, byte) = take(1usize)(rest)?;
+                let count = (1 - n as isize) as usize;
+                out.resize(out.len() + count, byte[0]);
+                rest
+            }
+        };
+    }
+    out.truncate(len);
+    Ok((input, out))
+}
+
+/// Encode a block with PackBits
+fn pack_bits(input: &[u8], out: &mut Vec<u8>) {
+    fn flush(literal: &[u8], out: &mut Vec<u8>) {
+        if !literal.is_empty() {
+            out.push((literal.len() - 1) as u8);
+            out.extend_from_slice(literal);
+        }
+    }
+
+    let mut start = 0;
+    let mut pos = 0;
+    while pos < input.len() {
+        let byte = input[pos];
+        let run = input[pos..]
+            .iter()
+            .take(128)
+            .take_while(|&&b| b == byte)
+            .count();
+        // a run of two only pays off if it does not interrupt a literal
+        if run >= 3 || (run == 2 && start == pos) {
+            flush(&input[start..pos], out);
+            out.push((1 - run as isize) as u8);
+            out.push(byte);
+            pos += run;
+            start = pos;
+        } else {
+            pos += 1;
+            if pos - start == 128 {
+                flush(&input[start..pos], out);
+                start = pos;
+            }
+        }
+    }
+    flush(&input[start..], out);
+}
+
+/// Parse a monochrome Degas image (`*.PI3` or `*.PC3`)
+///
+/// If the palette inverts the screen, the image is inverted so that
+/// `1` is always black.
+pub fn parse_degas(input: &[u8]) -> IResult<&[u8], (DegasHeader, MonochromeScreen)> {
+    let (mut input, header) = parse_degas_header(input)?;
+    let mut buffer = if header.compressed {
+        let mut buffer = Vec::with_capacity(32000);
+        for _ in 0..(32000 / BYTES_PER_LINE) {
+            let (rest, line) = unpack_bits(input, BYTES_PER_LINE)?;
+            buffer.extend_from_slice(&line);
+            input = rest;
+        }
+        buffer
+    } else {
+        let (rest, bytes) = take(32000usize)(input)?;
+        input = rest;
+        bytes.to_vec()
+    };
+    if header.inverted() {
+        buffer.iter_mut().for_each(|byte| *byte = !*byte);
+    }
+    Ok((input, (header, MonochromeScreen::new(buffer))))
+}
+
+fn write_header(out: &mut Vec<u8>, resolution: u16) {
+    out.extend_from_slice(&resolution.to_be_bytes());
+    for color in DEFAULT_PALETTE {
+        out.extend_from_slice(&color.to_be_bytes());
+    }
+}
+
+/// Encode a screen as an uncompressed Degas image (`*.PI3`)
+pub fn encode_pi3(screen: &MonochromeScreen) -> Vec<u8> {
+    let mut out = Vec::with_capacity(34 + 32000);
+    write_header(&mut out, HIGH_RES);
+    out.extend_from_slice(screen.as_slice());
+    out
+}
+
+/// Encode a screen as a compressed Degas Elite image (`*.PC3`)
+pub fn encode_pc3(screen: &MonochromeScreen) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_header(&mut out, HIGH_RES | COMPRESSED);
+    for line in screen.as_slice().chunks_exact(BYTES_PER_LINE) {
+        pack_bits(line, &mut out);
+    }
+    // no color animation
+    out.extend_from_slice(&[0; ANIMATION_LEN]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_pc3, encode_pi3, pack_bits, parse_degas, unpack_bits, DegasHeader};
+    use crate::images::imc::{parse_imc, MonochromeScreen};
+
+    const DATA: &[u8] = include_bytes!("../../res/TEST.IMC");
+
+    #[test]
+    fn test_pack_bits() {
+        // Example from Apple Technical Note TN1023
+        let unpacked = [
+            0xAA, 0xAA, 0xAA, 0x80, 0x00, 0x2A, 0xAA, 0xAA, 0xAA, 0xAA, 0x80, 0x00, 0x2A, 0x22,
+            0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA,
+        ];
+        let packed = [
+            0xFE, 0xAA, 0x02, 0x80, 0x00, 0x2A, 0xFD, 0xAA, 0x03, 0x80, 0x00, 0x2A, 0x22, 0xF7,
+            0xAA,
+        ];
+        let mut out = Vec::new();
+        pack_bits(&unpacked, &mut out);
+        assert_eq!(out, packed);
+        let (rest, decoded) = unpack_bits(&packed, unpacked.len()).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(decoded, unpacked);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (_, screen) = parse_imc(DATA).unwrap();
+        for encoded in [encode_pi3(&screen), encode_pc3(&screen)] {
+            assert!(DegasHeader::detect(&encoded));
+            let (_, (header, decoded)) = parse_degas(&encoded).unwrap();
+            assert_eq!(header.compressed, encoded[0] == 0x80);
+            assert_eq!(decoded.as_slice(), screen.as_slice());
+        }
+
+        let mut inverted = encode_pi3(&MonochromeScreen::new(vec![]));
+        inverted[2..4].copy_from_slice(&[0x07, 0x76]);
+        let (_, (_, decoded)) = parse_degas(&inverted).unwrap();
+        assert!(decoded.as_slice().iter().all(|&b| b == 0xFF));
+    }
+}
@@ -0,0 +1,326 @@
+//! # The GEM bit image (`*.IMG`) file format
+//!
+//! This is the raster image format of GEM, used by e.g. GEM Paint and many other
+//! ATARI ST programs. The file starts with a header of big-endian words, followed
+//! by the scanlines, which are compressed with solid runs, pattern runs, bit strings
+//! and vertical replication.
+//!
+//! Only monochrome images (a single plane) are supported.
+
+use std::convert::TryFrom;
+
+use nom::{
+    bytes::complete::take,
+    error::{Error, ErrorKind},
+    number::complete::{be_u16, u8 as any_u8},
+    Err, IResult,
+};
+
+use crate::raster::Page;
+
+/// The number of words in the basic header
+const HEADER_WORDS: u16 = 8;
+
+/// The size of a pixel on the ATARI monochrome monitor in micrometers
+const SCREEN_PIXEL_SIZE: u16 = 372;
+
+/// The pattern length used by the encoder
+const PATTERN_LEN: usize = 2;
+
+/// GEM bit image metadata
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ImgHeader {
+    /// File format version, usually `1`
+    pub version: u16,
+    /// The length of the header in words
+    pub header_len: u16,
+    /// The number of bit planes
+    pub planes: u16,
+    /// The number of bytes in a pattern run
+    pub pattern_len: u16,
+    /// The width of a pixel in micrometers
+    pub pixel_width: u16,
+    /// The height of a pixel in micrometers
+    pub pixel_height: u16,
+    /// The width of the image in pixels
+    pub width: u16,
+    /// The height of the image in pixels
+    pub height: u16,
+}
+
+impl ImgHeader {
+    /// Check whether the input looks like a monochrome GEM bit image
+    ///
+    /// The format has no magic bytes, so this checks for plausible header values.
+    pub fn detect(input: &[u8]) -> bool {
+        match parse_img_header(input) {
+            Ok((_, header)) => {
+                header.header_len >= HEADER_WORDS
+                    && usize::from(header.header_len) * 2 <= input.len()
+                    && header.planes == 1
+                    && (1..=8).contains(&header.pattern_len)
+                    && header.width > 0
+                    && header.height > 0
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+fn parse_img_header(input: &[u8]) -> IResult<&[u8], ImgHeader> {
+    let (input, version) = be_u16(input)?;
+    let (input, header_len) = be_u16(input)?;
+    let (input, planes) = be_u16(input)?;
+    let (input, pattern_len) = be_u16(input)?;
+    let (input, pixel_width) = be_u16(input)?;
+    let (input, pixel_height) = be_u16(input)?;
+    let (input, width) = be_u16(input)?;
+    let (input, height) = be_u16(input)?;
+
+    let header = ImgHeader {
+        version,
+        header_len,
+        planes,
+        pattern_len,
+        pixel_width,
+        pixel_height,
+        width,
+        height,
+    };
+    Ok((input, header))
+}
+
+fn error_at(input: &[u8], code: ErrorKind) -> Err<Error<&[u8]>> {
+    Err::Error(Error { input, code })
+}
+
+/// Decode a single scanline into `line`
+fn decode_line<'a>(
+    mut input: &'a [u8],
+    line: &mut [u8],
+    pattern_len: usize,
+) -> IResult<&'a [u8], ()> {
+    let mut x = 0;
+    while x < line.len() {
+        let (rest, code) = any_u8(input)?;
+        input = match code {
+            0x00 => {
+                // pattern run
+                let (rest, count) = any_u8(rest)?;
+                if count == 0 {
+                    // vertical replication is only allowed at the start of a line
+                    return Err(error_at(input, ErrorKind::Verify));
+                }
+                let (rest, pattern) = take(pattern_len)(rest)?;
+                for _ in 0..count {
+                    for &byte in pattern {
+                        if let Some(dest) = line.get_mut(x) {
+                            *dest = byte;
+                        }
+                        x += 1;
+                    }
+                }
+                rest
+            }
+            0x80 => {
+                // bit string
+                let (rest, count) = any_u8(rest)?;
+                let (rest, bytes) = take(count)(rest)?;
+                for &byte in bytes {
+                    if let Some(dest) = line.get_mut(x) {
+                        *dest = byte;
+                    }
+                    x += 1;
+                }
+                rest
+            }
+            _ => {
+                // solid run
+                let value = if code & 0x80 != 0 { 0xFF } else { 0x00 };
+                for _ in 0..(code & 0x7F) {
+                    if let Some(dest) = line.get_mut(x) {
+                        *dest = value;
+                    }
+                    x += 1;
+                }
+                rest
+            }
+        }
+    }
+    Ok((input, ()))
+}
+
+/// Parse a monochrome GEM bit image
+pub fn parse_img(input: &[u8]) -> IResult<&[u8], (ImgHeader, Page)> {
+    let (_, header) = parse_img_header(input)?;
+    if header.planes != 1 || header.pattern_len == 0 {
+        return Err(error_at(input, ErrorKind::Verify));
+    }
+    let (mut input, _) = take(usize::from(header.header_len) * 2)(input)?;
+
+    let width = u32::from(header.width);
+    let height = usize::from(header.height);
+    let bytes_per_line = usize::from(header.width).div_ceil(8);
+    let pattern_len = usize::from(header.pattern_len);
+
+    let mut buffer = Vec::with_capacity(bytes_per_line * height);
+    let mut line = vec![0; bytes_per_line];
+    let mut y = 0;
+    while y < height {
+        let mut repeat = 1;
+        if let [0x00, 0x00, 0xFF, count, rest @ ..] = input {
+            repeat = usize::from(*count).max(1);
+            input = rest;
+        }
+        let (rest, ()) = decode_line(input, &mut line, pattern_len)?;
+        input = rest;
+        for _ in 0..repeat.min(height - y) {
+            buffer.extend_from_slice(&line);
+        }
+        y += repeat;
+    }
+    let page = Page::from_buffer(width, height as u32, buffer);
+    Ok((input, (header, page)))
+}
+
+/// Encode a single scanline
+fn encode_line(line: &[u8], out: &mut Vec<u8>) {
+    fn flush(literal: &mut Vec<u8>, out: &mut Vec<u8>) {
+        if !literal.is_empty() {
+            out.push(0x80);
+            out.push(literal.len() as u8);
+            out.append(literal);
+        }
+    }
+
+    let mut literal = Vec::new();
+    let mut x = 0;
+    while x < line.len() {
+        let byte = line[x];
+        if byte == 0x00 || byte == 0xFF {
+            let run = line[x..]
+                .iter()
+                .take(0x7F)
+                .take_while(|&&b| b == byte)
+                .count();
+            flush(&mut literal, out);
+            out.push((byte & 0x80) | run as u8);
+            x += run;
+            continue;
+        }
+        let pattern = &line[x..(x + PATTERN_LEN).min(line.len())];
+        let count = line[x..]
+            .chunks_exact(PATTERN_LEN)
+            .take(0xFF)
+            .take_while(|chunk| *chunk == pattern)
+            .count();
+        if count >= 2 {
+            flush(&mut literal, out);
+            out.push(0x00);
+            out.push(count as u8);
+            out.extend_from_slice(pattern);
+            x += count * PATTERN_LEN;
+            continue;
+        }
+        literal.push(byte);
+        if literal.len() == 0xFF {
+            flush(&mut literal, out);
+        }
+        x += 1;
+    }
+    flush(&mut literal, out);
+}
+
+/// Encode a page as a monochrome GEM bit image
+///
+/// Identical consecutive lines are stored with vertical replication, the
+/// pixel size is that of the ATARI monochrome monitor.
+///
+/// ## Panics
+///
+/// If the page is wider or higher than 65535 pixels
+pub fn encode_img(page: &Page) -> Vec<u8> {
+    let header = [
+        1,
+        HEADER_WORDS,
+        1,
+        PATTERN_LEN as u16,
+        SCREEN_PIXEL_SIZE,
+        SCREEN_PIXEL_SIZE,
+        u16::try_from(page.bit_width()).expect("image too wide"),
+        u16::try_from(page.bit_height()).expect("image too high"),
+    ];
+    let mut out: Vec<u8> = header.iter().flat_map(|w| w.to_be_bytes()).collect();
+
+    // pages may have more than the minimal number of bytes per line
+    let bytes_per_line = page.bit_width().div_ceil(8) as usize;
+    let lines: Vec<&[u8]> = page
+        .as_slice()
+        .chunks_exact(page.bytes_per_line() as usize)
+        .map(|line| &line[..bytes_per_line])
+        .collect();
+    let mut y = 0;
+    while y < lines.len() {
+        let line = lines[y];
+        let repeat = lines[y..]
+            .iter()
+            .take(0xFF)
+            .take_while(|l| **l == line)
+            .count();
+        if repeat > 1 {
+            out.extend_from_slice(&[0x00, 0x00, 0xFF, repeat as u8]);
+        }
+        encode_line(line, &mut out);
+        y += repeat;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_img, parse_img, ImgHeader};
+    use crate::raster::Page;
+
+    #[test]
+    fn test_decode() {
+        #[rustfmt::skip]
+        let data = [
+            0, 1, 0, 8, 0, 1, 0, 2, 0, 85, 0, 85, 0, 20, 0, 4,
+            // 3 lines: solid black, bit string
+            0x00, 0x00, 0xFF, 0x03, 0x81, 0x80, 0x02, 0xAB, 0xC0,
+            // pattern run
+            0x00, 0x01, 0x0F, 0xF0, 0x80, 0x01, 0x00,
+        ];
+        let (rest, (header, page)) = parse_img(&data).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(header.pattern_len, 2);
+        assert_eq!((page.bit_width(), page.bit_height()), (20, 4));
+        #[rustfmt::skip]
+        assert_eq!(page.as_slice(), [
+            0xFF, 0xAB, 0xC0,
+            0xFF, 0xAB, 0xC0,
+            0xFF, 0xAB, 0xC0,
+            0x0F, 0xF0, 0x00,
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (width, height) = (100, 40);
+        let buffer: Vec<u8> = (0..13 * height)
+            .map(|i: u32| match (i / 13) % 4 {
+                0 => (i * 37 % 251) as u8,
+                1 => [0xAA, 0x55][i as usize % 2],
+                2 => 0xFF,
+                _ => 0x00,
+            })
+            .collect();
+        let page = Page::from_buffer(width, height, buffer);
+        let encoded = encode_img(&page);
+        assert!(ImgHeader::detect(&encoded));
+
+        let (_, (header, decoded)) = parse_img(&encoded).unwrap();
+        assert_eq!((header.width, header.height), (100, 40));
+        assert_eq!(decoded.as_slice(), page.as_slice());
+    }
+}
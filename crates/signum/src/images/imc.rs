@@ -6,7 +6,7 @@
 
 use crate::{
     docs::{bytes16, bytes32},
-    util::{BitIter, Bytes16, Bytes32},
+    util::{BitIter, BitWriter, Bytes16, Bytes32},
 };
 use core::fmt;
 use nom::{
@@ -36,8 +36,11 @@ pub struct ImcHeader {
     size_of_data: u32,
     /// Final XOR
     final_xor: Bytes16,
+    /// Unknown, `AA AA` in `TEST.IMC`
     u4: Bytes16,
+    /// Unknown, `55 55 AA AA` in `TEST.IMC`
     u5: Bytes32,
+    /// Unknown, `2B 6B D4 D4` in `TEST.IMC`
     u6: Bytes32,
 }
 
@@ -52,6 +55,9 @@ impl fmt::Debug for ImcHeader {
             .field("size_of_bits", &self.size_of_bits)
             .field("size_of_data", &self.size_of_data)
             .field("final_xor", &self.final_xor)
+            .field("u4", &self.u4)
+            .field("u5", &self.u5)
+            .field("u6", &self.u6)
             .finish()
     }
 }
@@ -105,6 +111,19 @@ fn load_chunk<'a>(src: &mut std::slice::Iter<'a, u8>, dest: &mut [u8]) -> IResul
 pub struct MonochromeScreen(Vec<u8>);
 
 impl MonochromeScreen {
+    /// The width of the screen in pixels
+    pub const WIDTH: u32 = 640;
+    /// The height of the screen in pixels
+    pub const HEIGHT: u32 = 400;
+    /// The number of bytes in the screen buffer
+    const SIZE: usize = 32000;
+
+    /// Create a screen from a buffer, which is padded or cut to 32000 bytes
+    pub(crate) fn new(mut buffer: Vec<u8>) -> Self {
+        buffer.resize(Self::SIZE, 0);
+        Self(buffer)
+    }
+
     /// Return the underlying buffer
     ///
     /// The buffer is ordered in scanlines, with one byte representing 8 consecutive pixels.
@@ -114,6 +133,11 @@ impl MonochromeScreen {
         self.0
     }
 
+    /// Return a reference to the underlying buffer
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
     /// Output the screen as a Portable Bitmap (PBM)
     pub fn write_as_pbm<W: std::fmt::Write>(&self, out: &mut W) -> std::fmt::Result {
         writeln!(out, "P1 640 400")?;
@@ -290,12 +314,174 @@ pub fn decode_imc(src: &[u8]) -> IResult<&[u8], (ImcHeader, MonochromeScreen)> {
     Ok((byte_iter.as_slice(), (header, MonochromeScreen(buffer))))
 }
 
+/// Select one half of the even or odd bytes of a chunk, as used by `load_chunk`
+fn half_indices(half: usize) -> impl Iterator<Item = usize> {
+    let start = (half / 2) * 16 + half % 2;
+    (0..8).map(move |i| start + 2 * i)
+}
+
+/// Apply the inverse of subroutine I (`stride = 2`) or J (`stride = 4`)
+fn delta_encode(chunk: &[u8; 32], stride: usize) -> [u8; 32] {
+    let mut out = *chunk;
+    for i in stride..32 {
+        out[i] = chunk[i] ^ chunk[i - stride];
+    }
+    out
+}
+
+/// The number of data bytes needed for the halves of a (delta-encoded) chunk
+fn packed_size(temp: &[u8; 32]) -> usize {
+    (0..4)
+        .map(
+            |half| match half_indices(half).filter(|&i| temp[i] != 0).count() {
+                0 => 0,
+                n => n + 1,
+            },
+        )
+        .sum()
+}
+
+/// Write a single (non-empty) chunk of 16 lines with 2 bytes each
+fn encode_chunk(chunk: &[u8; 32], bits: &mut BitWriter, data: &mut Vec<u8>) {
+    let candidates = [*chunk, delta_encode(chunk, 2), delta_encode(chunk, 4)];
+    let (d3, temp, size) = candidates
+        .iter()
+        .enumerate()
+        .map(|(d3, temp)| (d3, temp, packed_size(temp)))
+        .min_by_key(|&(_, _, size)| size)
+        .unwrap();
+
+    if size >= 32 {
+        // subroutine E
+        bits.write_bits(3, 2);
+        data.extend_from_slice(chunk);
+        return;
+    }
+
+    bits.write_bits(d3, 2);
+    for half in 0..4 {
+        let mask = half_indices(half)
+            .enumerate()
+            .filter(|&(_, i)| temp[i] != 0)
+            .fold(0u8, |mask, (k, _)| mask | (0x80 >> k));
+        bits.write_bit(mask != 0);
+        if mask != 0 {
+            data.push(mask);
+            data.extend(half_indices(half).map(|i| temp[i]).filter(|&b| b != 0));
+        }
+    }
+}
+
+const HCHUNKS: usize = (MonochromeScreen::WIDTH / 16) as usize;
+const VCHUNKS: usize = (MonochromeScreen::HEIGHT / 16) as usize;
+const BYTES_PER_LINE: usize = HCHUNKS * 2;
+
+/// The `final_xor` values the encoder tries, i.e. none and two checkerboard patterns
+const FINAL_XOR: [[u8; 2]; 3] = [[0x00, 0x00], [0xAA, 0x55], [0x55, 0xAA]];
+
+/// The unknown header fields `u4`, `u5` and `u6`
+///
+/// Their meaning is not known, so the encoder writes the values found in
+/// `TEST.IMC` instead of zeros.
+const HEADER_UNKNOWN: [u8; 10] = [0xAA, 0xAA, 0x55, 0x55, 0xAA, 0xAA, 0x2B, 0x6B, 0xD4, 0xD4];
+
+/// Encode the chunks of a screen buffer, returning the bit- and the byte-stream
+fn encode_chunks(buffer: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut bits = BitWriter::new();
+    let mut data = Vec::new();
+    for group in buffer.chunks_exact(BYTES_PER_LINE * 16).take(VCHUNKS) {
+        let chunks: Vec<[u8; 32]> = (0..HCHUNKS)
+            .map(|j| {
+                let mut chunk = [0u8; 32];
+                for (i, line) in group.chunks_exact(BYTES_PER_LINE).enumerate() {
+                    chunk[i * 2..][..2].copy_from_slice(&line[j * 2..][..2]);
+                }
+                chunk
+            })
+            .collect();
+        let has_data = chunks.iter().any(|chunk| chunk.iter().any(|&b| b != 0));
+        bits.write_bit(has_data);
+        if !has_data {
+            continue;
+        }
+        for chunk in &chunks {
+            let present = chunk.iter().any(|&b| b != 0);
+            bits.write_bit(present);
+            if present {
+                encode_chunk(chunk, &mut bits, &mut data);
+            }
+        }
+    }
+    (bits.done(), data)
+}
+
+/// Encode a screen as a Signum! .IMC image, including the magic bytes `bimc0002`
+///
+/// This is the inverse of [`parse_imc`]. Every chunk of 16x16 pixels uses the
+/// smallest of the available encodings and empty chunks and rows of chunks are
+/// skipped. If it makes the file smaller, a checkerboard pattern is removed
+/// before encoding and restored with the final XOR on decoding.
+pub fn encode_imc(screen: &MonochromeScreen) -> Vec<u8> {
+    let (final_xor, (bits, data)) = FINAL_XOR
+        .iter()
+        .map(|&[a, b]| {
+            let mut buffer = screen.0.clone();
+            for (k, line) in buffer.chunks_exact_mut(BYTES_PER_LINE).enumerate() {
+                let x = if k % 2 == 0 { a } else { b };
+                line.iter_mut().for_each(|byte| *byte ^= x);
+            }
+            ([a, b], encode_chunks(&buffer))
+        })
+        .min_by_key(|(_, (bits, data))| bits.len() + data.len())
+        .unwrap();
+
+    let mut out = Vec::with_capacity(40 + bits.len() + data.len());
+    out.extend_from_slice(b"bimc0002");
+    out.extend_from_slice(&((32 + bits.len() + data.len()) as u32).to_be_bytes());
+    out.extend_from_slice(&(MonochromeScreen::WIDTH as u16).to_be_bytes());
+    out.extend_from_slice(&(MonochromeScreen::HEIGHT as u16).to_be_bytes());
+    out.extend_from_slice(&(HCHUNKS as u16).to_be_bytes());
+    out.extend_from_slice(&(VCHUNKS as u16).to_be_bytes());
+    out.extend_from_slice(&(bits.len() as u32).to_be_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(&final_xor);
+    out.extend_from_slice(&HEADER_UNKNOWN);
+    out.extend_from_slice(&bits);
+    out.extend_from_slice(&data);
+    out
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{encode_imc, parse_imc, MonochromeScreen};
+
     const DATA: &[u8] = include_bytes!("../../res/TEST.IMC");
 
     #[test]
     fn test_decode() {
         super::parse_imc(DATA).unwrap();
     }
+
+    #[test]
+    fn test_round_trip() {
+        let (_, screen) = parse_imc(DATA).unwrap();
+        let encoded = encode_imc(&screen);
+        let (header, decoded) = parse_imc(&encoded).unwrap();
+        assert_eq!(header.size as usize, encoded.len() - 8);
+        assert_eq!(header.final_xor.0, 0xAA55);
+        assert_eq!(encoded[0x1C..0x28], DATA[0x1C..0x28]);
+        assert_eq!(decoded.as_slice(), screen.as_slice());
+
+        // raw, delta and empty chunks
+        let buffer = (0..32000u32)
+            .map(|i| match (i / 80) / 16 % 3 {
+                0 => (i * 37 % 251) as u8,
+                1 => 0xF0,
+                _ => 0,
+            })
+            .collect();
+        let screen = MonochromeScreen::new(buffer);
+        let (_, decoded) = parse_imc(&encode_imc(&screen)).unwrap();
+        assert_eq!(decoded.as_slice(), screen.as_slice());
+    }
 }
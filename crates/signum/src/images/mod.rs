@@ -1,2 +1,4 @@
 //! # Image formats
+pub mod degas;
+pub mod gem;
 pub mod imc;
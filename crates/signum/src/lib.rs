@@ -6,7 +6,8 @@
 //! and related formats, that were used by the word processor [Signum!]
 //! published in 1986 by [Application Systems Heidelberg][ASH] (Germany).
 //!
-//! At the moment, only reading the files is supported.
+//! At the moment, only reading the files is supported, with the exception
+//! of Signum!2 documents (see [`docs::SDoc::write_to`]).
 //!
 //! [Signum!]: https://de.wikipedia.org/wiki/Signum_(Textverarbeitungsprogramm)
 //! [ASH]: https://application-systems.de
@@ -12,7 +12,7 @@ mod util;
 pub use doc::render_doc_page;
 pub use font::{render_editor_text, render_printer_char};
 pub use page::Page;
-pub use trace::{straight_up_to, Dir};
+pub use trace::{decompose, trace, Curve, Dir, LatticePoint, Path, Point, Segment, ALPHA_MAX};
 
 #[derive(Debug)]
 /// Drawing Error
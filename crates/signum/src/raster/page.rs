@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, collections::VecDeque, slice::SliceIndex};
+use std::{cmp::Ordering, slice::SliceIndex};
 
 #[cfg(feature = "image")]
 use image::{GrayAlphaImage, GrayImage};
@@ -10,7 +10,7 @@ use crate::{
     util::{data::BIT_PROJECTION, BitIter, BitWriter},
 };
 
-use super::{scalers::VScaler, trace::Dir, DrawPrintErr};
+use super::{scalers::VScaler, DrawPrintErr};
 
 /// A virtual page that works just like the atari monochrome screen
 ///
@@ -37,6 +37,25 @@ impl From<MonochromeScreen> for Page {
     }
 }
 
+impl From<&'_ Page> for MonochromeScreen {
+    /// Copy the top left corner of a page into a (fixed-size) screen
+    ///
+    /// Parts of the page outside of the screen are cut off, smaller pages are
+    /// padded with white.
+    fn from(page: &Page) -> Self {
+        let bytes_per_line = (MonochromeScreen::WIDTH / 8) as usize;
+        let mut buffer = vec![0; bytes_per_line * MonochromeScreen::HEIGHT as usize];
+        let page_lines = page
+            .buffer
+            .chunks_exact(page.bytes_per_line.max(1) as usize);
+        for (line, page_line) in buffer.chunks_exact_mut(bytes_per_line).zip(page_lines) {
+            let len = bytes_per_line.min(page_line.len());
+            line[..len].copy_from_slice(&page_line[..len]);
+        }
+        MonochromeScreen::new(buffer)
+    }
+}
+
 impl From<&'_ PSetChar<'_>> for Page {
     fn from(value: &PSetChar<'_>) -> Self {
         let bytes_per_line = value.width.into();
@@ -61,6 +80,20 @@ impl Page {
         }
     }
 
+    /// Create a page from a buffer with `(width + 7) / 8` bytes per line
+    ///
+    /// The buffer is padded with white or cut to fit the height.
+    pub(crate) fn from_buffer(width: u32, height: u32, mut buffer: Vec<u8>) -> Self {
+        let bytes_per_line = width.div_ceil(8);
+        buffer.resize(bytes_per_line as usize * height as usize, 0);
+        Page {
+            bytes_per_line,
+            width,
+            height,
+            buffer,
+        }
+    }
+
     /// The width in B/W pixels
     pub fn bit_width(&self) -> u32 {
         self.width
@@ -91,74 +124,6 @@ impl Page {
         })
     }
 
-    /// Get the first outline
-    pub fn first_outline(&self) -> Option<impl Iterator<Item = (u32, u32)> + '_> {
-        self.first_ink().map(|p0| {
-            let (x0, y0) = p0;
-            let mut p = (x0, y0 + 1);
-            let mut first = VecDeque::from([p0, p]);
-            let mut dir = Dir::Down;
-            std::iter::from_fn(move || {
-                if let Some(x) = first.pop_front() {
-                    return Some(x);
-                }
-                if p == p0 {
-                    return None;
-                }
-                let (x, y) = p;
-                dir = match dir {
-                    Dir::Down => {
-                        // left is blank, right is ink
-                        let bl = x > 0 && self.ink_at(x - 1, y);
-                        let br = self.ink_at(x, y);
-                        match (bl, br) {
-                            (true, _) => Dir::Left,
-                            (false, true) => Dir::Down,
-                            (false, false) => Dir::Right,
-                        }
-                    }
-                    Dir::Up => {
-                        // left is ink, right is blank
-                        let tl = x > 0 && y > 0 && self.ink_at(x - 1, y - 1);
-                        let tr = y > 0 && self.ink_at(x, y - 1);
-                        match (tl, tr) {
-                            (_, true) => Dir::Right,
-                            (true, false) => Dir::Up,
-                            (false, false) => Dir::Left,
-                        }
-                    }
-                    Dir::Left => {
-                        // top is blank, bottom is ink
-                        let tl = x > 0 && y > 0 && self.ink_at(x - 1, y - 1);
-                        let bl = x > 0 && self.ink_at(x - 1, y);
-                        match (bl, tl) {
-                            (_, true) => Dir::Up,
-                            (true, false) => Dir::Left,
-                            (false, false) => Dir::Down,
-                        }
-                    }
-                    Dir::Right => {
-                        // top is ink, bottom is blank
-                        let tr = y > 0 && self.ink_at(x, y - 1);
-                        let br = self.ink_at(x, y);
-                        match (tr, br) {
-                            (_, true) => Dir::Down,
-                            (true, false) => Dir::Right,
-                            (false, false) => Dir::Up,
-                        }
-                    }
-                };
-                p = match dir {
-                    Dir::Down => (x, y + 1),
-                    Dir::Up => (x, y - 1),
-                    Dir::Left => (x - 1, y),
-                    Dir::Right => (x + 1, y),
-                };
-                Some(p)
-            })
-        })
-    }
-
     /// check whether there is ink at a given coordinate
     pub fn ink_at(&self, x: u32, y: u32) -> bool {
         if x >= self.width {
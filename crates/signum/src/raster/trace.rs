@@ -5,8 +5,23 @@
 //! the library of the same name and are not guaranteed to see the same results.
 //!
 //! [potrace]: https://potrace.sourceforge.net/potrace.pdf
+//!
+//! The pipeline has the same stages as described in the paper:
+//!
+//! 1. [`decompose`] splits a bitmap into closed [`Path`]s on the pixel lattice,
+//!    where holes are oriented opposite to the outer boundaries, and ambiguous
+//!    (diagonal) configurations connect ink.
+//! 2. [`Path::polygon`] finds the optimal polygon (i.e. the one with the fewest
+//!    segments and the smallest penalty) that approximates the path.
+//! 3. [`Path::curve`] adjusts the vertices of that polygon and smooths it into
+//!    a [`Curve`] of straight and cubic Bézier [`Segment`]s.
+//!
+//! The final curve optimization step of Potrace is not implemented.
+//!
+//! Coordinates are in pixels, with the origin at the top-left corner of the
+//! bitmap and the y-axis pointing down, just like for [`Page`].
 
-use std::cmp::Ordering;
+use super::Page;
 
 /// Cardinal Direction
 #[repr(u8)]
@@ -23,21 +38,6 @@ pub enum Dir {
 }
 
 impl Dir {
-    #[allow(dead_code)]
-    fn of((ax, ay): (u32, u32), (bx, by): (u32, u32)) -> Dir {
-        match ax.cmp(&bx) {
-            Ordering::Less => Dir::Right,
-            Ordering::Equal => {
-                if ay < by {
-                    Dir::Down
-                } else {
-                    Dir::Up
-                }
-            }
-            Ordering::Greater => Dir::Left,
-        }
-    }
-
     /// ```
     /// # use signum::raster::Dir;
     ///
@@ -61,149 +61,782 @@ impl Dir {
     }
 }
 
-/// A constraint on the angle from a cardinal direction that the points v_k can have
+/// A point on the pixel lattice
+pub type LatticePoint = (i32, i32);
+
+/// A point in the plane
+pub type Point = (f64, f64);
+
+/// A closed path along the boundaries of pixels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    /// Whether this path is the outer boundary of an area with ink (`true`),
+    /// or the boundary of a hole within such an area (`false`)
+    pub outer: bool,
+    /// The points of the path, every one adjacent to the next
+    pub points: Vec<LatticePoint>,
+}
+
+/// A segment of a [`Curve`]
 #[derive(Debug, Copy, Clone, PartialEq)]
-struct Constraint {
-    min: f64,
-    max: f64,
+pub enum Segment {
+    /// A straight line to `vertex`, followed by a straight line to `end`
+    Corner {
+        /// The corner point
+        vertex: Point,
+        /// The end point of the segment
+        end: Point,
+    },
+    /// A cubic Bézier curve with control points `c1` and `c2`
+    Bezier {
+        /// The first control point
+        c1: Point,
+        /// The second control point
+        c2: Point,
+        /// The end point of the segment
+        end: Point,
+    },
+}
+
+impl Segment {
+    /// The end point of the segment
+    pub fn end(&self) -> Point {
+        match *self {
+            Self::Corner { end, .. } | Self::Bezier { end, .. } => end,
+        }
+    }
 }
 
-type Val = fn(f64, f64) -> f64;
-type MakeConstraint = fn((f64, f64), (f64, f64)) -> Constraint;
+/// A closed, smoothed outline
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    /// See [`Path::outer`]
+    pub outer: bool,
+    /// The segments, where each one starts at the end of the previous one
+    pub segments: Vec<Segment>,
+}
 
-impl Constraint {
-    fn of(main_diff: f64, cross: f64) -> Self {
-        let main = main_diff.abs();
-        let near = (main - 1.0).max(0.0);
-        let far = main + 1.0;
+impl Curve {
+    /// The start (and end) point of the curve
+    pub fn start(&self) -> Point {
+        self.segments.last().map_or((0.0, 0.0), Segment::end)
+    }
+}
 
+/// Default for the `alphamax` parameter of [`Path::curve`]
+///
+/// Vertices with a larger "sharpness" than this are kept as corners.
+pub const ALPHA_MAX: f64 = 1.0;
+
+struct Bitmap {
+    width: i32,
+    height: i32,
+    bits: Vec<bool>,
+}
+
+impl Bitmap {
+    fn new(page: &Page) -> Self {
+        let width = page.bit_width() as i32;
+        let height = page.bit_height() as i32;
+        let mut bits = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                bits.push(page.ink_at(x as u32, y as u32));
+            }
+        }
         Self {
-            min: (cross - 1.0) / if cross >= 1.0 { far } else { near },
-            max: (cross + 1.0) / if cross <= -1.0 { far } else { near },
+            width,
+            height,
+            bits,
         }
     }
 
-    fn h_val(xdiff: f64, ydiff: f64) -> f64 {
-        ydiff / xdiff
+    fn get(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height && {
+            self.bits[(y * self.width + x) as usize]
+        }
     }
 
-    fn v_val(xdiff: f64, ydiff: f64) -> f64 {
-        xdiff / ydiff
+    fn first_ink(&self) -> Option<LatticePoint> {
+        let index = self.bits.iter().position(|b| *b)? as i32;
+        Some((index % self.width, index / self.width))
     }
 
-    fn of_h(v_i: (f64, f64), v_j: (f64, f64)) -> Self {
-        let xdiff = v_j.0 - v_i.0;
-        let ydiff = v_j.1 - v_i.1;
-        Self::of(xdiff, ydiff)
+    /// Follow the boundary that starts at the top-left corner of the pixel `(x0, y0)`,
+    /// keeping the ink on the left.
+    fn find_path(&self, (x0, y0): LatticePoint) -> Vec<LatticePoint> {
+        let (mut x, mut y) = (x0, y0);
+        let (mut dx, mut dy) = (0, 1);
+        let mut points = Vec::new();
+        loop {
+            points.push((x, y));
+            x += dx;
+            y += dy;
+            if (x, y) == (x0, y0) {
+                break;
+            }
+            let ahead_left = self.get(x + (dx + dy - 1) / 2, y + (dy - dx - 1) / 2);
+            let ahead_right = self.get(x + (dx - dy - 1) / 2, y + (dy + dx - 1) / 2);
+            (dx, dy) = match (ahead_left, ahead_right) {
+                // straight on
+                (true, false) => (dx, dy),
+                // turn left
+                (false, false) => (dy, -dx),
+                // turn right, this includes the ambiguous case (false, true)
+                // where turning right connects the diagonal pixels
+                (true, true) | (false, true) => (-dy, dx),
+            };
+        }
+        points
     }
 
-    fn of_v(v_i: (f64, f64), v_j: (f64, f64)) -> Self {
-        let xdiff = v_j.0 - v_i.0;
-        let ydiff = v_j.1 - v_i.1;
-        Self::of(ydiff, xdiff)
+    /// Invert all pixels within a closed path
+    fn xor_path(&mut self, points: &[LatticePoint]) {
+        let n = points.len();
+        for (i, &(x, y)) in points.iter().enumerate() {
+            let (nx, ny) = points[(i + 1) % n];
+            if x == nx {
+                let row = y.min(ny);
+                let start = (row * self.width + x) as usize;
+                let end = ((row + 1) * self.width) as usize;
+                for bit in &mut self.bits[start..end] {
+                    *bit = !*bit;
+                }
+            }
+        }
     }
+}
 
-    fn update(&mut self, new: Constraint) {
-        self.min = self.min.max(new.min);
-        self.max = self.max.min(new.max);
+/// Decompose a bitmap into closed paths
+///
+/// Every outer path is followed by the paths of the holes (and islands within)
+/// it contains.
+pub fn decompose(page: &Page) -> Vec<Path> {
+    let original = Bitmap::new(page);
+    let mut work = Bitmap::new(page);
+    let mut paths = Vec::new();
+    while let Some(p0) = work.first_ink() {
+        let mut points = work.find_path(p0);
+        work.xor_path(&points);
+        let outer = original.get(p0.0, p0.1);
+        if !outer {
+            // Reverse the orientation, so that holes are subtracted with the nonzero rule
+            points[1..].reverse();
+        }
+        paths.push(Path { outer, points });
     }
+    paths
+}
+
+/// Trace a bitmap into smooth curves
+pub fn trace(page: &Page) -> Vec<Curve> {
+    decompose(page)
+        .iter()
+        .map(|path| path.curve(ALPHA_MAX))
+        .collect()
 }
 
 #[inline]
-fn v_sub((ax, ay): (f64, f64), (bx, by): (f64, f64)) -> (f64, f64) {
-    (ax - bx, ay - by)
+fn sign(x: i32) -> i32 {
+    x.signum()
 }
 
 #[inline]
-#[allow(dead_code)]
-fn v_add((ax, ay): (f64, f64), (bx, by): (f64, f64)) -> (f64, f64) {
-    (ax + bx, ay + by)
+fn xprod((ax, ay): LatticePoint, (bx, by): LatticePoint) -> i64 {
+    i64::from(ax) * i64::from(by) - i64::from(ay) * i64::from(bx)
 }
 
+/// Check whether `b` is in the cyclic interval `[a, c)`
 #[inline]
-fn to_float((x, y): (u32, u32)) -> (f64, f64) {
-    (x as f64, y as f64)
+fn cyclic(a: usize, b: usize, c: usize) -> bool {
+    if a <= c {
+        a <= b && b < c
+    } else {
+        a <= b || b < c
+    }
+}
+
+#[inline]
+fn floordiv(a: i64, n: i64) -> i64 {
+    a.div_euclid(n)
+}
+
+#[inline]
+fn interval(lambda: f64, (ax, ay): Point, (bx, by): Point) -> Point {
+    (ax + lambda * (bx - ax), ay + lambda * (by - ay))
+}
+
+/// Running sums of the coordinates (relative to the first point)
+#[derive(Debug, Default, Copy, Clone)]
+struct Sums {
+    x: f64,
+    y: f64,
+    x2: f64,
+    xy: f64,
+    y2: f64,
 }
 
-/// Check for how many points the paths should be considered straight
-pub fn straight_up_to(points: &[(u32, u32)], i: usize) -> usize {
-    let n = points.len();
-    assert!(n >= 4);
-    let v_i = to_float(points[i]);
-    let i_1 = (i + 1) % n;
-    let mut v_jm1 = to_float(points[i_1]); // v_{j - 1}
-    let d0 = v_sub(v_jm1, v_i);
-    let dir = Dir::of_dir(d0);
-    let mut directions = dir as u8;
+type QuadForm = [[f64; 3]; 3];
+
+fn quadform(q: &QuadForm, (x, y): Point) -> f64 {
+    let v = [x, y, 1.0];
+    let mut sum = 0.0;
+    for (i, row) in q.iter().enumerate() {
+        for (j, val) in row.iter().enumerate() {
+            sum += v[i] * val * v[j];
+        }
+    }
+    sum
+}
+
+impl Path {
+    fn sums(&self) -> Vec<Sums> {
+        let (x0, y0) = self.points[0];
+        let mut sums = Vec::with_capacity(self.points.len() + 1);
+        let mut acc = Sums::default();
+        sums.push(acc);
+        for &(x, y) in &self.points {
+            let (x, y) = (f64::from(x - x0), f64::from(y - y0));
+            acc.x += x;
+            acc.y += y;
+            acc.x2 += x * x;
+            acc.xy += x * y;
+            acc.y2 += y * y;
+            sums.push(acc);
+        }
+        sums
+    }
+
+    /// For every point `i`, find the furthest point `lon[i]` such that the path
+    /// from `i` to `lon[i]` is straight
+    fn longest_straight(&self) -> Vec<usize> {
+        let pt = &self.points;
+        let n = pt.len();
+        let mut nc = vec![0; n];
+        let mut k = 0;
+        for i in (0..n).rev() {
+            if pt[i].0 != pt[k].0 && pt[i].1 != pt[k].1 {
+                k = i + 1;
+            }
+            nc[i] = k;
+        }
+
+        let mut pivk = vec![0; n];
+        for i in (0..n).rev() {
+            let mut ct = [0; 4];
+            let (x1, y1) = pt[(i + 1) % n];
+            let dir = (3 + 3 * (x1 - pt[i].0) + (y1 - pt[i].1)) / 2;
+            ct[dir as usize] += 1;
+
+            let mut constraint = [(0, 0), (0, 0)];
+            let mut k = nc[i];
+            let mut k1 = i;
+            let mut found = false;
+            loop {
+                let dir = (3 + 3 * sign(pt[k].0 - pt[k1].0) + sign(pt[k].1 - pt[k1].1)) / 2;
+                ct[dir as usize] += 1;
+
+                // all four directions have occurred
+                if ct.iter().all(|c| *c > 0) {
+                    pivk[i] = k1;
+                    found = true;
+                    break;
+                }
+
+                let cur = (pt[k].0 - pt[i].0, pt[k].1 - pt[i].1);
+                if xprod(constraint[0], cur) < 0 || xprod(constraint[1], cur) > 0 {
+                    break;
+                }
+
+                if cur.0.abs() > 1 || cur.1.abs() > 1 {
+                    let off = (
+                        cur.0
+                            + if cur.1 >= 0 && (cur.1 > 0 || cur.0 < 0) {
+                                1
+                            } else {
+                                -1
+                            },
+                        cur.1
+                            + if cur.0 <= 0 && (cur.0 < 0 || cur.1 < 0) {
+                                1
+                            } else {
+                                -1
+                            },
+                    );
+                    if xprod(constraint[0], off) >= 0 {
+                        constraint[0] = off;
+                    }
+                    let off = (
+                        cur.0
+                            + if cur.1 <= 0 && (cur.1 < 0 || cur.0 < 0) {
+                                1
+                            } else {
+                                -1
+                            },
+                        cur.1
+                            + if cur.0 >= 0 && (cur.0 > 0 || cur.1 < 0) {
+                                1
+                            } else {
+                                -1
+                            },
+                    );
+                    if xprod(constraint[1], off) <= 0 {
+                        constraint[1] = off;
+                    }
+                }
+                k1 = k;
+                k = nc[k1];
+                if !cyclic(k, i, k1) {
+                    break;
+                }
+            }
+            if found {
+                continue;
+            }
+            // k1 was the last corner satisfying the constraint, k the first one
+            // violating it. Find the last point between them that satisfies it.
+            let dk = (sign(pt[k].0 - pt[k1].0), sign(pt[k].1 - pt[k1].1));
+            let cur = (pt[k1].0 - pt[i].0, pt[k1].1 - pt[i].1);
+            let a = xprod(constraint[0], cur);
+            let b = xprod(constraint[0], dk);
+            let c = xprod(constraint[1], cur);
+            let d = xprod(constraint[1], dk);
+            let mut j = i64::MAX;
+            if b < 0 {
+                j = floordiv(a, -b);
+            }
+            if d > 0 {
+                j = j.min(floordiv(-c, d));
+            }
+            pivk[i] = (k1 as i64 + j).rem_euclid(n as i64) as usize;
+        }
+
+        let mut lon = vec![0; n];
+        let mut j = pivk[n - 1];
+        lon[n - 1] = j;
+        for i in (0..n - 1).rev() {
+            if cyclic(i + 1, pivk[i], j) {
+                j = pivk[i];
+            }
+            lon[i] = j;
+        }
+        let mut i = n - 1;
+        while cyclic((i + 1) % n, j, lon[i]) {
+            lon[i] = j;
+            if i == 0 {
+                break;
+            }
+            i -= 1;
+        }
+        lon
+    }
+
+    /// The penalty for a straight segment from `i` to `j` (where `j` may be `>= n`)
+    fn penalty(&self, sums: &[Sums], i: usize, j: usize) -> f64 {
+        let pt = &self.points;
+        let n = pt.len();
+        let (j, wrap) = if j >= n { (j - n, 1.0) } else { (j, 0.0) };
+        let s = |f: fn(&Sums) -> f64| f(&sums[j + 1]) - f(&sums[i]) + wrap * f(&sums[n]);
+        let x = s(|s| s.x);
+        let y = s(|s| s.y);
+        let x2 = s(|s| s.x2);
+        let xy = s(|s| s.xy);
+        let y2 = s(|s| s.y2);
+        let k = (j + 1) as f64 - i as f64 + wrap * n as f64;
+
+        let px = f64::from(pt[i].0 + pt[j].0) / 2.0 - f64::from(pt[0].0);
+        let py = f64::from(pt[i].1 + pt[j].1) / 2.0 - f64::from(pt[0].1);
+        let ey = f64::from(pt[j].0 - pt[i].0);
+        let ex = -f64::from(pt[j].1 - pt[i].1);
+
+        let a = (x2 - 2.0 * x * px) / k + px * px;
+        let b = (xy - x * py - y * px) / k + px * py;
+        let c = (y2 - 2.0 * y * py) / k + py * py;
+
+        let s = ex * ex * a + 2.0 * ex * ey * b + ey * ey * c;
+        s.max(0.0).sqrt()
+    }
+
+    /// Compute the optimal polygon for this path
+    ///
+    /// Returns the indices of the points that are the vertices of the polygon.
+    pub fn polygon(&self) -> Vec<usize> {
+        let n = self.points.len();
+        let sums = self.sums();
+        let lon = self.longest_straight();
 
-    let mut j = (i_1 + 1) % n;
-    let kmax = (j + n - 3) % n;
+        // clipped paths
+        let mut clip0 = vec![0; n];
+        for (i, clip) in clip0.iter_mut().enumerate() {
+            let mut c = (lon[(i + n - 1) % n] + n - 1) % n;
+            if c == i {
+                c = (i + 1) % n;
+            }
+            *clip = if c < i { n } else { c };
+        }
 
-    let (make_constraint, val): (MakeConstraint, Val) = match dir {
-        Dir::Down | Dir::Up => (Constraint::of_v, Constraint::v_val),
-        Dir::Left | Dir::Right => (Constraint::of_h, Constraint::h_val),
-    };
-    let mut constraint = make_constraint(v_i, v_jm1);
+        // backwards path clipping, j <= clip0[i] iff clip1[j] <= i
+        let mut clip1 = vec![0; n + 1];
+        let mut j = 1;
+        for (i, clip) in clip0.iter().enumerate() {
+            while j <= *clip {
+                clip1[j] = i;
+                j += 1;
+            }
+        }
+
+        // seg0[j]: longest path from 0 with j segments
+        let mut seg0 = vec![0; n + 1];
+        let mut i = 0;
+        let mut j = 0;
+        while i < n {
+            seg0[j] = i;
+            i = clip0[i];
+            j += 1;
+        }
+        seg0[j] = n;
+        let m = j;
+
+        // seg1[j]: longest path to n with m-j segments
+        let mut seg1 = vec![0; m + 1];
+        let mut i = n;
+        for j in (1..=m).rev() {
+            seg1[j] = i;
+            i = clip1[i];
+        }
 
-    while j != kmax {
-        let v_j = to_float(points[j]);
-        let d_j = v_sub(v_j, v_jm1);
-        directions |= Dir::of_dir(d_j) as u8;
+        // shortest path with m segments
+        let mut pen = vec![0.0; n + 1];
+        let mut prev = vec![0; n + 1];
+        for j in 1..=m {
+            for i in seg1[j]..=seg0[j] {
+                let mut best = -1.0;
+                let mut k = seg0[j - 1];
+                loop {
+                    let this = self.penalty(&sums, k, i) + pen[k];
+                    if best < 0.0 || this < best {
+                        prev[i] = k;
+                        best = this;
+                    }
+                    if k == clip1[i] {
+                        break;
+                    }
+                    k -= 1;
+                }
+                pen[i] = best;
+            }
+        }
 
-        if directions == 0b1111 {
-            return j - 1;
+        let mut po = vec![0; m];
+        let mut i = n;
+        for j in (0..m).rev() {
+            i = prev[i];
+            po[j] = i;
         }
-        let (xdiff, ydiff) = v_sub(v_j, v_i);
-        let r = val(xdiff, ydiff);
-        if r >= constraint.min && r <= constraint.max {
-            // Point valid, update constraint
-            constraint.update(make_constraint(v_i, v_j));
+        po
+    }
+
+    /// Best fitting line through the points `i..=j`, as center and direction
+    fn point_slope(&self, sums: &[Sums], i: usize, j: usize) -> (Point, Point) {
+        let n = self.points.len();
+        let (j, r) = if j >= n { (j - n, 1.0) } else { (j, 0.0) };
+        let s = |f: fn(&Sums) -> f64| f(&sums[j + 1]) - f(&sums[i]) + r * f(&sums[n]);
+        let x = s(|s| s.x);
+        let y = s(|s| s.y);
+        let x2 = s(|s| s.x2);
+        let xy = s(|s| s.xy);
+        let y2 = s(|s| s.y2);
+        let k = (j + 1) as f64 - i as f64 + r * n as f64;
+
+        let ctr = (x / k, y / k);
+
+        let mut a = (x2 - x * x / k) / k;
+        let b = (xy - x * y / k) / k;
+        let mut c = (y2 - y * y / k) / k;
+
+        // larger eigenvalue
+        let lambda2 = (a + c + ((a - c) * (a - c) + 4.0 * b * b).sqrt()) / 2.0;
+        a -= lambda2;
+        c -= lambda2;
+
+        let dir = if a.abs() >= c.abs() {
+            let l = (a * a + b * b).sqrt();
+            if l != 0.0 {
+                (-b / l, a / l)
+            } else {
+                (0.0, 0.0)
+            }
+        } else {
+            let l = (c * c + b * b).sqrt();
+            if l != 0.0 {
+                (-c / l, b / l)
+            } else {
+                (0.0, 0.0)
+            }
+        };
+        (ctr, dir)
+    }
+
+    /// Move the vertices of the polygon to the best fit with the path
+    ///
+    /// Each vertex stays within the unit square around its original position.
+    fn adjust_vertices(&self, po: &[usize]) -> Vec<Point> {
+        let n = self.points.len();
+        let m = po.len();
+        let sums = self.sums();
+        let (x0, y0) = self.points[0];
+
+        let q: Vec<QuadForm> = (0..m)
+            .map(|i| {
+                let j = (po[(i + 1) % m] + n - po[i]) % n + po[i];
+                let (ctr, dir) = self.point_slope(&sums, po[i], j);
+                let d = dir.0 * dir.0 + dir.1 * dir.1;
+                let mut q = [[0.0; 3]; 3];
+                if d != 0.0 {
+                    let v = [dir.1, -dir.0, dir.0 * ctr.1 - dir.1 * ctr.0];
+                    for (l, row) in q.iter_mut().enumerate() {
+                        for (k, val) in row.iter_mut().enumerate() {
+                            *val = v[l] * v[k] / d;
+                        }
+                    }
+                }
+                q
+            })
+            .collect();
+
+        let mut vertices = Vec::with_capacity(m);
+        for i in 0..m {
+            let (px, py) = self.points[po[i]];
+            let s = (f64::from(px - x0), f64::from(py - y0));
+            let j = (i + m - 1) % m;
+
+            let mut qf = [[0.0; 3]; 3];
+            for (l, row) in qf.iter_mut().enumerate() {
+                for (k, val) in row.iter_mut().enumerate() {
+                    *val = q[j][l][k] + q[i][l][k];
+                }
+            }
+
+            let w = loop {
+                let det = qf[0][0] * qf[1][1] - qf[0][1] * qf[1][0];
+                if det != 0.0 {
+                    break (
+                        (-qf[0][2] * qf[1][1] + qf[1][2] * qf[0][1]) / det,
+                        (qf[0][2] * qf[1][0] - qf[1][2] * qf[0][0]) / det,
+                    );
+                }
+                // the lines are parallel, add an orthogonal axis through the center
+                let (v0, v1) = if qf[0][0] > qf[1][1] {
+                    (-qf[0][1], qf[0][0])
+                } else if qf[1][1] != 0.0 {
+                    (-qf[1][1], qf[1][0])
+                } else {
+                    (1.0, 0.0)
+                };
+                let d = v0 * v0 + v1 * v1;
+                let v = [v0, v1, -v1 * s.1 - v0 * s.0];
+                for (l, row) in qf.iter_mut().enumerate() {
+                    for (k, val) in row.iter_mut().enumerate() {
+                        *val += v[l] * v[k] / d;
+                    }
+                }
+            };
+
+            let (xmin, ymin) = if (w.0 - s.0).abs() <= 0.5 && (w.1 - s.1).abs() <= 0.5 {
+                w
+            } else {
+                // minimize on the boundary of the unit square
+                let mut min = quadform(&qf, s);
+                let mut best = s;
+                let mut candidates = Vec::with_capacity(8);
+                if qf[0][0] != 0.0 {
+                    for z in [-0.5, 0.5].iter() {
+                        let y = s.1 + z;
+                        let x = -(qf[0][1] * y + qf[0][2]) / qf[0][0];
+                        if (x - s.0).abs() <= 0.5 {
+                            candidates.push((x, y));
+                        }
+                    }
+                }
+                if qf[1][1] != 0.0 {
+                    for z in [-0.5, 0.5].iter() {
+                        let x = s.0 + z;
+                        let y = -(qf[1][0] * x + qf[1][2]) / qf[1][1];
+                        if (y - s.1).abs() <= 0.5 {
+                            candidates.push((x, y));
+                        }
+                    }
+                }
+                for l in [-0.5, 0.5].iter() {
+                    for k in [-0.5, 0.5].iter() {
+                        candidates.push((s.0 + l, s.1 + k));
+                    }
+                }
+                for cand in candidates {
+                    let val = quadform(&qf, cand);
+                    if val < min {
+                        min = val;
+                        best = cand;
+                    }
+                }
+                best
+            };
+            vertices.push((xmin + f64::from(x0), ymin + f64::from(y0)));
         }
+        vertices
+    }
 
-        v_jm1 = v_j;
-        j = (j + 1) % n;
+    /// Trace this path into a smooth curve
+    ///
+    /// `alphamax` controls how sharp a vertex needs to be to be kept as a corner,
+    /// see [`ALPHA_MAX`].
+    pub fn curve(&self, alphamax: f64) -> Curve {
+        let po = self.polygon();
+        let vertex = self.adjust_vertices(&po);
+        let m = vertex.len();
+        let mut segments = Vec::with_capacity(m);
+        for i in 0..m {
+            let j = (i + 1) % m;
+            let k = (i + 2) % m;
+            let end = interval(0.5, vertex[k], vertex[j]);
+
+            let (vi, vj, vk) = (vertex[i], vertex[j], vertex[k]);
+            let ry = f64::from(sign_f(vk.0 - vi.0));
+            let rx = -f64::from(sign_f(vk.1 - vi.1));
+            let denom = ry * (vk.0 - vi.0) - rx * (vk.1 - vi.1);
+            let alpha = if denom != 0.0 {
+                let dpara = (vj.0 - vi.0) * (vk.1 - vi.1) - (vk.0 - vi.0) * (vj.1 - vi.1);
+                let dd = (dpara / denom).abs();
+                let alpha = if dd > 1.0 { 1.0 - 1.0 / dd } else { 0.0 };
+                alpha / 0.75
+            } else {
+                4.0 / 3.0
+            };
+
+            if alpha >= alphamax {
+                segments.push(Segment::Corner { vertex: vj, end });
+            } else {
+                let alpha = alpha.clamp(0.55, 1.0);
+                segments.push(Segment::Bezier {
+                    c1: interval(0.5 + 0.5 * alpha, vi, vj),
+                    c2: interval(0.5 + 0.5 * alpha, vk, vj),
+                    end,
+                });
+            }
+        }
+        Curve {
+            outer: self.outer,
+            segments,
+        }
     }
+}
 
-    0
+#[inline]
+fn sign_f(x: f64) -> i32 {
+    if x > 0.0 {
+        1
+    } else if x < 0.0 {
+        -1
+    } else {
+        0
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Constraint;
+    use super::{decompose, trace, Segment};
+    use crate::{chsets::printer::PSetChar, raster::Page};
+
+    fn page(rows: &[&str]) -> Page {
+        let width = rows[0].len().div_ceil(8);
+        let mut bitmap = vec![0; width * rows.len()];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.bytes().enumerate() {
+                if c == b'#' {
+                    bitmap[y * width + x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+        let pchar = PSetChar::new(width as u8, rows.len() as u8, 0, &bitmap);
+        Page::from(&pchar)
+    }
 
     #[test]
-    #[rustfmt::skip]
-    fn test_constraint() {
-        // Down, Right
-        let c1 = Constraint::of_h((0.0,0.0), (1.0, 1.0));
-        assert_eq!(c1, Constraint { min: 0.0, max: f64::INFINITY });
-        let c2 = Constraint::of_h((0.0,0.0), (2.0, 1.0));
-        assert_eq!(c2, Constraint { min: 0.0, max: 2.0 });
-        let c3 = Constraint::of_h((0.0,0.0), (3.0, 1.0));
-        assert_eq!(c3, Constraint { min: 0.0, max: 1.0 });
-        
-        // Right
-        let c4 = Constraint::of_h((0.0,0.0), (1.0, 0.0));
-        assert_eq!(c4, Constraint { min: f64::NEG_INFINITY, max: f64::INFINITY });
-        let c4 = Constraint::of_h((0.0,0.0), (2.0, 0.0));
-        assert_eq!(c4, Constraint { min: -1.0, max: 1.0 });
-        let c4 = Constraint::of_h((0.0,0.0), (3.0, 0.0));
-        assert_eq!(c4, Constraint { min: -0.5, max: 0.5 });
-
-        // Up, Right
-        let c4 = Constraint::of_h((0.0,0.0), (2.0, -2.0));
-        assert_eq!(c4, Constraint { min: -3.0, max: -1.0 / 3.0 });
-
-        // Up
-        let c4 = Constraint::of_h((0.0,0.0), (0.0, -2.0));
-        assert_eq!(c4, Constraint { min: f64::NEG_INFINITY, max: -1.0 });
-
-        // Up, Left
-        let c4 = Constraint::of_h((0.0,0.0), (-2.0, -2.0));
-        assert_eq!(c4, Constraint { min: -3.0, max: -1.0 / 3.0 });
-
-        // Down
-        let c4 = Constraint::of_h((0.0,0.0), (0.0, 2.0));
-        assert_eq!(c4, Constraint { min: 1.0, max: f64::INFINITY });
+    fn test_decompose() {
+        let page = page(&[
+            "        ", " ###### ", " #    # ", " # ## # ", " #    # ", " ###### ",
+        ]);
+        let paths = decompose(&page);
+        let outer: Vec<_> = paths.iter().map(|p| p.outer).collect();
+        assert_eq!(outer, [true, false, true]);
+        assert_eq!(paths[0].points.len(), 22);
+        assert_eq!(paths[0].points[..3], [(1, 1), (1, 2), (1, 3)]);
+        assert_eq!(paths[1].points.len(), 14);
+        assert_eq!(paths[2].points.len(), 6);
+
+        // holes have the opposite orientation
+        let area = |points: &[(i32, i32)]| -> i32 {
+            let n = points.len();
+            (0..n)
+                .map(|i| {
+                    let (ax, ay) = points[i];
+                    let (bx, by) = points[(i + 1) % n];
+                    ax * by - bx * ay
+                })
+                .sum::<i32>()
+                / 2
+        };
+        assert_eq!(area(&paths[0].points), -30);
+        assert_eq!(area(&paths[1].points), 12);
+        assert_eq!(area(&paths[2].points), -2);
+    }
+
+    #[test]
+    fn test_trace_square() {
+        // the corners of a large enough square are kept as corners
+        let mut rows = vec![" ".repeat(12)];
+        rows.extend(std::iter::repeat_n(format!(" {} ", "#".repeat(10)), 10));
+        rows.push(" ".repeat(12));
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let page = page(&rows);
+        let curves = trace(&page);
+        assert_eq!(curves.len(), 1);
+        let curve = &curves[0];
+        assert_eq!(curve.segments.len(), 4);
+        for segment in &curve.segments {
+            let vertex = match segment {
+                Segment::Corner { vertex, .. } => *vertex,
+                Segment::Bezier { .. } => panic!("expected corner"),
+            };
+            let is_corner = |v: f64| (v - 1.0).abs() < 1e-9 || (v - 11.0).abs() < 1e-9;
+            assert!(is_corner(vertex.0) && is_corner(vertex.1), "{:?}", vertex);
+        }
+    }
+
+    #[test]
+    fn test_trace_disc() {
+        let page = page(&[
+            "            ",
+            "    ####    ",
+            "  ########  ",
+            "  ########  ",
+            " ########## ",
+            " ########## ",
+            " ########## ",
+            " ########## ",
+            "  ########  ",
+            "  ########  ",
+            "    ####    ",
+            "            ",
+        ]);
+        let curves = trace(&page);
+        assert_eq!(curves.len(), 1);
+        let segments = &curves[0].segments;
+        assert!(segments.iter().all(|s| matches!(s, Segment::Bezier { .. })));
+        for s in segments {
+            let (x, y) = s.end();
+            let r = ((x - 6.0).powi(2) + (y - 6.0).powi(2)).sqrt();
+            assert!((4.0..=5.5).contains(&r), "{:?} at {}", s, r);
+        }
     }
 }
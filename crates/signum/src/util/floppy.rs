@@ -0,0 +1,531 @@
+//! # ATARI ST floppy disk images
+//!
+//! This module reads the FAT12 file system of raw (`*.ST`) and
+//! run-length compressed (`*.MSA`) floppy disk images, so that documents
+//! and fonts can be loaded without mounting the image first.
+
+use std::{
+    borrow::Cow,
+    convert::TryInto,
+    future::Future,
+    path::{Component, Path, PathBuf},
+    vec,
+};
+
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::chsets::encoding::decode_atari_str;
+
+use super::{AsyncIterator, VFS};
+
+/// Magic number at the start of an `*.MSA` file
+const MSA_MAGIC: [u8; 2] = [0x0E, 0x0F];
+/// Marker for a run in a compressed `*.MSA` track
+const MSA_RLE_MARKER: u8 = 0xE5;
+
+const ATTR_VOLUME: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+
+/// Errors when reading a floppy disk image
+#[derive(Debug, Display, Error)]
+pub enum FloppyError {
+    /// Unexpected end of the disk image
+    UnexpectedEnd,
+    /// Invalid MSA header
+    InvalidMsaHeader,
+    /// MSA track {0} has an invalid length
+    InvalidMsaTrack(usize),
+    /// Invalid boot sector
+    InvalidBootSector,
+    /// Invalid cluster chain at cluster {0}
+    InvalidCluster(u16),
+    /// File not found: {0}
+    NotFound(String),
+    /// Not a directory: {0}
+    NotADirectory(String),
+    /// Not a file: {0}
+    NotAFile(String),
+}
+
+fn le_u16_at(data: &[u8], pos: usize) -> Result<u16, FloppyError> {
+    let bytes = data.get(pos..pos + 2).ok_or(FloppyError::UnexpectedEnd)?;
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn be_u16_at(data: &[u8], pos: usize) -> Result<u16, FloppyError> {
+    let bytes = data.get(pos..pos + 2).ok_or(FloppyError::UnexpectedEnd)?;
+    Ok(u16::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Check whether the buffer starts with the `*.MSA` magic bytes
+pub fn is_msa(input: &[u8]) -> bool {
+    input.starts_with(&MSA_MAGIC)
+}
+
+/// Decompress an `*.MSA` file into a raw (`*.ST`) disk image
+pub fn decode_msa(input: &[u8]) -> Result<Vec<u8>, FloppyError> {
+    if !is_msa(input) {
+        return Err(FloppyError::InvalidMsaHeader);
+    }
+    let sectors = usize::from(be_u16_at(input, 2)?);
+    let sides = usize::from(be_u16_at(input, 4)?) + 1;
+    let start = usize::from(be_u16_at(input, 6)?);
+    let end = usize::from(be_u16_at(input, 8)?);
+    if sectors == 0 || sides > 2 || start > end {
+        return Err(FloppyError::InvalidMsaHeader);
+    }
+
+    let track_len = sectors * 512;
+    let mut out = vec![0; start * sides * track_len];
+    let mut pos = 10;
+    for track in (start * sides)..((end + 1) * sides) {
+        let len = usize::from(be_u16_at(input, pos)?);
+        let data = input
+            .get(pos + 2..pos + 2 + len)
+            .ok_or(FloppyError::UnexpectedEnd)?;
+        pos += 2 + len;
+
+        let track_start = out.len();
+        if len == track_len {
+            out.extend_from_slice(data);
+        } else {
+            let mut i = 0;
+            while i < data.len() {
+                if data[i] == MSA_RLE_MARKER {
+                    let value = *data.get(i + 1).ok_or(FloppyError::InvalidMsaTrack(track))?;
+                    let count =
+                        be_u16_at(data, i + 2).map_err(|_| FloppyError::InvalidMsaTrack(track))?;
+                    out.resize(out.len() + usize::from(count), value);
+                    i += 4;
+                } else {
+                    out.push(data[i]);
+                    i += 1;
+                }
+            }
+        }
+        if out.len() - track_start != track_len {
+            return Err(FloppyError::InvalidMsaTrack(track));
+        }
+    }
+    Ok(out)
+}
+
+/// An entry in a directory of a [`FloppyImage`]
+#[derive(Debug, Clone)]
+pub struct FloppyDirEntry {
+    path: PathBuf,
+    attr: u8,
+    cluster: u16,
+    size: u32,
+}
+
+impl FloppyDirEntry {
+    /// The path of the entry within the image
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The size of the file in bytes
+    pub fn len(&self) -> usize {
+        self.size as usize
+    }
+
+    /// Whether the file is empty
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Whether this entry is a directory
+    pub fn is_dir(&self) -> bool {
+        self.attr & ATTR_DIRECTORY != 0
+    }
+
+    /// Whether this entry is a regular file
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+}
+
+/// A FAT12 formatted floppy disk image
+pub struct FloppyImage {
+    data: Vec<u8>,
+    cluster_len: usize,
+    fat_start: usize,
+    root_start: usize,
+    root_entries: usize,
+    data_start: usize,
+}
+
+impl FloppyImage {
+    /// Load a disk image, decompressing it first if it is an `*.MSA` file
+    pub fn load(data: Vec<u8>) -> Result<Self, FloppyError> {
+        if is_msa(&data) {
+            Self::new(decode_msa(&data)?)
+        } else {
+            Self::new(data)
+        }
+    }
+
+    /// Create a new instance from a raw (`*.ST`) disk image
+    pub fn new(data: Vec<u8>) -> Result<Self, FloppyError> {
+        let bytes_per_sector = usize::from(le_u16_at(&data, 0x0B)?);
+        let sectors_per_cluster = usize::from(*data.get(0x0D).ok_or(FloppyError::UnexpectedEnd)?);
+        let reserved = usize::from(le_u16_at(&data, 0x0E)?);
+        let fat_count = usize::from(*data.get(0x10).ok_or(FloppyError::UnexpectedEnd)?);
+        let root_entries = usize::from(le_u16_at(&data, 0x11)?);
+        let sectors_per_fat = usize::from(le_u16_at(&data, 0x16)?);
+        if bytes_per_sector == 0 || bytes_per_sector % 32 != 0 || sectors_per_cluster == 0 {
+            return Err(FloppyError::InvalidBootSector);
+        }
+
+        let fat_start = reserved.max(1) * bytes_per_sector;
+        let root_start = fat_start + fat_count * sectors_per_fat * bytes_per_sector;
+        let root_len = root_entries * 32;
+        let root_sectors = root_len.div_ceil(bytes_per_sector);
+        let data_start = root_start + root_sectors * bytes_per_sector;
+        if data_start > data.len() {
+            return Err(FloppyError::InvalidBootSector);
+        }
+        Ok(Self {
+            data,
+            cluster_len: sectors_per_cluster * bytes_per_sector,
+            fat_start,
+            root_start,
+            root_entries,
+            data_start,
+        })
+    }
+
+    fn fat_entry(&self, cluster: u16) -> Result<u16, FloppyError> {
+        let offset = self.fat_start + usize::from(cluster) * 3 / 2;
+        let value = le_u16_at(&self.data, offset)?;
+        if cluster.is_multiple_of(2) {
+            Ok(value & 0x0FFF)
+        } else {
+            Ok(value >> 4)
+        }
+    }
+
+    fn cluster(&self, cluster: u16) -> Result<&[u8], FloppyError> {
+        let start = usize::from(cluster)
+            .checked_sub(2)
+            .map(|index| self.data_start + index * self.cluster_len)
+            .ok_or(FloppyError::InvalidCluster(cluster))?;
+        self.data
+            .get(start..start + self.cluster_len)
+            .ok_or(FloppyError::InvalidCluster(cluster))
+    }
+
+    /// Read all clusters in a chain, starting at `start`
+    fn read_chain(&self, start: u16, limit: Option<usize>) -> Result<Vec<u8>, FloppyError> {
+        let max_clusters = (self.data.len() - self.data_start) / self.cluster_len;
+        let mut out = Vec::new();
+        let mut cluster = start;
+        while (2..0xFF0).contains(&cluster) {
+            if limit.is_some_and(|l| out.len() >= l) {
+                break;
+            }
+            if out.len() / self.cluster_len > max_clusters {
+                // the chain contains a loop
+                return Err(FloppyError::InvalidCluster(cluster));
+            }
+            out.extend_from_slice(self.cluster(cluster)?);
+            cluster = self.fat_entry(cluster)?;
+        }
+        if let Some(limit) = limit {
+            if out.len() < limit {
+                return Err(FloppyError::InvalidCluster(cluster));
+            }
+            out.truncate(limit);
+        }
+        Ok(out)
+    }
+
+    fn parse_dir(&self, parent: &Path, buf: &[u8]) -> Vec<FloppyDirEntry> {
+        let mut entries = Vec::new();
+        for raw in buf.chunks_exact(32) {
+            match raw[0] {
+                0x00 => break,
+                0xE5 | b'.' => continue,
+                _ => {}
+            }
+            let attr = raw[0x0B];
+            if attr & ATTR_VOLUME != 0 {
+                continue;
+            }
+            let base = decode_atari_str(trim_name(&raw[0..8]));
+            let ext = decode_atari_str(trim_name(&raw[8..11]));
+            let name = if ext.is_empty() {
+                base.into_owned()
+            } else {
+                format!("{}.{}", base, ext)
+            };
+            entries.push(FloppyDirEntry {
+                path: parent.join(name),
+                attr,
+                cluster: u16::from_le_bytes([raw[0x1A], raw[0x1B]]),
+                size: u32::from_le_bytes(raw[0x1C..0x20].try_into().unwrap()),
+            });
+        }
+        entries
+    }
+
+    fn root_dir(&self) -> Result<Vec<FloppyDirEntry>, FloppyError> {
+        let len = self.root_entries * 32;
+        let buf = self
+            .data
+            .get(self.root_start..self.root_start + len)
+            .ok_or(FloppyError::UnexpectedEnd)?;
+        Ok(self.parse_dir(Path::new(""), buf))
+    }
+
+    fn sub_dir(&self, entry: &FloppyDirEntry) -> Result<Vec<FloppyDirEntry>, FloppyError> {
+        let buf = self.read_chain(entry.cluster, None)?;
+        Ok(self.parse_dir(&entry.path, &buf))
+    }
+
+    /// Find the entry for a path, comparing names case-insensitively
+    ///
+    /// Returns `None` for the root directory.
+    pub fn lookup(&self, path: &Path) -> Result<Option<FloppyDirEntry>, FloppyError> {
+        let not_found = || FloppyError::NotFound(path.display().to_string());
+        let mut current: Option<FloppyDirEntry> = None;
+        for component in path.components() {
+            let name = match component {
+                Component::Normal(name) => name.to_string_lossy().to_uppercase(),
+                Component::RootDir | Component::CurDir | Component::Prefix(_) => continue,
+                Component::ParentDir => return Err(not_found()),
+            };
+            let entries = match &current {
+                None => self.root_dir()?,
+                Some(dir) if dir.is_dir() => self.sub_dir(dir)?,
+                Some(_) => return Err(not_found()),
+            };
+            let found = entries.into_iter().find(|e| {
+                e.path
+                    .file_name()
+                    .is_some_and(|n| n.to_string_lossy().to_uppercase() == name)
+            });
+            current = Some(found.ok_or_else(not_found)?);
+        }
+        Ok(current)
+    }
+
+    /// List the entries of a directory
+    pub fn read_dir(&self, path: &Path) -> Result<Vec<FloppyDirEntry>, FloppyError> {
+        match self.lookup(path)? {
+            None => self.root_dir(),
+            Some(entry) if entry.is_dir() => self.sub_dir(&entry),
+            Some(_) => Err(FloppyError::NotADirectory(path.display().to_string())),
+        }
+    }
+
+    /// Read the content of a file
+    pub fn read_file(&self, path: &Path) -> Result<Vec<u8>, FloppyError> {
+        match self.lookup(path)? {
+            Some(entry) if entry.is_file() => self.read_entry(&entry),
+            _ => Err(FloppyError::NotAFile(path.display().to_string())),
+        }
+    }
+
+    /// Read the content of a file from its directory entry
+    pub fn read_entry(&self, entry: &FloppyDirEntry) -> Result<Vec<u8>, FloppyError> {
+        if entry.size == 0 {
+            return Ok(Vec::new());
+        }
+        self.read_chain(entry.cluster, Some(entry.len()))
+    }
+}
+
+fn trim_name(bytes: &[u8]) -> &[u8] {
+    let end = bytes.iter().rposition(|b| *b != b' ').map_or(0, |p| p + 1);
+    &bytes[..end]
+}
+
+/// VFS for a [`FloppyImage`]
+pub struct FloppyFS {
+    image: FloppyImage,
+    chsets_folder: PathBuf,
+}
+
+impl FloppyFS {
+    /// Create a new instance with the given `chsets_folder` within the image
+    pub fn new(image: FloppyImage, chsets_folder: PathBuf) -> Self {
+        Self {
+            image,
+            chsets_folder,
+        }
+    }
+
+    /// Get the underlying disk image
+    pub fn image(&self) -> &FloppyImage {
+        &self.image
+    }
+}
+
+/// Iterator over the entries of a directory in a [`FloppyImage`]
+pub struct FloppyDirIter(vec::IntoIter<FloppyDirEntry>);
+
+impl AsyncIterator for FloppyDirIter {
+    type Item = Result<FloppyDirEntry, FloppyError>;
+
+    async fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(Ok)
+    }
+}
+
+impl VFS for FloppyFS {
+    type Error = FloppyError;
+
+    type DirIter = FloppyDirIter;
+
+    type DirEntry = FloppyDirEntry;
+
+    type File = Vec<u8>;
+
+    fn root(&self) -> impl Future<Output = PathBuf> + 'static {
+        std::future::ready(self.chsets_folder.clone())
+    }
+
+    fn is_file(&self, path: &Path) -> impl Future<Output = bool> {
+        let is_file = matches!(self.image.lookup(path), Ok(Some(e)) if e.is_file());
+        std::future::ready(is_file)
+    }
+
+    fn is_dir(&self, path: &Path) -> impl Future<Output = bool> {
+        let is_dir = match self.image.lookup(path) {
+            Ok(Some(e)) => e.is_dir(),
+            Ok(None) => true,
+            Err(_) => false,
+        };
+        std::future::ready(is_dir)
+    }
+
+    async fn read_dir(&self, path: &Path) -> Result<Self::DirIter, Self::Error> {
+        let entries = self.image.read_dir(path)?;
+        Ok(FloppyDirIter(entries.into_iter()))
+    }
+
+    fn open(&self, path: &Path) -> impl Future<Output = Result<Self::File, Self::Error>> {
+        std::future::ready(self.image.read_file(path))
+    }
+
+    fn dir_entry_is_file(&self, entry: &Self::DirEntry) -> bool {
+        entry.is_file()
+    }
+
+    fn dir_entry_is_dir(&self, entry: &Self::DirEntry) -> bool {
+        entry.is_dir()
+    }
+
+    fn dir_entry_path<'a>(&self, entry: &'a Self::DirEntry) -> Cow<'a, Path> {
+        Cow::Borrowed(entry.path())
+    }
+
+    fn dir_entry_to_file(
+        &self,
+        dir_entry: &Self::DirEntry,
+    ) -> impl Future<Output = Result<Self::File, Self::Error>> {
+        std::future::ready(self.image.read_entry(dir_entry))
+    }
+
+    fn read(&self, path: &Path) -> impl Future<Output = Result<Vec<u8>, Self::Error>> {
+        std::future::ready(self.image.read_file(path))
+    }
+
+    fn is_file_not_found(e: &Self::Error) -> bool {
+        matches!(e, FloppyError::NotFound(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{decode_msa, FloppyImage};
+
+    fn dir_entry(out: &mut [u8], name: &[u8; 11], attr: u8, cluster: u16, size: u32) {
+        out[..11].copy_from_slice(name);
+        out[0x0B] = attr;
+        out[0x1A..0x1C].copy_from_slice(&cluster.to_le_bytes());
+        out[0x1C..0x20].copy_from_slice(&size.to_le_bytes());
+    }
+
+    /// A single sided disk with 9 sectors per track and 10 tracks
+    fn sample_image() -> Vec<u8> {
+        let mut data = vec![0; 90 * 512];
+        data[0x0B..0x0D].copy_from_slice(&512u16.to_le_bytes());
+        data[0x0D] = 2; // sectors per cluster
+        data[0x0E..0x10].copy_from_slice(&1u16.to_le_bytes());
+        data[0x10] = 2; // FATs
+        data[0x11..0x13].copy_from_slice(&112u16.to_le_bytes());
+        data[0x13..0x15].copy_from_slice(&90u16.to_le_bytes());
+        data[0x16..0x18].copy_from_slice(&1u16.to_le_bytes());
+
+        // FAT: clusters 2, 3 and 5 are single cluster files, 4 continues in 6
+        let fat = &mut data[512..1024];
+        fat[..12].copy_from_slice(&[
+            0xF9, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x06, 0xF0, 0xFF, 0xFF, 0x0F, 0x00,
+        ]);
+
+        let root = 3 * 512;
+        dir_entry(&mut data[root..], b"CHSETS     ", 0x10, 2, 0);
+        dir_entry(&mut data[root + 32..], b"TEST    SDO", 0x00, 3, 5);
+        dir_entry(&mut data[root + 64..], b"LONG    TXT", 0x00, 4, 1100);
+
+        let data_start = root + 7 * 512;
+        let cluster = |n: usize| data_start + (n - 2) * 1024;
+        dir_entry(&mut data[cluster(2)..], b".          ", 0x10, 2, 0);
+        dir_entry(&mut data[cluster(2) + 32..], b"S\x9ATT    E24", 0x00, 5, 3);
+        data[cluster(3)..cluster(3) + 5].copy_from_slice(b"sdoc!");
+        data[cluster(4)..cluster(4) + 1024].fill(b'a');
+        data[cluster(6)..cluster(6) + 76].fill(b'b');
+        data[cluster(5)..cluster(5) + 3].copy_from_slice(b"E24");
+        data
+    }
+
+    #[test]
+    fn test_read_files() {
+        let image = FloppyImage::new(sample_image()).unwrap();
+        let test = image.read_file(Path::new("test.sdo")).unwrap();
+        assert_eq!(test, b"sdoc!");
+
+        let long = image.read_file(Path::new("/LONG.TXT")).unwrap();
+        assert_eq!(long.len(), 1100);
+        assert_eq!(long[1023], b'a');
+        assert_eq!(long[1024], b'b');
+
+        let chsets = image.read_dir(Path::new("CHSETS")).unwrap();
+        assert_eq!(chsets.len(), 1);
+        assert_eq!(chsets[0].path(), Path::new("CHSETS/SÜTT.E24"));
+        let font = image.read_entry(&chsets[0]).unwrap();
+        assert_eq!(font, b"E24");
+
+        assert!(image.read_file(Path::new("MISSING.SDO")).is_err());
+    }
+
+    #[test]
+    fn test_decode_msa() {
+        let image = sample_image();
+        let mut msa = vec![0x0E, 0x0F, 0, 9, 0, 0, 0, 0, 0, 9];
+        for track in image.chunks(9 * 512) {
+            // compress every track into runs of equal bytes
+            let mut packed = Vec::new();
+            for run in track.chunk_by(|a, b| a == b) {
+                if run.len() > 4 || run[0] == 0xE5 {
+                    packed.extend_from_slice(&[0xE5, run[0]]);
+                    packed.extend_from_slice(&(run.len() as u16).to_be_bytes());
+                } else {
+                    packed.extend_from_slice(run);
+                }
+            }
+            if packed.len() >= track.len() {
+                packed = track.to_vec();
+            }
+            msa.extend_from_slice(&(packed.len() as u16).to_be_bytes());
+            msa.extend_from_slice(&packed);
+        }
+        assert_eq!(decode_msa(&msa).unwrap(), image);
+    }
+}
@@ -6,6 +6,7 @@ mod buf;
 mod bytes;
 pub mod data;
 mod file_format;
+mod floppy;
 mod four_cc;
 mod parsers;
 mod vfs;
@@ -17,6 +18,9 @@ pub use bytes::{Bytes16, Bytes32};
 pub use file_format::{
     FileFormatKind, FileFormatKindV1, Signum1Format, Signum3Format, SignumFormat,
 };
+pub use floppy::{
+    decode_msa, is_msa, FloppyDirEntry, FloppyDirIter, FloppyError, FloppyFS, FloppyImage,
+};
 pub use four_cc::FourCC;
 pub use parsers::V3Chunk;
 #[allow(unused_imports)]
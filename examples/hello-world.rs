@@ -87,6 +87,7 @@ pub fn main() -> eyre::Result<()> {
         media_box: Rectangle::a4_media_box(),
         resources,
         contents: contents.into_bytes(),
+        links: Vec::new(),
     };
     doc.pages.push(page);
 
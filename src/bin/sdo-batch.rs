@@ -1,21 +1,25 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
-    io::{BufReader, Read},
+    io::{BufReader, BufWriter, Read, Write},
     path::{Path, PathBuf},
     str::FromStr,
 };
 
-use color_eyre::eyre::{self, WrapErr};
+use color_eyre::eyre::{self, eyre, WrapErr};
 use log::info;
 use pdf_create::{
-    common::{PageLabel, PdfString},
-    high::{self, Handle},
+    common::{PageLabel, PdfString, StructType},
+    high::{self, Handle, StructElem, StructKid},
 };
 use sdo_pdf::{
-    font::Fonts, prepare_info, prepare_pdfa_output_intent, sdoc::generate_pdf_pages, Pdf,
+    font::Fonts,
+    prepare_info, prepare_pdfa_output_intent,
+    sdoc::{generate_pdf_pages_with, push_structure},
 };
 use signum::{
     chsets::{cache::ChsetCache, printer::PrinterKind, UseMatrix, UseTableVec},
+    docs::DocumentInfo,
     util::LocalFS,
 };
 
@@ -23,7 +27,7 @@ use sdo_tool::cli::{
     self,
     opt::{DocScript, OutlineItem},
     sdoc::{
-        pdf::{handle_out, GenCtx},
+        pdf::{pdf_out_path, GenCtx},
         Document,
     },
 };
@@ -51,6 +55,23 @@ fn map_outline_items(items: &[OutlineItem]) -> eyre::Result<Vec<high::OutlineIte
     Ok(result)
 }
 
+/// Load the chunks of a document, without fonts
+fn load_document(folder: &Path, doc_path: &Path) -> eyre::Result<Document> {
+    let doc_file = folder.join(doc_path);
+    let doc_file = doc_file.canonicalize().wrap_err_with(|| {
+        format!(
+            "Failed to canonicalize document file path `{}`",
+            doc_file.display()
+        )
+    })?;
+    let input = std::fs::read(&doc_file)?;
+
+    let mut document = Document::new();
+    info!("Loading document file '{}'", doc_file.display());
+    document.load_sdoc(&input)?;
+    Ok(document)
+}
+
 pub fn run(buffer: &[u8], opt: RunOpts) -> eyre::Result<()> {
     let script_str_res = std::str::from_utf8(buffer);
     let script_str = WrapErr::wrap_err(script_str_res, "Failed to parse as string")?;
@@ -74,45 +95,27 @@ pub fn run(buffer: &[u8], opt: RunOpts) -> eyre::Result<()> {
         std::fs::create_dir_all(&opt.out)?;
     }
 
-    let capacity = script.files.len();
-
-    // Load documents
-    let mut doc_files = Vec::with_capacity(capacity);
-    for doc_path in &script.files {
-        let doc_file = folder.join(doc_path);
-        let doc_file = doc_file.canonicalize().wrap_err_with(|| {
-            format!(
-                "Failed to canonicalize document file path `{}`",
-                doc_file.display()
-            )
-        })?;
-        let input = std::fs::read(&doc_file)?;
-        doc_files.push((doc_file, input));
-    }
-
-    let mut documents = Vec::with_capacity(capacity);
-    for (doc_file, input) in &doc_files {
-        let mut document = Document::new();
-
-        info!("Loading document file '{}'", doc_file.display());
-        let di = document.process_sdoc(input, &fs, &mut fc)?;
-        documents.push((document, di));
-    }
-
     // Preprare output
     let mut hnd = Handle::new();
 
     prepare_info(&mut hnd.meta, &script.meta.to_pdf_meta())?;
-    prepare_pdfa_output_intent(&mut hnd)?;
+    prepare_pdfa_output_intent(&mut hnd, script.pdfa.into())?;
+    hnd.object_streams = script.object_streams;
 
+    // Load the documents once to find the fonts and used characters. They are
+    // loaded again when generating the pages, so that only one is in memory at a time.
     let mut use_table_vec = UseTableVec::new();
     let mut use_table_vec_bold = UseTableVec::new();
-    for (doc, di) in &documents {
+    let mut doc_fonts = Vec::with_capacity(script.files.len());
+    for doc_path in &script.files {
+        let doc = load_document(folder, doc_path)?;
+        let fonts = doc.load_fonts(&fs, &mut fc)?;
         let pages = doc.text_pages();
         let use_matrix = UseMatrix::of_matching(pages, |k| !k.style.is_bold());
         let use_matrix_bold = UseMatrix::of_matching(pages, |k| k.style.is_bold());
-        use_table_vec.append(&di.fonts, use_matrix);
-        use_table_vec_bold.append(&di.fonts, use_matrix_bold);
+        use_table_vec.append(&fonts, use_matrix);
+        use_table_vec_bold.append(&fonts, use_matrix_bold);
+        doc_fonts.push(fonts);
     }
 
     // FIXME: Auto-Detect from font cache
@@ -123,10 +126,68 @@ pub fn run(buffer: &[u8], opt: RunOpts) -> eyre::Result<()> {
 
     font_info.make_fonts(&fc, &mut hnd.res, use_table_vec, use_table_vec_bold, pk);
 
+    let mut links: BTreeMap<usize, Vec<high::Link>> = BTreeMap::new();
+    for link in &script.links {
+        links
+            .entry(link.page)
+            .or_default()
+            .push(link.to_pdf_link()?);
+    }
+
+    let mut out: Box<dyn Write> = if opt.out == Path::new("-") {
+        Box::new(std::io::stdout().lock())
+    } else {
+        let out_path = pdf_out_path(Some(&opt.out), &opt.file);
+        info!("Writing `{}` ...", out_path.display());
+        Box::new(BufWriter::new(File::create(&out_path)?))
+    };
+    let mut writer = hnd.write_incremental(&mut out)?;
+    writer.set_tagged(script.tagged);
+
     let overrides = script.meta.to_overrides();
-    for (doc, di) in &documents {
-        let gc = GenCtx::new(doc, di);
-        generate_pdf_pages(&gc, &mut hnd, &overrides, &font_info)?;
+    let mut link_elems = Vec::new();
+    for (doc_path, fonts) in script.files.iter().zip(doc_fonts) {
+        let doc = load_document(folder, doc_path)?;
+        let di = DocumentInfo::new(fonts, doc.decode_images());
+        let gc = GenCtx::new(&doc, &di);
+        let first_page = writer.page_count();
+        generate_pdf_pages_with(
+            &gc,
+            &mut hnd,
+            &overrides,
+            &font_info,
+            script.tagged,
+            first_page,
+            |hnd, mut page| {
+                let index = writer.page_count();
+                let page_links = links.remove(&index).unwrap_or_default();
+                if script.tagged {
+                    for link in page.links.len()..page.links.len() + page_links.len() {
+                        let mut elem = StructElem::new(StructType::Link);
+                        elem.children.push(StructKid::Link {
+                            page: index,
+                            index: link,
+                        });
+                        link_elems.push(elem);
+                    }
+                }
+                page.links.extend(page_links);
+                writer.write_page(hnd, &page)?;
+                eyre::Ok(())
+            },
+        )?;
+    }
+
+    if let Some(page) = links.keys().next() {
+        return Err(eyre!(
+            "Link on page {}, but the document has {} pages",
+            page,
+            writer.page_count()
+        ));
+    }
+
+    for elem in link_elems {
+        push_structure(&mut hnd, elem);
     }
 
     for (key, value) in &script.page_labels {
@@ -157,7 +218,13 @@ pub fn run(buffer: &[u8], opt: RunOpts) -> eyre::Result<()> {
         hnd.outline.children = map_outline_items(&script.outline)?;
     }
 
-    handle_out(Some(&opt.out), &opt.file, Pdf::from_raw(hnd))?;
+    for (name, dest) in &script.dests {
+        hnd.dests.insert(PdfString::from_str(name)?, (*dest).into());
+    }
+
+    writer.finish(&hnd)?;
+    out.flush()?;
+    info!("Done!");
     Ok(())
 }
 
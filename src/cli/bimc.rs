@@ -1,7 +1,16 @@
+use std::path::PathBuf;
+
 use color_eyre::eyre::{self, eyre};
 use image::ImageFormat;
-use log::info;
-use signum::{images::imc::parse_imc, raster::Page};
+use log::{info, warn};
+use signum::{
+    images::{
+        degas::{encode_pc3, encode_pi3, parse_degas},
+        gem::{encode_img, parse_img},
+        imc::{encode_imc, parse_imc, MonochromeScreen},
+    },
+    raster::Page,
+};
 
 use super::opt::{Format, Options};
 
@@ -10,30 +19,101 @@ pub fn process_bimc(buffer: &[u8], opt: Options) -> eyre::Result<()> {
     let (header, decoded) = parse_imc(buffer) //
         .map_err(|err| eyre!("Failed to parse: {}", err))?;
 
+    if opt.format == Format::Plain {
+        info!("Signum! Bitmap: {:#?}", header);
+    }
+    save_image(Page::from(decoded), opt)
+}
+
+/// The magic bytes at the start of a PNG file
+pub const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+pub fn process_png(buffer: &[u8], opt: Options) -> eyre::Result<()> {
+    info!("Found PNG image");
+    let image = image::load_from_memory_with_format(buffer, ImageFormat::Png)?.to_luma8();
+    let page = Page::from_image(&image, 128, (0, 0));
+
+    if opt.format == Format::Plain {
+        info!("PNG Image: {}x{}", page.bit_width(), page.bit_height());
+    }
+    save_image(page, opt)
+}
+
+pub fn process_gem_img(buffer: &[u8], opt: Options) -> eyre::Result<()> {
+    info!("Found GEM bit image (IMG)");
+    let (_, (header, page)) = parse_img(buffer) //
+        .map_err(|err| eyre!("Failed to parse: {}", err))?;
+
+    if opt.format == Format::Plain {
+        info!("GEM Bit Image: {:#?}", header);
+    }
+    save_image(page, opt)
+}
+
+pub fn process_degas(buffer: &[u8], opt: Options) -> eyre::Result<()> {
+    info!("Found Degas image (PI3/PC3)");
+    let (_, (header, screen)) = parse_degas(buffer) //
+        .map_err(|err| eyre!("Failed to parse: {}", err))?;
+
+    if opt.format == Format::Plain {
+        info!("Degas Image: {:#?}", header);
+    }
+    save_image(Page::from(screen), opt)
+}
+
+/// Copy a page into a screen, warning if parts of it are cut off
+fn to_screen(page: &Page) -> MonochromeScreen {
+    let (width, height) = (page.bit_width(), page.bit_height());
+    if width > MonochromeScreen::WIDTH || height > MonochromeScreen::HEIGHT {
+        warn!(
+            "Image is larger than {}x{}, cutting off at the right and bottom",
+            MonochromeScreen::WIDTH,
+            MonochromeScreen::HEIGHT
+        );
+    }
+    MonochromeScreen::from(page)
+}
+
+/// Save a monochrome image in the selected image format
+fn save_image(page: Page, opt: Options) -> eyre::Result<()> {
     let file = opt.file;
-    match &opt.format {
+    let out = opt.out;
+    let out_path = |extension: &str| -> eyre::Result<PathBuf> {
+        let out_path = out
+            .clone()
+            .unwrap_or_else(|| file.with_extension(extension));
+        if out_path == file {
+            return Err(eyre!(
+                "Refusing to overwrite the input file, please specify an output path"
+            ));
+        }
+        Ok(out_path)
+    };
+
+    let (out_path, bytes) = match opt.format {
         Format::Png => {
-            let out_path = opt.out.unwrap_or_else(|| file.with_extension("png"));
-            let image = Page::from(decoded).to_image();
+            let out_path = out_path("png")?;
+            let image = page.to_image();
 
             image.save_with_format(&out_path, ImageFormat::Png)?;
             info!("Saved image as '{}'", out_path.display());
+            return Ok(());
         }
         Format::Pbm => {
             let mut out = String::new();
-            decoded.write_as_pbm(&mut out).unwrap();
-
-            let out_path = opt.out.unwrap_or_else(|| file.with_extension("pbm"));
-            std::fs::write(&out_path, out)?;
-            info!("Saved image as '{}'", out_path.display());
+            to_screen(&page).write_as_pbm(&mut out).unwrap();
+            (out_path("pbm")?, out.into_bytes())
         }
-        f => {
-            if *f == Format::Plain {
-                info!("Signum! Bitmap: {:#?}", header);
-            }
-            info!("Use `--format png` or `--format pbm` to convert");
+        Format::Imc => (out_path("IMC")?, encode_imc(&to_screen(&page))),
+        Format::Img => (out_path("IMG")?, encode_img(&page)),
+        Format::Pi3 => (out_path("PI3")?, encode_pi3(&to_screen(&page))),
+        Format::Pc3 => (out_path("PC3")?, encode_pc3(&to_screen(&page))),
+        _ => {
+            info!("Use `--format png`, `pbm`, `imc`, `img`, `pi3` or `pc3` to convert");
+            return Ok(());
         }
-    }
-
+    };
+    std::fs::write(&out_path, bytes)?;
+    info!("Saved image as '{}'", out_path.display());
     Ok(())
 }
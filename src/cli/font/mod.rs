@@ -3,17 +3,24 @@ use ccitt_t4_t6::g42d::G4Encoder;
 use color_eyre::eyre::{self, eyre};
 use eyre::Context;
 use image::ImageFormat;
+use log::{info, warn};
+use sdo_pdf::{
+    font::glyph_widths,
+    outline::{bitmap_widths, OutlineFont},
+};
 use sdo_ps::out::PsWriter;
 use signum::{
     chsets::{
         editor::parse_eset,
+        encoding::p_mapping_file,
         printer::{parse_ls30, parse_ps09, parse_ps24, PSet, PrinterKind},
     },
     raster::Page,
     util::{data::BIT_STRING, Buf},
 };
 use std::{
-    io::Stdout,
+    fs::File,
+    io::{BufWriter, Stdout},
     path::{Path, PathBuf},
 };
 
@@ -94,6 +101,47 @@ fn save_as_ccitt(pset: &PSet, opt: &Options, file: &Path) -> eyre::Result<()> {
     Ok(())
 }
 
+/// Trace the printer font and save it as an OpenType font
+///
+/// The advance widths are taken from the editor font (`*.E24`) and the unicode
+/// mapping from the mapping file (`*.TXT`) next to the file, if present.
+fn save_as_otf(pset: &PSet, opt: &Options) -> eyre::Result<()> {
+    let file = &opt.file;
+    let name = file.file_stem().unwrap().to_string_lossy();
+
+    let eset_file = file.with_extension("E24");
+    let widths = match std::fs::read(&eset_file) {
+        Ok(buffer) => match parse_eset(&buffer) {
+            Ok((_, eset)) => {
+                info!("Using widths from '{}'", eset_file.display());
+                glyph_widths(&eset)
+            }
+            Err(e) => return Err(eyre!("Failed to parse Editor Charset: \n{}", e)),
+        },
+        Err(_) => {
+            warn!("Missing editor font, using the widths of the bitmaps");
+            bitmap_widths(pset)
+        }
+    };
+    let mapping = match std::fs::read_to_string(file.with_extension("TXT")) {
+        Ok(input) => Some(p_mapping_file(&input)?),
+        Err(_) => {
+            warn!("Missing mapping file, writing a symbol font");
+            None
+        }
+    };
+
+    let font = OutlineFont::new(&name, pset, &widths);
+    let out = opt
+        .out
+        .clone()
+        .unwrap_or_else(|| file.with_extension("otf"));
+    let mut writer = BufWriter::new(File::create(&out)?);
+    font.write_otf(&mut writer, mapping.as_ref())?;
+    info!("Saved OpenType font to '{}'", out.display());
+    Ok(())
+}
+
 fn print_pset(pset: &PSet) {
     fn print_border(w: u8) {
         print!("+");
@@ -165,13 +213,14 @@ pub fn process_ps09(buffer: &[u8], opt: &Options) -> eyre::Result<()> {
             }
             save_pset_png(&pset, PrinterKind::Needle9, &out)?;
         }
+        Format::Otf => save_as_otf(&pset, opt)?,
         _ => print_pset(&pset),
     }
 
     Ok(())
 }
 
-pub fn process_ps24(buffer: &[u8], _opt: &Options) -> eyre::Result<()> {
+pub fn process_ps24(buffer: &[u8], opt: &Options) -> eyre::Result<()> {
     let pset = match util::load(parse_ps24, buffer) {
         Ok(result) => result,
         Err(e) => {
@@ -179,7 +228,10 @@ pub fn process_ps24(buffer: &[u8], _opt: &Options) -> eyre::Result<()> {
         }
     };
 
-    print_pset(&pset);
+    match opt.format {
+        Format::Otf => save_as_otf(&pset, opt)?,
+        _ => print_pset(&pset),
+    }
 
     Ok(())
 }
@@ -200,6 +252,9 @@ pub fn process_ls30(buffer: &[u8], opt: &Options) -> eyre::Result<()> {
         Format::CcItt6 => {
             save_as_ccitt(&lset, opt, &opt.file)?;
         }
+        Format::Otf => {
+            save_as_otf(&lset, opt)?;
+        }
         _ => {
             print_pset(&lset);
         }
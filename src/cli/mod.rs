@@ -6,6 +6,7 @@ pub mod font;
 pub mod opt;
 pub mod sdoc;
 mod util;
+pub mod vfs;
 
 /// Set up CLI
 pub fn init<T: clap::Parser>() -> color_eyre::Result<T> {
@@ -1,8 +1,11 @@
 use std::{borrow::Cow, collections::BTreeMap, fmt, io, path::PathBuf, str::FromStr};
 
 use clap::Parser;
-use pdf_create::high;
-use sdo_pdf::MetaInfo;
+use pdf_create::{
+    common::{PdfString, Point, Rectangle},
+    high,
+};
+use sdo_pdf::{MetaInfo, PdfAConformance};
 use serde::{Deserialize, Serialize};
 use signum::{chsets::FontKind, docs::Overrides};
 use thiserror::*;
@@ -28,12 +31,24 @@ pub enum Format {
     Png,
     /// Portable Bitmap Format (Images)
     Pbm,
+    /// Signum! hardcopy image (Images)
+    Imc,
+    /// GEM bit image (Images)
+    Img,
+    /// Degas high resolution image (Images)
+    Pi3,
+    /// Degas Elite compressed high resolution image (Images)
+    Pc3,
+    /// Multi-page TIFF with CCITT group 4 compression (Documents)
+    Tiff,
     /// Glyph Bitmap Distribution Format (Fonts)
     Bdf,
     /// A dvips-compatible inline postscript bitmap font (unstable)
     DviPsBitmapFont,
     /// A sequence of CCITT group 4 encoded bitmaps (Fonts)
     CcItt6,
+    /// OpenType font with outlines traced from the bitmaps (Fonts)
+    Otf,
 }
 
 #[derive(Debug)]
@@ -44,7 +59,7 @@ impl fmt::Display for FormatError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Use one of `plain`, `html`, `pdf`, `ps`, `png`, `pbm` or `pdraw`"
+            "Use one of `plain`, `html`, `pdf`, `ps`, `png`, `pbm`, `imc`, `img`, `pi3`, `pc3`, `tiff` or `pdraw`"
         )?;
         Ok(())
     }
@@ -62,9 +77,15 @@ impl FromStr for Format {
             "png" => Ok(Self::Png),
             "pdf" => Ok(Self::Pdf),
             "pbm" => Ok(Self::Pbm),
+            "imc" => Ok(Self::Imc),
+            "img" => Ok(Self::Img),
+            "pi3" => Ok(Self::Pi3),
+            "pc3" => Ok(Self::Pc3),
+            "tif" | "tiff" => Ok(Self::Tiff),
             "pdraw" => Ok(Self::PDraw),
             "dvipsbf" => Ok(Self::DviPsBitmapFont),
             "ccitt" | "ccitt-t6" => Ok(Self::CcItt6),
+            "otf" => Ok(Self::Otf),
             _ => Err(FormatError {}),
         }
     }
@@ -78,11 +99,17 @@ impl Format {
             Self::PostScript => "ps",
             Self::Png => "png",
             Self::Pbm => "pbm",
+            Self::Imc => "imc",
+            Self::Img => "img",
+            Self::Pi3 => "pi3",
+            Self::Pc3 => "pc3",
+            Self::Tiff => "tiff",
             Self::Pdf => "pdf",
             Self::PDraw => "pdraw",
             Self::DviPsBitmapFont => "dvipsbf",
             Self::CcItt6 => "ccitt-t6",
             Self::Bdf => "bdf",
+            Self::Otf => "otf",
         }
     }
 }
@@ -95,11 +122,14 @@ impl fmt::Display for Format {
 #[derive(Parser)]
 /// Convert a Signum file to another format
 pub struct Options {
-    /// The file to be processed (e.g. *.SDO, *.E24, *.IMC)
+    /// The file to be processed (e.g. *.SDO, *.E24, *.IMC, *.PNG, *.IMG, *.PI3)
     pub file: PathBuf,
     /// Where to store the output
     pub out: Option<PathBuf>,
     /// If specified, extract all embedded images to that folder
+    ///
+    /// An `images.json` file lists the physical page number (`phys_pnr`), page
+    /// index, site and selection of every placed image.
     #[clap(long = "with-images", short = 'I')]
     pub with_images: Option<PathBuf>,
     /// Select the printer font (and resolution).
@@ -114,9 +144,24 @@ pub struct Options {
     pub page: Option<Vec<usize>>,
     /// Format of the output. Valid choices are:
     ///
-    /// "plain", "html", "pdf", "ps", "png", "pbm", "bdf" and "pdraw"
+    /// "plain", "html", "pdf", "ps", "png", "pbm", "imc", "img", "pi3", "pc3", "tiff", "bdf", "otf" and "pdraw"
     #[clap(default_value_t, long, short = 'F')]
     pub format: Format,
+    /// Skip over damaged parts of a document instead of failing
+    #[clap(long)]
+    pub salvage: bool,
+    /// Read `file` and the fonts from an ATARI ST disk image (*.ST, *.MSA)
+    #[clap(long)]
+    pub disk: Option<PathBuf>,
+    /// Embed outlines traced from the printer fonts into PDFs instead of bitmaps
+    #[clap(long)]
+    pub outline_fonts: bool,
+    /// Add the logical structure (paragraphs, figures and links) to PDFs
+    #[clap(long)]
+    pub tagged: bool,
+    /// The PDF/A conformance level of PDFs. Valid choices are "1b", "2b" and "3b"
+    #[clap(default_value_t, long)]
+    pub pdfa: PdfA,
 
     /// Meta Parameters passed as command line args
     #[clap(flatten)]
@@ -219,6 +264,62 @@ impl Meta {
     }
 }
 
+/// The PDF/A conformance level of a PDF
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize)]
+pub enum PdfA {
+    /// PDF/A-1b
+    A1b,
+    /// PDF/A-2b
+    #[default]
+    A2b,
+    /// PDF/A-3b
+    A3b,
+}
+
+#[derive(Debug)]
+/// Failed to parse a PDF/A conformance level
+pub struct PdfAError {}
+
+impl fmt::Display for PdfAError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Use one of `1b`, `2b` or `3b`")
+    }
+}
+
+impl std::error::Error for PdfAError {}
+
+impl FromStr for PdfA {
+    type Err = PdfAError;
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val {
+            "1b" | "a1b" => Ok(Self::A1b),
+            "2b" | "a2b" => Ok(Self::A2b),
+            "3b" | "a3b" => Ok(Self::A3b),
+            _ => Err(PdfAError {}),
+        }
+    }
+}
+
+impl fmt::Display for PdfA {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::A1b => write!(f, "1b"),
+            Self::A2b => write!(f, "2b"),
+            Self::A3b => write!(f, "3b"),
+        }
+    }
+}
+
+impl From<PdfA> for PdfAConformance {
+    fn from(pdfa: PdfA) -> Self {
+        match pdfa {
+            PdfA::A1b => Self::A1b,
+            PdfA::A2b => Self::A2b,
+            PdfA::A3b => Self::A3b,
+        }
+    }
+}
+
 fn chsets_path() -> PathBuf {
     PathBuf::from("CHSETS")
 }
@@ -243,6 +344,26 @@ pub struct DocScript {
     #[serde(default)]
     pub outline_file: Option<PathBuf>,
 
+    /// The link annotations
+    #[serde(default)]
+    pub links: Vec<Link>,
+
+    /// The named destinations
+    #[serde(default)]
+    pub dests: BTreeMap<String, Destination>,
+
+    /// Whether to compress the PDF with object streams
+    #[serde(default)]
+    pub object_streams: bool,
+
+    /// Whether to add the logical structure of the documents and links
+    #[serde(default)]
+    pub tagged: bool,
+
+    /// The PDF/A conformance level
+    #[serde(default)]
+    pub pdfa: PdfA,
+
     /// The path to the fonts folder
     #[serde(default = "chsets_path")]
     pub chsets: PathBuf,
@@ -259,19 +380,78 @@ pub struct OutlineItem {
     pub children: Vec<OutlineItem>,
 }
 
+/// A rectangle in PDF units, as `(left, bottom, right, top)`
+pub type Rect = (i32, i32, i32, i32);
+
+fn to_rectangle((left, bottom, right, top): Rect) -> Rectangle<i32> {
+    Rectangle {
+        ll: Point { x: left, y: bottom },
+        ur: Point { x: right, y: top },
+    }
+}
+
 #[derive(Debug, Copy, Clone, Deserialize, Serialize)]
 pub enum Destination {
     PageFitH(usize, usize),
+    PageFit(usize),
+    PageXYZ(usize, Option<i32>, Option<i32>, Option<f32>),
+    PageFitR(usize, Rect),
 }
 
 impl From<Destination> for high::Destination {
     fn from(d: Destination) -> Self {
         match d {
             Destination::PageFitH(a, b) => Self::PageFitH(a, b),
+            Destination::PageFit(a) => Self::PageFit(a),
+            Destination::PageXYZ(a, left, top, zoom) => Self::PageXYZ(a, left, top, zoom),
+            Destination::PageFitR(a, rect) => Self::PageFitR(a, to_rectangle(rect)),
         }
     }
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct Link {
+    /// The index of the page in the output PDF that contains the link
+    ///
+    /// This counts the pages of all files in the script, starting at 0.
+    pub page: usize,
+    /// The active area of the link
+    pub rect: Rect,
+    /// Where the link goes
+    pub target: LinkTarget,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum LinkTarget {
+    /// A destination within the document
+    Dest(Destination),
+    /// A named destination
+    Named(String),
+    /// A (7-bit ASCII) URI
+    Uri(String),
+}
+
+impl Link {
+    /// Get the high-level link annotation
+    pub fn to_pdf_link(&self) -> io::Result<high::Link> {
+        let action = match &self.target {
+            LinkTarget::Dest(dest) => high::LinkAction::GoTo((*dest).into()),
+            LinkTarget::Named(name) => high::LinkAction::GoToNamed(PdfString::from_str(name)?),
+            LinkTarget::Uri(uri) if uri.is_ascii() => {
+                high::LinkAction::Uri(PdfString::from_str(uri)?)
+            }
+            LinkTarget::Uri(uri) => {
+                let msg = format!("URI {:?} is not 7-bit ASCII", uri);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+            }
+        };
+        Ok(high::Link {
+            rect: to_rectangle(self.rect),
+            action,
+        })
+    }
+}
+
 #[derive(Debug, Copy, Clone, Deserialize)]
 pub enum PageLabelKind {
     None,
@@ -60,7 +60,7 @@ fn print_tebu_data(
 
             while space > 2 {
                 print!(" ");
-                if space >= space_width {
+                if space_width > 0 && space >= space_width {
                     space -= space_width;
                 } else {
                     space = 0;
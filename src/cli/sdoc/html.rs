@@ -131,7 +131,7 @@ impl<'a> HtmlGen<'a> {
 
                 while space > 2 {
                     write!(self.out, " ")?;
-                    if space >= self.space_width {
+                    if self.space_width > 0 && space >= self.space_width {
                         space -= self.space_width;
                     } else {
                         space = 0;
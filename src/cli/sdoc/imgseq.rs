@@ -1,9 +1,11 @@
-use std::path::PathBuf;
+use std::{fs::File, io::BufWriter, path::PathBuf};
 
+use ccitt_t4_t6::tiff::{TiffPage, TiffWriter};
 use color_eyre::eyre::{self, eyre};
 use image::ImageFormat;
+use log::info;
 use signum::{
-    chsets::{cache::ChsetCache, FontKind},
+    chsets::{cache::ChsetCache, Device, FontKind},
     raster::render_doc_page,
 };
 
@@ -46,3 +48,49 @@ pub fn output_print(
     }
     Ok(())
 }
+
+/// Write all pages into a single (multi-page) TIFF file with CCITT Group 4 compression
+///
+/// Like the PDF output, the file is written to `<out>/<stem>.tiff`, or next to
+/// the document if no output folder is given.
+pub fn output_tiff(
+    doc: &Document,
+    opt: &Options,
+    fc: &ChsetCache,
+    info: &DocumentInfo,
+    pd: Option<FontKind>,
+) -> eyre::Result<()> {
+    let out_dir = opt
+        .out
+        .as_deref()
+        .unwrap_or_else(|| opt.file.parent().unwrap());
+    let mut out_path = out_dir.join(opt.file.file_stem().unwrap());
+    out_path.set_extension("tiff");
+
+    let pd = pd.ok_or_else(|| eyre!("Print driver not set!"))?;
+    let res = pd.resolution();
+
+    let file = BufWriter::new(File::create(&out_path)?);
+    let mut writer = TiffWriter::new(file)?;
+    for page_text in &doc.tebu.pages {
+        let index = page_text.index as usize;
+        let pbuf_entry = doc.pages[index].as_ref().unwrap();
+        if let Some(pages) = &opt.page {
+            if !pages.contains(&(pbuf_entry.log_pnr as usize)) {
+                continue;
+            }
+        }
+        let page = render_doc_page(page_text, pbuf_entry, doc.image_sites(), info, pd, fc);
+        writer.write_page(&TiffPage {
+            width: page.bit_width(),
+            height: page.bit_height(),
+            bitmap: page.as_slice(),
+            bytes_per_line: page.bytes_per_line(),
+            dpi: (res.x, res.y),
+        })?;
+    }
+    let pages = writer.pages();
+    writer.into_inner()?;
+    info!("Saved {} page(s) to '{}'", pages, out_path.display());
+    Ok(())
+}
@@ -3,24 +3,27 @@ use std::path::Path;
 use crate::cli::opt::{Format, Options};
 use color_eyre::eyre::{self, eyre};
 use image::ImageFormat;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use serde::Serialize;
 use signum::{
-    chsets::cache::ChsetCache,
+    chsets::cache::{ChsetCache, DocumentFontCacheInfo},
     docs::{
         container::{parse_sdoc0001_container, Chunk},
         cset::CSet,
-        hcim::{Hcim, ImageSite},
+        hcim::{Hcim, ImageEntry, ImageSite, PlacedImage},
         header::parse_header,
         pbuf::{self, PBuf},
+        salvage::{salvage_sdoc0001, Salvaged},
+        sclb::Clipboard,
         sysp::SysP,
         tebu::{PageText, TeBu},
-        v3::{parse_sdoc_v3, Stream},
+        v3::{parse_sdoc_v3, FontMap, Layout, SDocV3, Stream},
         DocumentInfo,
     },
-    util::{Buf, FourCC, LocalFS, VFS},
+    util::{Buf, FourCC, VFS},
 };
 
-use super::util;
+use super::{util, vfs::CliFS};
 
 mod console;
 mod html;
@@ -114,8 +117,9 @@ impl Document {
             Format::PostScript => ps::output_postscript(self, opt, fc, print, pd),
             Format::PDraw => pdraw::output_pdraw(self),
             Format::Png => imgseq::output_print(self, opt, fc, info, pd),
+            Format::Tiff => imgseq::output_tiff(self, opt, fc, info, pd),
             Format::Pdf => pdf::output_pdf(self, opt, fc, info, pd),
-            Format::DviPsBitmapFont | Format::CcItt6 => {
+            Format::DviPsBitmapFont | Format::CcItt6 | Format::Otf => {
                 error!("Document can't be formatted as a font");
                 Ok(())
             }
@@ -123,6 +127,10 @@ impl Document {
                 error!("Document export as PBM (bitmap) not supported!");
                 Ok(())
             }
+            Format::Imc | Format::Img | Format::Pi3 | Format::Pc3 => {
+                error!("Document export as {} (image) not supported!", opt.format);
+                Ok(())
+            }
             Format::Bdf => {
                 error!("Document export as BDF (font) not supported!");
                 Ok(())
@@ -144,6 +152,12 @@ impl Document {
         fs: &FS,
         fc: &mut ChsetCache,
     ) -> eyre::Result<DocumentInfo> {
+        self.load_sdoc(input)?;
+        self.document_info(fs, fc)
+    }
+
+    /// Load the chunks of a document, without loading the fonts
+    pub fn load_sdoc(&mut self, input: &[u8]) -> eyre::Result<()> {
         let sdoc = util::load(parse_sdoc0001_container, input)?;
 
         for Chunk { tag, buf } in sdoc.chunks {
@@ -160,18 +174,120 @@ impl Document {
                 }
             }?;
         }
+        Ok(())
+    }
+
+    /// Load as much as possible of a damaged document
+    pub fn salvage_sdoc<FS: VFS>(
+        &mut self,
+        input: &[u8],
+        fs: &FS,
+        fc: &mut ChsetCache,
+    ) -> eyre::Result<DocumentInfo> {
+        let Salvaged { sdoc, report } = salvage_sdoc0001(input);
+        for skipped in &report.skipped {
+            warn!(
+                "Skipped damaged bytes {}..{} in {}",
+                skipped.range.start,
+                skipped.range.end,
+                skipped
+                    .chunk
+                    .map_or("container".into(), |tag| format!("'{}'", tag))
+            );
+        }
+        for tag in &report.missing {
+            warn!("Could not recover '{}' chunk", tag);
+        }
+
+        let sdoc = sdoc.into_owned();
+        info!("File created: {}", sdoc.header.ctime);
+        info!("File modified: {}", sdoc.header.mtime);
+        self.page_count = sdoc.pbuf.page_count as usize;
+        self.pages = sdoc
+            .pbuf
+            .pages
+            .into_iter()
+            .map(|f| f.map(|(p, _b)| p))
+            .collect();
+        self.cset = Some(sdoc.cset);
+        self.sysp = Some(sdoc.sysp);
+        self.tebu = sdoc.tebu;
+        self.hcim = sdoc.hcim;
 
+        self.document_info(fs, fc)
+    }
+
+    fn document_info<FS: VFS>(&self, fs: &FS, fc: &mut ChsetCache) -> eyre::Result<DocumentInfo> {
+        let fonts = self.load_fonts(fs, fc)?;
+        Ok(DocumentInfo::new(fonts, self.decode_images()))
+    }
+
+    /// Load the fonts of the document into the cache
+    pub fn load_fonts<FS: VFS>(
+        &self,
+        fs: &FS,
+        fc: &mut ChsetCache,
+    ) -> eyre::Result<DocumentFontCacheInfo> {
         let cset = self
             .cset
             .as_ref()
             .ok_or_else(|| eyre!("Document has no CSET chunk"))?;
-        let fonts = futures_lite::future::block_on(fc.load(fs, cset));
-        let images = self
-            .hcim
+        Ok(futures_lite::future::block_on(fc.load(fs, cset)))
+    }
+
+    /// Decode the images embedded in the document
+    pub fn decode_images(&self) -> Vec<ImageEntry> {
+        self.hcim
             .as_ref()
             .map(|hcim| hcim.decode_images())
-            .unwrap_or_default();
-        Ok(DocumentInfo::new(fonts, images))
+            .unwrap_or_default()
+    }
+
+    pub fn process_sdoc_v3<FS: VFS>(
+        &mut self,
+        sdoc: &SDocV3<'_>,
+        fs: &FS,
+        fc: &mut ChsetCache,
+    ) -> eyre::Result<DocumentInfo> {
+        warn!("Signum! 3/4 output is a degraded preview:");
+        warn!("- the page format is not decoded, the default Signum! 1/2 format is used");
+        let font_count = sdoc.foused01().fonts().len();
+        if font_count > FontMap::MAX_CSETS {
+            warn!(
+                "- the document uses {} fonts, all beyond the first {} are replaced by the first",
+                font_count,
+                FontMap::MAX_CSETS
+            );
+        }
+        let layout = Layout::new(sdoc, SysP::default());
+        self.page_count = layout.pages.len();
+        info!("Laid out text on {} page(s)", self.page_count);
+        self.pages = layout.pages;
+        self.tebu = layout.tebu;
+        self.cset = Some(layout.cset);
+        self.sysp = Some(layout.sysp);
+
+        self.document_info(fs, fc)
+    }
+
+    pub fn process_sclb<FS: VFS>(
+        &mut self,
+        input: &[u8],
+        fs: &FS,
+        fc: &mut ChsetCache,
+    ) -> eyre::Result<DocumentInfo> {
+        let clb =
+            Clipboard::parse(input).map_err(|e| eyre!("Failed to parse clipboard: {:?}", e))?;
+        info!("Loaded {} line(s) from the clipboard", clb.lines.len());
+        info!("CHSETS: {:?}", clb.cset.names);
+        let sysp = SysP::default();
+        self.tebu = clb.to_tebu();
+        self.pages = vec![Some(pbuf::Page::from_sysp(0, &sysp))];
+        self.page_count = 1;
+        self.cset = Some(clb.cset.into_owned());
+        self.sysp = Some(sysp);
+
+        self.document_info(fs, fc)
     }
 
     pub fn text_buffer(&self) -> &TeBu {
@@ -179,29 +295,67 @@ impl Document {
     }
 }
 
-fn output_images(doc: &DocumentInfo, out_img: &Path) -> eyre::Result<()> {
+/// An entry in the `images.json` sidecar
+#[derive(Serialize)]
+struct ImageSidecarEntry<'a> {
+    /// The name of the PNG file in the images folder
+    file: &'a str,
+    #[serde(flatten)]
+    placed: PlacedImage<'a>,
+}
+
+fn output_images(
+    doc: &DocumentInfo,
+    sites: &[ImageSite],
+    pages: &[Option<pbuf::Page>],
+    out_img: &Path,
+) -> eyre::Result<()> {
     std::fs::create_dir_all(out_img)?;
+    let mut files = Vec::new();
     for (index, im) in doc.images().enumerate() {
         let name = format!("{:02}-{}.png", index, im.key);
-        let path = out_img.join(name);
+        let path = out_img.join(&name);
         let img = im.image.to_image();
         img.save_with_format(&path, ImageFormat::Png)?;
+        files.push(name);
     }
+
+    let entries: Vec<ImageSidecarEntry> = doc
+        .placed_images(sites, pages)
+        .map(|placed| ImageSidecarEntry {
+            file: &files[usize::from(placed.img)],
+            placed,
+        })
+        .collect();
+    let path = out_img.join("images.json");
+    let file = std::fs::File::create(&path)?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), &entries)?;
+    info!("Saved image metadata as '{}'", path.display());
     Ok(())
 }
 
-pub fn process_sdoc(input: &[u8], opt: Options) -> eyre::Result<()> {
-    let mut document = Document::new();
+fn load_sdoc<FS: VFS>(
+    document: &mut Document,
+    input: &[u8],
+    fs: &FS,
+    fc: &mut ChsetCache,
+    salvage: bool,
+) -> eyre::Result<DocumentInfo> {
+    if salvage {
+        document.salvage_sdoc(input, fs, fc)
+    } else {
+        document.process_sdoc(input, fs, fc)
+    }
+}
 
-    let folder = opt.file.parent().unwrap();
-    let chsets_folder = folder.join(&opt.chsets_path);
-    let fs = LocalFS::new(chsets_folder);
+pub fn process_sdoc(input: &[u8], opt: Options, fs: &CliFS) -> eyre::Result<()> {
+    let mut document = Document::new();
     let mut fc = ChsetCache::new();
-    let di = document.process_sdoc(input, &fs, &mut fc)?;
+    let di = load_sdoc(&mut document, input, fs, &mut fc, opt.salvage)?;
 
     // Output images
     if let Some(out_img) = opt.with_images.as_ref() {
-        output_images(&di, out_img)?;
+        output_images(&di, document.image_sites(), &document.pages, out_img)?;
     }
 
     // Output the document
@@ -210,15 +364,22 @@ pub fn process_sdoc(input: &[u8], opt: Options) -> eyre::Result<()> {
     Ok(())
 }
 
-fn process_stream(i: usize, stream: &Stream<'_>) -> eyre::Result<()> {
-    log::info!("Stream {i}");
+pub fn process_sclb(input: &[u8], opt: Options, fs: &CliFS) -> eyre::Result<()> {
+    let mut document = Document::new();
+    let mut fc = ChsetCache::new();
+    let di = document.process_sclb(input, fs, &mut fc)?;
+    document.output(&fc, &di, &opt)?;
+    Ok(())
+}
+
+fn process_stream(i: usize, stream: &Stream<'_>) {
+    debug!("Stream {i}");
     for line in stream.text() {
-        log::info!("{:?}", line);
+        debug!("{:?}", line);
     }
-    Ok(())
 }
 
-pub fn process_sdoc_v3(input: &[u8], _opt: Options) -> eyre::Result<()> {
+pub fn process_sdoc_v3(input: &[u8], opt: Options, fs: &CliFS) -> eyre::Result<()> {
     let (_, sdoc) = util::load_partial(parse_sdoc_v3, input)?;
     let head = sdoc.sdoc03();
     log::info!("Created: {}", head.ctime);
@@ -229,14 +390,19 @@ pub fn process_sdoc_v3(input: &[u8], _opt: Options) -> eyre::Result<()> {
     }
     for chapter in sdoc.chapters() {
         log::info!("Chapter: {:?}", chapter.header());
-        process_stream(1, chapter.main())?;
-        process_stream(2, chapter.header_footer())?;
+        process_stream(1, chapter.main());
+        process_stream(2, chapter.header_footer());
         if let Some(stream3) = chapter.stream3() {
-            process_stream(3, stream3)?;
+            process_stream(3, stream3);
         }
         if let Some(stream4) = chapter.stream4() {
-            process_stream(4, stream4)?;
+            process_stream(4, stream4);
         }
     }
+
+    let mut document = Document::new();
+    let mut fc = ChsetCache::new();
+    let di = document.process_sdoc_v3(&sdoc, fs, &mut fc)?;
+    document.output(&fc, &di, &opt)?;
     Ok(())
 }
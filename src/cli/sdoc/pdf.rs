@@ -1,11 +1,15 @@
-use std::{fs::File, io::BufWriter, path::Path};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
 
 use crate::cli::opt::Options;
 
 use super::{Document, DocumentInfo};
 use color_eyre::eyre::{self, eyre, OptionExt};
 use log::info;
-use sdo_pdf::{generate_pdf, MetaInfo, Pdf};
+use sdo_pdf::{font::FontMode, generate_pdf, MetaInfo, Pdf};
 use signum::{
     chsets::{cache::ChsetCache, FontKind},
     docs::{hcim::ImageSite, pbuf, tebu::PageText, GenerationContext, Overrides},
@@ -74,12 +78,36 @@ pub fn output_pdf(
     }?;
     let (meta, overrides) = doc_meta(opt)?;
     let out_path = opt.out.as_deref();
+    let font_mode = if opt.outline_fonts {
+        FontMode::Outline
+    } else {
+        FontMode::Bitmap
+    };
 
-    let pdf = generate_pdf(fc, pk, &meta, &overrides, &GenCtx::new(doc, di))?;
+    let gc = GenCtx::new(doc, di);
+    let pdf = generate_pdf(
+        fc,
+        pk,
+        &meta,
+        &overrides,
+        font_mode,
+        opt.tagged,
+        opt.pdfa.into(),
+        &gc,
+    )?;
     handle_out(out_path, &opt.file, pdf)?;
     Ok(())
 }
 
+/// The path of the PDF for `file` in the folder `out`, or next to `file`
+pub fn pdf_out_path(out: Option<&Path>, file: &Path) -> PathBuf {
+    let out = out.unwrap_or_else(|| file.parent().unwrap());
+    let file = file.file_stem().unwrap();
+    let mut buf = out.join(file);
+    buf.set_extension("pdf");
+    buf
+}
+
 pub fn handle_out(out: Option<&Path>, file: &Path, hnd: Pdf) -> eyre::Result<()> {
     if out == Some(Path::new("-")) {
         println!("----------------------------- PDF -----------------------------");
@@ -89,13 +117,7 @@ pub fn handle_out(out: Option<&Path>, file: &Path, hnd: Pdf) -> eyre::Result<()>
         println!("---------------------------------------------------------------");
         Ok(())
     } else {
-        let out = out.unwrap_or_else(|| file.parent().unwrap());
-        let file = file.file_stem().unwrap();
-        let out = {
-            let mut buf = out.join(file);
-            buf.set_extension("pdf");
-            buf
-        };
+        let out = pdf_out_path(out, file);
         let out_file = File::create(&out)?;
         let mut out_buf = BufWriter::new(out_file);
         info!("Writing `{}` ...", out.display());
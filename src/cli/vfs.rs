@@ -0,0 +1,198 @@
+//! The file system used by the CLI, i.e. the local one or a disk image
+use std::{
+    borrow::Cow,
+    fs,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+};
+
+use color_eyre::eyre::{self, WrapErr};
+use log::info;
+use signum::util::{
+    AsyncIterator, FloppyDirEntry, FloppyDirIter, FloppyError, FloppyFS, FloppyImage, LocalFS, VFS,
+};
+use thiserror::Error;
+
+use super::opt::Options;
+
+/// Either the local file system or an ATARI ST disk image
+pub enum CliFS {
+    /// The local file system
+    Local(LocalFS),
+    /// The disk image given with `--disk`
+    Floppy(FloppyFS),
+}
+
+impl CliFS {
+    /// Open the file system for `opt`, loading the disk image (if any) once
+    pub fn new(opt: &Options) -> eyre::Result<Self> {
+        let folder = opt.file.parent().unwrap();
+        let chsets_folder = folder.join(&opt.chsets_path);
+        Ok(if let Some(disk) = &opt.disk {
+            let image = FloppyImage::load(std::fs::read(disk)?)?;
+            Self::Floppy(FloppyFS::new(image, chsets_folder))
+        } else {
+            Self::Local(LocalFS::new(chsets_folder))
+        })
+    }
+
+    /// Read the input file from this file system
+    pub fn load_file(&self, opt: &Options) -> eyre::Result<Vec<u8>> {
+        match (self, &opt.disk) {
+            (Self::Floppy(fs), Some(disk)) => {
+                let buffer = fs.image().read_file(&opt.file).wrap_err_with(|| {
+                    format!(
+                        "Failed to read `{}` from `{}`",
+                        opt.file.display(),
+                        disk.display()
+                    )
+                })?;
+                info!(
+                    "Loaded file `{}` from `{}`",
+                    opt.file.display(),
+                    disk.display()
+                );
+                Ok(buffer)
+            }
+            _ => {
+                let buffer = fs::read(&opt.file)
+                    .wrap_err_with(|| format!("Failed to open file: `{}`", opt.file.display()))?;
+                info!("Loaded file `{}`", opt.file.display());
+                Ok(buffer)
+            }
+        }
+    }
+}
+
+/// Error of a [`CliFS`]
+#[derive(Debug, Error)]
+pub enum CliFSError {
+    /// Error on the local file system
+    #[error(transparent)]
+    Local(#[from] io::Error),
+    /// Error on the disk image
+    #[error(transparent)]
+    Floppy(#[from] FloppyError),
+}
+
+/// Directory iterator of a [`CliFS`]
+pub enum CliDirIter {
+    /// Directory on the local file system
+    Local(fs::ReadDir),
+    /// Directory on the disk image
+    Floppy(FloppyDirIter),
+}
+
+impl AsyncIterator for CliDirIter {
+    type Item = Result<CliDirEntry, CliFSError>;
+
+    async fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Local(iter) => AsyncIterator::next(iter)
+                .await
+                .map(|r| r.map(CliDirEntry::Local).map_err(CliFSError::from)),
+            Self::Floppy(iter) => iter
+                .next()
+                .await
+                .map(|r| r.map(CliDirEntry::Floppy).map_err(CliFSError::from)),
+        }
+    }
+}
+
+/// Directory entry of a [`CliFS`]
+pub enum CliDirEntry {
+    /// Entry on the local file system
+    Local(fs::DirEntry),
+    /// Entry on the disk image
+    Floppy(FloppyDirEntry),
+}
+
+impl VFS for CliFS {
+    type Error = CliFSError;
+
+    type DirIter = CliDirIter;
+
+    type DirEntry = CliDirEntry;
+
+    type File = Vec<u8>;
+
+    fn root(&self) -> impl Future<Output = PathBuf> + 'static {
+        let root: Pin<Box<dyn Future<Output = PathBuf>>> = match self {
+            Self::Local(fs) => Box::pin(fs.root()),
+            Self::Floppy(fs) => Box::pin(fs.root()),
+        };
+        root
+    }
+
+    async fn is_file(&self, path: &Path) -> bool {
+        match self {
+            Self::Local(fs) => fs.is_file(path).await,
+            Self::Floppy(fs) => fs.is_file(path).await,
+        }
+    }
+
+    async fn is_dir(&self, path: &Path) -> bool {
+        match self {
+            Self::Local(fs) => fs.is_dir(path).await,
+            Self::Floppy(fs) => fs.is_dir(path).await,
+        }
+    }
+
+    async fn read_dir(&self, path: &Path) -> Result<Self::DirIter, Self::Error> {
+        Ok(match self {
+            Self::Local(fs) => CliDirIter::Local(fs.read_dir(path).await?),
+            Self::Floppy(fs) => CliDirIter::Floppy(fs.read_dir(path).await?),
+        })
+    }
+
+    async fn open(&self, path: &Path) -> Result<Self::File, Self::Error> {
+        self.read(path).await
+    }
+
+    fn dir_entry_is_file(&self, entry: &Self::DirEntry) -> bool {
+        match (self, entry) {
+            (Self::Local(fs), CliDirEntry::Local(e)) => fs.dir_entry_is_file(e),
+            (Self::Floppy(fs), CliDirEntry::Floppy(e)) => fs.dir_entry_is_file(e),
+            _ => false,
+        }
+    }
+
+    fn dir_entry_is_dir(&self, entry: &Self::DirEntry) -> bool {
+        match (self, entry) {
+            (Self::Local(fs), CliDirEntry::Local(e)) => fs.dir_entry_is_dir(e),
+            (Self::Floppy(fs), CliDirEntry::Floppy(e)) => fs.dir_entry_is_dir(e),
+            _ => false,
+        }
+    }
+
+    fn dir_entry_path<'a>(&self, entry: &'a Self::DirEntry) -> Cow<'a, Path> {
+        match entry {
+            CliDirEntry::Local(e) => Cow::Owned(e.path()),
+            CliDirEntry::Floppy(e) => Cow::Borrowed(e.path()),
+        }
+    }
+
+    async fn dir_entry_to_file(
+        &self,
+        dir_entry: &Self::DirEntry,
+    ) -> Result<Self::File, Self::Error> {
+        let path = self.dir_entry_path(dir_entry);
+        self.read(&path).await
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, Self::Error> {
+        Ok(match self {
+            Self::Local(fs) => fs.read(path).await?,
+            Self::Floppy(fs) => fs.read(path).await?,
+        })
+    }
+
+    fn is_file_not_found(e: &Self::Error) -> bool {
+        match e {
+            CliFSError::Local(e) => LocalFS::is_file_not_found(e),
+            CliFSError::Floppy(e) => FloppyFS::is_file_not_found(e),
+        }
+    }
+}
@@ -1,39 +1,32 @@
 //! # Signum! file tool
 #![warn(missing_docs)]
 
-use color_eyre::eyre::{self, eyre, WrapErr};
-use log::{error, info};
+use color_eyre::eyre::{self, eyre};
+use log::error;
 use sdo_tool::cli::{
-    bimc::process_bimc,
+    bimc::{process_bimc, process_degas, process_gem_img, process_png, PNG_MAGIC},
     font::{process_cset_v2, process_eset, process_ls30, process_ps09, process_ps24},
     init,
     opt::Options,
-    sdoc::{process_sdoc, process_sdoc_v3},
+    sdoc::{process_sclb, process_sdoc, process_sdoc_v3},
+    vfs::CliFS,
 };
 use signum::{
     docs::four_cc,
+    images::{degas::DegasHeader, gem::ImgHeader},
     util::{FourCC, Signum3Format},
 };
-use std::{
-    fs::File,
-    io::{BufReader, Read},
-};
 
 fn main() -> eyre::Result<()> {
     let opt = init::<Options>()?;
-    let file_res = File::open(&opt.file);
-    let file = WrapErr::wrap_err_with(file_res, || {
-        format!("Failed to open file: `{}`", opt.file.display())
-    })?;
-    let mut reader = BufReader::new(file);
-    let mut buffer = Vec::new();
-    reader.read_to_end(&mut buffer)?;
-    info!("Loaded file `{}`", opt.file.display());
+    let fs = CliFS::new(&opt)?;
+    let buffer = fs.load_file(&opt)?;
 
     let (_, four_cc) = four_cc::<signum::nom::error::Error<&'_ [u8]>>(&buffer[..])
         .map_err(|_| eyre!("File has less than 4 bytes"))?;
     match four_cc {
-        FourCC::SDOC => process_sdoc(&buffer, opt),
+        FourCC::SDOC => process_sdoc(&buffer, opt, &fs),
+        FourCC::SCLB => process_sclb(&buffer, opt, &fs),
         FourCC::ESET => process_eset(&buffer, None, None),
         FourCC::PS09 => process_ps09(&buffer, &opt),
         FourCC::PS24 => process_ps24(&buffer, &opt),
@@ -42,9 +35,15 @@ fn main() -> eyre::Result<()> {
         fourcc => {
             if let Some(sig3) = Signum3Format::detect(&buffer) {
                 match sig3 {
-                    Signum3Format::Document => process_sdoc_v3(&buffer, opt),
+                    Signum3Format::Document => process_sdoc_v3(&buffer, opt, &fs),
                     Signum3Format::Font { compressed: _ } => process_cset_v2(&buffer, opt),
                 }
+            } else if buffer.starts_with(PNG_MAGIC) {
+                process_png(&buffer, opt)
+            } else if DegasHeader::detect(&buffer) {
+                process_degas(&buffer, opt)
+            } else if ImgHeader::detect(&buffer) {
+                process_gem_img(&buffer, opt)
             } else {
                 error!("Unknown file type {fourcc}");
                 Ok(())